@@ -0,0 +1,13 @@
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    #[cfg(feature = "grpc")]
+    {
+        // Bundled protoc, so building doesn't depend on one being installed.
+        std::env::set_var("PROTOC", protoc_bin_vendored::protoc_bin_path()?);
+
+        tonic_prost_build::configure()
+            .build_client(false)
+            .compile_protos(&["proto/open_rgb_client.proto"], &["proto"])?;
+    }
+
+    Ok(())
+}
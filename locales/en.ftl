@@ -0,0 +1,133 @@
+## Setup wizard
+
+setup-offer = No configuration found, run the setup wizard?
+wizard-intro = Let's set up the OpenRGB client, press enter to accept the [default] answers.
+wizard-server = OpenRGB server
+wizard-connect-failed = Failed to connect to { $host }: { $error }
+wizard-metrics = Pick the metric each device shows (cpu, gpu, cpu_temperature, gpu_temperature, memory, disk, power, thermal, battery, mail or none to leave it alone).
+wizard-device = { $name } ({ $leds ->
+        [one] { $leds } LED
+       *[other] { $leds } LEDs
+    })
+wizard-palettes = Pick a palette:
+wizard-palette = Palette
+wizard-palette-unknown = Unknown palette: { $answer }
+wizard-idle-color = Idle color, #RRGGBB or a name like orange
+wizard-load-color = Load color, #RRGGBB or a name like orange
+wizard-saved = Configuration written to { $path }.
+wizard-aborted = No answer, setup aborted.
+palette-white-red = White to red
+palette-blue-red = Blue to red
+palette-green-red = Green to red
+palette-cyan-magenta = Cyan to magenta
+palette-custom = Custom
+confirm-hint-yes = Y/n
+confirm-hint-no = y/N
+confirm-invalid = Please answer yes or no.
+
+## Status
+
+status-state-file = State file: { $path }
+status-safe-mode = Safe mode: active ({ $starts ->
+        [one] { $starts } unclean start recently
+       *[other] { $starts } unclean starts recently
+    }).
+status-no-controllers = No controller has been driven yet.
+status-controller = { $controller }: { $hours } h active, { $brightness }% average brightness
+status-metrics = Metrics: { $metrics }
+status-runtime = Client: { $cpu }% CPU, { $memory } MiB memory, { $tasks } tasks, { $jitter } ms frame jitter
+status-not-running = The client isn't running.
+status-connecting = OpenRGB: connecting
+status-connected = OpenRGB: connected to { $server } for { $seconds } s
+status-disconnected = OpenRGB: disconnected ({ $reason })
+status-averages = Averages: CPU { $cpu }%, GPU { $gpu }%
+status-profile = Profile: { $profile }
+status-scene = Scene: { $scene }
+status-updated = { $controller }: updated { $seconds } s ago
+status-updated-latency = { $controller }: updated { $seconds } s ago, shown { $latency } ms after being sent
+status-controller-power = { $controller }: { $watts } W estimated
+status-power = Estimated LED power: { $watts } W
+
+## Configuration check
+
+check-config-ok = The configuration matches the { $controllers ->
+        [one] { $controllers } controller
+       *[other] { $controllers } controllers
+    } of OpenRGB.
+check-config-unknown-controller = No controller matches { $name }, listed in `devices`.
+check-config-unknown-zone = No zone of { $controller } matches { $zone }.
+check-config-unknown-listed = No controller matches { $name }, listed in `controllers.{ $list }`.
+check-config-unknown-sequence = No sequence named { $name }, set in `reconnected_sequence`.
+init-config-saved = Starter configuration for { $controllers ->
+        [one] { $controllers } controller
+       *[other] { $controllers } controllers
+    } written to { $path }.
+builtin-config-saved = Configuration reproducing the built-in layouts written to { $path }.
+layout-imported = Layout of { $zones ->
+        [one] { $zones } zone
+       *[other] { $zones } zones
+    } written to { $path }.
+
+## Device list
+
+list-devices-controller = { $name }: { $leds } LEDs
+list-devices-location = Location: { $location }
+list-devices-aliases = Aliases: { $aliases }
+list-devices-modes = Modes: { $modes }
+list-devices-active-mode = { $mode } (active)
+list-devices-left-alone = Left alone, per the { $rule }
+list-devices-metric = Metric: { $metric }, per the { $rule }
+list-devices-layout = Layout: { $rule }
+list-devices-effect = Effect: { $effect }
+list-devices-zone = { $zone }: { $leds } LEDs, { $rendering }
+list-devices-disabled = disabled
+list-devices-metric-off = { $metric } turned off, { $color }
+list-devices-whole-controller = part of the whole controller
+
+## Discovery
+
+discover-none = No OpenRGB servers found.
+
+## Stress test
+
+stress-start = Driving { $controllers ->
+        [one] { $controllers } controller
+       *[other] { $controllers } controllers
+    } at { $fps } FPS for { $minutes } minutes, press Ctrl+C to stop early...
+stress-controller = { $controller }: { $frames } frames, { $errors } errors ({ $rate }%)
+stress-latency = Frame latency: { $median } ms median, { $p99 } ms 99th percentile, { $max } ms max
+stress-summary = Achieved { $fps } FPS, { $reconnects } reconnects.
+
+## Test patterns
+
+test-pattern-start = Showing the { $pattern } test pattern on { $leds ->
+        [one] { $leds } LED
+       *[other] { $leds } LEDs
+    }, press Ctrl+C to stop...
+test-pattern-led = LED { $index }
+test-pattern-bit = Bit { $bit }: lit LEDs have { $value } in their index.
+test-pattern-color = { $color ->
+        [red] Red
+        [green] Green
+       *[blue] Blue
+    }: other colors mean another channel order, see the channel-order filter.
+
+## Set color
+
+set-color-done = Set { $leds ->
+        [one] { $leds } LED
+       *[other] { $leds } LEDs
+    } of { $controller } to { $color }.
+
+## Bot
+
+bot-paused = Paused, the LEDs are off until /resume.
+bot-resumed = Resumed.
+bot-theme = Switched to the theme { $theme }.
+bot-themes = Themes: { $themes }, or none for the configuration without profile.
+bot-error = Failed: { $message }
+bot-help = Commands: /status, /pause, /resume, /theme <name>
+
+## Secrets
+
+secret-prompt = Enter the secret { $name }, stored in the keychain:
@@ -0,0 +1,109 @@
+## Setup wizard
+
+setup-offer = 設定が見つかりません。セットアップウィザードを実行しますか？
+wizard-intro = OpenRGB クライアントをセットアップします。Enter キーで [既定] の回答を選択します。
+wizard-server = OpenRGB サーバー
+wizard-connect-failed = { $host } に接続できませんでした: { $error }
+wizard-metrics = 各デバイスに表示する指標を選んでください (cpu、gpu、cpu_temperature、gpu_temperature、memory、disk、power、thermal、battery、mail、または変更しない場合は none)。
+wizard-device = { $name } (LED { $leds } 個)
+wizard-palettes = パレットを選んでください:
+wizard-palette = パレット
+wizard-palette-unknown = 不明なパレット: { $answer }
+wizard-idle-color = アイドル時の色 (#RRGGBB または orange などの名前)
+wizard-load-color = 高負荷時の色 (#RRGGBB または orange などの名前)
+wizard-saved = 設定を { $path } に書き込みました。
+wizard-aborted = 回答がないため、セットアップを中止しました。
+palette-white-red = 白から赤
+palette-blue-red = 青から赤
+palette-green-red = 緑から赤
+palette-cyan-magenta = シアンからマゼンタ
+palette-custom = カスタム
+confirm-hint-yes = Y/n
+confirm-hint-no = y/N
+confirm-invalid = yes か no で答えてください。
+
+## Status
+
+status-state-file = 状態ファイル: { $path }
+status-safe-mode = セーフモード: 有効 (最近の異常終了後の起動 { $starts } 回)。
+status-no-controllers = まだ制御したコントローラーはありません。
+status-controller = { $controller }: 稼働 { $hours } 時間、平均輝度 { $brightness }%
+status-metrics = メトリクス: { $metrics }
+status-runtime = クライアント: CPU { $cpu }%、メモリ { $memory } MiB、タスク { $tasks } 個、フレームのジッター { $jitter } ms
+status-not-running = クライアントは実行されていません。
+status-connecting = OpenRGB: 接続中
+status-connected = OpenRGB: { $server } に接続 ({ $seconds } 秒前から)
+status-disconnected = OpenRGB: 切断 ({ $reason })
+status-averages = 平均: CPU { $cpu }%、GPU { $gpu }%
+status-profile = プロファイル: { $profile }
+status-scene = シーン: { $scene }
+status-updated = { $controller }: { $seconds } 秒前に更新
+status-updated-latency = { $controller }: { $seconds } 秒前に更新、送信から { $latency } ms で表示
+status-controller-power = { $controller }: 推定 { $watts } W
+status-power = LED の推定消費電力: { $watts } W
+
+## Configuration check
+
+check-config-ok = 設定は OpenRGB の { $controllers } 台のコントローラーと一致しています。
+check-config-unknown-controller = `devices` の { $name } に一致するコントローラーがありません。
+check-config-unknown-zone = { $controller } に { $zone } に一致するゾーンがありません。
+check-config-unknown-listed = `controllers.{ $list }` の { $name } に一致するコントローラーがありません。
+check-config-unknown-sequence = `reconnected_sequence` のシーケンス { $name } がありません。
+init-config-saved = { $controllers } 台のコントローラーの初期設定を { $path } に書き込みました。
+builtin-config-saved = 組み込みのレイアウトを再現する設定を { $path } に書き込みました。
+layout-imported = { $zones } 個のゾーンのレイアウトを { $path } に書き込みました。
+
+## Device list
+
+list-devices-controller = { $name }: LED { $leds } 個
+list-devices-location = 場所: { $location }
+list-devices-aliases = エイリアス: { $aliases }
+list-devices-modes = モード: { $modes }
+list-devices-active-mode = { $mode } (使用中)
+list-devices-left-alone = 制御しない ({ $rule })
+list-devices-metric = メトリクス: { $metric } ({ $rule })
+list-devices-layout = レイアウト: { $rule }
+list-devices-effect = エフェクト: { $effect }
+list-devices-zone = { $zone }: LED { $leds } 個、{ $rendering }
+list-devices-disabled = 無効
+list-devices-metric-off = { $metric } はオフ、{ $color }
+list-devices-whole-controller = コントローラー全体の一部
+
+## Discovery
+
+discover-none = OpenRGB サーバーが見つかりませんでした。
+
+## Stress test
+
+stress-start = { $controllers } 台のコントローラーを { $fps } FPS で { $minutes } 分間駆動します。Ctrl+C で中断できます...
+stress-controller = { $controller }: { $frames } フレーム、エラー { $errors } 回 ({ $rate }%)
+stress-latency = フレーム遅延: 中央値 { $median } ms、99 パーセンタイル { $p99 } ms、最大 { $max } ms
+stress-summary = 実効 { $fps } FPS、再接続 { $reconnects } 回。
+
+## Test patterns
+
+test-pattern-start = { $leds } 個の LED にテストパターン { $pattern } を表示します。Ctrl+C で終了します...
+test-pattern-led = LED { $index }
+test-pattern-bit = ビット { $bit }: 点灯している LED のインデックスには { $value } が含まれます。
+test-pattern-color = { $color ->
+        [red] 赤
+        [green] 緑
+       *[blue] 青
+    }: 他の色が表示される場合はチャンネル順が異なります (channel-order フィルターを参照)。
+
+## Set color
+
+set-color-done = { $controller } の { $leds } 個の LED を { $color } に設定しました。
+
+## Bot
+
+bot-paused = 一時停止しました。/resume まで LED は消灯します。
+bot-resumed = 再開しました。
+bot-theme = テーマ { $theme } に切り替えました。
+bot-themes = テーマ: { $themes }。プロファイルなしの設定には none を指定します。
+bot-error = 失敗しました: { $message }
+bot-help = コマンド: /status、/pause、/resume、/theme <名前>
+
+## Secrets
+
+secret-prompt = シークレット { $name } を入力してください (キーチェーンに保存されます):
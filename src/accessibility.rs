@@ -0,0 +1,88 @@
+use crate::{
+    config::Palette,
+    effects::{Clock, Countdown, Effect, Solid, TrafficLight},
+    mapping::Plan,
+};
+use openrgb::data::Color;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Blue to yellow, telling idle from load apart with any color vision.
+const HIGH_CONTRAST_PALETTE: Palette = Palette {
+    idle: Color::new(0x00, 0x00, 0xFF),
+    load: Color::new(0xFF, 0xFF, 0x00),
+};
+
+/// Modes for users sensitive to flashing lights or needing more contrast, from the
+/// configuration and switched at runtime through the control socket.
+#[derive(Debug, Default)]
+pub struct Accessibility {
+    pub reduced_motion: AtomicBool,
+    pub high_contrast: AtomicBool,
+}
+
+impl Accessibility {
+    pub fn new(reduced_motion: bool, high_contrast: bool) -> Self {
+        Self {
+            reduced_motion: AtomicBool::new(reduced_motion),
+            high_contrast: AtomicBool::new(high_contrast),
+        }
+    }
+
+    /// Whether reduced motion and high contrast are enabled.
+    pub fn modes(&self) -> (bool, bool) {
+        (
+            self.reduced_motion.load(Ordering::Relaxed),
+            self.high_contrast.load(Ordering::Relaxed),
+        )
+    }
+
+    /// Adapts a plan to the modes enabled.
+    pub fn adjust(&self, plan: &mut Plan) {
+        if self.high_contrast.load(Ordering::Relaxed) {
+            plan.palette = HIGH_CONTRAST_PALETTE;
+        }
+
+        if self.reduced_motion.load(Ordering::Relaxed) {
+            for segment in &mut plan.segments {
+                segment.effect = steady(&segment.effect);
+                segment.idle_effect = segment.idle_effect.as_ref().map(steady);
+            }
+        }
+    }
+
+    /// Adapts an effect shown over a whole controller, with the controller's palette.
+    pub fn adjust_effect(&self, effect: &Effect, palette: &mut Palette) -> Effect {
+        if self.high_contrast.load(Ordering::Relaxed) {
+            *palette = HIGH_CONTRAST_PALETTE;
+        }
+
+        match self.reduced_motion.load(Ordering::Relaxed) {
+            true => steady(effect),
+            false => effect.clone(),
+        }
+    }
+}
+
+/// The effect without blinking, flashing or moving colors.
+fn steady(effect: &Effect) -> Effect {
+    match effect {
+        Effect::TrafficLight(traffic_light) => Effect::TrafficLight(TrafficLight {
+            blink: false,
+            ..traffic_light.clone()
+        }),
+        Effect::Countdown(countdown) => Effect::Countdown(Countdown {
+            flash: false,
+            ..countdown.clone()
+        }),
+        Effect::Clock(clock) => Effect::Clock(Clock {
+            seconds: clock.background,
+            ..clock.clone()
+        }),
+        Effect::Color(solid) => Effect::Color(Solid {
+            blink: false,
+            ..solid.clone()
+        }),
+        Effect::Rainbow | Effect::Wave(_) => Effect::Block,
+        effect => effect.clone(),
+    }
+}
@@ -0,0 +1,299 @@
+use log::*;
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    cell::Cell,
+    future::Future,
+    pin::Pin,
+    sync::atomic::{AtomicBool, AtomicUsize, Ordering},
+    task::{Context, Poll},
+};
+
+/// Frames rendered before the frames must stop allocating, and the memory in use is taken as
+/// the steady state.
+const WARM_UP_FRAMES: u32 = 20;
+/// Growth of the memory in use over the steady state tolerated, e.g. for caches filling up.
+const MAX_GROWTH: usize = 1024 * 1024;
+/// Frames between the reports of the allocations.
+const REPORT_FRAMES: u32 = 100;
+
+static LIVE_BYTES: AtomicUsize = AtomicUsize::new(0);
+/// Whether the current frame has a reason to allocate, see `expect_allocations`.
+static EXPECTED: AtomicBool = AtomicBool::new(false);
+
+thread_local! {
+    static THREAD_ALLOCATIONS: Cell<u64> = const { Cell::new(0) };
+    static THREAD_LIVE_BYTES: Cell<isize> = const { Cell::new(0) };
+}
+
+/// System allocator counting the memory in use by the process, and the allocations and memory
+/// in use of each thread.
+pub struct CountingAllocator;
+
+impl CountingAllocator {
+    fn record(allocations: u64, allocated: usize, freed: usize) {
+        LIVE_BYTES.fetch_add(allocated, Ordering::Relaxed);
+        LIVE_BYTES.fetch_sub(freed, Ordering::Relaxed);
+
+        // Not available while the thread is torn down.
+        let _ = THREAD_ALLOCATIONS.try_with(|count| count.set(count.get() + allocations));
+        let _ = THREAD_LIVE_BYTES
+            .try_with(|live| live.set(live.get() + allocated as isize - freed as isize));
+    }
+}
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let pointer = System.alloc(layout);
+        if !pointer.is_null() {
+            Self::record(1, layout.size(), 0);
+        }
+
+        pointer
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        let pointer = System.alloc_zeroed(layout);
+        if !pointer.is_null() {
+            Self::record(1, layout.size(), 0);
+        }
+
+        pointer
+    }
+
+    unsafe fn dealloc(&self, pointer: *mut u8, layout: Layout) {
+        System.dealloc(pointer, layout);
+        Self::record(0, 0, layout.size());
+    }
+
+    unsafe fn realloc(&self, pointer: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        let new_pointer = System.realloc(pointer, layout, new_size);
+        if !new_pointer.is_null() {
+            Self::record(1, new_size, layout.size());
+        }
+
+        new_pointer
+    }
+}
+
+/// Lets the current frame allocate, for what only some frames do: setting the controllers up
+/// again, rendering a notification, formatting an event or an error...
+pub fn expect_allocations() {
+    EXPECTED.store(true, Ordering::Relaxed);
+}
+
+/// Runs `f` without counting its allocations, for what a frame does that isn't part of it, like
+/// writing a log line.
+pub fn uncounted<R>(f: impl FnOnce() -> R) -> R {
+    let allocations = thread_allocations();
+    let result = f();
+    let _ = THREAD_ALLOCATIONS.try_with(|count| count.set(allocations));
+
+    result
+}
+
+fn thread_allocations() -> u64 {
+    THREAD_ALLOCATIONS.try_with(Cell::get).unwrap_or_default()
+}
+
+/// Checks that the render loop runs without allocating: once warmed up, a frame must allocate
+/// nothing unless it has a reason to, see `expect_allocations`, and the memory in use at the
+/// end of a frame must not keep growing. Reports the allocations of the frames regularly.
+#[derive(Debug, Default)]
+pub struct FrameAudit {
+    frames: u32,
+    steady: Option<usize>,
+    /// Allocations of the current frame, and since the last report.
+    frame: u64,
+    allocations: u64,
+}
+
+impl FrameAudit {
+    pub fn start_frame(&mut self) {
+        self.frame = 0;
+        EXPECTED.store(false, Ordering::Relaxed);
+    }
+
+    /// Counts the allocations made while polling `frame`: the runtime polls it on any of its
+    /// threads, running other tasks between the polls.
+    pub fn count<F: Future + Unpin>(&mut self, frame: F) -> Counted<'_, F> {
+        Counted { audit: self, frame }
+    }
+
+    pub fn end_frame(&mut self) {
+        let live_bytes = LIVE_BYTES.load(Ordering::Relaxed);
+        let expected = EXPECTED.swap(false, Ordering::Relaxed);
+        self.frames += 1;
+        self.allocations += self.frame;
+
+        if self.frames.is_multiple_of(REPORT_FRAMES) {
+            info!(
+                "Allocation audit: {} allocations per frame, {} bytes in use.",
+                self.allocations / REPORT_FRAMES as u64,
+                live_bytes
+            );
+
+            self.allocations = 0;
+        }
+
+        if self.frames < WARM_UP_FRAMES {
+            return;
+        }
+        let steady = *self.steady.get_or_insert(live_bytes);
+
+        assert!(
+            expected || self.frame == 0,
+            "Frame {} allocated {} times.",
+            self.frames,
+            self.frame
+        );
+        assert!(
+            live_bytes <= steady + MAX_GROWTH,
+            "Memory in use grew from {} to {} bytes over {} frames.",
+            steady,
+            live_bytes,
+            self.frames - WARM_UP_FRAMES
+        );
+    }
+}
+
+/// A frame whose allocations are counted, see `FrameAudit::count`.
+pub struct Counted<'a, F> {
+    audit: &'a mut FrameAudit,
+    frame: F,
+}
+
+impl<F: Future + Unpin> Future for Counted<'_, F> {
+    type Output = F::Output;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<F::Output> {
+        let start = thread_allocations();
+        let poll = Pin::new(&mut self.frame).poll(cx);
+        self.audit.frame += thread_allocations() - start;
+
+        poll
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        color_manager::Pipeline,
+        config::Config,
+        control::ControlState,
+        effects::Effect,
+        mapping::{controller, zoned_controller},
+        notifications::Notification,
+        safety::FlashLimiter,
+        sampler::{Brightness, Samples},
+    };
+    use std::{
+        sync::Arc,
+        time::{Duration, UNIX_EPOCH},
+    };
+
+    /// Allocations of the current thread, and its memory in use, which can be negative when it
+    /// frees memory allocated by other threads.
+    fn thread() -> (u64, isize) {
+        (
+            THREAD_ALLOCATIONS.with(Cell::get),
+            THREAD_LIVE_BYTES.with(Cell::get),
+        )
+    }
+
+    #[test]
+    fn rendering_does_not_allocate() {
+        let config: Config = toml::from_str(
+            r#"
+            [[devices]]
+            name = "X570 AORUS ELITE"
+            burn_in = "shift"
+            filters = [
+                { type = "gamma", value = 2.2 },
+                { type = "power-cap", watts = 2.5 },
+                { type = "dither" },
+            ]
+            zones = [
+                { name = "D_LED2 Top", leds = 10, effect = { type = "gradient" } },
+                { name = "Motherboard", effect = { type = "histogram" } },
+            ]
+
+            [[devices]]
+            name = "Corsair Vengeance Pro RGB"
+            effect = { type = "traffic-light", thresholds = [0.6, 0.9], blink = true }
+
+            [[devices]]
+            name = "Corsair Lighting Node Pro"
+            effect = { type = "rainbow" }
+            "#,
+        )
+        .unwrap();
+        let controllers = [
+            zoned_controller(
+                "X570 AORUS ELITE",
+                &[("D_LED2 Top", 12), ("Motherboard", 4)],
+            ),
+            controller("Corsair Vengeance Pro RGB", ""),
+            zoned_controller(
+                "Corsair Lighting Node Pro",
+                &[("Channel 1", 32), ("Channel 2", 16)],
+            ),
+        ];
+
+        let control = ControlState::new(Arc::new(config.clone()));
+        let mut pipeline = Pipeline::new(FlashLimiter::new(&config.safety), false);
+        for (id, controller) in controllers.into_iter().enumerate() {
+            let key = format!("{} ()", controller.name);
+            pipeline.add(&config, &control, id as u32, key, controller);
+        }
+
+        let samples = Samples {
+            cpu: 0.7,
+            gpu: Some(0.3),
+            cpu_temperature: None,
+            gpu_temperature: None,
+            memory: Some(0.5),
+            disk: None,
+            power: None,
+            thermal: None,
+            battery: None,
+            mail: None,
+            brightness: Brightness::default(),
+        };
+        for second in 0..100 {
+            control.history.push(
+                UNIX_EPOCH + Duration::from_secs(second),
+                samples,
+                config.history.retention,
+            );
+        }
+        let usage = samples.usage();
+        let mut render = |frame: u64| {
+            let now = UNIX_EPOCH + Duration::from_millis(frame * 50);
+            for index in 0..3 {
+                pipeline.render(index, &config, &usage, &control.history, &control, now);
+            }
+        };
+
+        // Grows the buffers to the controllers.
+        render(0);
+
+        let start = thread();
+        for frame in 1..100 {
+            render(frame);
+        }
+        assert_eq!(thread(), start);
+
+        // Rendered with a plan of their own.
+        control.notifications.show(Notification {
+            controller: "X570 AORUS ELITE".to_owned(),
+            zone: None,
+            effect: Some(Effect::Rainbow),
+            palette: None,
+            duration: Duration::from_secs(60),
+        });
+        EXPECTED.store(false, Ordering::Relaxed);
+        render(100);
+        assert!(EXPECTED.load(Ordering::Relaxed));
+    }
+}
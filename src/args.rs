@@ -0,0 +1,544 @@
+use crate::{
+    config::parse_color,
+    connection::{ConnectionOptions, TlsOptions, DEFAULT_CONNECT_TIMEOUT, DEFAULT_PORT},
+    control::{parse_duration, parse_switch, Injection},
+    demo::Waveform,
+    logging::LogFilters,
+    profile::Profile,
+    remote::Remote,
+    sampler::SamplingOptions,
+    server::ServerOptions,
+    state::SafeModeOptions,
+    stress::StressOptions,
+    test_pattern::Pattern,
+};
+use anyhow::{anyhow, bail, Context, Result};
+use openrgb::data::Color;
+use std::{fmt, path::PathBuf, str::FromStr, time::Duration};
+
+/// Prefix of the environment variables standing for options, for services whose command line
+/// is inconvenient to edit, e.g. `OPENRGB_CLIENT_PORT` for `--port`.
+const ENV_PREFIX: &str = "OPENRGB_CLIENT_";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Command {
+    Run,
+    Install,
+    Remove,
+    Service,
+    Discover,
+    Status,
+    Setup,
+    ExplainMapping,
+    ListDevices,
+    CheckConfig,
+    InitConfig,
+    Explain,
+    StressTest,
+    Demo,
+    TestPattern,
+    SetColor,
+    Inject,
+    Play,
+    SwitchProfile,
+    SwitchScene,
+    History,
+    Enable,
+    Disable,
+    ReducedMotion,
+    HighContrast,
+    LogLevel,
+    Relay,
+    SetSecret,
+    DeleteSecret,
+    ImportLayout,
+    MigrateBuiltin,
+}
+
+/// Controller, and optionally zone, given to `explain`, `test-pattern`, `set-color`, `enable` and
+/// `disable`.
+#[derive(Debug, Clone, Default)]
+pub struct Target {
+    /// Controller name, as reported by OpenRGB.
+    pub controller: String,
+    pub zone: Option<String>,
+}
+
+/// Metric values to inject into the running client, see `control`.
+#[derive(Debug, Clone)]
+pub struct InjectOptions {
+    pub injections: Vec<Injection>,
+    pub duration: Duration,
+}
+
+impl Default for InjectOptions {
+    fn default() -> Self {
+        Self {
+            injections: vec![],
+            duration: Duration::from_secs(30),
+        }
+    }
+}
+
+/// What to do when the sampling and rendering pipeline panics.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PanicPolicy {
+    /// Abort the process and let the service manager restart it.
+    Abort,
+    /// Restart the pipeline within the process.
+    Restart,
+    /// Restart the pipeline in safe mode.
+    SafeMode,
+}
+
+impl FromStr for PanicPolicy {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self> {
+        match value.to_ascii_lowercase().as_str() {
+            "abort" => Ok(Self::Abort),
+            "restart" => Ok(Self::Restart),
+            "safe-mode" => Ok(Self::SafeMode),
+            _ => bail!("Unknown panic policy: {}", value),
+        }
+    }
+}
+
+impl fmt::Display for PanicPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Abort => "abort",
+            Self::Restart => "restart",
+            Self::SafeMode => "safe-mode",
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Args {
+    pub command: Command,
+    /// Configuration file, instead of `open_rgb_client.toml` in the configuration directory.
+    pub config: Option<PathBuf>,
+    /// Directory of the configuration, state and log files, instead of the platform ones.
+    pub data_dir: Option<PathBuf>,
+    /// Language of the terminal output, instead of the system one.
+    pub language: Option<String>,
+    /// Verbosity of the log, instead of info, and of the modules logging more or less. For
+    /// `log-level`, the one the running client switches to.
+    pub log_level: Option<LogFilters>,
+    /// Defaults of this kind of machine, instead of the detected one.
+    pub profile: Option<Profile>,
+    pub connection: ConnectionOptions,
+    pub sampling: SamplingOptions,
+    pub server: Option<ServerOptions>,
+    /// Running along with OpenRGB, which is always launched or followed.
+    pub sidecar: bool,
+    pub safe_mode: SafeModeOptions,
+    pub panic_policy: PanicPolicy,
+    pub target: Target,
+    pub stress: StressOptions,
+    /// Pattern shown by `test-pattern`.
+    pub pattern: Pattern,
+    /// Color set by `set-color`.
+    pub color: Color,
+    /// Load shown by `demo`.
+    pub waveform: Waveform,
+    /// Log the LED updates instead of sending them.
+    pub dry_run: bool,
+    /// `--install` and `--remove` a scheduled task rather than a service, on Windows.
+    pub scheduled_task: bool,
+    /// Print the colors of each frame in the terminal.
+    pub preview: bool,
+    pub inject: InjectOptions,
+    /// Sequence to play, for `play`.
+    pub sequence: String,
+    /// Named profile to switch to, for `switch-profile`, `None` for the configuration without
+    /// profile.
+    pub named_profile: Option<String>,
+    /// Scene to switch to, for `switch-scene`, `None` for the configuration without scene.
+    pub scene: Option<String>,
+    /// Time the samples printed by `history` go back.
+    pub history: Duration,
+    /// On or off, for `reduced-motion` and `high-contrast`.
+    pub switch: bool,
+    /// Secret name, for `secret set` and `secret delete`.
+    pub secret: String,
+    /// Visual Map export read by `import-layout`.
+    pub visual_map: PathBuf,
+    /// Machine the control commands go to over SSH, instead of the client running here.
+    pub remote: Option<Remote>,
+    /// Arguments this version doesn't know, ignored with a warning so the command lines of
+    /// services installed by other versions keep working.
+    pub ignored: Vec<String>,
+}
+
+impl Args {
+    /// Options from the command line, and from the environment for the ones it doesn't give.
+    pub fn from_env(sidecar: bool) -> Result<Self> {
+        Self::parse(
+            std::env::args().skip(1),
+            |name| std::env::var(format!("{}{}", ENV_PREFIX, name)).ok(),
+            sidecar,
+        )
+    }
+
+    /// Options from `args`, `env` giving the value of an environment variable without its
+    /// prefix. `HOST` is a comma separated list of hosts.
+    pub fn parse(
+        args: impl IntoIterator<Item = String>,
+        env: impl Fn(&str) -> Option<String>,
+        sidecar: bool,
+    ) -> Result<Self> {
+        let env = |name: &str| env(name).filter(|value| !value.is_empty());
+
+        let mut command = Command::Run;
+        let mut config = None;
+        let mut data_dir = None;
+        let mut language = None;
+        let mut log_level = parse_env(&env, "LOG_LEVEL")?;
+        let mut profile = None;
+        let mut connection = ConnectionOptions::default();
+        if let Some(port) = parse_env(&env, "PORT")? {
+            connection.port = port;
+        }
+        let mut sampling = SamplingOptions::default();
+        let mut tls = false;
+        let mut tls_options = TlsOptions::default();
+        let mut ignored = vec![];
+        let mut launch_server = false;
+        let mut server = ServerOptions::default();
+        let mut safe_mode = SafeModeOptions::default();
+        let mut panic_policy = PanicPolicy::Abort;
+        let mut target = Target::default();
+        let mut stress = StressOptions::default();
+        let mut pattern = Pattern::default();
+        let mut color = None;
+        let mut waveform = Waveform::default();
+        let mut dry_run = false;
+        let mut preview = false;
+        let mut scheduled_task = false;
+        let mut inject = InjectOptions::default();
+        let mut sequence = String::new();
+        let mut named_profile = None;
+        let mut scene = None;
+        let mut history = Duration::from_secs(10 * 60);
+        let mut switch = false;
+        let mut secret = String::new();
+        let mut visual_map = PathBuf::new();
+        let mut remote = None;
+        let mut remote_command = "open_rgb_client".to_owned();
+
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            let mut value = || {
+                args.next()
+                    .ok_or_else(|| anyhow!("Missing value for {}.", arg))
+            };
+
+            match arg.to_ascii_lowercase().as_str() {
+                "--install" => command = Command::Install,
+                "--remove" => command = Command::Remove,
+                "--service" => command = Command::Service,
+                "discover" => command = Command::Discover,
+                "status" => command = Command::Status,
+                "setup" => command = Command::Setup,
+                "explain-mapping" => command = Command::ExplainMapping,
+                "list-devices" => command = Command::ListDevices,
+                "--check-config" => command = Command::CheckConfig,
+                "--init-config" => command = Command::InitConfig,
+                "explain" => {
+                    command = Command::Explain;
+                    target.controller = value()?;
+                }
+                "enable" | "disable" => {
+                    command = match arg.to_ascii_lowercase().as_str() {
+                        "enable" => Command::Enable,
+                        _ => Command::Disable,
+                    };
+                    target.controller = value()?;
+                }
+                "reduced-motion" => {
+                    command = Command::ReducedMotion;
+                    switch = parse_switch(&value()?)?;
+                }
+                "log-level" => {
+                    command = Command::LogLevel;
+                    log_level = Some(value()?.parse()?);
+                }
+                "high-contrast" => {
+                    command = Command::HighContrast;
+                    switch = parse_switch(&value()?)?;
+                }
+                "secret" => {
+                    command = match value()?.to_ascii_lowercase().as_str() {
+                        "set" => Command::SetSecret,
+                        "delete" => Command::DeleteSecret,
+                        action => bail!("Unknown secret action: {}", action),
+                    };
+                    secret = value()?;
+                }
+                "relay" => command = Command::Relay,
+                "import-layout" => {
+                    command = Command::ImportLayout;
+                    visual_map = value()?.into();
+                }
+                "migrate-builtin" => command = Command::MigrateBuiltin,
+                "--remote" => remote = Some(value()?),
+                "--remote-command" => remote_command = value()?,
+                "--zone" => target.zone = Some(value()?),
+                "stress-test" => command = Command::StressTest,
+                "test-pattern" => {
+                    command = Command::TestPattern;
+                    target.controller = value()?;
+                }
+                "--pattern" => pattern = value()?.parse()?,
+                "set-color" => command = Command::SetColor,
+                "--controller" => target.controller = value()?,
+                "--color" => color = Some(parse_color(&value()?)?),
+                "demo" => command = Command::Demo,
+                "--waveform" => waveform = value()?.parse()?,
+                "--dry-run" => dry_run = true,
+                "--preview" => preview = true,
+                "--scheduled-task" => scheduled_task = true,
+                "inject" => command = Command::Inject,
+                "play" => {
+                    command = Command::Play;
+                    sequence = value()?;
+                }
+                "switch-profile" => {
+                    command = Command::SwitchProfile;
+                    named_profile = Some(value()?).filter(|name| name != "none");
+                }
+                "switch-scene" => {
+                    command = Command::SwitchScene;
+                    scene = Some(value()?).filter(|name| name != "none");
+                }
+                "history" => command = Command::History,
+                "--last" => history = parse_duration(&value()?)?,
+                "--for" => inject.duration = parse_duration(&value()?)?,
+                _ if command == Command::Inject && arg.contains('=') => {
+                    inject.injections.push(arg.parse()?)
+                }
+                "--fps" => stress.fps = value()?.parse()?,
+                "--duration" => {
+                    stress.duration = Duration::from_secs(value()?.parse::<u64>()? * 60)
+                }
+                "--config" => config = Some(value()?.into()),
+                "--data-dir" => data_dir = Some(value()?.into()),
+                "--lang" => language = Some(value()?),
+                "--log-level" => log_level = Some(value()?.parse()?),
+                "--profile" => profile = Some(value()?.parse()?),
+                "--host" => connection.hosts.push(value()?.parse()?),
+                "--port" => connection.port = value()?.parse()?,
+                "--connect-timeout" => {
+                    connection.timeout = Duration::from_millis(value()?.parse()?)
+                }
+                "--sample-interval" => sampling.interval = Some(value()?.parse()?),
+                "--averaging-window" => sampling.window = Some(value()?.parse()?),
+                "--discover-port" => connection.discover_port = true,
+                "--mdns" if cfg!(feature = "mdns") => connection.mdns = true,
+                "--mdns" => bail!("This build has no mDNS support."),
+                "--tls" => tls = true,
+                "--tls-server-name" => tls_options.server_name = Some(value()?),
+                "--tls-ca" => tls_options.ca_file = Some(value()?.into()),
+                "--tls-pin" => tls_options.pins.push(value()?),
+                "--launch-server" => launch_server = true,
+                "--server-path" => server.path = value()?.into(),
+                "--server-args" => {
+                    server.args = value()?.split_whitespace().map(String::from).collect()
+                }
+                "--safe-mode-restarts" => safe_mode.restarts = value()?.parse()?,
+                "--safe-mode-window" => {
+                    safe_mode.window = Duration::from_secs(value()?.parse::<u64>()? * 60)
+                }
+                "--panic-policy" => panic_policy = value()?.parse()?,
+                _ => ignored.push(arg),
+            }
+        }
+
+        connection.tls = tls.then_some(tls_options);
+
+        if command == Command::SetColor {
+            if target.controller.is_empty() {
+                bail!("set-color needs a --controller.");
+            }
+            if color.is_none() {
+                bail!("set-color needs a --color.");
+            }
+        }
+
+        if connection.hosts.is_empty() {
+            if let Some(hosts) = env("HOST") {
+                connection.hosts = hosts
+                    .split(',')
+                    .map(|host| host.trim().parse())
+                    .collect::<Result<_>>()
+                    .with_context(|| format!("Invalid {}HOST", ENV_PREFIX))?;
+            }
+        }
+        if profile.is_none() {
+            profile = parse_env(&env, "PROFILE")?;
+        }
+
+        Ok(Self {
+            command,
+            config,
+            data_dir,
+            language,
+            log_level,
+            profile,
+            connection,
+            sampling,
+            server: (launch_server || sidecar).then_some(server),
+            sidecar,
+            safe_mode,
+            panic_policy,
+            target,
+            stress,
+            pattern,
+            color: color.unwrap_or_default(),
+            waveform,
+            dry_run,
+            scheduled_task,
+            preview,
+            inject,
+            sequence,
+            named_profile,
+            scene,
+            history,
+            switch,
+            secret,
+            visual_map,
+            remote: remote.map(|destination| Remote {
+                destination,
+                command: remote_command,
+            }),
+            ignored,
+        })
+    }
+
+    /// Arguments the installed service must be started with to reproduce the current options.
+    pub fn service_arguments(&self) -> Vec<String> {
+        let mut arguments = vec![];
+
+        if let Some(config) = &self.config {
+            // The service doesn't run from the current directory.
+            let config = std::path::absolute(config).unwrap_or_else(|_| config.clone());
+
+            arguments.push("--config".to_owned());
+            arguments.push(config.to_string_lossy().into_owned());
+        }
+
+        if let Some(data_dir) = &self.data_dir {
+            let data_dir = std::path::absolute(data_dir).unwrap_or_else(|_| data_dir.clone());
+
+            arguments.push("--data-dir".to_owned());
+            arguments.push(data_dir.to_string_lossy().into_owned());
+        }
+
+        for host in &self.connection.hosts {
+            arguments.push("--host".to_owned());
+            arguments.push(host.to_string());
+        }
+
+        if self.connection.port != DEFAULT_PORT {
+            arguments.push("--port".to_owned());
+            arguments.push(self.connection.port.to_string());
+        }
+
+        if self.connection.timeout != DEFAULT_CONNECT_TIMEOUT {
+            arguments.push("--connect-timeout".to_owned());
+            arguments.push(self.connection.timeout.as_millis().to_string());
+        }
+
+        if self.connection.discover_port {
+            arguments.push("--discover-port".to_owned());
+        }
+
+        if self.connection.mdns {
+            arguments.push("--mdns".to_owned());
+        }
+
+        if let Some(tls) = &self.connection.tls {
+            arguments.push("--tls".to_owned());
+
+            if let Some(server_name) = &tls.server_name {
+                arguments.push("--tls-server-name".to_owned());
+                arguments.push(server_name.clone());
+            }
+
+            if let Some(ca_file) = &tls.ca_file {
+                arguments.push("--tls-ca".to_owned());
+                arguments.push(ca_file.to_string_lossy().into_owned());
+            }
+
+            for pin in &tls.pins {
+                arguments.push("--tls-pin".to_owned());
+                arguments.push(pin.clone());
+            }
+        }
+
+        if let Some(log_level) = &self.log_level {
+            arguments.push("--log-level".to_owned());
+            arguments.push(log_level.to_string());
+        }
+
+        if let Some(profile) = self.profile {
+            arguments.push("--profile".to_owned());
+            arguments.push(profile.to_string());
+        }
+
+        if let Some(interval) = self.sampling.interval {
+            arguments.push("--sample-interval".to_owned());
+            arguments.push(interval.to_string());
+        }
+
+        if let Some(window) = self.sampling.window {
+            arguments.push("--averaging-window".to_owned());
+            arguments.push(window.to_string());
+        }
+
+        let default_safe_mode = SafeModeOptions::default();
+        if self.safe_mode.restarts != default_safe_mode.restarts {
+            arguments.push("--safe-mode-restarts".to_owned());
+            arguments.push(self.safe_mode.restarts.to_string());
+        }
+
+        if self.safe_mode.window != default_safe_mode.window {
+            arguments.push("--safe-mode-window".to_owned());
+            arguments.push((self.safe_mode.window.as_secs() / 60).to_string());
+        }
+
+        if self.panic_policy != PanicPolicy::Abort {
+            arguments.push("--panic-policy".to_owned());
+            arguments.push(self.panic_policy.to_string());
+        }
+
+        if let Some(server) = &self.server {
+            arguments.push("--launch-server".to_owned());
+            arguments.push("--server-path".to_owned());
+            arguments.push(server.path.to_string_lossy().into_owned());
+
+            if !server.args.is_empty() {
+                arguments.push("--server-args".to_owned());
+                arguments.push(server.args.join(" "));
+            }
+        }
+
+        arguments
+    }
+}
+
+/// Parses the environment variable `name`, given without its prefix.
+fn parse_env<T: FromStr>(env: &impl Fn(&str) -> Option<String>, name: &str) -> Result<Option<T>>
+where
+    T::Err: fmt::Display,
+{
+    env(name)
+        .map(|value| {
+            value
+                .parse()
+                .map_err(|e| anyhow!("Invalid {}{}: {}", ENV_PREFIX, name, e))
+        })
+        .transpose()
+}
@@ -0,0 +1,44 @@
+use crate::sampler::read_file;
+use anyhow::Result;
+use std::path::PathBuf;
+
+/// Samples the charge of the first battery reported by the kernel, e.g. a laptop or handheld's.
+pub struct BatteryMonitor {
+    capacity: Option<PathBuf>,
+    /// Contents of `capacity`, kept from one sample to the next.
+    buffer: String,
+}
+
+impl BatteryMonitor {
+    pub fn new() -> Self {
+        let capacity = std::fs::read_dir("/sys/class/power_supply")
+            .into_iter()
+            .flatten()
+            .flatten()
+            .map(|supply| supply.path())
+            .find(|supply| {
+                std::fs::read_to_string(supply.join("type"))
+                    .is_ok_and(|kind| kind.trim() == "Battery")
+            })
+            .map(|supply| supply.join("capacity"));
+
+        Self {
+            capacity,
+            buffer: String::new(),
+        }
+    }
+
+    /// Drained charge from 0 (full) to 1 (empty), so a low battery shows the load color, or
+    /// `None` without a battery.
+    pub fn drain(&mut self) -> Result<Option<f32>> {
+        let Some(capacity) = &self.capacity else {
+            return Ok(None);
+        };
+
+        let capacity = read_file(capacity, &mut self.buffer)?
+            .trim()
+            .parse::<f32>()?;
+
+        Ok(Some(1.0 - (capacity / 100.0).clamp(0.0, 1.0)))
+    }
+}
@@ -0,0 +1,8 @@
+// The client built to be started instead of OpenRGB, e.g. from its shortcut or autostart entry:
+// it launches OpenRGB with its SDK server, or follows the one already running, renders the
+// same configuration while it runs and exits with it, so nothing has to be installed as a
+// service.
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    open_rgb_client::run(true).await
+}
@@ -0,0 +1,122 @@
+use crate::{
+    color_manager::{average, render, Usage},
+    config::{optional_hex_color, Config, Metric},
+    connection::{ConnectionOptions, Connector},
+    history::History,
+    mapping::{resolve, ControllerInfo},
+    sdk, sync,
+};
+use anyhow::Result;
+use log::*;
+use openrgb::data::{Color, ColorMode, Controller, Mode, ModeFlag};
+use serde::{Deserialize, Serialize};
+
+/// Frame saved by `--install` to the controllers that can keep a mode in their flash memory,
+/// so the LEDs show it from power-on until the service connects.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BootConfig {
+    /// Color of every LED, instead of the idle frame of the configuration.
+    #[serde(with = "optional_hex_color", skip_serializing_if = "Option::is_none")]
+    pub color: Option<Color>,
+}
+
+/// How the frame is shown by a mode that can be saved.
+enum Fill {
+    /// The colors of the LEDs are saved with the mode.
+    PerLed,
+    /// The mode has its own colors, the LEDs all get the average color of the frame.
+    ModeColors,
+}
+
+/// Saves the boot frame to every controller the configuration doesn't leave alone, switching
+/// them back to their current mode afterwards.
+pub async fn save(
+    config: &Config,
+    boot: &BootConfig,
+    connection: &ConnectionOptions,
+) -> Result<()> {
+    let mut connector = Connector::new(connection)?;
+    let client = connector.connect().await?;
+    let mut stream = connector.raw_stream().await?;
+
+    for controller_id in 0..client.get_controller_count().await? {
+        let controller = client.get_controller(controller_id).await?;
+
+        let info = ControllerInfo::from(&controller).with_overrides(config);
+        let plan = resolve(config, &info);
+        if matches!(plan.metric, None | Some(Metric::None)) {
+            continue;
+        }
+
+        let Some((mode_id, fill)) = savable_mode(&controller) else {
+            info!(
+                "{} can't save a mode, it keeps its own colors at boot.",
+                controller.name
+            );
+
+            continue;
+        };
+
+        let colors = match boot.color {
+            Some(color) => vec![color; controller.leds.len()],
+            None => info.fit(
+                &ControllerInfo::from(&controller),
+                render(&plan, &Usage::default(), &History::default(), sync::now()),
+            ),
+        };
+
+        let mut mode = sdk::copy_mode(&controller.modes[mode_id]);
+        match fill {
+            Fill::PerLed => {
+                mode.color_mode = Some(ColorMode::PerLED);
+
+                // The colors go over the client's connection, the count comes back once the
+                // server has applied them.
+                client.update_leds(controller_id, colors).await?;
+                client.get_controller_count().await?;
+            }
+            Fill::ModeColors => {
+                mode.color_mode = Some(ColorMode::ModeSpecific);
+                mode.colors = vec![average(&colors); mode.colors.len()];
+            }
+        }
+
+        sdk::save_mode(&mut stream, controller_id, mode_id, &mode).await?;
+        info!(
+            "Saved the boot frame to {} in mode {}.",
+            controller.name, mode.name
+        );
+
+        let active_mode = controller.active_mode as usize;
+        if active_mode != mode_id {
+            if let Some(active) = controller.modes.get(active_mode) {
+                sdk::update_mode(&mut stream, &mut vec![], controller_id, active_mode, active)
+                    .await?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// First mode that can be saved showing the frame, per-LED colors first.
+fn savable_mode(controller: &Controller) -> Option<(usize, Fill)> {
+    let savable = |mode: &Mode, flag: ModeFlag| {
+        mode.flags.contains(ModeFlag::ManualSave) && mode.flags.contains(flag)
+    };
+
+    let modes = &controller.modes;
+    modes
+        .iter()
+        .position(|mode| savable(mode, ModeFlag::HasPerLEDColor))
+        .map(|mode_id| (mode_id, Fill::PerLed))
+        .or_else(|| {
+            modes
+                .iter()
+                .position(|mode| {
+                    savable(mode, ModeFlag::HasModeSpecificColor) && !mode.colors.is_empty()
+                })
+                .map(|mode_id| (mode_id, Fill::ModeColors))
+        })
+}
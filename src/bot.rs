@@ -0,0 +1,231 @@
+use serde::{Deserialize, Serialize};
+
+/// Chat bot checking on and adjusting the client from anywhere, through Telegram, when built
+/// with the `bot` feature.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BotConfig {
+    /// Token of the bot, from @BotFather. Best kept as a `secret://` name.
+    pub token: String,
+    /// Chats allowed to use the bot, the messages of the others are ignored and their chat
+    /// logged, to find out one's own.
+    #[serde(default)]
+    pub chats: Vec<i64>,
+}
+
+#[cfg(feature = "bot")]
+pub use telegram::run;
+
+#[cfg(feature = "bot")]
+mod telegram {
+    use super::BotConfig;
+    use crate::{
+        budget::{source, Budget, Source},
+        control::{self, ControlRequest, ControlState},
+        i18n::tr,
+    };
+    use anyhow::{anyhow, bail, Result};
+    use log::*;
+    use serde::{de::DeserializeOwned, Deserialize};
+    use serde_json::json;
+    use std::{sync::Arc, time::Duration};
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::TcpStream,
+        time::{sleep, timeout},
+    };
+    use tokio_rustls::{
+        rustls::{crypto::ring, pki_types::ServerName, ClientConfig, RootCertStore},
+        TlsConnector,
+    };
+
+    const API_HOST: &str = "api.telegram.org";
+    /// Requests to the Bot API: the long polls take about one a minute, the answers the rest.
+    const TELEGRAM_BUDGET: Budget = Budget::per_hour(300);
+    /// Time a poll waits for messages before returning empty.
+    const POLL_TIMEOUT: Duration = Duration::from_secs(50);
+    const RETRY_DELAY: Duration = Duration::from_secs(30);
+
+    #[derive(Debug, Deserialize)]
+    struct Response<T> {
+        ok: bool,
+        result: Option<T>,
+        description: Option<String>,
+    }
+
+    #[derive(Debug, Deserialize)]
+    struct Update {
+        update_id: i64,
+        message: Option<Message>,
+    }
+
+    #[derive(Debug, Deserialize)]
+    struct Message {
+        chat: Chat,
+        text: Option<String>,
+    }
+
+    #[derive(Debug, Deserialize)]
+    struct Chat {
+        id: i64,
+    }
+
+    /// Answers the commands of the allowed chats until aborted, polling again after errors.
+    pub async fn run(config: BotConfig, state: Arc<ControlState>) {
+        let telegram = source("telegram", &state.config().budgets, TELEGRAM_BUDGET);
+        let mut offset = 0;
+
+        loop {
+            let updates = call::<Vec<Update>>(
+                &config.token,
+                &telegram,
+                "getUpdates",
+                json!({
+                    "offset": offset,
+                    "timeout": POLL_TIMEOUT.as_secs(),
+                    "allowed_updates": ["message"],
+                }),
+            )
+            .await;
+            let updates = match updates {
+                Ok(updates) => updates,
+                Err(e) => {
+                    warn!("Telegram bot unavailable: {}", e);
+                    sleep(RETRY_DELAY).await;
+
+                    continue;
+                }
+            };
+
+            for update in updates {
+                offset = update.update_id + 1;
+
+                let Some(Message {
+                    chat,
+                    text: Some(text),
+                }) = update.message
+                else {
+                    continue;
+                };
+                if !config.chats.contains(&chat.id) {
+                    info!(
+                        "Ignored a bot message from chat {}, add it to `chats` to allow it.",
+                        chat.id
+                    );
+
+                    continue;
+                }
+
+                let answer = answer(&text, &state);
+                if let Err(e) = call::<serde_json::Value>(
+                    &config.token,
+                    &telegram,
+                    "sendMessage",
+                    json!({ "chat_id": chat.id, "text": answer }),
+                )
+                .await
+                {
+                    warn!("Failed to answer the bot message: {}", e);
+                }
+            }
+        }
+    }
+
+    /// Carries out a command, e.g. `/theme nord`, and returns the answer.
+    fn answer(text: &str, state: &ControlState) -> String {
+        let mut words = text.split_whitespace();
+        // Commands get the name of the bot appended in groups, e.g. `/status@rig_bot`.
+        let command = words
+            .next()
+            .and_then(|command| command.split('@').next())
+            .unwrap_or_default();
+        let argument = words.collect::<Vec<_>>().join(" ");
+
+        let (request, done) = match command {
+            "/status" => return state.live_status().lines().join("\n"),
+            "/pause" => (ControlRequest::Brightness(0.0), tr!("bot-paused")),
+            "/resume" => (ControlRequest::Brightness(1.0), tr!("bot-resumed")),
+            "/theme" if argument.is_empty() => {
+                return tr!("bot-themes", "themes" => state.profiles.names().join(", "));
+            }
+            "/theme" => {
+                let name = Some(argument).filter(|name| name != "none");
+                let done = tr!(
+                    "bot-theme",
+                    "theme" => name.clone().unwrap_or_else(|| "none".to_owned())
+                );
+
+                (ControlRequest::SwitchProfile { name }, done)
+            }
+            _ => return tr!("bot-help"),
+        };
+
+        match control::apply(request, state, &state.config()) {
+            Ok(_) => done,
+            Err(e) => tr!("bot-error", "message" => e.to_string()),
+        }
+    }
+
+    /// Calls a method of the Bot API, the token staying out of the logs and errors.
+    async fn call<T: DeserializeOwned>(
+        token: &str,
+        telegram: &Source,
+        method: &str,
+        parameters: serde_json::Value,
+    ) -> Result<T> {
+        telegram.take().await;
+
+        let body = parameters.to_string();
+        // HTTP/1.0 so the response is never chunked.
+        let request = format!(
+            "POST /bot{}/{} HTTP/1.0\r\nHost: {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n{}",
+            token,
+            method,
+            API_HOST,
+            body.len(),
+            body
+        );
+
+        let response = timeout(POLL_TIMEOUT + RETRY_DELAY, async {
+            let mut stream = tls_connect().await?;
+            stream.write_all(request.as_bytes()).await?;
+
+            let mut response = vec![];
+            stream.read_to_end(&mut response).await?;
+
+            Ok::<_, anyhow::Error>(response)
+        })
+        .await
+        .map_err(|_| anyhow!("{} timed out.", method))??;
+
+        let response = String::from_utf8_lossy(&response);
+        let (_, body) = response
+            .split_once("\r\n\r\n")
+            .ok_or_else(|| anyhow!("Invalid response to {}.", method))?;
+        let response = serde_json::from_str::<Response<T>>(body)?;
+        match response.result {
+            Some(result) if response.ok => Ok(result),
+            _ => bail!(
+                "{} failed: {}",
+                method,
+                response.description.unwrap_or_default()
+            ),
+        }
+    }
+
+    async fn tls_connect() -> Result<tokio_rustls::client::TlsStream<TcpStream>> {
+        let mut roots = RootCertStore::empty();
+        roots.extend(webpki_roots::TLS_SERVER_ROOTS.iter().cloned());
+
+        let config = ClientConfig::builder_with_provider(Arc::new(ring::default_provider()))
+            .with_safe_default_protocol_versions()?
+            .with_root_certificates(roots)
+            .with_no_client_auth();
+
+        let stream = TcpStream::connect((API_HOST, 443)).await?;
+        let stream = TlsConnector::from(Arc::new(config))
+            .connect(ServerName::try_from(API_HOST)?, stream)
+            .await?;
+
+        Ok(stream)
+    }
+}
@@ -0,0 +1,168 @@
+// The network sources are optional features.
+#![cfg_attr(not(any(feature = "bot", feature = "mail")), allow(dead_code))]
+
+use crate::sequence::duration;
+use anyhow::Result;
+use log::*;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, HashMap},
+    future::Future,
+    sync::{Arc, Mutex, OnceLock},
+    time::{Duration, Instant},
+};
+
+/// Requests a network source may make over a period, e.g. 10 per hour. Unused requests add up
+/// to a burst of `requests`.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Budget {
+    pub requests: u32,
+    #[serde(with = "duration")]
+    pub per: Duration,
+}
+
+impl Budget {
+    pub const fn per_hour(requests: u32) -> Self {
+        Self {
+            requests,
+            per: Duration::from_secs(3600),
+        }
+    }
+}
+
+/// Token bucket refilled at the rate of a budget.
+#[derive(Debug)]
+struct TokenBucket {
+    budget: Budget,
+    tokens: f64,
+    refilled: Instant,
+}
+
+impl TokenBucket {
+    fn new(budget: Budget) -> Self {
+        Self {
+            budget,
+            tokens: budget.requests as f64,
+            refilled: Instant::now(),
+        }
+    }
+
+    /// Takes a token, or returns how long until the next one.
+    fn take(&mut self, now: Instant) -> Result<(), Duration> {
+        let rate = self.budget.requests as f64 / self.budget.per.as_secs_f64().max(f64::EPSILON);
+        let elapsed = now.saturating_duration_since(self.refilled).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * rate).min(self.budget.requests as f64);
+        self.refilled = now;
+
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+
+            Ok(())
+        } else if rate > 0.0 {
+            Err(Duration::from_secs_f64((1.0 - self.tokens) / rate))
+        } else {
+            Err(self.budget.per)
+        }
+    }
+}
+
+/// A network source, e.g. a mail server or an API, sharing its budget and cached responses
+/// across everything using it in the process.
+#[derive(Debug)]
+pub struct Source {
+    name: String,
+    bucket: Mutex<TokenBucket>,
+    cache: Mutex<HashMap<String, (String, Instant)>>,
+}
+
+impl Source {
+    /// Waits until the budget allows another request.
+    pub async fn take(&self) {
+        loop {
+            let wait = match self.bucket.lock().unwrap().take(Instant::now()) {
+                Ok(()) => return,
+                Err(wait) => wait,
+            };
+
+            info!(
+                "Request budget of {} used up, waiting {:.0?}.",
+                self.name, wait
+            );
+            tokio::time::sleep(wait).await;
+        }
+    }
+
+    /// Response cached under `key`, fetched within the budget when missing or expired. The
+    /// fetch returns the response and how long it stays valid.
+    pub async fn fetch<F>(&self, key: &str, fetch: F) -> Result<String>
+    where
+        F: Future<Output = Result<(String, Duration)>>,
+    {
+        if let Some((response, until)) = self.cache.lock().unwrap().get(key) {
+            if *until > Instant::now() {
+                return Ok(response.clone());
+            }
+        }
+
+        self.take().await;
+        let (response, valid) = fetch.await?;
+
+        self.cache
+            .lock()
+            .unwrap()
+            .insert(key.to_owned(), (response.clone(), Instant::now() + valid));
+
+        Ok(response)
+    }
+}
+
+/// The source named `name`, with the budget configured for it or `default`. Sources live as
+/// long as the process, so their budgets survive reconnects.
+pub fn source(name: &str, budgets: &BTreeMap<String, Budget>, default: Budget) -> Arc<Source> {
+    static SOURCES: OnceLock<Mutex<HashMap<String, Arc<Source>>>> = OnceLock::new();
+
+    let budget = budgets.get(name).copied().unwrap_or(default);
+
+    let mut sources = SOURCES.get_or_init(Default::default).lock().unwrap();
+    let source = sources.entry(name.to_owned()).or_insert_with(|| {
+        Arc::new(Source {
+            name: name.to_owned(),
+            bucket: Mutex::new(TokenBucket::new(budget)),
+            cache: Default::default(),
+        })
+    });
+
+    // Budget changed since the source was created.
+    let mut bucket = source.bucket.lock().unwrap();
+    if bucket.budget != budget {
+        *bucket = TokenBucket::new(budget);
+    }
+    drop(bucket);
+
+    source.clone()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bucket_refills_at_the_budget_rate() {
+        let start = Instant::now();
+        let mut bucket = TokenBucket::new(Budget {
+            requests: 2,
+            per: Duration::from_secs(60),
+        });
+        bucket.refilled = start;
+
+        assert!(bucket.take(start).is_ok());
+        assert!(bucket.take(start).is_ok());
+        assert_eq!(bucket.take(start), Err(Duration::from_secs(30)));
+
+        // Half a token after 15 seconds.
+        let later = start + Duration::from_secs(15);
+        assert_eq!(bucket.take(later), Err(Duration::from_secs(15)));
+
+        assert!(bucket.take(start + Duration::from_secs(30)).is_ok());
+    }
+}
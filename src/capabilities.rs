@@ -0,0 +1,311 @@
+use crate::{
+    color_manager::average,
+    config::{format_color, Config},
+    connection::Connector,
+    sdk,
+    state::controller_key,
+};
+use anyhow::{anyhow, Result};
+use log::*;
+use openrgb::{
+    data::{Color, ColorMode, Controller, Mode, ModeFlag},
+    OpenRGB,
+};
+use std::collections::{HashMap, HashSet};
+use tokio::net::TcpStream;
+
+/// Most LEDs changed in a frame that are updated one by one rather than with the whole frame.
+const SINGLE_LED_UPDATES: usize = 2;
+
+/// What a controller takes from the SDK, learned when connecting.
+#[derive(Debug, Default)]
+pub struct Capabilities {
+    /// Has a direct mode, whose colors aren't written to the controller's memory.
+    pub direct: bool,
+    /// Has a mode showing per-LED colors.
+    pub per_led: bool,
+    /// Has zones, which can be updated on their own.
+    pub zone_update: bool,
+    /// Has zones whose LED count can be changed.
+    pub resize: bool,
+    /// Mode with colors of its own, standing in for the per-LED colors.
+    mode_color: Option<(usize, Mode)>,
+}
+
+impl Capabilities {
+    pub fn of(controller: &Controller) -> Self {
+        let modes = &controller.modes;
+
+        Self {
+            direct: modes
+                .iter()
+                .any(|mode| mode.name.eq_ignore_ascii_case("direct")),
+            per_led: modes
+                .iter()
+                .any(|mode| mode.flags.contains(ModeFlag::HasPerLEDColor)),
+            zone_update: !controller.zones.is_empty(),
+            resize: controller
+                .zones
+                .iter()
+                .any(|zone| zone.leds_min < zone.leds_max),
+            // Modes saving themselves would wear out the memory at every frame.
+            mode_color: modes
+                .iter()
+                .position(|mode| {
+                    mode.flags.contains(ModeFlag::HasModeSpecificColor)
+                        && !mode.flags.contains(ModeFlag::AutomaticSave)
+                        && !mode.colors.is_empty()
+                })
+                .map(|mode_id| {
+                    let mut mode = sdk::copy_mode(&modes[mode_id]);
+                    mode.color_mode = Some(ColorMode::ModeSpecific);
+
+                    (mode_id, mode)
+                }),
+        }
+    }
+}
+
+/// How the frames of a controller are sent.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Strategy {
+    PerLed,
+    /// The average color of the frame, as the color of a mode.
+    ModeColor,
+    /// Nothing the controller takes shows colors, it's left alone.
+    Skip,
+}
+
+/// Capabilities of the controllers of the current connection and what was last sent to them,
+/// and the warnings about what they can't do, logged once per controller rather than at every
+/// frame.
+#[derive(Default)]
+pub struct CapabilityMap {
+    controllers: Vec<Capabilities>,
+    warned: HashSet<String>,
+    /// Connection the frames are sent over: the SDK client gets the mode colors wrong and
+    /// allocates every request.
+    stream: Option<TcpStream>,
+    /// Packet last sent over `stream`, its memory reused for the next one.
+    packet: Vec<u8>,
+    /// Whether the server reported its controller list changed since last asked.
+    list_changed: bool,
+    /// Last mode color sent to each controller.
+    sent: HashMap<u32, Color>,
+    /// Last colors sent to each controller's LEDs, to send only the ones that changed, empty
+    /// when unknown.
+    frames: HashMap<u32, Vec<Color>>,
+    /// Nothing is sent to the controllers, the colors are logged instead.
+    dry_run: bool,
+}
+
+impl CapabilityMap {
+    pub fn new(dry_run: bool) -> Self {
+        Self {
+            dry_run,
+            ..Default::default()
+        }
+    }
+
+    pub fn dry_run(&self) -> bool {
+        self.dry_run
+    }
+
+    /// Learns the capabilities of every controller, switching the ones driven with per-LED
+    /// colors to their direct or custom mode when they're in another one.
+    pub async fn learn(
+        &mut self,
+        client: &OpenRGB<TcpStream>,
+        connector: &Connector,
+        config: &Config,
+    ) -> Result<()> {
+        self.controllers.clear();
+        self.sent.clear();
+        self.frames.clear();
+        self.stream = None;
+        self.list_changed = false;
+        self.stream = Some(connector.raw_stream().await?);
+
+        for controller_id in 0..client.get_controller_count().await? {
+            let controller = client.get_controller(controller_id).await?;
+            let capabilities = Capabilities::of(&controller);
+            let key = controller_key(&controller);
+
+            // Left alone, whatever it can do.
+            if !config.allows(&controller.name, &controller.location) {
+                self.controllers.push(capabilities);
+
+                continue;
+            }
+
+            if capabilities.per_led {
+                let per_led_active = controller
+                    .modes
+                    .get(controller.active_mode as usize)
+                    .is_some_and(|mode| mode.flags.contains(ModeFlag::HasPerLEDColor));
+                if !per_led_active {
+                    info!("Switching {} to per-LED colors.", key);
+
+                    if !self.dry_run {
+                        client.set_custom_mode(controller_id).await?;
+                    }
+                }
+
+                if !capabilities.direct {
+                    self.warn_once(format!(
+                        "{} has no direct mode, it may write every frame to its memory.",
+                        key
+                    ));
+                }
+            } else if capabilities.mode_color.is_some() {
+                self.warn_once(format!(
+                    "{} has no per-LED colors, it shows the average color of its LEDs.",
+                    key
+                ));
+            } else {
+                self.warn_once(format!("{} can't show colors, it's left alone.", key));
+            }
+
+            self.controllers.push(capabilities);
+        }
+
+        Ok(())
+    }
+
+    /// Capabilities of a controller, `None` for the ones that showed up since connecting.
+    pub fn get(&self, controller_id: u32) -> Option<&Capabilities> {
+        self.controllers.get(controller_id as usize)
+    }
+
+    pub fn strategy(&self, controller_id: u32) -> Strategy {
+        match self.get(controller_id) {
+            None => Strategy::PerLed,
+            Some(capabilities) if capabilities.per_led => Strategy::PerLed,
+            Some(capabilities) if capabilities.mode_color.is_some() => Strategy::ModeColor,
+            Some(_) => Strategy::Skip,
+        }
+    }
+
+    /// Number of controllers of the server, which answers once it applied the frames sent
+    /// before.
+    pub async fn controller_count(&mut self) -> Result<u32> {
+        let (count, changed) = sdk::controller_count(connected(&mut self.stream)?).await?;
+        self.list_changed |= changed;
+
+        Ok(count)
+    }
+
+    /// Whether the server reported its controller list changed since the last call.
+    pub fn list_changed(&mut self) -> bool {
+        std::mem::take(&mut self.list_changed)
+    }
+
+    /// Shows the average of `colors` with the mode color of a controller, when it changed.
+    /// Writes the colors shown to `shown`.
+    pub async fn send_mode_color(
+        &mut self,
+        controller_id: u32,
+        colors: &[Color],
+        shown: &mut Vec<Color>,
+    ) -> Result<()> {
+        let color = average(colors);
+
+        shown.clear();
+        let Some((mode_id, mode)) = self
+            .controllers
+            .get_mut(controller_id as usize)
+            .and_then(|capabilities| capabilities.mode_color.as_mut())
+        else {
+            return Ok(());
+        };
+        shown.resize(colors.len(), color);
+        if self.sent.get(&controller_id) == Some(&color) {
+            return Ok(());
+        }
+
+        mode.colors.fill(color);
+        if self.dry_run {
+            info!(
+                "Dry run: controller {}, mode {}: {}.",
+                controller_id,
+                mode.name,
+                format_color(&color)
+            );
+        } else {
+            let stream = connected(&mut self.stream)?;
+            sdk::update_mode(stream, &mut self.packet, controller_id, *mode_id, mode).await?;
+        }
+        self.sent.insert(controller_id, color);
+
+        Ok(())
+    }
+
+    /// Sends the colors of every LED of a controller, or only the few that changed since the
+    /// last frame, one by one, which is cheaper than the whole frame.
+    pub async fn send_leds(&mut self, controller_id: u32, colors: &[Color]) -> Result<()> {
+        let stream = connected(&mut self.stream)?;
+        let last = self.frames.entry(controller_id).or_default();
+
+        let changed = (last.len() == colors.len()).then(|| {
+            last.iter()
+                .zip(colors)
+                .filter(|(last, color)| last != color)
+                .count()
+        });
+        match changed {
+            Some(changed) if changed <= SINGLE_LED_UPDATES => {
+                for (index, (last, color)) in last.iter().zip(colors).enumerate() {
+                    if last != color {
+                        sdk::update_led(
+                            stream,
+                            &mut self.packet,
+                            controller_id,
+                            index as u32,
+                            *color,
+                        )
+                        .await?;
+                    }
+                }
+            }
+            _ => sdk::update_leds(stream, &mut self.packet, controller_id, colors).await?,
+        }
+
+        last.clear();
+        last.extend_from_slice(colors);
+
+        Ok(())
+    }
+
+    /// Sends the colors of the LEDs of a zone of a controller.
+    pub async fn send_zone_leds(
+        &mut self,
+        controller_id: u32,
+        zone_id: u32,
+        colors: &[Color],
+    ) -> Result<()> {
+        let stream = connected(&mut self.stream)?;
+
+        sdk::update_zone_leds(stream, &mut self.packet, controller_id, zone_id, colors).await
+    }
+
+    /// Forgets the last frame of a controller, whose LEDs weren't all sent.
+    pub fn forget_frame(&mut self, controller_id: u32) {
+        if let Some(last) = self.frames.get_mut(&controller_id) {
+            last.clear();
+        }
+    }
+
+    pub fn warn_once(&mut self, message: String) {
+        if !self.warned.contains(&message) {
+            warn!("{}", message);
+
+            self.warned.insert(message);
+        }
+    }
+}
+
+fn connected(stream: &mut Option<TcpStream>) -> Result<&mut TcpStream> {
+    stream
+        .as_mut()
+        .ok_or_else(|| anyhow!("Not connected to the OpenRGB server."))
+}
@@ -0,0 +1,190 @@
+use crate::{
+    config::{name_matches, Config},
+    connection::{ConnectionOptions, Connector},
+    i18n::tr,
+    mapping::ControllerInfo,
+};
+use anyhow::{bail, Result};
+use std::fmt;
+
+/// Something the configuration refers to that doesn't exist.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Problem {
+    /// An entry of `devices` matching no controller.
+    Controller { name: String },
+    /// A zone of a `devices` entry matching no zone of its controllers.
+    Zone { controller: String, zone: String },
+    /// A name of `controllers.allow` or `controllers.deny` matching no controller.
+    Listed { list: &'static str, name: String },
+    /// A sequence referred to by name that doesn't exist.
+    Sequence { name: String },
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let message = match self {
+            Self::Controller { name } => {
+                tr!("check-config-unknown-controller", "name" => name.as_str())
+            }
+            Self::Zone { controller, zone } => tr!(
+                "check-config-unknown-zone",
+                "controller" => controller.as_str(),
+                "zone" => zone.as_str()
+            ),
+            Self::Listed { list, name } => tr!(
+                "check-config-unknown-listed",
+                "list" => *list,
+                "name" => name.as_str()
+            ),
+            Self::Sequence { name } => {
+                tr!("check-config-unknown-sequence", "name" => name.as_str())
+            }
+        };
+
+        f.write_str(&message)
+    }
+}
+
+/// Checks the configuration against the controllers OpenRGB reports, without changing any of
+/// their colors. Fails when it finds problems.
+pub async fn check_config(config: &Config, connection: &ConnectionOptions) -> Result<()> {
+    let client = Connector::new(connection)?.connect().await?;
+
+    let mut controllers = vec![];
+    for controller_id in 0..client.get_controller_count().await? {
+        controllers.push(ControllerInfo::from(
+            &client.get_controller(controller_id).await?,
+        ));
+    }
+
+    let problems = problems(config, &controllers);
+    if problems.is_empty() {
+        println!(
+            "{}",
+            tr!("check-config-ok", "controllers" => controllers.len())
+        );
+
+        return Ok(());
+    }
+
+    for problem in &problems {
+        println!("{}", problem);
+    }
+
+    bail!("The configuration has {} problems.", problems.len())
+}
+
+/// Names of the configuration matching none of `controllers`, and references to sequences that
+/// don't exist.
+pub fn problems(config: &Config, controllers: &[ControllerInfo]) -> Vec<Problem> {
+    let mut problems = vec![];
+
+    for device in &config.devices {
+        let matching = controllers
+            .iter()
+            .filter(|controller| {
+                config.matches(&device.name, &controller.name, &controller.location)
+            })
+            .collect::<Vec<_>>();
+        if matching.is_empty() {
+            problems.push(Problem::Controller {
+                name: device.name.clone(),
+            });
+
+            continue;
+        }
+
+        for zone_config in &device.zones {
+            let found = matching.iter().any(|controller| {
+                controller
+                    .zones
+                    .iter()
+                    .any(|zone| name_matches(&zone_config.name, &zone.name))
+            });
+            if !found {
+                problems.push(Problem::Zone {
+                    controller: device.name.clone(),
+                    zone: zone_config.name.clone(),
+                });
+            }
+        }
+    }
+
+    for (list, names) in [
+        ("allow", &config.controllers.allow),
+        ("deny", &config.controllers.deny),
+    ] {
+        for name in names {
+            if !controllers
+                .iter()
+                .any(|controller| config.matches(name, &controller.name, &controller.location))
+            {
+                problems.push(Problem::Listed {
+                    list,
+                    name: name.clone(),
+                });
+            }
+        }
+    }
+
+    for name in [&config.started_sequence, &config.reconnected_sequence]
+        .into_iter()
+        .flatten()
+    {
+        if config.sequence(name).is_none() {
+            problems.push(Problem::Sequence { name: name.clone() });
+        }
+    }
+
+    problems
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mapping::zoned_controller;
+
+    #[test]
+    fn reports_names_matching_nothing() {
+        let config: Config = toml::from_str(
+            r#"
+            reconnected_sequence = "reconnected"
+
+            [controllers]
+            deny = ["Razer*"]
+
+            [[devices]]
+            name = "X570 AORUS ELITE"
+            zones = [{ name = "D_LED1 Bottom" }, { name = "D_LED3" }]
+
+            [[devices]]
+            name = "Corsair Lighting Node Pro"
+            "#,
+        )
+        .unwrap();
+        let controllers = [zoned_controller(
+            "X570 AORUS ELITE",
+            &[("D_LED1 Bottom", 8)],
+        )];
+
+        assert_eq!(
+            problems(&config, &controllers),
+            vec![
+                Problem::Zone {
+                    controller: "X570 AORUS ELITE".to_owned(),
+                    zone: "D_LED3".to_owned(),
+                },
+                Problem::Controller {
+                    name: "Corsair Lighting Node Pro".to_owned(),
+                },
+                Problem::Listed {
+                    list: "deny",
+                    name: "Razer*".to_owned(),
+                },
+                Problem::Sequence {
+                    name: "reconnected".to_owned(),
+                },
+            ]
+        );
+    }
+}
@@ -1,103 +1,704 @@
+use crate::{
+    capabilities::{CapabilityMap, Strategy},
+    config::{format_color, name_matches, Config, Metric, Palette},
+    connection::Connector,
+    control::ControlState,
+    effects::Effect,
+    filters::PostProcessor,
+    history::History,
+    layout::Point,
+    mapping::{resolve, ControllerInfo, Plan},
+    safety::FlashLimiter,
+    sampler::Brightness,
+    sdk,
+    state::controller_key,
+    sync,
+};
 use anyhow::Result;
+use log::*;
 use openrgb::{data::Color, OpenRGB};
+use std::time::{Instant, SystemTime};
 use tokio::net::TcpStream;
 
-pub async fn set_all_light_color(
+/// Value under which the metric is idle, and segments show their idle effect.
+const IDLE_LEVEL: f32 = 0.1;
+
+/// Latest sample of each metric, from 0 (idle) to 1 (full load).
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Usage {
+    pub cpu: f32,
+    pub gpu: f32,
+    pub cpu_temperature: f32,
+    pub gpu_temperature: f32,
+    pub memory: f32,
+    pub disk: f32,
+    pub power: f32,
+    pub thermal: f32,
+    pub battery: f32,
+    pub mail: f32,
+    pub brightness: Brightness,
+}
+
+impl Usage {
+    fn get(&self, metric: Metric) -> f32 {
+        match metric {
+            Metric::Cpu => self.cpu,
+            Metric::Gpu => self.gpu,
+            Metric::CpuTemperature => self.cpu_temperature,
+            Metric::GpuTemperature => self.gpu_temperature,
+            Metric::Memory => self.memory,
+            Metric::Disk => self.disk,
+            Metric::Power => self.power,
+            Metric::Thermal => self.thermal,
+            Metric::Battery => self.battery,
+            Metric::Mail => self.mail,
+            Metric::None => 0.0,
+        }
+    }
+}
+
+/// What the frames are rendered and sent with, kept from one frame to the next so that a frame
+/// allocates nothing: the controllers and their plans, set up again when the server's list
+/// changes, and the buffers the colors go through.
+pub struct Pipeline {
+    pub capabilities: CapabilityMap,
+    post: PostProcessor,
+    canvas: Canvas,
+    /// Controllers that aren't left alone.
+    slots: Vec<Slot>,
+    /// Key and colors sent of each slot.
+    frame: Vec<(String, Vec<Color>)>,
+    /// Controller count the slots were set up for, `None` to set them up at the next frame.
+    controllers: Option<u32>,
+    /// Accessibility modes and version of the disabled zones the slots were adjusted to.
+    accessibility: Option<(bool, bool)>,
+    disabled_zones: Option<u64>,
+}
+
+/// A controller lit by the client, as set up for the frames.
+struct Slot {
+    id: u32,
+    /// The controller with the configured LED counts, and as OpenRGB reports it.
+    info: ControllerInfo,
+    reported: ControllerInfo,
+    /// Plan of the configuration, and adjusted to the accessibility modes.
+    resolved: Plan,
+    plan: Plan,
+    /// Whether the controller, and each of its reported zones, is left alone.
+    disabled: bool,
+    disabled_zones: Vec<bool>,
+    /// Colors fitted to the reported zones.
+    colors: Vec<Color>,
+}
+
+impl Slot {
+    /// Whether a zone, or the whole controller for `None`, is left alone.
+    fn disables(&self, zone_id: Option<usize>) -> bool {
+        match zone_id {
+            Some(zone_id) => self.disabled_zones[zone_id],
+            None => self.disabled,
+        }
+    }
+}
+
+impl Pipeline {
+    pub fn new(limiter: FlashLimiter, dry_run: bool) -> Self {
+        Self {
+            capabilities: CapabilityMap::new(dry_run),
+            post: PostProcessor::new(limiter),
+            canvas: Canvas::default(),
+            slots: vec![],
+            frame: vec![],
+            controllers: None,
+            accessibility: None,
+            disabled_zones: None,
+        }
+    }
+
+    /// Learns the capabilities of the controllers of a new connection and resizes their zones,
+    /// the slots being set up at the first frame.
+    pub async fn connect(
+        &mut self,
+        client: &OpenRGB<TcpStream>,
+        connector: &Connector,
+        config: &Config,
+    ) {
+        self.controllers = None;
+
+        if let Err(e) = self.capabilities.learn(client, connector, config).await {
+            warn!("Failed to learn the capabilities of the controllers: {}", e);
+        }
+
+        if let Err(e) = resize_zones(client, connector, config, &mut self.capabilities).await {
+            warn!("Failed to resize the zones: {}", e);
+        }
+    }
+
+    /// Adds a controller that isn't left alone, identified by `key`, to the frames.
+    pub fn add(
+        &mut self,
+        config: &Config,
+        control: &ControlState,
+        id: u32,
+        key: String,
+        reported: ControllerInfo,
+    ) {
+        let info = reported.clone().with_overrides(config);
+        let resolved = resolve(config, &info);
+        if resolved.metric.is_none() {
+            return;
+        }
+        control.fleet.bind(&resolved);
+
+        self.slots.push(Slot {
+            id,
+            info,
+            reported,
+            plan: resolved.clone(),
+            resolved,
+            disabled: false,
+            disabled_zones: vec![],
+            colors: vec![],
+        });
+        self.frame.push((key, vec![]));
+        self.accessibility = None;
+        self.disabled_zones = None;
+    }
+
+    /// Gets the slots ready for a frame: set up again when the controllers changed, adjusted
+    /// again when the accessibility modes or the disabled zones did.
+    async fn prepare(
+        &mut self,
+        client: &OpenRGB<TcpStream>,
+        config: &Config,
+        control: &ControlState,
+    ) -> Result<()> {
+        // A dry run logs the colors of every frame.
+        #[cfg(feature = "alloc-audit")]
+        if self.capabilities.dry_run() {
+            crate::alloc_audit::expect_allocations();
+        }
+
+        let count = self.capabilities.controller_count().await?;
+        if self.capabilities.list_changed() || self.controllers != Some(count) {
+            #[cfg(feature = "alloc-audit")]
+            crate::alloc_audit::expect_allocations();
+
+            self.slots.clear();
+            self.frame.clear();
+            for controller_id in 0..client.get_controller_count().await? {
+                let controller = client.get_controller(controller_id).await?;
+                let key = controller_key(&controller);
+
+                self.add(
+                    config,
+                    control,
+                    controller_id,
+                    key,
+                    ControllerInfo::from(&controller),
+                );
+            }
+            self.controllers = Some(count);
+        }
+
+        let accessibility = control.accessibility.modes();
+        if self.accessibility != Some(accessibility) {
+            #[cfg(feature = "alloc-audit")]
+            crate::alloc_audit::expect_allocations();
+
+            for slot in &mut self.slots {
+                slot.plan.clone_from(&slot.resolved);
+                control.accessibility.adjust(&mut slot.plan);
+            }
+            self.accessibility = Some(accessibility);
+        }
+
+        let disabled_zones = control.zones.version();
+        if self.disabled_zones != Some(disabled_zones) {
+            for slot in &mut self.slots {
+                let disabled = |zone: &str| {
+                    slot.plan.disables(zone)
+                        || control.zones.is_disabled(config, &slot.reported, zone)
+                };
+
+                slot.disabled = disabled("");
+                slot.disabled_zones = slot
+                    .reported
+                    .zones
+                    .iter()
+                    .map(|zone| disabled(&zone.name))
+                    .collect();
+            }
+            self.disabled_zones = Some(disabled_zones);
+        }
+
+        Ok(())
+    }
+
+    /// Renders the colors of a slot following its plan, with the notifications over them,
+    /// then finishes them, see `finish`.
+    pub fn render(
+        &mut self,
+        index: usize,
+        config: &Config,
+        usage: &Usage,
+        history: &History,
+        control: &ControlState,
+        now: SystemTime,
+    ) -> &[Color] {
+        let slot = &self.slots[index];
+        let colors = self.canvas.render(&slot.plan, usage, history, now);
+        control
+            .notifications
+            .apply(config, &slot.info, &slot.plan, colors, |mut plan| {
+                control.accessibility.adjust(&mut plan);
+
+                render(&plan, usage, history, now)
+            });
+
+        self.finish(index, control, now)
+    }
+
+    /// Renders `effect` over the LEDs of a slot instead of its plan, then finishes them, see
+    /// `finish`.
+    fn render_effect(
+        &mut self,
+        index: usize,
+        effect: &Effect,
+        palette: &Palette,
+        control: &ControlState,
+        now: SystemTime,
+    ) {
+        let slot = &self.slots[index];
+        self.canvas.render_effect(
+            effect,
+            palette,
+            slot.plan.positions.as_deref(),
+            slot.info.leds,
+            now,
+        );
+
+        self.finish(index, control, now);
+    }
+
+    /// Applies the master brightness and the filters to the colors of the canvas, and fits
+    /// them to the zones OpenRGB reports.
+    fn finish(&mut self, index: usize, control: &ControlState, now: SystemTime) -> &[Color] {
+        let (slot, (key, _)) = (&mut self.slots[index], &self.frame[index]);
+        let colors = &mut self.canvas.colors;
+
+        control.brightness.apply(colors);
+        self.post.process(key, &slot.plan, colors, now);
+        slot.info.fit_into(&slot.reported, colors, &mut slot.colors);
+
+        &slot.colors
+    }
+
+    /// Sends the colors of a slot, measuring how long its controller takes to show them when
+    /// `measuring`.
+    async fn send(&mut self, index: usize, control: &ControlState, measuring: bool) -> Result<()> {
+        let (slot, (key, sent)) = (&self.slots[index], &mut self.frame[index]);
+        let capabilities = &mut self.capabilities;
+
+        let start = Instant::now();
+        update(capabilities, slot, sent).await?;
+        if measuring && !sent.is_empty() && !capabilities.dry_run() {
+            measure_latency(capabilities, control, key, start).await?;
+        }
+        control
+            .power
+            .record(key, slot.plan.power.estimate_watts(sent.iter().copied()));
+
+        Ok(())
+    }
+}
+
+/// Renders a frame of every controller that isn't left alone following its plan, and sends
+/// it. Returns the colors sent to each controller.
+pub async fn set_all_light_color<'a>(
     client: &OpenRGB<TcpStream>,
-    cpu_usage: f32,
-    gpu_usage: f32,
-    start_color: &Color,
-    end_color: &Color,
+    config: &Config,
+    usage: &Usage,
+    history: &History,
+    control: &ControlState,
+    pipeline: &'a mut Pipeline,
+) -> Result<&'a [(String, Vec<Color>)]> {
+    pipeline.prepare(client, config, control).await?;
+
+    let measuring = control.latencies.measuring();
+    for index in 0..pipeline.slots.len() {
+        // Rendered for the time the frame shows at.
+        let now = sync::now() + control.latencies.lead(&pipeline.frame[index].0);
+        pipeline.render(index, config, usage, history, control, now);
+        pipeline.send(index, control, measuring).await?;
+    }
+
+    Ok(&pipeline.frame)
+}
+
+/// Renders `effect` over every controller that isn't left alone, instead of its plan.
+pub async fn set_all_effect<'a>(
+    client: &OpenRGB<TcpStream>,
+    config: &Config,
+    effect: &Effect,
+    control: &ControlState,
+    pipeline: &'a mut Pipeline,
+) -> Result<&'a [(String, Vec<Color>)]> {
+    pipeline.prepare(client, config, control).await?;
+
+    let measuring = control.latencies.measuring();
+    for index in 0..pipeline.slots.len() {
+        let mut palette = pipeline.slots[index].resolved.palette.clone();
+        let effect = control.accessibility.adjust_effect(effect, &mut palette);
+
+        let now = sync::now() + control.latencies.lead(&pipeline.frame[index].0);
+        pipeline.render_effect(index, &effect, &palette, control, now);
+        pipeline.send(index, control, measuring).await?;
+    }
+
+    Ok(&pipeline.frame)
+}
+
+/// Measures the time a controller took to show the colors sent at `sent`: the server answers
+/// the next request once it applied them.
+async fn measure_latency(
+    capabilities: &mut CapabilityMap,
+    control: &ControlState,
+    key: &str,
+    sent: Instant,
 ) -> Result<()> {
-    let controller_count = client.get_controller_count().await?;
-    for controller_id in 0..controller_count {
+    capabilities.controller_count().await?;
+    control.latencies.record(key, sent.elapsed());
+
+    Ok(())
+}
+
+/// Resizes the zones with a `resize` setting to their configured LED count, when OpenRGB
+/// reports another one.
+pub async fn resize_zones(
+    client: &OpenRGB<TcpStream>,
+    connector: &Connector,
+    config: &Config,
+    capabilities: &mut CapabilityMap,
+) -> Result<()> {
+    let mut stream = None;
+    for controller_id in 0..client.get_controller_count().await? {
         let controller = client.get_controller(controller_id).await?;
-        let led_count = controller.leds.len();
-        let colors = match controller.name.as_str() {
-            "ENE DRAM" => {
-                generate_gradient_led_colors(1.0 - cpu_usage, end_color, start_color, led_count)
-            }
-            "EVGA GeForce RTX 3080Ti FTW3 Ultra" => {
-                generate_block_led_colors(gpu_usage, start_color, end_color, led_count)
-            }
-            "X570 AORUS ELITE" => controller
+        let Some(device) = config.device(&controller.name, &controller.location) else {
+            continue;
+        };
+
+        let resizable = capabilities
+            .get(controller_id)
+            .is_none_or(|capabilities| capabilities.resize);
+        if !resizable && device.zones.iter().any(|zone_config| zone_config.resize) {
+            capabilities.warn_once(format!(
+                "{} has no zones that can be resized, `resize` is ignored.",
+                controller.name
+            ));
+
+            continue;
+        }
+
+        for zone_config in device.zones.iter().filter(|zone_config| zone_config.resize) {
+            let Some(leds) = zone_config.leds else {
+                continue;
+            };
+            let Some((zone_id, zone)) = controller
                 .zones
                 .iter()
-                .flat_map(|zone| match zone.name.as_str() {
-                    "D_LED1 Bottom" => generate_block_led_colors(
-                        cpu_usage,
-                        start_color,
-                        end_color,
-                        zone.leds_count as usize,
-                    ),
-                    "D_LED2 Top" => generate_gradient_led_colors(
-                        cpu_usage,
-                        start_color,
-                        end_color,
-                        zone.leds_count as usize,
-                    ),
-                    "Motherboard" => {
-                        let mut colors =
-                            generate_block_led_colors(cpu_usage, start_color, end_color, 1);
-
-                        colors.append(&mut generate_block_led_colors(
-                            cpu_usage,
-                            start_color,
-                            end_color,
-                            zone.leds_count as usize - 1,
-                        ));
-
-                        colors
-                    }
-                    _ => panic!("Unknown zone!"),
-                })
-                .collect(),
-            _ => generate_block_led_colors(cpu_usage, start_color, end_color, led_count),
-        };
+                .enumerate()
+                .find(|(_, zone)| name_matches(&zone_config.name, &zone.name))
+            else {
+                continue;
+            };
+            if zone.leds_count as usize == leds {
+                continue;
+            }
 
-        client.update_leds(controller_id, colors.clone()).await?;
+            if !(zone.leds_min as usize..=zone.leds_max as usize).contains(&leds) {
+                capabilities.warn_once(format!(
+                    "Can't resize {} of {} to {} LEDs, it takes {} to {}.",
+                    zone.name, controller.name, leds, zone.leds_min, zone.leds_max
+                ));
+
+                continue;
+            }
+
+            info!(
+                "Resizing {} of {} to {} LEDs.",
+                zone.name, controller.name, leds
+            );
+            if capabilities.dry_run() {
+                continue;
+            }
+            if stream.is_none() {
+                stream = Some(connector.raw_stream().await?);
+            }
+            if let Some(stream) = &mut stream {
+                sdk::resize_zone(stream, controller_id, zone_id as u32, leds as u32).await?;
+            }
+        }
     }
 
     Ok(())
 }
 
-fn lerp(value: f32, start: f32, end: f32) -> f32 {
-    (1.0 - value) * start + (value * end)
+/// Sends the colors of a slot as its controller's capabilities allow, zone by zone when some
+/// are disabled so these are left alone. Writes the colors sent to `sent`.
+async fn update(
+    capabilities: &mut CapabilityMap,
+    slot: &Slot,
+    sent: &mut Vec<Color>,
+) -> Result<()> {
+    let (controller_id, colors) = (slot.id, &slot.colors);
+
+    sent.clear();
+    match capabilities.strategy(controller_id) {
+        Strategy::PerLed => {}
+        Strategy::ModeColor => {
+            return capabilities
+                .send_mode_color(controller_id, colors, sent)
+                .await
+        }
+        Strategy::Skip => return Ok(()),
+    }
+
+    let zone_update = capabilities
+        .get(controller_id)
+        .map_or(!slot.reported.zones.is_empty(), |capabilities| {
+            capabilities.zone_update
+        });
+    if !zone_update {
+        if slot.disables(None) {
+            return Ok(());
+        }
+
+        send_leds(capabilities, slot).await?;
+        sent.extend_from_slice(colors);
+
+        return Ok(());
+    }
+
+    if !slot.disabled_zones.contains(&true) {
+        send_leds(capabilities, slot).await?;
+        sent.extend_from_slice(colors);
+
+        return Ok(());
+    }
+
+    // Zones are laid out one after the other.
+    capabilities.forget_frame(controller_id);
+    let mut start = 0;
+    for (zone_id, zone) in slot.reported.zones.iter().enumerate() {
+        let end = (start + zone.leds).min(colors.len());
+        if !slot.disables(Some(zone_id)) {
+            if capabilities.dry_run() {
+                log_dry_run(&slot.reported, Some(zone_id), &colors[start..end]);
+            } else {
+                capabilities
+                    .send_zone_leds(controller_id, zone_id as u32, &colors[start..end])
+                    .await?;
+            }
+
+            sent.extend_from_slice(&colors[start..end]);
+        }
+
+        start = end;
+    }
+
+    Ok(())
+}
+
+/// Sends the colors of every LED of a slot's controller, only logging them in a dry run.
+async fn send_leds(capabilities: &mut CapabilityMap, slot: &Slot) -> Result<()> {
+    if capabilities.dry_run() {
+        log_dry_run(&slot.reported, None, &slot.colors);
+
+        return Ok(());
+    }
+
+    capabilities.send_leds(slot.id, &slot.colors).await
+}
+
+/// Logs the colors a dry run doesn't send to a controller, zone by zone, from the zone
+/// `zone_id` or the first one.
+pub fn log_dry_run(controller: &ControllerInfo, zone_id: Option<usize>, colors: &[Color]) {
+    let log = |zone: &str, colors: &[Color]| {
+        if let (Some(first), Some(last)) = (colors.first(), colors.last()) {
+            info!(
+                "Dry run: {}, {}: {} LEDs from {} to {}.",
+                controller.name,
+                zone,
+                colors.len(),
+                format_color(first),
+                format_color(last)
+            );
+        }
+    };
+
+    match zone_id {
+        Some(zone_id) => log(&controller.zones[zone_id].name, colors),
+        None if controller.zones.is_empty() => log("all LEDs", colors),
+        None => {
+            let mut start = 0;
+            for zone in &controller.zones {
+                let end = (start + zone.leds).min(colors.len());
+                log(&zone.name, &colors[start..end]);
+
+                start = end;
+            }
+        }
+    }
+}
+
+/// Buffers a controller's colors are rendered into, kept from one render to the next so that
+/// rendering allocates nothing once they've grown to the largest controller.
+#[derive(Debug, Default)]
+pub struct Canvas {
+    colors: Vec<Color>,
+    history: Vec<f32>,
 }
 
-fn lerp_color(value: f32, start_color: &Color, end_color: &Color) -> Color {
+impl Canvas {
+    /// Colors of every LED of a controller, following its plan.
+    pub fn render(
+        &mut self,
+        plan: &Plan,
+        usage: &Usage,
+        history: &History,
+        now: SystemTime,
+    ) -> &mut [Color] {
+        self.colors.clear();
+        let metric = match plan.metric {
+            None | Some(Metric::None) => return &mut self.colors,
+            Some(metric) => metric,
+        };
+
+        let mut start = 0;
+        for segment in &plan.segments {
+            let positions = plan
+                .positions
+                .as_ref()
+                .and_then(|positions| positions.get(start..start + segment.leds));
+            start += segment.leds;
+
+            let end = self.colors.len();
+            self.colors.resize(end + segment.leds, Color::new(0, 0, 0));
+            let colors = &mut self.colors[end..];
+
+            let metric = segment.metric.unwrap_or(metric);
+            if let Some(placeholder) = segment.placeholder {
+                colors.fill(placeholder);
+
+                continue;
+            }
+            let value = usage.get(metric);
+            history.values(metric, &mut self.history);
+
+            let effect = match &segment.idle_effect {
+                Some(idle_effect) if value < IDLE_LEVEL => idle_effect,
+                _ => &segment.effect,
+            };
+
+            match effect {
+                Effect::HueShift(hue_shift) => {
+                    hue_shift.render(value, usage.get(hue_shift.metric), &plan.palette, colors)
+                }
+                effect => effect.render_placed(
+                    value,
+                    &self.history,
+                    &plan.palette,
+                    positions,
+                    colors,
+                    now,
+                ),
+            }
+
+            // Dimmed while the metric is stale.
+            let brightness = usage.brightness.get(metric);
+            for color in colors {
+                *color = dim(*color, brightness);
+            }
+        }
+
+        &mut self.colors
+    }
+
+    /// Colors of `leds` LEDs showing `effect`, whatever the metrics.
+    pub fn render_effect(
+        &mut self,
+        effect: &Effect,
+        palette: &Palette,
+        positions: Option<&[Point]>,
+        leds: usize,
+        now: SystemTime,
+    ) -> &mut [Color] {
+        self.colors.clear();
+        self.colors.resize(leds, Color::new(0, 0, 0));
+        effect.render_placed(0.0, &[], palette, positions, &mut self.colors, now);
+
+        &mut self.colors
+    }
+}
+
+/// Colors of every LED of a controller, following its plan, in a new canvas.
+pub fn render(plan: &Plan, usage: &Usage, history: &History, now: SystemTime) -> Vec<Color> {
+    Canvas::default().render(plan, usage, history, now).to_vec()
+}
+
+/// Average color of a frame, for the controllers showing a single color.
+pub fn average(colors: &[Color]) -> Color {
+    let count = colors.len().max(1) as u32;
+    let mean = |channel: fn(&Color) -> u8| {
+        (colors
+            .iter()
+            .map(|color| channel(color) as u32)
+            .sum::<u32>()
+            / count) as u8
+    };
+
     Color::new(
-        lerp(value, start_color.r as f32, end_color.r as f32) as u8,
-        lerp(value, start_color.g as f32, end_color.g as f32) as u8,
-        lerp(value, start_color.b as f32, end_color.b as f32) as u8,
+        mean(|color| color.r),
+        mean(|color| color.g),
+        mean(|color| color.b),
     )
 }
 
-fn generate_gradient_led_colors(
-    value: f32,
-    start_color: &Color,
-    end_color: &Color,
-    size: usize,
-) -> Vec<Color> {
-    let value = value * size as f32;
-
-    (0..size)
-        .map(|index| {
-            let value = value - index as f32;
-            let value = value.clamp(0.0, 1.0);
+pub fn dim(color: Color, brightness: f32) -> Color {
+    if brightness >= 1.0 {
+        return color;
+    }
 
-            lerp_color(value, start_color, end_color)
-        })
-        .collect()
+    Color::new(
+        (color.r as f32 * brightness) as u8,
+        (color.g as f32 * brightness) as u8,
+        (color.b as f32 * brightness) as u8,
+    )
 }
 
-fn generate_block_led_colors(
-    value: f32,
-    start_color: &Color,
-    end_color: &Color,
-    size: usize,
-) -> Vec<Color> {
-    let color = lerp_color(value, start_color, end_color);
+/// Sets every LED of the controllers the configuration allows to `color`, only logging them in
+/// a dry run.
+pub async fn set_all_static_color(
+    client: &OpenRGB<TcpStream>,
+    config: &Config,
+    color: &Color,
+    dry_run: bool,
+) -> Result<()> {
+    let controller_count = client.get_controller_count().await?;
+    for controller_id in 0..controller_count {
+        let controller = client.get_controller(controller_id).await?;
+        if !config.allows(&controller.name, &controller.location) {
+            continue;
+        }
+
+        let colors = vec![*color; controller.leds.len()];
+        if dry_run {
+            log_dry_run(&ControllerInfo::from(&controller), None, &colors);
+        } else {
+            client.update_leds(controller_id, colors).await?;
+        }
+    }
 
-    vec![color; size]
+    Ok(())
 }
@@ -0,0 +1,327 @@
+use crate::{
+    control::{self, ControlRequest, ControlState, LiveStatus},
+    events::Event,
+    fleet::host_name,
+    hex,
+    websocket::{self, Frame},
+};
+use anyhow::{anyhow, bail, Result};
+use hmac::{Hmac, KeyInit, Mac};
+use log::*;
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::{net::Ipv4Addr, sync::Arc, time::Duration};
+use tokio::{
+    io::AsyncWrite,
+    net::{TcpStream, UdpSocket},
+    sync::{broadcast::error::RecvError, mpsc},
+    time::timeout,
+};
+
+/// Datagram the apps broadcast to find the clients on the LAN.
+const DISCOVERY_REQUEST: &[u8] = b"open_rgb_client discover";
+/// Time an app has to authenticate once connected.
+const AUTH_TIMEOUT: Duration = Duration::from_secs(10);
+/// Message whose HMAC keyed with the token is the bearer token of the HTTP requests.
+const BEARER_MESSAGE: &str = "open_rgb_client bearer";
+
+/// Phone apps on the LAN, finding the client over UDP and controlling it over the `/companion`
+/// WebSocket of the HTTP server.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CompanionConfig {
+    /// Shared with the apps, which prove they know it without sending it. Best kept as a
+    /// `secret://` name.
+    pub token: String,
+    /// UDP port the discovery requests are answered on.
+    #[serde(default = "CompanionConfig::default_discovery_port")]
+    pub discovery_port: u16,
+}
+
+impl CompanionConfig {
+    fn default_discovery_port() -> u16 {
+        6746
+    }
+}
+
+/// Answer to a discovery request.
+#[derive(Debug, Serialize)]
+struct Announcement {
+    service: &'static str,
+    host: String,
+    /// Port of the HTTP server, serving `/companion`.
+    port: u16,
+}
+
+/// Messages of the apps.
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+enum Request {
+    /// HMAC-SHA256 of the challenge nonce keyed with the token, in hexadecimal.
+    Auth {
+        mac: String,
+    },
+    Status,
+    SwitchProfile {
+        name: Option<String>,
+    },
+    SwitchScene {
+        name: Option<String>,
+    },
+    Brightness {
+        level: f32,
+    },
+}
+
+/// Messages to the apps, on top of the events of the client.
+#[derive(Debug, Serialize)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+enum Reply {
+    Challenge {
+        nonce: String,
+    },
+    Status {
+        #[serde(flatten)]
+        status: Box<LiveStatus>,
+        profiles: Vec<String>,
+        brightness: f32,
+    },
+    Ok,
+    Error {
+        message: String,
+    },
+}
+
+/// Answers the discovery requests of the apps with the port of the HTTP server, until the
+/// process exits.
+pub async fn run_discovery(
+    discovery_port: u16,
+    http_port: u16,
+    state: Arc<ControlState>,
+) -> Result<()> {
+    let socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, discovery_port)).await?;
+    info!("Answering companion discovery on port {}.", discovery_port);
+
+    let mut buffer = [0; 64];
+    loop {
+        let (length, from) = socket.recv_from(&mut buffer).await?;
+        if buffer[..length].trim_ascii() != DISCOVERY_REQUEST {
+            continue;
+        }
+
+        let announcement = Announcement {
+            service: "open_rgb_client",
+            host: host_name(&state.config()),
+            port: http_port,
+        };
+        if let Err(e) = socket
+            .send_to(&serde_json::to_vec(&announcement)?, from)
+            .await
+        {
+            debug!("Failed to answer the discovery of {}: {}", from, e);
+        }
+    }
+}
+
+/// Serves an app over an upgraded WebSocket: a challenge first, then, once authenticated, the
+/// events of the client and the answers to its requests.
+pub async fn session(stream: TcpStream, state: &ControlState, token: &str) -> Result<()> {
+    let address = stream.peer_addr()?;
+    let (mut reader, mut writer) = stream.into_split();
+
+    let nonce = nonce()?;
+    send(
+        &mut writer,
+        &Reply::Challenge {
+            nonce: nonce.clone(),
+        },
+    )
+    .await?;
+
+    let authenticated = timeout(AUTH_TIMEOUT, async {
+        loop {
+            let frame = websocket::read_frame(&mut reader).await?;
+            match frame.opcode {
+                websocket::TEXT => {
+                    return match serde_json::from_slice(&frame.payload)? {
+                        Request::Auth { mac } => verify(token, &nonce, &mac),
+                        _ => Err(anyhow!("Not authenticated.")),
+                    };
+                }
+                websocket::PING => {
+                    websocket::write_frame(&mut writer, websocket::PONG, &frame.payload).await?
+                }
+                websocket::CLOSE => bail!("Closed before authenticating."),
+                _ => {}
+            }
+        }
+    })
+    .await
+    .unwrap_or_else(|_| Err(anyhow!("Timed out.")));
+    if let Err(e) = authenticated {
+        warn!("Rejected companion app at {}: {}", address, e);
+
+        send(
+            &mut writer,
+            &Reply::Error {
+                message: e.to_string(),
+            },
+        )
+        .await?;
+
+        return websocket::write_frame(&mut writer, websocket::CLOSE, &[]).await;
+    }
+
+    info!("Companion app connected from {}.", address);
+    let mut events = state.events.subscribe();
+    send(&mut writer, &status(state)).await?;
+
+    // Frames from the app are read on the side, reading isn't cancel safe.
+    let (frames_sender, mut frames) = mpsc::channel(4);
+    let reader = tokio::spawn(async move {
+        while let Ok(frame) = websocket::read_frame(&mut reader).await {
+            if frames_sender.send(frame).await.is_err() {
+                break;
+            }
+        }
+    });
+
+    let result = async {
+        loop {
+            tokio::select! {
+                event = events.recv() => match event {
+                    // Frames are for overlays, too much for a phone.
+                    Ok(Event::Frame { .. }) => {}
+                    Ok(event) => {
+                        websocket::write_frame(&mut writer, websocket::TEXT, event.to_json().as_bytes()).await?
+                    }
+                    Err(RecvError::Lagged(missed)) => debug!("Companion app missed {} events.", missed),
+                    Err(RecvError::Closed) => break,
+                },
+                frame = frames.recv() => match frame {
+                    Some(Frame { opcode: websocket::TEXT, payload }) => {
+                        let reply = handle(&payload, state).unwrap_or_else(|e| Reply::Error {
+                            message: e.to_string(),
+                        });
+                        send(&mut writer, &reply).await?
+                    }
+                    Some(Frame { opcode: websocket::PING, payload }) => {
+                        websocket::write_frame(&mut writer, websocket::PONG, &payload).await?
+                    }
+                    Some(Frame { opcode: websocket::CLOSE, .. }) | None => {
+                        websocket::write_frame(&mut writer, websocket::CLOSE, &[]).await?;
+
+                        break;
+                    }
+                    Some(_) => {}
+                },
+            }
+        }
+
+        Ok(())
+    }
+    .await;
+
+    reader.abort();
+    info!("Companion app at {} disconnected.", address);
+
+    result
+}
+
+fn handle(payload: &[u8], state: &ControlState) -> Result<Reply> {
+    let request = match serde_json::from_slice(payload)? {
+        Request::Auth { .. } => bail!("Already authenticated."),
+        Request::Status => return Ok(status(state)),
+        Request::SwitchProfile { name } => ControlRequest::SwitchProfile { name },
+        Request::SwitchScene { name } => ControlRequest::SwitchScene { name },
+        Request::Brightness { level } if (0.0..=1.0).contains(&level) => {
+            ControlRequest::Brightness(level)
+        }
+        Request::Brightness { level } => {
+            bail!("Brightness out of range: {} (expected 0 to 1)", level)
+        }
+    };
+
+    control::apply(request, state, &state.config())?;
+
+    Ok(Reply::Ok)
+}
+
+fn status(state: &ControlState) -> Reply {
+    Reply::Status {
+        status: Box::new(state.live_status()),
+        profiles: state.profiles.names(),
+        brightness: state.brightness.level(),
+    }
+}
+
+async fn send(writer: &mut (impl AsyncWrite + Unpin), reply: &Reply) -> Result<()> {
+    websocket::write_frame(writer, websocket::TEXT, &serde_json::to_vec(reply)?).await
+}
+
+/// Random challenge from the operating system, so a recorded authentication can't be replayed.
+fn nonce() -> Result<String> {
+    let mut bytes = [0; 32];
+    getrandom::fill(&mut bytes)?;
+
+    Ok(hex::encode(&bytes))
+}
+
+/// Bearer token of the HTTP requests, derived from the token so that the token itself isn't
+/// sent: the HMAC-SHA256 of `open_rgb_client bearer` keyed with it, in hexadecimal.
+pub fn bearer(token: &str) -> Result<String> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(token.as_bytes())?;
+    mac.update(BEARER_MESSAGE.as_bytes());
+
+    Ok(hex::encode(&mac.finalize().into_bytes()))
+}
+
+/// Checks the bearer token of an HTTP request.
+pub fn verify_bearer(token: &str, bearer: Option<&str>) -> Result<()> {
+    let bearer = bearer.ok_or_else(|| anyhow!("Missing bearer token."))?;
+
+    verify(token, BEARER_MESSAGE, bearer)
+}
+
+/// Checks that `mac` is the HMAC-SHA256 of the nonce keyed with the token.
+fn verify(token: &str, nonce: &str, mac: &str) -> Result<()> {
+    let mac = hex::decode(mac).ok_or_else(|| anyhow!("Invalid MAC."))?;
+
+    let mut expected = Hmac::<Sha256>::new_from_slice(token.as_bytes())?;
+    expected.update(nonce.as_bytes());
+    if expected.verify_slice(&mac).is_err() {
+        bail!("Wrong token.");
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn apps_authenticate_with_the_mac_of_the_nonce() {
+        let nonce = nonce().unwrap();
+        assert_eq!(nonce.len(), 64);
+        assert_ne!(nonce, super::nonce().unwrap());
+
+        let mut mac = Hmac::<Sha256>::new_from_slice(b"token").unwrap();
+        mac.update(nonce.as_bytes());
+        let mac = hex::encode(&mac.finalize().into_bytes());
+
+        assert!(verify("token", &nonce, &mac).is_ok());
+        assert!(verify("another token", &nonce, &mac).is_err());
+        assert!(verify("token", &super::nonce().unwrap(), &mac).is_err());
+    }
+
+    #[test]
+    fn http_requests_authenticate_with_the_derived_bearer() {
+        let bearer = bearer("token").unwrap();
+        assert_ne!(bearer, super::bearer("another token").unwrap());
+
+        assert!(verify_bearer("token", Some(&bearer)).is_ok());
+        assert!(verify_bearer("another token", Some(&bearer)).is_err());
+        assert!(verify_bearer("token", Some("token")).is_err());
+        assert!(verify_bearer("token", None).is_err());
+    }
+}
@@ -0,0 +1,1356 @@
+use crate::{
+    boot::BootConfig,
+    bot::BotConfig,
+    budget::Budget,
+    color_names,
+    companion::CompanionConfig,
+    connection::ReconnectConfig,
+    control::GrpcConfig,
+    effects::Effect,
+    filters::{BurnIn, Filter},
+    fleet::PushConfig,
+    github::GithubConfig,
+    history::HistoryConfig,
+    http::HttpConfig,
+    layout::LayoutConfig,
+    migration::{self, CURRENT_VERSION},
+    paths::{self, Kind},
+    power::PowerModel,
+    priority::ProcessConfig,
+    safety::SafetyConfig,
+    scene::{AlertRule, SceneConfig},
+    secrets,
+    sequence::{duration, Sequence},
+    sync::SyncConfig,
+    wled::OutageConfig,
+};
+use anyhow::{anyhow, bail, Context, Result};
+use openrgb::data::Color;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::{
+    cmp::Ordering,
+    collections::BTreeMap,
+    fmt,
+    path::{Path, PathBuf},
+    str::FromStr,
+    time::Duration,
+};
+use toml::{Table, Value};
+
+const CONFIG_FILE: &str = "open_rgb_client.toml";
+const MAX_INCLUDE_DEPTH: usize = 8;
+/// Directory next to the configuration whose files are included without being listed.
+pub const DROP_IN_DIRECTORY: &str = "devices.d";
+
+/// User configuration, read from `open_rgb_client.toml` in the configuration directory.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    /// OpenRGB servers to try in order, used when none is given on the command line.
+    pub hosts: Vec<String>,
+    /// Metric shown by the devices not listed in `devices`.
+    pub default_metric: Option<Metric>,
+    /// Metrics turned off, e.g. `gpu` on a laptop on battery: they aren't sampled and their
+    /// zones show their `placeholder`.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub disabled_metrics: Vec<Metric>,
+    /// Time each sample is measured over, in milliseconds.
+    pub sample_interval: Option<u64>,
+    /// Time the CPU and GPU utilization are averaged over, in milliseconds.
+    pub averaging_window: Option<u64>,
+    /// Longest interval, in milliseconds, the sampling slows down to while the metrics are
+    /// stable. Without it, the sampling interval never changes.
+    pub max_sample_interval: Option<u64>,
+    pub palette: Palette,
+    /// Palette of the devices showing a metric, instead of the global one.
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub palettes: BTreeMap<Metric, Palette>,
+    pub thermal: ThermalConfig,
+    pub cpu_temperature: CpuTemperatureConfig,
+    pub gpu_temperature: GpuTemperatureConfig,
+    pub disk: DiskConfig,
+    pub power: PowerConfig,
+    pub staleness: StalenessConfig,
+    /// Waits between the attempts to reach OpenRGB.
+    pub reconnect: ReconnectConfig,
+    /// How long the samples are kept in memory.
+    pub history: HistoryConfig,
+    /// Mailbox of the `mail` metric.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mail: Option<MailConfig>,
+    pub devices: Vec<DeviceConfig>,
+    /// Positions of the LEDs in the case, for the rainbow to move across it.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub layout: Option<LayoutConfig>,
+    /// Names standing for the controllers they match, wherever a controller name is expected.
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub aliases: BTreeMap<String, Alias>,
+    /// Controllers the client drives, the others being left to OpenRGB.
+    #[serde(skip_serializing_if = "ControllersConfig::is_empty")]
+    pub controllers: ControllersConfig,
+    /// Requests allowed to each network source, e.g. `mail`, instead of its default.
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub budgets: BTreeMap<String, Budget>,
+    /// Chat bot controlling the client from anywhere.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bot: Option<BotConfig>,
+    /// Phone apps controlling the client, through the HTTP server.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub companion: Option<CompanionConfig>,
+    /// Sequence played when the client first connects to OpenRGB, and again when the server
+    /// restarted.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub started_sequence: Option<String>,
+    /// Sequence played when the connection to OpenRGB comes back.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reconnected_sequence: Option<String>,
+    /// Shown on a spare WLED strip while OpenRGB can't be reached.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub outage: Option<OutageConfig>,
+    /// Animations in phase with the other clients on the network.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sync: Option<SyncConfig>,
+    /// Control requests over gRPC, on top of the control socket.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub grpc: Option<GrpcConfig>,
+    /// Web server for dashboards and overlays.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub http: Option<HttpConfig>,
+    /// GitHub webhook served by the web server.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub github: Option<GithubConfig>,
+    /// Metrics pushed to the dashboard of another client.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub push: Option<PushConfig>,
+    /// No blinking, flashing or moving colors, see `accessibility`.
+    pub reduced_motion: bool,
+    /// Blue to yellow palette instead of the configured ones.
+    pub high_contrast: bool,
+    /// Priority and cores of the client, applied when it starts.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub process: Option<ProcessConfig>,
+    /// Frame saved to the memory of the controllers when installing the service, shown at boot.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub boot: Option<BootConfig>,
+    /// Flash limits applied to everything the client shows.
+    pub safety: SafetyConfig,
+    /// Sequences the control socket can play.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub sequences: Vec<Sequence>,
+    /// Mappings and colors switched between at runtime, e.g. `work` and `gaming`.
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub profiles: BTreeMap<String, ProfileConfig>,
+    /// Profile applied when the client starts, none when not set.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub active_profile: Option<String>,
+    /// Notifications shown when a metric goes above a level.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub alerts: Vec<AlertRule>,
+    /// Profiles, alerts and triggers switched to as a whole, e.g. `night` or `stream`.
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub scenes: BTreeMap<String, SceneConfig>,
+    /// Scene applied when the client starts, instead of `active_profile`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub active_scene: Option<String>,
+}
+
+/// Settings of a named profile, applied over the rest of the configuration.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ProfileConfig {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub default_metric: Option<Metric>,
+    /// Replace the configured ones.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub disabled_metrics: Option<Vec<Metric>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub palette: Option<Palette>,
+    /// Merged into the configured ones.
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub palettes: BTreeMap<Metric, Palette>,
+    /// Entries replacing the configured ones with the same name, and taking precedence over
+    /// the others.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub devices: Vec<DeviceConfig>,
+}
+
+/// Colors the LEDs blend between as the metric goes from idle to full load.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Palette {
+    #[serde(with = "hex_color")]
+    pub idle: Color,
+    #[serde(with = "hex_color")]
+    pub load: Color,
+}
+
+impl Default for Palette {
+    fn default() -> Self {
+        Self {
+            idle: Color::new(0xFF, 0xFF, 0xFF),
+            load: Color::new(0xFF, 0x0, 0x0),
+        }
+    }
+}
+
+/// Thermal zone used by the `thermal` metric, and the temperatures mapped to idle and full load.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ThermalConfig {
+    /// Index of the zone in `/sys/class/thermal`, 0 being the SoC on a Raspberry Pi.
+    pub zone: u32,
+    /// Degrees Celsius.
+    pub idle: f32,
+    /// Degrees Celsius.
+    pub hot: f32,
+}
+
+impl Default for ThermalConfig {
+    fn default() -> Self {
+        Self {
+            zone: 0,
+            idle: 40.0,
+            hot: 80.0,
+        }
+    }
+}
+
+/// Sensor used by the `cpu_temperature` metric, and the temperatures mapped to idle and full
+/// load.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CpuTemperatureConfig {
+    /// Label of the sensor, e.g. `Tccd1`, instead of the package one: a hwmon `temp*_label` on
+    /// Linux, a LibreHardwareMonitor sensor name on Windows.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sensor: Option<String>,
+    /// Degrees Celsius.
+    pub idle: f32,
+    /// Degrees Celsius.
+    pub hot: f32,
+}
+
+impl Default for CpuTemperatureConfig {
+    fn default() -> Self {
+        Self {
+            sensor: None,
+            idle: 40.0,
+            hot: 90.0,
+        }
+    }
+}
+
+/// Temperatures of the GPU mapped to idle and full load by the `gpu_temperature` metric.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GpuTemperatureConfig {
+    /// Degrees Celsius.
+    pub idle: f32,
+    /// Degrees Celsius.
+    pub hot: f32,
+}
+
+impl Default for GpuTemperatureConfig {
+    fn default() -> Self {
+        Self {
+            idle: 40.0,
+            hot: 85.0,
+        }
+    }
+}
+
+/// Disks summed up by the `disk` metric, and the throughput shown as full load.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DiskConfig {
+    /// Disks to watch, e.g. `nvme0n1` on Linux or `0 C:` on Windows, every physical disk when
+    /// empty.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub devices: Vec<String>,
+    pub direction: DiskDirection,
+    /// Megabytes per second.
+    pub saturation: f32,
+}
+
+impl Default for DiskConfig {
+    fn default() -> Self {
+        Self {
+            devices: vec![],
+            direction: DiskDirection::Both,
+            saturation: 500.0,
+        }
+    }
+}
+
+/// Transfers counted by the `disk` metric.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DiskDirection {
+    Read,
+    Write,
+    Both,
+}
+
+/// Estimated power of the LEDs shown as full load by the `power` metric.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PowerConfig {
+    /// Watts.
+    pub saturation: f32,
+}
+
+impl Default for PowerConfig {
+    fn default() -> Self {
+        Self { saturation: 50.0 }
+    }
+}
+
+/// How the zones of a metric that can't be sampled anymore dim, showing its last value.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct StalenessConfig {
+    /// Time the last value is shown as is.
+    #[serde(with = "duration")]
+    pub after: Duration,
+    /// Time the zones then take to dim down to `min_brightness`, where they stay until the
+    /// metric is back.
+    #[serde(with = "duration")]
+    pub fade: Duration,
+    pub min_brightness: f32,
+}
+
+impl Default for StalenessConfig {
+    fn default() -> Self {
+        Self {
+            after: Duration::from_secs(30),
+            fade: Duration::from_secs(120),
+            min_brightness: 0.2,
+        }
+    }
+}
+
+impl StalenessConfig {
+    /// Brightness of the zones of a metric last sampled `age` ago.
+    pub fn brightness(&self, age: Duration) -> f32 {
+        let faded = age.saturating_sub(self.after).as_secs_f32()
+            / self.fade.as_secs_f32().max(f32::EPSILON);
+        let min_brightness = self.min_brightness.clamp(0.0, 1.0);
+
+        1.0 - faded.clamp(0.0, 1.0) * (1.0 - min_brightness)
+    }
+}
+
+/// IMAP mailbox watched by the `mail` metric, and the unread count mapped to full load.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MailConfig {
+    /// IMAP server, over TLS.
+    pub host: String,
+    #[serde(default = "MailConfig::default_port")]
+    pub port: u16,
+    pub user: String,
+    /// Password, or app password for accounts with two-factor authentication.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub password: Option<String>,
+    /// OAuth2 credentials, instead of a password.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub oauth2: Option<OAuth2Config>,
+    #[serde(default = "MailConfig::default_mailbox")]
+    pub mailbox: String,
+    /// IMAP search criteria of the messages counted, e.g. `UNSEEN FLAGGED` for important mail.
+    #[serde(default = "MailConfig::default_search")]
+    pub search: String,
+    /// Unread count shown as full load.
+    #[serde(default = "MailConfig::default_full")]
+    pub full: u32,
+}
+
+impl MailConfig {
+    fn default_port() -> u16 {
+        993
+    }
+
+    fn default_mailbox() -> String {
+        "INBOX".to_owned()
+    }
+
+    fn default_search() -> String {
+        "UNSEEN".to_owned()
+    }
+
+    fn default_full() -> u32 {
+        10
+    }
+}
+
+/// Refresh token of an OAuth2 client allowed to read the mailbox, exchanged for access tokens
+/// at `token_url`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OAuth2Config {
+    /// E.g. `https://oauth2.googleapis.com/token`.
+    pub token_url: String,
+    pub client_id: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_secret: Option<String>,
+    pub refresh_token: String,
+}
+
+/// Controller names, as reported by OpenRGB, patterns or aliases, the client drives or leaves
+/// alone.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ControllersConfig {
+    /// Only these controllers are driven, all of them when empty.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub allow: Vec<String>,
+    /// Never driven, even when allowed.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub deny: Vec<String>,
+}
+
+impl ControllersConfig {
+    fn is_empty(&self) -> bool {
+        self.allow.is_empty() && self.deny.is_empty()
+    }
+}
+
+/// Controllers an alias stands for, so the configuration and the commands survive OpenRGB
+/// renaming a device between releases.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Alias {
+    /// Pattern of the controller name, as reported by OpenRGB.
+    pub name: String,
+    /// Pattern of the controller location, e.g. `HID: /dev/hidraw3`, telling identical devices
+    /// apart. Any location when not set.
+    #[serde(default = "any_location")]
+    pub location: String,
+}
+
+fn any_location() -> String {
+    "*".to_owned()
+}
+
+impl Alias {
+    pub fn matches(&self, name: &str, location: &str) -> bool {
+        name_matches(&self.name, name) && name_matches(&self.location, location)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeviceConfig {
+    /// Controller name, as reported by OpenRGB, pattern or alias.
+    pub name: String,
+    /// Metric shown by the device, `default_metric` when not set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metric: Option<Metric>,
+    /// Palette of the device, instead of the global one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub palette: Option<Palette>,
+    /// Effect over all the LEDs of the device, instead of its built-in layout.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub effect: Option<Effect>,
+    /// Effect shown instead while the metric is idle, e.g. a clock.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub idle_effect: Option<Effect>,
+    /// Burn-in protection for LEDs showing the same colors for long periods.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub burn_in: Option<BurnIn>,
+    /// Post-processing of the device's frames, in order, after the burn-in protection.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub filters: Vec<Filter>,
+    /// Electrical model of the LEDs, for the power estimate and `power_cap`, a typical 5V
+    /// addressable LED when not set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub power: Option<PowerModel>,
+    /// Effects of single zones, overriding the device's.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub zones: Vec<ZoneConfig>,
+    /// Color of the LEDs while the metric is in `disabled_metrics`, off when not set.
+    #[serde(
+        default,
+        with = "optional_hex_color",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub placeholder: Option<Color>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ZoneConfig {
+    /// Zone name, as reported by OpenRGB.
+    pub name: String,
+    /// Metric shown by the zone, instead of the device's.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metric: Option<Metric>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub effect: Option<Effect>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub idle_effect: Option<Effect>,
+    /// Leave the zone alone, e.g. a keyboard lit by other software.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub disabled: bool,
+    /// Actual LED count of the zone, for controllers reporting a wrong one, e.g. for strips
+    /// plugged into resizable ARGB headers.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub leds: Option<usize>,
+    /// Resize the zone in OpenRGB to `leds` when connecting, so all its LEDs are driven.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub resize: bool,
+    /// Color of the LEDs while the metric is in `disabled_metrics`, instead of the device's.
+    #[serde(
+        default,
+        with = "optional_hex_color",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub placeholder: Option<Color>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Metric {
+    Cpu,
+    Gpu,
+    /// Package temperature of the CPU, see `CpuTemperatureConfig`.
+    #[serde(alias = "cpu-temperature")]
+    CpuTemperature,
+    /// Temperature of the GPU, see `GpuTemperatureConfig`.
+    #[serde(alias = "gpu-temperature")]
+    GpuTemperature,
+    /// Share of the physical memory in use.
+    Memory,
+    /// Read and write throughput of the disks, see `DiskConfig`.
+    Disk,
+    /// Estimated power drawn by the LEDs for the last frame, see `PowerConfig`.
+    Power,
+    /// Temperature of a thermal zone, see `ThermalConfig`.
+    Thermal,
+    /// Drained battery charge, the load color meaning an empty battery.
+    Battery,
+    /// Unread mail, see `MailConfig`.
+    Mail,
+    /// Leave the device alone.
+    None,
+}
+
+impl fmt::Display for Metric {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Cpu => write!(f, "cpu"),
+            Self::Gpu => write!(f, "gpu"),
+            Self::CpuTemperature => write!(f, "cpu_temperature"),
+            Self::GpuTemperature => write!(f, "gpu_temperature"),
+            Self::Memory => write!(f, "memory"),
+            Self::Disk => write!(f, "disk"),
+            Self::Power => write!(f, "power"),
+            Self::Thermal => write!(f, "thermal"),
+            Self::Battery => write!(f, "battery"),
+            Self::Mail => write!(f, "mail"),
+            Self::None => write!(f, "none"),
+        }
+    }
+}
+
+impl FromStr for Metric {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self> {
+        match value.to_ascii_lowercase().as_str() {
+            "cpu" => Ok(Self::Cpu),
+            "gpu" => Ok(Self::Gpu),
+            "cpu_temperature" | "cpu-temperature" => Ok(Self::CpuTemperature),
+            "gpu_temperature" | "gpu-temperature" => Ok(Self::GpuTemperature),
+            "memory" | "ram" => Ok(Self::Memory),
+            "disk" => Ok(Self::Disk),
+            "power" => Ok(Self::Power),
+            "thermal" => Ok(Self::Thermal),
+            "battery" => Ok(Self::Battery),
+            "mail" => Ok(Self::Mail),
+            "none" => Ok(Self::None),
+            _ => bail!("Unknown metric: {}", value),
+        }
+    }
+}
+
+impl Config {
+    /// `open_rgb_client.toml`, or a YAML or JSON configuration when there's no TOML one.
+    pub fn default_path() -> Result<PathBuf> {
+        let path = paths::file(Kind::Config, CONFIG_FILE)?;
+        if path.exists() {
+            return Ok(path);
+        }
+
+        for extension in ["yaml", "yml", "json"] {
+            let other = paths::file(
+                Kind::Config,
+                &Path::new(CONFIG_FILE)
+                    .with_extension(extension)
+                    .to_string_lossy(),
+            )?;
+            if other.exists() {
+                return Ok(other);
+            }
+        }
+
+        Ok(path)
+    }
+
+    pub fn path(path: Option<&Path>) -> Result<PathBuf> {
+        path.map_or_else(Self::default_path, |path| Ok(path.to_owned()))
+    }
+
+    /// Loads the configuration, falling back to the defaults when the file doesn't exist, and
+    /// migrates it first when it was written for an older schema.
+    pub fn load(path: Option<&Path>) -> Result<Self> {
+        let path = Self::path(path)?;
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+
+        let mut table = read_table(&path)?;
+        migration::upgrade(&path, &mut table)?;
+        table.remove("version");
+        let directory = path.parent().unwrap_or(Path::new("."));
+        include_files(&mut table, directory, 0)?;
+        for path in drop_in_files(&directory.join(DROP_IN_DIRECTORY))? {
+            include_file(&mut table, &path, directory, 0)?;
+        }
+        expand_templates(&mut table)?;
+        secrets::resolve(&mut table)?;
+
+        let config: Self = Value::Table(table)
+            .try_into()
+            .with_context(|| format!("Invalid configuration {:?}", path))?;
+        if let Some(name) = &config.active_profile {
+            if !config.profiles.contains_key(name) {
+                bail!("No profile named {:?}, set in `active_profile`.", name);
+            }
+        }
+        if let Some(name) = &config.active_scene {
+            if !config.scenes.contains_key(name) {
+                bail!("No scene named {:?}, set in `active_scene`.", name);
+            }
+        }
+        for (section, idle, hot) in [
+            (
+                "cpu_temperature",
+                config.cpu_temperature.idle,
+                config.cpu_temperature.hot,
+            ),
+            (
+                "gpu_temperature",
+                config.gpu_temperature.idle,
+                config.gpu_temperature.hot,
+            ),
+            ("thermal", config.thermal.idle, config.thermal.hot),
+        ] {
+            if idle.partial_cmp(&hot) != Some(Ordering::Less) {
+                bail!(
+                    "The idle temperature of `[{}]`, {} °C, must be below the hot one, {} °C.",
+                    section,
+                    idle,
+                    hot
+                );
+            }
+        }
+        if let Some(http) = &config.http {
+            if !http.address.ip().is_loopback() && config.companion.is_none() {
+                bail!(
+                    "The web server listens on {} for other machines, set a `[companion]` token to authenticate their requests.",
+                    http.address
+                );
+            }
+        }
+        for (name, scene) in &config.scenes {
+            if let Some(profile) = &scene.profile {
+                if !config.profiles.contains_key(profile) {
+                    bail!(
+                        "No profile named {:?}, used by the scene {}.",
+                        profile,
+                        name
+                    );
+                }
+            }
+        }
+
+        Ok(config)
+    }
+
+    /// The configuration with a profile applied over it, `None` when there is no such profile.
+    pub fn with_profile(&self, name: &str) -> Option<Self> {
+        let profile = self.profiles.get(name)?;
+
+        let mut config = self.clone();
+        config.apply_layers(profile);
+        config.active_profile = Some(name.to_owned());
+
+        Some(config)
+    }
+
+    /// The configuration with a scene applied over it, its profile first, `None` when there is
+    /// no such scene.
+    pub fn with_scene(&self, name: &str) -> Option<Self> {
+        let scene = self.scenes.get(name)?;
+
+        let mut config = match &scene.profile {
+            Some(profile) => self.with_profile(profile)?,
+            None => self.clone(),
+        };
+        config.apply_layers(&scene.layers);
+        config.alerts.extend(scene.alerts.iter().cloned());
+        config.active_scene = Some(name.to_owned());
+
+        Some(config)
+    }
+
+    fn apply_layers(&mut self, layers: &ProfileConfig) {
+        if layers.default_metric.is_some() {
+            self.default_metric = layers.default_metric;
+        }
+        if let Some(disabled_metrics) = &layers.disabled_metrics {
+            self.disabled_metrics = disabled_metrics.clone();
+        }
+        if let Some(palette) = &layers.palette {
+            self.palette = palette.clone();
+        }
+        self.palettes.extend(layers.palettes.clone());
+        self.devices
+            .retain(|device| !layers.devices.iter().any(|entry| entry.name == device.name));
+        self.devices.splice(0..0, layers.devices.iter().cloned());
+    }
+
+    /// Writes the configuration in the format of the file, from its extension.
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let mut table = Table::try_from(self)?;
+        table.insert("version".to_owned(), Value::Integer(CURRENT_VERSION.into()));
+
+        paths::create_parent(path)?;
+        std::fs::write(path, Format::of(path).write(&table)?)?;
+
+        Ok(())
+    }
+
+    /// Entry of a controller, the one with its exact name first, otherwise the first one whose
+    /// pattern or alias matches it.
+    pub fn device(&self, name: &str, location: &str) -> Option<&DeviceConfig> {
+        self.devices
+            .iter()
+            .find(|device| device.name == name)
+            .or_else(|| {
+                self.devices
+                    .iter()
+                    .find(|device| self.matches(&device.name, name, location))
+            })
+    }
+
+    /// Whether a name of the configuration, an alias or a pattern, matches a controller.
+    pub fn matches(&self, pattern: &str, name: &str, location: &str) -> bool {
+        match self.aliases.get(pattern) {
+            Some(alias) => alias.matches(name, location),
+            None => name_matches(pattern, name),
+        }
+    }
+
+    /// Whether a name given to a command or a notification, an alias or the controller name in
+    /// any case, designates a controller.
+    pub fn designates(&self, given: &str, name: &str, location: &str) -> bool {
+        let alias = self
+            .aliases
+            .iter()
+            .find(|(alias, _)| alias.eq_ignore_ascii_case(given));
+
+        match alias {
+            Some((_, alias)) => alias.matches(name, location),
+            None => name.eq_ignore_ascii_case(given),
+        }
+    }
+
+    /// Whether the client drives a controller, per the `controllers` allow and deny lists.
+    pub fn allows(&self, name: &str, location: &str) -> bool {
+        let controllers = &self.controllers;
+
+        (controllers.allow.is_empty()
+            || controllers
+                .allow
+                .iter()
+                .any(|allowed| self.matches(allowed, name, location)))
+            && !controllers
+                .deny
+                .iter()
+                .any(|denied| self.matches(denied, name, location))
+    }
+
+    pub fn sequence(&self, name: &str) -> Option<&Sequence> {
+        self.sequences
+            .iter()
+            .find(|sequence| sequence.name.eq_ignore_ascii_case(name))
+    }
+
+    pub fn device_metric(&self, name: &str, location: &str) -> Option<Metric> {
+        self.device(name, location).and_then(|device| device.metric)
+    }
+
+    /// Palette of a device showing `metric`: its own, the metric's or the global one.
+    pub fn device_palette(&self, name: &str, location: &str, metric: Metric) -> &Palette {
+        self.device(name, location)
+            .and_then(|device| device.palette.as_ref())
+            .or_else(|| self.palettes.get(&metric))
+            .unwrap_or(&self.palette)
+    }
+
+    /// Whether a device or zone of the configuration, of a profile or of a scene shows
+    /// `metric`, directly or as the hue of a `hue-shift`. The built-in rules of known
+    /// controllers aren't counted, they depend on the hardware found.
+    pub fn maps(&self, metric: Metric) -> bool {
+        let shows = |effect: &Option<Effect>| matches!(effect, Some(Effect::HueShift(hue_shift)) if hue_shift.metric == metric);
+        let devices_show = |devices: &[DeviceConfig]| {
+            devices.iter().any(|device| {
+                device.metric == Some(metric)
+                    || shows(&device.effect)
+                    || shows(&device.idle_effect)
+                    || device.zones.iter().any(|zone| {
+                        zone.metric == Some(metric)
+                            || shows(&zone.effect)
+                            || shows(&zone.idle_effect)
+                    })
+            })
+        };
+
+        self.default_metric.unwrap_or(Metric::Cpu) == metric
+            || devices_show(&self.devices)
+            || self
+                .profiles
+                .values()
+                .chain(self.scenes.values().map(|scene| &scene.layers))
+                .any(|layers| {
+                    layers.default_metric == Some(metric) || devices_show(&layers.devices)
+                })
+    }
+
+    /// Whether `metric` is mapped, see `maps`, or raises alerts of the configuration or of a
+    /// scene.
+    pub fn uses(&self, metric: Metric) -> bool {
+        self.maps(metric)
+            || self
+                .alerts
+                .iter()
+                .chain(self.scenes.values().flat_map(|scene| &scene.alerts))
+                .any(|alert| alert.metric == metric)
+    }
+}
+
+/// Whether a controller or zone name matches a name of the configuration, in which `*` stands
+/// for any text and `?` for any character, as names vary between OpenRGB versions and firmware
+/// revisions.
+pub fn name_matches(pattern: &str, name: &str) -> bool {
+    let (pattern, name) = (
+        pattern.chars().collect::<Vec<_>>(),
+        name.chars().collect::<Vec<_>>(),
+    );
+    let (mut p, mut n) = (0, 0);
+    // Last `*` seen and the character of the name it was tried at, to backtrack to.
+    let mut star = None;
+
+    while n < name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                star = Some((p, n));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => match star {
+                Some((star_p, star_n)) => {
+                    // The `*` takes one more character.
+                    star = Some((star_p, star_n + 1));
+                    p = star_p + 1;
+                    n = star_n + 1;
+                }
+                None => return false,
+            },
+        }
+    }
+
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+/// Syntax of a configuration file, from its extension: YAML for `.yaml` and `.yml`, JSON for
+/// `.json` and TOML otherwise.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Toml,
+    Yaml,
+    Json,
+}
+
+impl Format {
+    pub fn of(path: &Path) -> Self {
+        let extension = path
+            .extension()
+            .map(|extension| extension.to_string_lossy().to_ascii_lowercase());
+
+        match extension.as_deref() {
+            Some("yaml" | "yml") => Self::Yaml,
+            Some("json") => Self::Json,
+            _ => Self::Toml,
+        }
+    }
+
+    pub fn parse(self, contents: &str) -> Result<Table> {
+        match self {
+            Self::Toml => Ok(toml::from_str(contents)?),
+            Self::Json => Ok(serde_json::from_str(contents)?),
+            #[cfg(feature = "yaml")]
+            Self::Yaml => Ok(serde_norway::from_str(contents)?),
+            #[cfg(not(feature = "yaml"))]
+            Self::Yaml => bail!("YAML configurations need a client built with `--features yaml`."),
+        }
+    }
+
+    pub fn write(self, table: &Table) -> Result<String> {
+        match self {
+            Self::Toml => Ok(toml::to_string(table)?),
+            Self::Json => Ok(serde_json::to_string_pretty(table)? + "\n"),
+            #[cfg(feature = "yaml")]
+            Self::Yaml => Ok(serde_norway::to_string(table)?),
+            #[cfg(not(feature = "yaml"))]
+            Self::Yaml => bail!("YAML configurations need a client built with `--features yaml`."),
+        }
+    }
+}
+
+fn read_table(path: &Path) -> Result<Table> {
+    let contents =
+        std::fs::read_to_string(path).with_context(|| format!("Cannot read {:?}", path))?;
+
+    Format::of(path)
+        .parse(&contents)
+        .with_context(|| format!("Invalid configuration {:?}", path))
+}
+
+/// Merges the devices and templates of the files listed in `include`, relative to `directory`.
+fn include_files(table: &mut Table, directory: &Path, depth: usize) -> Result<()> {
+    let Some(include) = table.remove("include") else {
+        return Ok(());
+    };
+
+    if depth >= MAX_INCLUDE_DEPTH {
+        bail!("Includes nested too deeply, is a file including itself?");
+    }
+
+    let patterns = include
+        .try_into::<Vec<String>>()
+        .context("`include` must be a list of paths")?;
+
+    for pattern in patterns {
+        for path in find_files(&directory.join(&pattern))? {
+            include_file(table, &path, directory, depth)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Merges the devices, templates and aliases of the file at `path`, and of the files it
+/// includes in turn.
+fn include_file(table: &mut Table, path: &Path, directory: &Path, depth: usize) -> Result<()> {
+    let mut included = read_table(path)?;
+    migration::upgrade(path, &mut included)?;
+    included.remove("version");
+    let included_directory = path.parent().unwrap_or(directory);
+    include_files(&mut included, included_directory, depth + 1)?;
+
+    for (key, value) in included {
+        match (key.as_str(), value) {
+            ("devices", Value::Array(devices)) => {
+                let entry = table
+                    .entry("devices")
+                    .or_insert_with(|| Value::Array(vec![]));
+                if let Value::Array(existing) = entry {
+                    existing.extend(devices);
+                }
+            }
+            (key @ ("templates" | "aliases"), Value::Table(values)) => {
+                let entry = table
+                    .entry(key)
+                    .or_insert_with(|| Value::Table(Table::new()));
+                if let Value::Table(existing) = entry {
+                    existing.extend(values);
+                }
+            }
+            // The zones are added to the layout, the including file's width winning.
+            ("layout", Value::Table(layout)) => {
+                let entry = table
+                    .entry("layout")
+                    .or_insert_with(|| Value::Table(Table::new()));
+                if let Value::Table(existing) = entry {
+                    for (key, value) in layout {
+                        match (existing.get_mut(&key), value) {
+                            (Some(Value::Array(zones)), Value::Array(more)) => zones.extend(more),
+                            (Some(_), _) => {}
+                            (None, value) => {
+                                existing.insert(key, value);
+                            }
+                        }
+                    }
+                }
+            }
+            (key, _) => bail!(
+                "{:?}: only devices, templates, aliases and layout can be included, not {}",
+                path,
+                key
+            ),
+        }
+    }
+
+    Ok(())
+}
+
+/// Configuration files of the drop-in directory, in name order, none when it doesn't exist.
+/// Other files, e.g. the backups of migrated files, are ignored.
+fn drop_in_files(directory: &Path) -> Result<Vec<PathBuf>> {
+    if !directory.is_dir() {
+        return Ok(vec![]);
+    }
+
+    let mut files = std::fs::read_dir(directory)
+        .with_context(|| format!("Cannot list {:?}", directory))?
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| {
+            path.is_file()
+                && path.extension().is_some_and(|extension| {
+                    ["toml", "yaml", "yml", "json"]
+                        .iter()
+                        .any(|known| extension.eq_ignore_ascii_case(known))
+                })
+        })
+        .collect::<Vec<_>>();
+    files.sort();
+
+    Ok(files)
+}
+
+/// Lists the files matching `pattern`, whose file name may contain `*` and `?` wildcards.
+fn find_files(pattern: &Path) -> Result<Vec<PathBuf>> {
+    let file_pattern = pattern
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+
+    if !file_pattern.contains(['*', '?']) {
+        if !pattern.is_file() {
+            bail!("Included file {:?} not found", pattern);
+        }
+
+        return Ok(vec![pattern.to_owned()]);
+    }
+
+    let directory = pattern.parent().unwrap_or(Path::new("."));
+    let mut files = std::fs::read_dir(directory)
+        .with_context(|| format!("Cannot list {:?}", directory))?
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| {
+            path.is_file()
+                && path.file_name().is_some_and(|name| {
+                    wildcard_match(file_pattern.as_bytes(), name.to_string_lossy().as_bytes())
+                })
+        })
+        .collect::<Vec<_>>();
+
+    // Later files override the templates of earlier ones, make that predictable.
+    files.sort();
+
+    Ok(files)
+}
+
+fn wildcard_match(pattern: &[u8], name: &[u8]) -> bool {
+    match (pattern.first(), name.first()) {
+        (None, None) => true,
+        (Some(b'*'), _) => {
+            wildcard_match(&pattern[1..], name)
+                || (!name.is_empty() && wildcard_match(pattern, &name[1..]))
+        }
+        (Some(b'?'), Some(_)) => wildcard_match(&pattern[1..], &name[1..]),
+        (Some(p), Some(n)) if p == n => wildcard_match(&pattern[1..], &name[1..]),
+        _ => false,
+    }
+}
+
+/// Replaces the devices using a template with the template's fields, their `{parameters}`
+/// substituted, overridden by the fields set on the device itself.
+fn expand_templates(table: &mut Table) -> Result<()> {
+    let templates = match table.remove("templates") {
+        Some(Value::Table(templates)) => templates,
+        Some(_) => bail!("`templates` must be a table"),
+        None => Table::new(),
+    };
+
+    if let Some(Value::Array(devices)) = table.get_mut("devices") {
+        expand_devices(devices, &templates)?;
+    }
+    if let Some(Value::Table(profiles)) = table.get_mut("profiles") {
+        for (_, profile) in profiles.iter_mut() {
+            if let Some(Value::Array(devices)) = profile.get_mut("devices") {
+                expand_devices(devices, &templates)?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn expand_devices(devices: &mut [Value], templates: &Table) -> Result<()> {
+    for device in devices {
+        let Value::Table(fields) = device else {
+            continue;
+        };
+
+        let Some(template_name) = fields.remove("template") else {
+            continue;
+        };
+        let template_name = template_name
+            .as_str()
+            .context("`template` must be a template name")?
+            .to_owned();
+
+        let Some(Value::Table(template)) = templates.get(&template_name) else {
+            bail!("Unknown template: {}", template_name);
+        };
+
+        let params = match fields.remove("params") {
+            Some(Value::Table(params)) => params,
+            Some(_) => bail!("`params` must be a table"),
+            None => Table::new(),
+        };
+
+        let mut expanded = template.clone();
+        for (_, value) in expanded.iter_mut() {
+            substitute(value, &params, &template_name)?;
+        }
+
+        expanded.extend(std::mem::take(fields));
+        *fields = expanded;
+    }
+
+    Ok(())
+}
+
+fn substitute(value: &mut Value, params: &Table, template_name: &str) -> Result<()> {
+    match value {
+        Value::String(text) => {
+            for (name, param) in params {
+                let param = match param {
+                    Value::String(param) => param.clone(),
+                    param => param.to_string(),
+                };
+
+                *text = text.replace(&format!("{{{}}}", name), &param);
+            }
+
+            if let Some(start) = text.find('{') {
+                if let Some(end) = text[start..].find('}') {
+                    bail!(
+                        "Missing parameter {} for template {}",
+                        &text[start..=start + end],
+                        template_name
+                    );
+                }
+            }
+        }
+        Value::Array(values) => {
+            for value in values {
+                substitute(value, params, template_name)?;
+            }
+        }
+        Value::Table(values) => {
+            for (_, value) in values.iter_mut() {
+                substitute(value, params, template_name)?;
+            }
+        }
+        _ => {}
+    }
+
+    Ok(())
+}
+
+/// Parses `#RRGGBB`, `#RGB` or a CSS color name such as `orange`. The `#` can be left out of
+/// six digits, as older configurations did.
+pub fn parse_color(value: &str) -> Result<Color> {
+    let invalid = || {
+        anyhow!(
+            "Invalid color: {} (expected #RRGGBB, #RGB or a CSS color name)",
+            value
+        )
+    };
+    if let Some(color) = color_names::lookup(value) {
+        return Ok(color);
+    }
+
+    let digits = match value.strip_prefix('#') {
+        Some(digits) if digits.len() == 3 || digits.len() == 6 => digits,
+        None if value.len() == 6 => value,
+        _ => return Err(invalid()),
+    };
+    if !digits.bytes().all(|digit| digit.is_ascii_hexdigit()) {
+        return Err(invalid());
+    }
+
+    let channel = |index: usize| {
+        if digits.len() == 3 {
+            // `#F80` stands for `#FF8800`.
+            u8::from_str_radix(&digits[index..index + 1], 16).map(|digit| digit * 0x11)
+        } else {
+            u8::from_str_radix(&digits[index * 2..index * 2 + 2], 16)
+        }
+        .map_err(|_| invalid())
+    };
+
+    Ok(Color::new(channel(0)?, channel(1)?, channel(2)?))
+}
+
+/// The color as `#RRGGBB`, which every parser of colors reads back.
+pub fn format_color(color: &Color) -> String {
+    format!("#{:02X}{:02X}{:02X}", color.r, color.g, color.b)
+}
+
+/// The color for people to read, as `#RRGGBB` followed by its CSS name when it has one.
+pub fn describe_color(color: &Color) -> String {
+    match color_names::name_of(color) {
+        Some(name) => format!("{} ({})", format_color(color), name),
+        None => format_color(color),
+    }
+}
+
+pub mod hex_color {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(color: &Color, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&format_color(color))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Color, D::Error> {
+        let value = String::deserialize(deserializer)?;
+
+        parse_color(&value).map_err(serde::de::Error::custom)
+    }
+}
+
+pub mod optional_hex_color {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(
+        color: &Option<Color>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        match color {
+            Some(color) => serializer.serialize_some(&format_color(color)),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<Color>, D::Error> {
+        Option::<String>::deserialize(deserializer)?
+            .map(|value| parse_color(&value).map_err(serde::de::Error::custom))
+            .transpose()
+    }
+}
+
+pub mod hex_colors {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(colors: &[Color], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(colors.iter().map(format_color))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<Color>, D::Error> {
+        Vec::<String>::deserialize(deserializer)?
+            .iter()
+            .map(|value| parse_color(value).map_err(serde::de::Error::custom))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn colors_parse_strictly_and_print_back() {
+        for value in ["#FF8000", "#f80", "FF8000", "DarkOrange", "darkorange"] {
+            let color = parse_color(value).unwrap();
+
+            assert_eq!(parse_color(&format_color(&color)).unwrap(), color);
+        }
+        assert_eq!(format_color(&parse_color("#f80").unwrap()), "#FF8800");
+        assert_eq!(
+            describe_color(&parse_color("#00ffff").unwrap()),
+            "#00FFFF (aqua)"
+        );
+        for value in [
+            "",
+            "#",
+            "##FF8000",
+            "#FF80",
+            "F80",
+            "#GG8000",
+            "#FF8000 ",
+            "ultraviolet",
+        ] {
+            assert!(parse_color(value).is_err(), "{:?}", value);
+        }
+    }
+
+    #[test]
+    fn json_configurations_read_like_toml_ones() {
+        assert_eq!(Format::of(Path::new("open_rgb_client.YML")), Format::Yaml);
+        assert_eq!(Format::of(Path::new("devices.d/ram.json")), Format::Json);
+        assert_eq!(Format::of(Path::new("open_rgb_client")), Format::Toml);
+
+        let table = Format::Json
+            .parse(r#"{"hosts": ["desktop.lan"], "devices": [{"name": "RAM", "metric": "gpu"}]}"#)
+            .unwrap();
+        let config: Config = Value::Table(table).try_into().unwrap();
+        assert_eq!(config.hosts, ["desktop.lan"]);
+        assert_eq!(config.devices[0].metric, Some(Metric::Gpu));
+
+        let written = Format::Json
+            .write(&Table::try_from(&config).unwrap())
+            .unwrap();
+        let config: Config = Value::Table(Format::Json.parse(&written).unwrap())
+            .try_into()
+            .unwrap();
+        assert_eq!(config.devices[0].name, "RAM");
+    }
+
+    #[test]
+    fn mapped_metrics_include_hue_shifts_and_profiles() {
+        let config: Config = toml::from_str(
+            r#"
+            default_metric = "cpu"
+
+            [[devices]]
+            name = "Strip"
+            effect = { type = "hue-shift", metric = "gpu_temperature" }
+
+            [profiles.gaming]
+            devices = [{ name = "RAM", zones = [{ name = "Top", metric = "thermal" }] }]
+
+            [[scenes.night.alerts]]
+            metric = "cpu_temperature"
+            above = 0.9
+            notification = { controller = "*", effect = { type = "rainbow" }, duration = "10s" }
+            "#,
+        )
+        .unwrap();
+
+        for metric in [Metric::Cpu, Metric::GpuTemperature, Metric::Thermal] {
+            assert!(config.maps(metric), "{}", metric);
+        }
+        assert!(!config.maps(Metric::Gpu));
+        assert!(Config::default().maps(Metric::Cpu));
+        assert!(!config.maps(Metric::CpuTemperature));
+        assert!(config.uses(Metric::CpuTemperature));
+    }
+}
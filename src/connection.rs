@@ -0,0 +1,410 @@
+use crate::sequence::duration;
+use anyhow::{anyhow, bail, Result};
+use log::*;
+use openrgb::{OpenRGB, DEFAULT_ADDR};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::hash_map::{DefaultHasher, RandomState},
+    fmt,
+    hash::{BuildHasher, Hash, Hasher},
+    net::{IpAddr, SocketAddr},
+    ops::RangeInclusive,
+    path::PathBuf,
+    str::FromStr,
+    sync::Mutex,
+    time::Duration,
+};
+use tokio::{
+    net::{lookup_host, TcpStream},
+    time::timeout,
+};
+
+pub const DEFAULT_PORT: u16 = DEFAULT_ADDR.1;
+pub const DEFAULT_CONNECT_TIMEOUT: Duration = Duration::from_secs(2);
+
+// Ports users typically move the SDK server to when the default one is taken.
+const DISCOVERY_PORTS: RangeInclusive<u16> = 6742..=6752;
+const PROBE_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// How long to wait between the attempts to reach OpenRGB: doubling from `initial` up to `max`,
+/// each wait shortened by up to `jitter` of it so clients restarted together don't retry in
+/// lockstep.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ReconnectConfig {
+    #[serde(with = "duration")]
+    pub initial: Duration,
+    #[serde(with = "duration")]
+    pub max: Duration,
+    /// Fraction of each wait randomly taken off, from 0 to 1.
+    pub jitter: f32,
+    /// Failed attempts in a row after which the client exits with an error, for service
+    /// managers to restart it or alert. Retries forever without.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_retries: Option<u32>,
+}
+
+impl Default for ReconnectConfig {
+    fn default() -> Self {
+        Self {
+            initial: Duration::from_secs(1),
+            max: Duration::from_secs(30),
+            jitter: 0.2,
+            max_retries: None,
+        }
+    }
+}
+
+/// Failed attempts to reach OpenRGB since the last connection.
+#[derive(Debug, Default)]
+pub struct Backoff {
+    failures: u32,
+}
+
+impl Backoff {
+    /// Records a failed attempt and returns how long to wait before the next one, failing when
+    /// `max_retries` is reached.
+    pub fn next_delay(&mut self, config: &ReconnectConfig) -> Result<Duration> {
+        self.failures += 1;
+        if config.max_retries.is_some_and(|max| self.failures > max) {
+            bail!("Could not reach OpenRGB after {} attempts.", self.failures);
+        }
+
+        let delay = config
+            .initial
+            .saturating_mul(1 << (self.failures - 1).min(16))
+            .min(config.max);
+        let jitter = config.jitter.clamp(0.0, 1.0) as f64 * random_fraction();
+
+        Ok(delay.mul_f64(1.0 - jitter))
+    }
+
+    pub fn reset(&mut self) {
+        self.failures = 0;
+    }
+}
+
+/// Version of the controller list of a server: the controllers, their active modes and zone
+/// sizes. A server that restarted resets the modes and sizes the client set, so it comes back
+/// with another version than the one the client left it at, unlike a connection that dropped.
+pub async fn controller_list_version(client: &OpenRGB<TcpStream>) -> Result<u64> {
+    let mut hasher = DefaultHasher::new();
+    for controller_id in 0..client.get_controller_count().await? {
+        let controller = client.get_controller(controller_id).await?;
+
+        controller.name.hash(&mut hasher);
+        controller.location.hash(&mut hasher);
+        controller.active_mode.hash(&mut hasher);
+        for zone in &controller.zones {
+            zone.leds_count.hash(&mut hasher);
+        }
+    }
+
+    Ok(hasher.finish())
+}
+
+/// Random number from 0 to 1, good enough to spread retries.
+fn random_fraction() -> f64 {
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_u32(std::process::id());
+
+    (hasher.finish() >> 11) as f64 / (1u64 << 53) as f64
+}
+
+/// OpenRGB server address: a hostname, IPv4 or IPv6 literal, with an optional port.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Host {
+    pub name: String,
+    pub port: Option<u16>,
+}
+
+impl FromStr for Host {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self> {
+        if let Ok(address) = value.parse::<SocketAddr>() {
+            return Ok(Self {
+                name: address.ip().to_string(),
+                port: Some(address.port()),
+            });
+        }
+
+        let unbracketed = value.trim_start_matches('[').trim_end_matches(']');
+        if let Ok(ip) = unbracketed.parse::<IpAddr>() {
+            return Ok(Self {
+                name: ip.to_string(),
+                port: None,
+            });
+        }
+
+        let (name, port) = match value.rsplit_once(':') {
+            Some((name, port)) => (name, Some(port.parse()?)),
+            None => (value, None),
+        };
+
+        if name.is_empty() || name.contains(':') {
+            bail!("Invalid host: {}", value);
+        }
+
+        Ok(Self {
+            name: name.to_owned(),
+            port,
+        })
+    }
+}
+
+impl fmt::Display for Host {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = if self.name.contains(':') {
+            format!("[{}]", self.name)
+        } else {
+            self.name.clone()
+        };
+
+        match self.port {
+            Some(port) => write!(f, "{}:{}", name, port),
+            None => write!(f, "{}", name),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct TlsOptions {
+    /// Name the server certificate is checked against, instead of the host name.
+    pub server_name: Option<String>,
+    /// PEM file with the certificate authorities to trust instead of the public web roots.
+    pub ca_file: Option<PathBuf>,
+    /// SHA-256 fingerprints of the accepted server certificates, bypassing CA validation.
+    pub pins: Vec<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ConnectionOptions {
+    /// Servers to try in order, the first one answering wins.
+    pub hosts: Vec<Host>,
+    /// Port used for hosts that don't specify one.
+    pub port: u16,
+    /// Time allowed to each resolved address to accept the connection.
+    pub timeout: Duration,
+    pub discover_port: bool,
+    pub mdns: bool,
+    pub tls: Option<TlsOptions>,
+}
+
+impl Default for ConnectionOptions {
+    fn default() -> Self {
+        Self {
+            hosts: vec![],
+            port: DEFAULT_PORT,
+            timeout: DEFAULT_CONNECT_TIMEOUT,
+            discover_port: false,
+            mdns: false,
+            tls: None,
+        }
+    }
+}
+
+impl ConnectionOptions {
+    pub fn hosts(&self) -> Vec<Host> {
+        if self.hosts.is_empty() {
+            vec![Host {
+                name: "localhost".to_owned(),
+                port: None,
+            }]
+        } else {
+            self.hosts.clone()
+        }
+    }
+}
+
+/// Connects to the OpenRGB SDK server, remembering a discovered address across reconnects.
+pub struct Connector {
+    options: ConnectionOptions,
+    discovered: Option<SocketAddr>,
+    /// Address and host name of the server last connected to.
+    server: Mutex<Option<(SocketAddr, String)>>,
+    #[cfg(feature = "tls")]
+    tls: Option<crate::tls::TlsConnector>,
+}
+
+impl Connector {
+    pub fn new(options: &ConnectionOptions) -> Result<Self> {
+        #[cfg(not(feature = "tls"))]
+        if options.tls.is_some() {
+            bail!("This build has no TLS support.");
+        }
+
+        Ok(Self {
+            options: options.clone(),
+            discovered: None,
+            server: Mutex::new(None),
+            #[cfg(feature = "tls")]
+            tls: options
+                .tls
+                .as_ref()
+                .map(crate::tls::TlsConnector::new)
+                .transpose()?,
+        })
+    }
+
+    pub async fn connect(&mut self) -> Result<OpenRGB<TcpStream>> {
+        if let Some(address) = self.discovered {
+            if let Some(client) = self.probe(address, self.options.timeout).await {
+                return Ok(client);
+            }
+        }
+
+        let mut last_error = None;
+        for host in self.options.hosts() {
+            match self.connect_host(&host).await {
+                Ok(client) => return Ok(client),
+                Err(e) => {
+                    debug!("Failed to connect to {}: {}", host, e);
+
+                    last_error = Some(e);
+                }
+            }
+        }
+
+        if self.options.discover_port {
+            debug!("No OpenRGB server answered, probing ports...");
+
+            if let Some(client) = self.probe_ports().await {
+                return Ok(client);
+            }
+        }
+
+        #[cfg(feature = "mdns")]
+        if self.options.mdns {
+            debug!("No OpenRGB server answered, browsing mDNS...");
+
+            if let Some(client) = self.browse_mdns().await {
+                return Ok(client);
+            }
+        }
+
+        Err(last_error.unwrap_or_else(|| anyhow!("No OpenRGB server to connect to.")))
+    }
+
+    async fn connect_host(&self, host: &Host) -> Result<OpenRGB<TcpStream>> {
+        let mut last_error = anyhow!("{} did not resolve to any address.", host);
+        for address in self.resolve(host).await? {
+            match timeout(self.options.timeout, self.open(address, &host.name)).await {
+                Ok(Ok(client)) => return Ok(client),
+                Ok(Err(e)) => last_error = e,
+                Err(_) => last_error = anyhow!("Timed out connecting to {}.", address),
+            }
+        }
+
+        Err(last_error)
+    }
+
+    async fn resolve(&self, host: &Host) -> Result<Vec<SocketAddr>> {
+        let port = host.port.unwrap_or(self.options.port);
+
+        Ok(lookup_host((host.name.as_str(), port)).await?.collect())
+    }
+
+    async fn probe_ports(&mut self) -> Option<OpenRGB<TcpStream>> {
+        for host in self.options.hosts() {
+            let Ok(addresses) = self.resolve(&host).await else {
+                continue;
+            };
+
+            for address in addresses {
+                for port in DISCOVERY_PORTS.filter(|port| *port != address.port()) {
+                    let address = SocketAddr::new(address.ip(), port);
+                    if let Some(client) = self.probe(address, PROBE_TIMEOUT).await {
+                        info!("Discovered OpenRGB SDK server on {}.", address);
+                        self.discovered = Some(address);
+
+                        return Some(client);
+                    }
+                }
+            }
+        }
+
+        None
+    }
+
+    #[cfg(feature = "mdns")]
+    async fn browse_mdns(&mut self) -> Option<OpenRGB<TcpStream>> {
+        use crate::discovery::{browse_servers, BROWSE_TIMEOUT};
+
+        let servers = match browse_servers(BROWSE_TIMEOUT, 1).await {
+            Ok(servers) => servers,
+            Err(e) => {
+                warn!("Failed to browse mDNS: {}", e);
+
+                return None;
+            }
+        };
+
+        for server in servers {
+            for address in server.addresses {
+                if let Some(client) = self.probe(address, self.options.timeout).await {
+                    info!(
+                        "Discovered OpenRGB SDK server {} at {}.",
+                        server.name, address
+                    );
+                    self.discovered = Some(address);
+
+                    return Some(client);
+                }
+            }
+        }
+
+        None
+    }
+
+    async fn probe(&self, address: SocketAddr, duration: Duration) -> Option<OpenRGB<TcpStream>> {
+        // Something else may be listening on the port, so don't wait forever for a handshake.
+        timeout(duration, self.open(address, &address.ip().to_string()))
+            .await
+            .ok()?
+            .ok()
+    }
+
+    async fn open(&self, address: SocketAddr, host: &str) -> Result<OpenRGB<TcpStream>> {
+        let client = OpenRGB::new(self.open_stream(address, host).await?).await?;
+        *self.server.lock().unwrap() = Some((address, host.to_owned()));
+
+        Ok(client)
+    }
+
+    async fn open_stream(&self, address: SocketAddr, host: &str) -> Result<TcpStream> {
+        #[cfg(feature = "tls")]
+        if let Some(tls) = &self.tls {
+            return tls.connect(address, host).await;
+        }
+
+        #[cfg(not(feature = "tls"))]
+        let _ = host;
+
+        Ok(TcpStream::connect(address).await?)
+    }
+
+    /// Address of the server last connected to.
+    pub fn server(&self) -> Option<SocketAddr> {
+        self.server
+            .lock()
+            .unwrap()
+            .as_ref()
+            .map(|(address, _)| *address)
+    }
+
+    /// Another stream to the server last connected to, for the requests the SDK client gets
+    /// wrong, see `sdk`.
+    pub async fn raw_stream(&self) -> Result<TcpStream> {
+        let (address, host) = self
+            .server
+            .lock()
+            .unwrap()
+            .clone()
+            .ok_or_else(|| anyhow!("Not connected."))?;
+
+        timeout(self.options.timeout, self.open_stream(address, &host))
+            .await
+            .map_err(|_| anyhow!("Timed out connecting to {}.", address))?
+    }
+}
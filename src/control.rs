@@ -0,0 +1,1243 @@
+use crate::{
+    accessibility::Accessibility,
+    color_manager::dim,
+    config::{Config, Metric},
+    demo::Waveform,
+    events::{Event, Events},
+    fleet::Fleet,
+    history::{unix_millis, History},
+    i18n::tr,
+    latency::Latencies,
+    logging::{self, LogFilters},
+    mapping::ControllerInfo,
+    notifications::Notifications,
+    power::LedPower,
+    remote,
+    runtime::RuntimeStats,
+    sampler::Samples,
+    scene::Alerts,
+    sequence::Player,
+};
+use anyhow::{anyhow, bail, Result};
+use log::*;
+use openrgb::data::Color;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    net::{Ipv4Addr, SocketAddr},
+    str::FromStr,
+    sync::{
+        atomic::{AtomicU32, AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader},
+    sync::watch,
+};
+
+#[cfg(unix)]
+const CONTROL_SOCKET: &str = "open_rgb_client.sock";
+/// Directory of the control socket of a client running as root, e.g. as a service.
+#[cfg(unix)]
+const SYSTEM_SOCKET_DIRECTORY: &str = "/run/open_rgb_client";
+/// Group whose members can use the control socket of a client running as root.
+#[cfg(unix)]
+pub const CONTROL_GROUP: &str = "open_rgb_client";
+/// Longest request read from the control socket, newline included, far above any valid one.
+const MAX_REQUEST_LENGTH: u64 = 4096;
+#[cfg(windows)]
+const CONTROL_PIPE: &str = r"\\.\pipe\open_rgb_client";
+
+/// Full access for LocalSystem, the administrators and the owner, read and write access for
+/// the users logged in interactively, so they can control the instance running as a service.
+/// Remote clients are rejected regardless.
+#[cfg(windows)]
+const CONTROL_PIPE_SDDL: &str = "D:P(A;;GA;;;SY)(A;;GA;;;BA)(A;;GA;;;OW)(A;;GRGW;;;IU)";
+
+/// The control requests served over gRPC as well, see `proto/open_rgb_client.proto`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GrpcConfig {
+    /// Anyone reaching this address controls the client, keep it local unless the network is
+    /// trusted.
+    #[serde(default = "GrpcConfig::default_address")]
+    pub address: SocketAddr,
+}
+
+impl GrpcConfig {
+    fn default_address() -> SocketAddr {
+        (Ipv4Addr::LOCALHOST, 6744).into()
+    }
+}
+
+/// A metric value replacing the sampled one.
+#[derive(Debug, Clone, Copy)]
+pub struct Injection {
+    pub metric: Metric,
+    pub value: f32,
+}
+
+impl FromStr for Injection {
+    type Err = anyhow::Error;
+
+    /// Parses `<metric>=<value>`, e.g. `cpu=0.9`.
+    fn from_str(value: &str) -> Result<Self> {
+        let (metric, level) = value
+            .split_once('=')
+            .ok_or_else(|| anyhow!("Invalid injection: {} (expected <metric>=<value>)", value))?;
+
+        let metric = metric.parse()?;
+        if metric == Metric::None {
+            bail!("Cannot inject a value for the none metric.");
+        }
+
+        let level = level.parse::<f32>()?;
+        if !(0.0..=1.0).contains(&level) {
+            bail!("Invalid value: {} (expected 0 to 1)", level);
+        }
+
+        Ok(Self {
+            metric,
+            value: level,
+        })
+    }
+}
+
+/// Requests sent to a running client through the control socket, one per line.
+#[derive(Debug, Clone)]
+pub enum ControlRequest {
+    Inject {
+        injections: Vec<Injection>,
+        duration: Duration,
+    },
+    /// Plays a sequence of the configuration.
+    Play {
+        sequence: String,
+    },
+    ReducedMotion(bool),
+    HighContrast(bool),
+    /// Scales the brightness of every LED, from 0 to 1.
+    Brightness(f32),
+    /// Changes what gets logged, to debug without restarting.
+    LogLevel(LogFilters),
+    /// Stops or resumes updating a zone, or a whole controller without zone.
+    SetZone {
+        controller: String,
+        zone: Option<String>,
+        enabled: bool,
+    },
+    /// Switches to a named profile, or back to the configuration as loaded without name.
+    SwitchProfile {
+        name: Option<String>,
+    },
+    /// Switches to a scene, or back to the configuration as loaded without name.
+    SwitchScene {
+        name: Option<String>,
+    },
+    /// Samples of the history taken within `last`, answered as a JSON array.
+    History {
+        last: Duration,
+    },
+    /// State of the running client, answered as a JSON `LiveStatus`.
+    Status,
+}
+
+impl ControlRequest {
+    fn to_line(&self) -> String {
+        match self {
+            Self::Inject {
+                injections,
+                duration,
+            } => {
+                let mut line = "inject".to_owned();
+                for injection in injections {
+                    line += &format!(" {}={}", injection.metric, injection.value);
+                }
+
+                format!("{} {}\n", line, duration.as_secs_f64())
+            }
+            Self::Play { sequence } => format!("play {}\n", sequence),
+            Self::ReducedMotion(enabled) => format!("reduced-motion {}\n", switch(*enabled)),
+            Self::HighContrast(enabled) => format!("high-contrast {}\n", switch(*enabled)),
+            Self::Brightness(level) => format!("brightness {}\n", level),
+            Self::LogLevel(filters) => format!("log-level {}\n", filters),
+            // Names have spaces, tabs separate them.
+            Self::SetZone {
+                controller,
+                zone,
+                enabled,
+            } => format!(
+                "{} {}\t{}\n",
+                if *enabled { "enable" } else { "disable" },
+                controller,
+                zone.as_deref().unwrap_or_default()
+            ),
+            Self::SwitchProfile { name } => {
+                format!("switch-profile {}\n", name.as_deref().unwrap_or_default())
+            }
+            Self::SwitchScene { name } => {
+                format!("switch-scene {}\n", name.as_deref().unwrap_or_default())
+            }
+            Self::History { last } => format!("history {}\n", last.as_secs_f64()),
+            Self::Status => "status\n".to_owned(),
+        }
+    }
+
+    fn parse(line: &str) -> Result<Self> {
+        if let Some((request @ ("enable" | "disable"), names)) = line.trim_end().split_once(' ') {
+            let (controller, zone) = names.split_once('\t').unwrap_or((names, ""));
+
+            return Ok(Self::SetZone {
+                controller: controller.to_owned(),
+                zone: (!zone.is_empty()).then(|| zone.to_owned()),
+                enabled: request == "enable",
+            });
+        }
+
+        let mut words = line.split_whitespace();
+        match words.next() {
+            Some("inject") => {
+                let mut words = words.collect::<Vec<_>>();
+                let duration = words
+                    .pop()
+                    .ok_or_else(|| anyhow!("Missing duration."))?
+                    .parse::<f64>()?;
+
+                Ok(Self::Inject {
+                    injections: words.into_iter().map(str::parse).collect::<Result<_>>()?,
+                    duration: Duration::try_from_secs_f64(duration)?,
+                })
+            }
+            Some("play") => Ok(Self::Play {
+                sequence: words.collect::<Vec<_>>().join(" "),
+            }),
+            Some("switch-profile") => {
+                let name = words.collect::<Vec<_>>().join(" ");
+
+                Ok(Self::SwitchProfile {
+                    name: (!name.is_empty()).then_some(name),
+                })
+            }
+            Some("switch-scene") => {
+                let name = words.collect::<Vec<_>>().join(" ");
+
+                Ok(Self::SwitchScene {
+                    name: (!name.is_empty()).then_some(name),
+                })
+            }
+            Some("status") => Ok(Self::Status),
+            Some("history") => Ok(Self::History {
+                last: Duration::try_from_secs_f64(
+                    words
+                        .next()
+                        .ok_or_else(|| anyhow!("Missing duration."))?
+                        .parse()?,
+                )?,
+            }),
+            Some("reduced-motion") => Ok(Self::ReducedMotion(parse_switch(
+                words.next().unwrap_or_default(),
+            )?)),
+            Some("high-contrast") => Ok(Self::HighContrast(parse_switch(
+                words.next().unwrap_or_default(),
+            )?)),
+            Some("brightness") => {
+                let level = words.next().unwrap_or_default().parse::<f32>()?;
+                if !(0.0..=1.0).contains(&level) {
+                    bail!("Brightness out of range: {} (expected 0 to 1)", level);
+                }
+
+                Ok(Self::Brightness(level))
+            }
+            Some("log-level") => Ok(Self::LogLevel(words.collect::<String>().parse()?)),
+            Some(request) => bail!("Unknown request: {}", request),
+            None => bail!("Empty request."),
+        }
+    }
+}
+
+/// What the control socket changes in the running client.
+#[derive(Debug, Default)]
+pub struct ControlState {
+    pub injections: Injections,
+    pub player: Player,
+    pub zones: DisabledZones,
+    pub accessibility: Accessibility,
+    pub events: Events,
+    pub notifications: Notifications,
+    /// Alerts raised, not to show them again on every sample.
+    pub alerts: Alerts,
+    /// Reports of the machines pushing to this client, for the dashboard.
+    pub fleet: Fleet,
+    /// Samples of the last `history.retention`.
+    pub history: History,
+    /// Latest samples, for the control transports streaming them.
+    pub samples: watch::Sender<Option<Samples>>,
+    /// Current configuration, replaced when the file changes or another profile is switched to.
+    pub config: watch::Sender<Arc<Config>>,
+    pub profiles: Profiles,
+    /// Resources used by the client, for the metrics endpoint.
+    pub runtime: watch::Sender<Option<RuntimeStats>>,
+    pub connection: watch::Sender<Connection>,
+    /// Scale of the brightness of every LED, set at runtime.
+    pub brightness: MasterBrightness,
+    /// Unix time in milliseconds each controller was last updated, by controller key.
+    pub updated: Mutex<BTreeMap<String, u64>>,
+    /// Time each controller takes to show a frame, which its animations are rendered ahead by.
+    pub latencies: Latencies,
+    pub power: LedPower,
+    /// Version of the controller list the client last left the server at, see
+    /// `controller_list_version`.
+    pub controller_list_version: Mutex<Option<u64>>,
+    /// Synthetic load sampled instead of the machine's, for `demo`.
+    pub demo: Option<Waveform>,
+    /// The LED updates are logged rather than sent, see `--dry-run`.
+    pub dry_run: bool,
+    /// The colors of each frame are printed in the terminal, see `--preview`.
+    pub preview: bool,
+}
+
+impl ControlState {
+    pub fn new(config: Arc<Config>) -> Self {
+        let profiles = Profiles::default();
+        let config = Arc::new(profiles.apply((*config).clone()));
+
+        Self {
+            accessibility: Accessibility::new(config.reduced_motion, config.high_contrast),
+            config: watch::Sender::new(config),
+            profiles,
+            ..Default::default()
+        }
+    }
+
+    pub fn config(&self) -> Arc<Config> {
+        self.config.borrow().clone()
+    }
+
+    pub fn live_status(&self) -> LiveStatus {
+        LiveStatus {
+            connection: self.connection.borrow().clone(),
+            samples: *self.samples.borrow(),
+            profile: self.profiles.active(),
+            scene: self.profiles.scene(),
+            updated: self.updated.lock().unwrap().clone(),
+            latency: self.latencies.millis(),
+            watts: self.power.controllers(),
+        }
+    }
+
+    /// Records the controllers a frame was sent to, the ones left alone having no colors.
+    pub fn record_updates(&self, frame: &[(String, Vec<Color>)]) {
+        let now = unix_millis(SystemTime::now());
+
+        let mut updated = self.updated.lock().unwrap();
+        for (controller, colors) in frame {
+            if colors.is_empty() {
+                continue;
+            }
+            match updated.get_mut(controller) {
+                Some(updated) => *updated = now,
+                None => {
+                    updated.insert(controller.clone(), now);
+                }
+            }
+        }
+        self.power.retain(frame);
+    }
+}
+
+/// Brightness of every LED on top of the rendering, from 0 to 1.
+#[derive(Debug)]
+pub struct MasterBrightness(AtomicU32);
+
+impl Default for MasterBrightness {
+    fn default() -> Self {
+        Self(AtomicU32::new(1.0f32.to_bits()))
+    }
+}
+
+impl MasterBrightness {
+    pub fn level(&self) -> f32 {
+        f32::from_bits(self.0.load(Ordering::Relaxed))
+    }
+
+    pub fn set(&self, level: f32) {
+        self.0
+            .store(level.clamp(0.0, 1.0).to_bits(), Ordering::Relaxed);
+    }
+
+    pub fn apply(&self, colors: &mut [Color]) {
+        let level = self.level();
+        for color in colors {
+            *color = dim(*color, level);
+        }
+    }
+}
+
+/// State of the connection to OpenRGB.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(tag = "state", rename_all = "kebab-case")]
+pub enum Connection {
+    #[default]
+    Connecting,
+    Connected {
+        server: Option<SocketAddr>,
+        /// Unix time in milliseconds.
+        since: u64,
+    },
+    Disconnected {
+        reason: String,
+    },
+}
+
+/// What the running client is doing, answered to `status`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LiveStatus {
+    pub connection: Connection,
+    /// Latest samples, the CPU and GPU ones averaged, `None` before the first ones.
+    pub samples: Option<Samples>,
+    pub profile: Option<String>,
+    #[serde(default)]
+    pub scene: Option<String>,
+    /// Unix time in milliseconds each controller was last updated, by controller key.
+    pub updated: BTreeMap<String, u64>,
+    /// Milliseconds each controller takes to show a frame once it is sent, by controller key.
+    #[serde(default)]
+    pub latency: BTreeMap<String, f64>,
+    /// Estimated power drawn by the LEDs of each controller for the last frame, by controller
+    /// key.
+    #[serde(default)]
+    pub watts: BTreeMap<String, f64>,
+}
+
+impl LiveStatus {
+    /// What the running client is doing, for the terminal and the chat bot.
+    pub fn lines(&self) -> Vec<String> {
+        let now = unix_millis(SystemTime::now());
+        let seconds_ago = |time: u64| format!("{:.1}", now.saturating_sub(time) as f64 / 1000.0);
+
+        let mut lines = vec![match &self.connection {
+            Connection::Connecting => tr!("status-connecting"),
+            Connection::Connected { server, since } => tr!(
+                "status-connected",
+                "server" => server.map_or("?".to_owned(), |server| server.to_string()),
+                "seconds" => seconds_ago(*since)
+            ),
+            Connection::Disconnected { reason } => {
+                tr!("status-disconnected", "reason" => reason.as_str())
+            }
+        }];
+
+        if let Some(samples) = &self.samples {
+            lines.push(tr!(
+                "status-averages",
+                "cpu" => format!("{:.0}", samples.cpu * 100.0),
+                "gpu" => samples
+                    .gpu
+                    .map_or("-".to_owned(), |gpu| format!("{:.0}", gpu * 100.0))
+            ));
+        }
+
+        lines.push(tr!(
+            "status-profile",
+            "profile" => self.profile.clone().unwrap_or_else(|| "-".to_owned())
+        ));
+        if let Some(scene) = &self.scene {
+            lines.push(tr!("status-scene", "scene" => scene.as_str()));
+        }
+
+        for (controller, updated) in &self.updated {
+            lines.push(match self.latency.get(controller) {
+                Some(latency) => tr!(
+                    "status-updated-latency",
+                    "controller" => controller.as_str(),
+                    "seconds" => seconds_ago(*updated),
+                    "latency" => format!("{:.0}", latency)
+                ),
+                None => tr!(
+                    "status-updated",
+                    "controller" => controller.as_str(),
+                    "seconds" => seconds_ago(*updated)
+                ),
+            });
+        }
+
+        for (controller, watts) in &self.watts {
+            lines.push(tr!(
+                "status-controller-power",
+                "controller" => controller.as_str(),
+                "watts" => format!("{:.1}", watts)
+            ));
+        }
+        lines.push(tr!(
+            "status-power",
+            "watts" => format!("{:.1}", self.watts.values().sum::<f64>())
+        ));
+
+        lines
+    }
+}
+
+/// Named profile or scene in use, applied over the configuration as loaded.
+#[derive(Debug, Default)]
+pub struct Profiles {
+    /// Configuration as loaded, without any profile applied.
+    base: Mutex<Arc<Config>>,
+    /// `None` for the configuration as loaded.
+    active: Mutex<Option<String>>,
+    /// Scene in use, applied instead of the profile.
+    scene: Mutex<Option<String>>,
+}
+
+impl Profiles {
+    /// Replaces the configuration as loaded, returning it with the profile or scene in use
+    /// applied. The profile or scene switched to stays in use, unless `active_profile` or
+    /// `active_scene` changed or it was removed.
+    pub fn apply(&self, base: Config) -> Config {
+        let mut current = self.base.lock().unwrap();
+        let mut active = self.active.lock().unwrap();
+        let mut scene = self.scene.lock().unwrap();
+        if base.active_profile != current.active_profile
+            || active
+                .as_ref()
+                .is_some_and(|name| !base.profiles.contains_key(name))
+        {
+            active.clone_from(&base.active_profile);
+        }
+        if base.active_scene != current.active_scene
+            || scene
+                .as_ref()
+                .is_some_and(|name| !base.scenes.contains_key(name))
+        {
+            scene.clone_from(&base.active_scene);
+        }
+
+        *current = Arc::new(base);
+
+        resolve(&current, active.as_deref(), scene.as_deref())
+    }
+
+    /// Switches to a profile, or back to the configuration as loaded, returning the resulting
+    /// configuration. The scene in use is left.
+    pub fn switch(&self, name: Option<&str>) -> Result<Config> {
+        let current = self.base.lock().unwrap();
+        if let Some(name) = name {
+            if !current.profiles.contains_key(name) {
+                bail!("No profile named {:?} in the configuration.", name);
+            }
+        }
+
+        *self.active.lock().unwrap() = name.map(str::to_owned);
+        *self.scene.lock().unwrap() = None;
+
+        Ok(resolve(&current, name, None))
+    }
+
+    /// Switches to a scene, or back to the configuration as loaded, returning the resulting
+    /// configuration.
+    pub fn switch_scene(&self, name: Option<&str>) -> Result<Config> {
+        let current = self.base.lock().unwrap();
+        if let Some(name) = name {
+            if !current.scenes.contains_key(name) {
+                bail!("No scene named {:?} in the configuration.", name);
+            }
+        }
+
+        *self.active.lock().unwrap() = None;
+        *self.scene.lock().unwrap() = name.map(str::to_owned);
+
+        Ok(resolve(&current, None, name))
+    }
+
+    /// Profile in use, the one of the scene in use when there is one.
+    pub fn active(&self) -> Option<String> {
+        match self.scene() {
+            Some(scene) => self
+                .base
+                .lock()
+                .unwrap()
+                .scenes
+                .get(&scene)?
+                .profile
+                .clone(),
+            None => self.active.lock().unwrap().clone(),
+        }
+    }
+
+    pub fn scene(&self) -> Option<String> {
+        self.scene.lock().unwrap().clone()
+    }
+
+    /// Profiles of the configuration as loaded.
+    pub fn names(&self) -> Vec<String> {
+        self.base.lock().unwrap().profiles.keys().cloned().collect()
+    }
+}
+
+fn resolve(base: &Config, profile: Option<&str>, scene: Option<&str>) -> Config {
+    match scene {
+        Some(scene) => base.with_scene(scene),
+        None => profile.and_then(|name| base.with_profile(name)),
+    }
+    .unwrap_or_else(|| base.clone())
+}
+
+/// Zones disabled through the control socket, on top of the configured ones.
+#[derive(Debug, Default)]
+pub struct DisabledZones {
+    /// Controller and zone names, `None` for every zone of the controller.
+    disabled: Mutex<Vec<(String, Option<String>)>>,
+    /// Number of changes so far, for the frames to check the zones again only when it moves.
+    changes: AtomicU64,
+}
+
+impl DisabledZones {
+    pub fn set(&self, controller: &str, zone: Option<&str>, enabled: bool) {
+        let entry = (controller.to_lowercase(), zone.map(str::to_lowercase));
+
+        let mut disabled = self.disabled.lock().unwrap();
+        disabled.retain(|disabled| *disabled != entry);
+        if !enabled {
+            disabled.push(entry);
+        }
+        self.changes.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn version(&self) -> u64 {
+        self.changes.load(Ordering::Relaxed)
+    }
+
+    #[cfg(feature = "grpc")]
+    /// Controller and zone names disabled, `None` for every zone of the controller.
+    pub fn list(&self) -> Vec<(String, Option<String>)> {
+        self.disabled.lock().unwrap().clone()
+    }
+
+    pub fn is_disabled(&self, config: &Config, controller: &ControllerInfo, zone: &str) -> bool {
+        self.disabled
+            .lock()
+            .unwrap()
+            .iter()
+            .any(|(disabled_controller, disabled_zone)| {
+                config.designates(disabled_controller, &controller.name, &controller.location)
+                    && disabled_zone
+                        .as_ref()
+                        .is_none_or(|disabled_zone| disabled_zone.eq_ignore_ascii_case(zone))
+            })
+    }
+}
+
+/// Metric values injected through the control socket, overriding the sampled ones until they
+/// expire.
+#[derive(Debug, Default)]
+pub struct Injections {
+    active: Mutex<Vec<(Injection, Instant)>>,
+}
+
+impl Injections {
+    /// Overrides the metric of `injection` until `until`, see `expiry`.
+    pub fn inject(&self, injection: Injection, until: Instant) {
+        let mut active = self.active.lock().unwrap();
+        active.retain(|(active, _)| active.metric != injection.metric);
+        active.push((injection, until));
+    }
+
+    /// End of an injection lasting `duration`, rejecting the ones too long to be represented.
+    pub fn expiry(duration: Duration) -> Result<Instant> {
+        Instant::now()
+            .checked_add(duration)
+            .ok_or_else(|| anyhow!("Duration too long: {:?}.", duration))
+    }
+
+    pub fn apply(&self, samples: &mut Samples) {
+        let mut active = self.active.lock().unwrap();
+        active.retain(|(_, until)| *until > Instant::now());
+
+        for (injection, _) in active.iter() {
+            match injection.metric {
+                Metric::Cpu => samples.cpu = injection.value,
+                Metric::Gpu => samples.gpu = Some(injection.value),
+                Metric::CpuTemperature => samples.cpu_temperature = Some(injection.value),
+                Metric::GpuTemperature => samples.gpu_temperature = Some(injection.value),
+                Metric::Memory => samples.memory = Some(injection.value),
+                Metric::Disk => samples.disk = Some(injection.value),
+                Metric::Power => samples.power = Some(injection.value),
+                Metric::Thermal => samples.thermal = Some(injection.value),
+                Metric::Battery => samples.battery = Some(injection.value),
+                Metric::Mail => samples.mail = Some(injection.value),
+                Metric::None => {}
+            }
+        }
+    }
+}
+
+/// Serves the control socket of a running client, until the process exits.
+pub async fn serve(state: Arc<ControlState>) -> Result<()> {
+    #[cfg(unix)]
+    {
+        let (listener, path) = bind_socket()?;
+        info!("Control socket listening on {:?}.", path);
+
+        loop {
+            let (stream, _) = listener.accept().await?;
+            tokio::spawn(handle_connection(stream, state.clone()));
+        }
+    }
+
+    #[cfg(windows)]
+    {
+        use tokio::net::windows::named_pipe::ServerOptions;
+
+        let mut security = PipeSecurity::new()?;
+        let mut server = security.create(ServerOptions::new().first_pipe_instance(true))?;
+        info!("Control pipe listening on {}.", CONTROL_PIPE);
+
+        loop {
+            server.connect().await?;
+
+            let stream = server;
+            server = security.create(&ServerOptions::new())?;
+            tokio::spawn(handle_connection(stream, state.clone()));
+        }
+    }
+}
+
+async fn handle_connection(stream: impl AsyncRead + AsyncWrite + Unpin, state: Arc<ControlState>) {
+    let mut stream = BufReader::new(stream).take(MAX_REQUEST_LENGTH);
+
+    let mut line = String::new();
+    if let Err(e) = stream.read_line(&mut line).await {
+        debug!("Failed to read control request: {}", e);
+
+        return;
+    }
+
+    let request = match stream.limit() {
+        0 => Err(anyhow!("Request longer than {} bytes.", MAX_REQUEST_LENGTH)),
+        _ => ControlRequest::parse(&line),
+    };
+    let response = match request.and_then(|request| apply(request, &state, &state.config())) {
+        Ok(answer) => answer.unwrap_or_else(|| "ok".to_owned()),
+        Err(e) => format!("error: {}", e),
+    };
+
+    if let Err(e) = stream
+        .get_mut()
+        .get_mut()
+        .write_all(format!("{}\n", response).as_bytes())
+        .await
+    {
+        debug!("Failed to answer control request: {}", e);
+    }
+}
+
+/// Carries out a request, from the control socket or another control transport, returning the
+/// answer of the ones querying the client.
+pub fn apply(
+    request: ControlRequest,
+    state: &ControlState,
+    config: &Config,
+) -> Result<Option<String>> {
+    match request {
+        ControlRequest::Inject {
+            injections,
+            duration,
+        } => {
+            let until = Injections::expiry(duration)?;
+            for injection in injections {
+                info!(
+                    "Injecting {}={} for {:?}.",
+                    injection.metric, injection.value, duration
+                );
+
+                state.injections.inject(injection, until);
+            }
+        }
+        ControlRequest::Play { sequence } => {
+            let sequence = config
+                .sequence(&sequence)
+                .ok_or_else(|| anyhow!("No sequence named {:?} in the configuration.", sequence))?;
+            info!("Playing sequence {}.", sequence.name);
+
+            state.player.play(sequence.clone());
+            state.events.emit(Event::Sequence {
+                name: sequence.name.clone(),
+            });
+        }
+        ControlRequest::ReducedMotion(enabled) => {
+            info!("Reduced motion {}.", switch(enabled));
+
+            state
+                .accessibility
+                .reduced_motion
+                .store(enabled, Ordering::Relaxed);
+            state.events.emit(Event::Mode {
+                mode: "reduced-motion",
+                enabled,
+            });
+        }
+        ControlRequest::HighContrast(enabled) => {
+            info!("High contrast {}.", switch(enabled));
+
+            state
+                .accessibility
+                .high_contrast
+                .store(enabled, Ordering::Relaxed);
+            state.events.emit(Event::Mode {
+                mode: "high-contrast",
+                enabled,
+            });
+        }
+        ControlRequest::Brightness(level) => {
+            info!("Brightness at {:.0}%.", level * 100.0);
+
+            state.brightness.set(level);
+            state.events.emit(Event::Brightness { level });
+        }
+        ControlRequest::LogLevel(filters) => {
+            info!("Log level set to {}.", filters);
+
+            logging::set(filters);
+        }
+        ControlRequest::SetZone {
+            controller,
+            zone,
+            enabled,
+        } => {
+            info!(
+                "{} {}{}.",
+                if enabled { "Enabling" } else { "Disabling" },
+                controller,
+                zone.as_ref()
+                    .map(|zone| format!(" / {}", zone))
+                    .unwrap_or_default()
+            );
+
+            state.zones.set(&controller, zone.as_deref(), enabled);
+            state.events.emit(Event::Zone {
+                controller,
+                zone,
+                enabled,
+            });
+        }
+        ControlRequest::SwitchProfile { name } => {
+            let config = state.profiles.switch(name.as_deref())?;
+            match &name {
+                Some(name) => info!("Switching to profile {}.", name),
+                None => info!("Switching back to the configuration without profile."),
+            }
+
+            // The pipeline restarts with the new configuration, as when the file changes.
+            state.config.send_replace(Arc::new(config));
+            state.events.emit(Event::Profile { name });
+        }
+        ControlRequest::SwitchScene { name } => {
+            let switched = state.profiles.switch_scene(name.as_deref())?;
+            let Some(name) = name else {
+                info!("Switching back to the configuration without scene.");
+
+                state.config.send_replace(Arc::new(switched));
+                state.events.emit(Event::Scene { name: None });
+
+                return Ok(None);
+            };
+            info!("Switching to scene {}.", name);
+
+            let scene = &switched.scenes[&name];
+            if let Some(level) = scene.brightness {
+                let level = level.clamp(0.0, 1.0);
+                state.brightness.set(level);
+                state.events.emit(Event::Brightness { level });
+            }
+            if let Some(sequence) = &scene.sequence {
+                match switched.sequence(sequence) {
+                    Some(sequence) => state.player.play(sequence.clone()),
+                    None => warn!(
+                        "No sequence named {:?}, set in the scene {}.",
+                        sequence, name
+                    ),
+                }
+            }
+
+            state.config.send_replace(Arc::new(switched));
+            state.events.emit(Event::Scene { name: Some(name) });
+        }
+        ControlRequest::History { last } => {
+            let entries = state
+                .history
+                .since(SystemTime::now().checked_sub(last).unwrap_or(UNIX_EPOCH));
+
+            return Ok(Some(serde_json::to_string(&entries)?));
+        }
+        ControlRequest::Status => {
+            return Ok(Some(serde_json::to_string(&state.live_status())?));
+        }
+    }
+
+    Ok(None)
+}
+
+/// Sends a request to the running client, failing with its error if it refused it.
+pub async fn send(request: &ControlRequest) -> Result<()> {
+    query(request).await.map(drop)
+}
+
+/// Sends a request to the running client and returns its answer, failing with its error if it
+/// refused it.
+pub async fn query(request: &ControlRequest) -> Result<String> {
+    let response = match remote::destination() {
+        Some(remote) => remote::exchange(remote, &request.to_line()).await?,
+        None => exchange(&request.to_line()).await?,
+    };
+
+    match response.trim().strip_prefix("error: ") {
+        Some(error) => bail!("{}", error),
+        None => Ok(response.trim().to_owned()),
+    }
+}
+
+/// Sends a request line to the client running on this machine and returns its answer line.
+pub async fn exchange(line: &str) -> Result<String> {
+    #[cfg(unix)]
+    let stream = connect_socket()
+        .await
+        .map_err(|e| anyhow!("Cannot reach the running client ({}), is it running?", e))?;
+
+    #[cfg(windows)]
+    let stream = tokio::net::windows::named_pipe::ClientOptions::new()
+        .open(CONTROL_PIPE)
+        .map_err(|e| anyhow!("Cannot reach the running client ({}), is it running?", e))?;
+
+    let mut stream = BufReader::new(stream);
+    stream.get_mut().write_all(line.as_bytes()).await?;
+
+    let mut response = String::new();
+    stream.read_line(&mut response).await?;
+
+    Ok(response)
+}
+
+/// Security attributes of the control pipe, see `CONTROL_PIPE_SDDL`.
+#[cfg(windows)]
+struct PipeSecurity {
+    attributes: windows_sys::Win32::Security::SECURITY_ATTRIBUTES,
+}
+
+#[cfg(windows)]
+impl PipeSecurity {
+    fn new() -> std::io::Result<Self> {
+        use windows_sys::Win32::Security::{
+            Authorization::{
+                ConvertStringSecurityDescriptorToSecurityDescriptorW, SDDL_REVISION_1,
+            },
+            SECURITY_ATTRIBUTES,
+        };
+
+        let sddl = CONTROL_PIPE_SDDL
+            .encode_utf16()
+            .chain(std::iter::once(0))
+            .collect::<Vec<_>>();
+        let mut descriptor = std::ptr::null_mut();
+
+        // Safety: the SDDL string is null terminated, the descriptor is freed on drop.
+        let converted = unsafe {
+            ConvertStringSecurityDescriptorToSecurityDescriptorW(
+                sddl.as_ptr(),
+                SDDL_REVISION_1,
+                &mut descriptor,
+                std::ptr::null_mut(),
+            )
+        };
+        if converted == 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+
+        Ok(Self {
+            attributes: SECURITY_ATTRIBUTES {
+                nLength: std::mem::size_of::<SECURITY_ATTRIBUTES>() as u32,
+                lpSecurityDescriptor: descriptor,
+                bInheritHandle: 0,
+            },
+        })
+    }
+
+    fn create(
+        &mut self,
+        options: &tokio::net::windows::named_pipe::ServerOptions,
+    ) -> std::io::Result<tokio::net::windows::named_pipe::NamedPipeServer> {
+        // Safety: the attributes and the descriptor they point to outlive the call.
+        unsafe {
+            options.create_with_security_attributes_raw(
+                CONTROL_PIPE,
+                &mut self.attributes as *mut _ as *mut std::ffi::c_void,
+            )
+        }
+    }
+}
+
+// Safety: the descriptor is owned by the struct and never changed once created.
+#[cfg(windows)]
+unsafe impl Send for PipeSecurity {}
+
+#[cfg(windows)]
+impl Drop for PipeSecurity {
+    fn drop(&mut self) {
+        // Safety: allocated by ConvertStringSecurityDescriptorToSecurityDescriptorW.
+        unsafe {
+            windows_sys::Win32::Foundation::LocalFree(self.attributes.lpSecurityDescriptor);
+        }
+    }
+}
+
+/// Directory of the control socket of a client run by this user: the runtime directory, only
+/// accessible to the user, a private directory of the temporary one without it, or
+/// `SYSTEM_SOCKET_DIRECTORY` for root.
+#[cfg(unix)]
+fn user_socket_directory() -> std::path::PathBuf {
+    // Safety: always succeeds.
+    let uid = unsafe { libc::geteuid() };
+    if uid == 0 {
+        return SYSTEM_SOCKET_DIRECTORY.into();
+    }
+
+    match std::env::var_os("XDG_RUNTIME_DIR").filter(|directory| !directory.is_empty()) {
+        Some(directory) => directory.into(),
+        None => std::env::temp_dir().join(format!("open_rgb_client-{}", uid)),
+    }
+}
+
+/// Creates the directory of the control socket, or checks the existing one is owned by the
+/// user running the client and closed to everyone else, so nobody can plant a socket in it.
+/// The one of root is opened to `CONTROL_GROUP`, when the group exists.
+#[cfg(unix)]
+fn prepare_socket_directory(directory: &std::path::Path) -> Result<Option<u32>> {
+    use std::os::unix::fs::{DirBuilderExt, MetadataExt, PermissionsExt};
+
+    // Safety: always succeeds.
+    let uid = unsafe { libc::geteuid() };
+    let group = (uid == 0).then(control_group).flatten();
+    let mode = if group.is_some() { 0o750 } else { 0o700 };
+
+    match std::fs::DirBuilder::new().mode(mode).create(directory) {
+        Ok(()) => {}
+        Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {}
+        Err(e) => return Err(e.into()),
+    }
+
+    let metadata = std::fs::symlink_metadata(directory)?;
+    if !metadata.is_dir() || metadata.uid() != uid {
+        bail!(
+            "{:?} isn't a directory owned by the user running the client.",
+            directory
+        );
+    }
+
+    // The runtime directory is left as the session set it up.
+    if directory.as_os_str() == std::env::var_os("XDG_RUNTIME_DIR").unwrap_or_default() {
+        if metadata.mode() & 0o077 != 0 {
+            bail!("{:?} is accessible to other users.", directory);
+        }
+    } else {
+        if group.is_some() {
+            std::os::unix::fs::chown(directory, None, group)?;
+        }
+        std::fs::set_permissions(directory, std::fs::Permissions::from_mode(mode))?;
+    }
+
+    Ok(group)
+}
+
+/// Problems `prepare_socket_directory` would run into, found without creating or changing
+/// anything, each explaining how to fix it.
+#[cfg(unix)]
+pub fn check_socket_directory() -> Vec<String> {
+    use std::os::unix::{ffi::OsStrExt, fs::MetadataExt};
+
+    // Safety: always succeeds.
+    let uid = unsafe { libc::geteuid() };
+    let directory = user_socket_directory();
+    let mut problems = vec![];
+
+    match std::fs::symlink_metadata(&directory) {
+        Ok(metadata) if !metadata.is_dir() || metadata.uid() != uid => problems.push(format!(
+            "{:?} isn't a directory owned by the user running the client, the control socket \
+             can't be created in it.\n\
+             Remove it, the client creates it again when it starts:\n\
+             \n    sudo rm -r {:?}\n",
+            directory, directory
+        )),
+        Ok(metadata)
+            if directory.as_os_str() == std::env::var_os("XDG_RUNTIME_DIR").unwrap_or_default()
+                && metadata.mode() & 0o077 != 0 =>
+        {
+            problems.push(format!(
+                "The runtime directory {:?} is accessible to other users, the control socket \
+                 won't be created in it.\n\
+                 Close it to them, as the session manager normally does:\n\
+                 \n    chmod 700 {:?}\n",
+                directory, directory
+            ))
+        }
+        Ok(_) => {}
+        Err(_) => {
+            let parent = directory.parent().unwrap_or(&directory);
+            let parent_path = std::ffi::CString::new(parent.as_os_str().as_bytes());
+            // Safety: the path is null terminated.
+            let writable = parent_path.is_ok_and(|parent_path| unsafe {
+                libc::access(parent_path.as_ptr(), libc::W_OK | libc::X_OK) == 0
+            });
+            if !writable {
+                problems.push(format!(
+                    "Cannot create {:?} for the control socket, {:?} isn't writable.\n\
+                     Run the client in a login session, which sets XDG_RUNTIME_DIR to a \
+                     private directory, or point XDG_RUNTIME_DIR to a directory only the user \
+                     running the client can access.",
+                    directory, parent
+                ));
+            }
+        }
+    }
+
+    if uid == 0 && control_group().is_none() {
+        problems.push(format!(
+            "The {} group doesn't exist, only root can use the control socket.\n\
+             Create it and add the users allowed to control the client, then restart it:\n\
+             \n    sudo groupadd --system {}\n    sudo usermod -aG {} <user>\n",
+            CONTROL_GROUP, CONTROL_GROUP, CONTROL_GROUP
+        ));
+    }
+
+    problems
+}
+
+/// Id of `CONTROL_GROUP`, `None` when it doesn't exist.
+#[cfg(unix)]
+fn control_group() -> Option<u32> {
+    let name = std::ffi::CString::new(CONTROL_GROUP).ok()?;
+
+    // Safety: the name is null terminated, the entry is read before any other call to the
+    // group database.
+    let group = unsafe { libc::getgrnam(name.as_ptr()) };
+    if group.is_null() {
+        return None;
+    }
+
+    // Safety: checked above.
+    Some(unsafe { (*group).gr_gid })
+}
+
+/// Listens on the control socket, created with its final permissions: owner only, and
+/// `CONTROL_GROUP` for root.
+#[cfg(unix)]
+fn bind_socket() -> Result<(tokio::net::UnixListener, std::path::PathBuf)> {
+    use std::os::unix::fs::{FileTypeExt, PermissionsExt};
+
+    let directory = user_socket_directory();
+    let group = prepare_socket_directory(&directory)?;
+    let path = directory.join(CONTROL_SOCKET);
+
+    // Left behind by a previous run that didn't shut down cleanly.
+    match std::fs::symlink_metadata(&path) {
+        Ok(metadata) if metadata.file_type().is_socket() => std::fs::remove_file(&path)?,
+        Ok(_) => bail!("{:?} exists and isn't a socket.", path),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+        Err(e) => return Err(e.into()),
+    }
+
+    // Nobody else can reach the socket before its permissions are set, the directory is closed
+    // to them.
+    let listener = tokio::net::UnixListener::bind(&path)?;
+    if group.is_some() {
+        std::os::unix::fs::chown(&path, None, group)?;
+    }
+    let mode = if group.is_some() { 0o660 } else { 0o600 };
+    std::fs::set_permissions(&path, std::fs::Permissions::from_mode(mode))?;
+
+    Ok((listener, path))
+}
+
+/// Connects to the control socket of the client run by this user, or else to the one of the
+/// client running as root. Only sockets in directories owned by the user or root are trusted.
+#[cfg(unix)]
+async fn connect_socket() -> std::io::Result<tokio::net::UnixStream> {
+    use std::os::unix::fs::MetadataExt;
+
+    // Safety: always succeeds.
+    let uid = unsafe { libc::geteuid() };
+
+    let mut last_error = std::io::Error::from(std::io::ErrorKind::NotFound);
+    for (directory, owner) in [
+        (user_socket_directory(), uid),
+        (SYSTEM_SOCKET_DIRECTORY.into(), 0),
+    ] {
+        let trusted = std::fs::symlink_metadata(&directory)
+            .is_ok_and(|metadata| metadata.is_dir() && metadata.uid() == owner);
+        if !trusted {
+            continue;
+        }
+
+        match tokio::net::UnixStream::connect(directory.join(CONTROL_SOCKET)).await {
+            Ok(stream) => return Ok(stream),
+            Err(e) => last_error = e,
+        }
+    }
+
+    Err(last_error)
+}
+
+/// Parses `on` or `off`.
+pub fn parse_switch(value: &str) -> Result<bool> {
+    match value.to_ascii_lowercase().as_str() {
+        "on" | "true" => Ok(true),
+        "off" | "false" => Ok(false),
+        _ => bail!("Invalid switch: {:?} (expected on or off)", value),
+    }
+}
+
+fn switch(enabled: bool) -> &'static str {
+    if enabled {
+        "on"
+    } else {
+        "off"
+    }
+}
+
+/// Parses a duration such as `30s`, `2m` or `500ms`, seconds without unit.
+pub fn parse_duration(value: &str) -> Result<Duration> {
+    let split = value
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(value.len());
+    let (amount, unit) = value.split_at(split);
+    let amount = amount
+        .parse::<f64>()
+        .map_err(|_| anyhow!("Invalid duration: {}", value))?;
+
+    let seconds = match unit {
+        "ms" => amount / 1000.0,
+        "" | "s" => amount,
+        "m" => amount * 60.0,
+        "h" => amount * 3600.0,
+        _ => bail!(
+            "Invalid duration: {} (expected e.g. 30s, 2m or 500ms)",
+            value
+        ),
+    };
+
+    Ok(Duration::try_from_secs_f64(seconds)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn injections_too_long_are_rejected() {
+        let request = ControlRequest::parse("inject cpu=0.5 1e19\n").unwrap();
+        let ControlRequest::Inject { duration, .. } = request else {
+            panic!("{:?}", request);
+        };
+
+        assert!(Injections::expiry(duration).is_err());
+        assert!(Injections::expiry(Duration::from_secs(60)).is_ok());
+    }
+}
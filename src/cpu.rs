@@ -0,0 +1,76 @@
+use anyhow::Result;
+#[cfg(target_os = "linux")]
+use {crate::sampler::read_file, anyhow::anyhow};
+
+/// Reads the time the CPUs spent since boot, the utilization being the share of it they
+/// weren't idle between two readings.
+pub struct CpuMonitor {
+    /// Contents of /proc/stat, kept from one sample to the next.
+    #[cfg(target_os = "linux")]
+    stat: String,
+}
+
+/// Time spent by the CPUs at some point, in clock ticks.
+#[cfg(target_os = "linux")]
+#[derive(Debug, Clone, Copy)]
+pub struct CpuTimes {
+    total: f64,
+    idle: f64,
+}
+
+#[cfg(not(target_os = "linux"))]
+#[derive(Debug, Clone, Copy)]
+pub struct CpuTimes(cpu_monitor::CpuInstant);
+
+impl CpuMonitor {
+    pub fn new() -> Self {
+        Self {
+            #[cfg(target_os = "linux")]
+            stat: String::new(),
+        }
+    }
+
+    /// Read from /proc/stat rather than with `cpu_monitor`, which allocates a list of every CPU
+    /// at each reading.
+    #[cfg(target_os = "linux")]
+    pub fn times(&mut self) -> Result<CpuTimes> {
+        let stat = read_file("/proc/stat", &mut self.stat)?;
+        let totals = stat
+            .lines()
+            .find_map(|line| line.strip_prefix("cpu "))
+            .ok_or_else(|| anyhow!("No CPU totals in /proc/stat."))?;
+
+        let mut times = CpuTimes {
+            total: 0.0,
+            idle: 0.0,
+        };
+        for (index, ticks) in totals.split_whitespace().enumerate() {
+            let ticks = ticks.parse::<u64>()? as f64;
+            times.total += ticks;
+            // Idle, and waiting for I/O.
+            if index == 3 || index == 4 {
+                times.idle += ticks;
+            }
+        }
+
+        Ok(times)
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    pub fn times(&mut self) -> Result<CpuTimes> {
+        Ok(CpuTimes(cpu_monitor::CpuInstant::now()?))
+    }
+}
+
+impl CpuTimes {
+    /// Share of the time the CPUs weren't idle from `start` to these times.
+    #[cfg(target_os = "linux")]
+    pub fn non_idle_since(self, start: CpuTimes) -> f32 {
+        (1.0 - (self.idle - start.idle) / (self.total - start.total)) as f32
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    pub fn non_idle_since(self, start: CpuTimes) -> f32 {
+        (self.0 - start.0).non_idle() as f32
+    }
+}
@@ -0,0 +1,290 @@
+use crate::config::{Config, CpuTemperatureConfig, Metric};
+use anyhow::Result;
+
+/// Samples the package temperature of the CPU, from the hwmon driver of the CPU on Linux and
+/// from LibreHardwareMonitor on Windows.
+pub struct CpuTemperatureMonitor {
+    /// Found when the metric is first used, and dropped when it no longer is: on Windows, it
+    /// queries LibreHardwareMonitor from a thread.
+    sensor: Option<Sensor>,
+    label: Option<String>,
+    idle: f32,
+    hot: f32,
+}
+
+impl CpuTemperatureMonitor {
+    pub fn new(config: &CpuTemperatureConfig) -> Self {
+        Self {
+            sensor: None,
+            label: config.sensor.clone(),
+            idle: config.idle,
+            hot: config.hot,
+        }
+    }
+
+    /// Temperature mapped from 0 at the idle temperature to 1 at the hot one, or `None` when
+    /// the machine has no sensor for it or nothing in `config` uses it.
+    pub fn load(&mut self, config: &Config) -> Result<Option<f32>> {
+        if !config.uses(Metric::CpuTemperature) {
+            self.sensor = None;
+
+            return Ok(None);
+        }
+
+        let sensor = self
+            .sensor
+            .get_or_insert_with(|| Sensor::new(self.label.as_deref()));
+        let Some(temperature) = sensor.celsius()? else {
+            return Ok(None);
+        };
+
+        Ok(Some(
+            ((temperature - self.idle) / (self.hot - self.idle)).clamp(0.0, 1.0),
+        ))
+    }
+}
+
+#[cfg(target_os = "linux")]
+use linux::Sensor;
+#[cfg(windows)]
+use windows::Sensor;
+
+#[cfg(not(any(target_os = "linux", windows)))]
+struct Sensor;
+
+#[cfg(not(any(target_os = "linux", windows)))]
+impl Sensor {
+    fn new(_: Option<&str>) -> Self {
+        Self
+    }
+
+    fn celsius(&self) -> Result<Option<f32>> {
+        Ok(None)
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod linux {
+    use crate::sampler::read_file;
+    use anyhow::Result;
+    use std::path::{Path, PathBuf};
+
+    /// hwmon drivers of the CPUs: Intel, AMD, and the SoC of ARM boards.
+    const CPU_DRIVERS: [&str; 4] = ["coretemp", "k10temp", "zenpower", "cpu_thermal"];
+    /// Labels of the package temperature, by preference. AMD reports Tctl with an offset on
+    /// some models, Tdie being the actual temperature when both are there.
+    const PACKAGE_LABELS: [&str; 3] = ["Package id 0", "Tdie", "Tctl"];
+
+    pub struct Sensor {
+        /// `temp*_input` file, in millidegrees Celsius.
+        input: Option<PathBuf>,
+        /// Contents of `input`, kept from one sample to the next.
+        buffer: String,
+    }
+
+    impl Sensor {
+        pub fn new(label: Option<&str>) -> Self {
+            Self {
+                input: find_input(Path::new("/sys/class/hwmon"), label),
+                buffer: String::new(),
+            }
+        }
+
+        pub fn celsius(&mut self) -> Result<Option<f32>> {
+            let Some(input) = &self.input else {
+                return Ok(None);
+            };
+
+            Ok(Some(
+                read_file(input, &mut self.buffer)?.trim().parse::<f32>()? / 1000.0,
+            ))
+        }
+    }
+
+    /// Input of the sensor with `label`, or of the package one, of the first CPU driver under
+    /// `root`, falling back to its first sensor when none is labeled.
+    pub(super) fn find_input(root: &Path, label: Option<&str>) -> Option<PathBuf> {
+        let mut hwmons = std::fs::read_dir(root)
+            .into_iter()
+            .flatten()
+            .flatten()
+            .map(|hwmon| hwmon.path())
+            .collect::<Vec<_>>();
+        hwmons.sort();
+
+        let hwmon = hwmons.into_iter().find(|hwmon| {
+            std::fs::read_to_string(hwmon.join("name"))
+                .is_ok_and(|name| CPU_DRIVERS.contains(&name.trim()))
+        })?;
+
+        let labels = (1..=64)
+            .filter_map(|index| {
+                let label = std::fs::read_to_string(hwmon.join(format!("temp{}_label", index)));
+
+                Some((label.ok()?.trim().to_owned(), index))
+            })
+            .collect::<Vec<_>>();
+        let wanted = match label {
+            Some(label) => vec![label],
+            None => PACKAGE_LABELS.to_vec(),
+        };
+        let index = wanted
+            .iter()
+            .find_map(|wanted| {
+                labels
+                    .iter()
+                    .find(|(label, _)| label == wanted)
+                    .map(|(_, index)| *index)
+            })
+            .or(if label.is_none() { Some(1) } else { None })?;
+
+        Some(hwmon.join(format!("temp{}_input", index))).filter(|input| input.exists())
+    }
+}
+
+#[cfg(windows)]
+mod windows {
+    use anyhow::{anyhow, Result};
+    use std::{
+        os::windows::process::CommandExt,
+        process::Command,
+        sync::{Arc, Mutex, Weak},
+        time::Duration,
+    };
+
+    /// Time between two queries, each starting PowerShell: the temperature moves slowly.
+    const POLL_INTERVAL: Duration = Duration::from_secs(2);
+    /// Longest time between two queries, doubling from `POLL_INTERVAL` while they fail, e.g.
+    /// while LibreHardwareMonitor isn't running.
+    const MAX_POLL_INTERVAL: Duration = Duration::from_secs(60);
+    /// Names of the package sensor of Intel and AMD CPUs in LibreHardwareMonitor.
+    const PACKAGE_SENSORS: [&str; 2] = ["CPU Package", "Core (Tctl/Tdie)"];
+    const CREATE_NO_WINDOW: u32 = 0x0800_0000;
+
+    type Reading = Result<Option<f32>, String>;
+
+    /// Latest temperature published by LibreHardwareMonitor in WMI, queried from a thread so
+    /// the sampling doesn't wait for PowerShell.
+    pub struct Sensor {
+        reading: Arc<Mutex<Reading>>,
+    }
+
+    impl Sensor {
+        pub fn new(label: Option<&str>) -> Self {
+            let reading = Arc::new(Mutex::new(Ok(None)));
+            let script = script(label);
+            let shared = Arc::downgrade(&reading);
+            std::thread::spawn(move || poll(&script, shared));
+
+            Self { reading }
+        }
+
+        pub fn celsius(&self) -> Result<Option<f32>> {
+            self.reading.lock().unwrap().clone().map_err(|e| anyhow!(e))
+        }
+    }
+
+    /// Queries the sensor until the monitor is dropped, less and less often while it fails.
+    fn poll(script: &str, reading: Weak<Mutex<Reading>>) {
+        let mut interval = POLL_INTERVAL;
+        loop {
+            let value = query(script);
+            interval = match value {
+                Ok(_) => POLL_INTERVAL,
+                Err(_) => (interval * 2).min(MAX_POLL_INTERVAL),
+            };
+
+            let Some(reading) = reading.upgrade() else {
+                return;
+            };
+            *reading.lock().unwrap() = value;
+            drop(reading);
+
+            std::thread::sleep(interval);
+        }
+    }
+
+    fn query(script: &str) -> Reading {
+        let output = Command::new("powershell")
+            .args(["-NoProfile", "-NonInteractive", "-Command", script])
+            .creation_flags(CREATE_NO_WINDOW)
+            .output()
+            .map_err(|e| format!("Cannot run PowerShell: {}", e))?;
+        if !output.status.success() {
+            return Err(format!(
+                "LibreHardwareMonitor can't be queried, is it running? {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            ));
+        }
+
+        let value = String::from_utf8_lossy(&output.stdout);
+        let value = value.trim();
+        if value.is_empty() {
+            return Ok(None);
+        }
+
+        value
+            .replace(',', ".")
+            .parse::<f32>()
+            .map(Some)
+            .map_err(|e| format!("Invalid temperature {:?}: {}", value, e))
+    }
+
+    /// PowerShell reading the temperature of the sensor named `label`, or of the package one,
+    /// of the CPU.
+    fn script(label: Option<&str>) -> String {
+        let names = match label {
+            Some(label) => vec![label],
+            None => PACKAGE_SENSORS.to_vec(),
+        };
+        let names = names
+            .iter()
+            .map(|name| format!("'{}'", name.replace('\'', "''")))
+            .collect::<Vec<_>>()
+            .join(",");
+
+        format!(
+            "$ErrorActionPreference = 'Stop'; \
+             Get-CimInstance -Namespace root/LibreHardwareMonitor -ClassName Sensor \
+             -Filter \"SensorType='Temperature'\" | \
+             Where-Object {{ $_.Identifier -like '/*cpu/*' -and $_.Name -in @({}) }} | \
+             Select-Object -First 1 -ExpandProperty Value",
+            names
+        )
+    }
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use super::linux::find_input;
+
+    #[test]
+    fn the_package_sensor_of_the_cpu_driver_is_read() {
+        let root =
+            std::env::temp_dir().join(format!("open_rgb_client_hwmon_{}", std::process::id()));
+        for (hwmon, name, labels) in [
+            ("hwmon0", "nvme", &["Composite"][..]),
+            ("hwmon1", "k10temp", &["Tctl", "Tdie", "Tccd1"][..]),
+        ] {
+            let dir = root.join(hwmon);
+            std::fs::create_dir_all(&dir).unwrap();
+            std::fs::write(dir.join("name"), format!("{}\n", name)).unwrap();
+            for (index, label) in labels.iter().enumerate() {
+                std::fs::write(dir.join(format!("temp{}_label", index + 1)), label).unwrap();
+                std::fs::write(dir.join(format!("temp{}_input", index + 1)), "45000").unwrap();
+            }
+        }
+
+        assert_eq!(
+            find_input(&root, None),
+            Some(root.join("hwmon1/temp2_input"))
+        );
+        assert_eq!(
+            find_input(&root, Some("Tccd1")),
+            Some(root.join("hwmon1/temp3_input"))
+        );
+        assert_eq!(find_input(&root, Some("Tccd2")), None);
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -0,0 +1,91 @@
+use anyhow::{bail, Result};
+use std::{f32::consts::TAU, fmt, str::FromStr, time::Duration};
+
+/// Period of the sine and ramp waveforms, long enough for the averaging to follow them.
+const PERIOD: Duration = Duration::from_secs(30);
+/// Time between the spikes of the spiky waveform, and how long each one lasts.
+const SPIKE_INTERVAL: Duration = Duration::from_secs(8);
+const SPIKE_LENGTH: Duration = Duration::from_secs(2);
+/// Load between the spikes.
+const BASELINE: f32 = 0.1;
+
+/// Synthetic load shown by `demo` instead of the sampled one, for showing the lighting off
+/// without loading the machine.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Waveform {
+    /// Smooth waves, the GPU a quarter period behind the CPU.
+    #[default]
+    Sine,
+    /// A low load with short bursts, as when compiling or loading a game level.
+    Spiky,
+    /// The load climbing to full then dropping, the GPU half a period behind the CPU.
+    Ramp,
+}
+
+impl FromStr for Waveform {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self> {
+        match value.to_ascii_lowercase().as_str() {
+            "sine" => Ok(Self::Sine),
+            "spiky" => Ok(Self::Spiky),
+            "ramp" => Ok(Self::Ramp),
+            _ => bail!("Unknown waveform: {}", value),
+        }
+    }
+}
+
+impl fmt::Display for Waveform {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Sine => "sine",
+            Self::Spiky => "spiky",
+            Self::Ramp => "ramp",
+        })
+    }
+}
+
+impl Waveform {
+    /// CPU and GPU utilization, from 0 to 1, `elapsed` into the demo.
+    pub fn load(self, elapsed: Duration) -> (f32, f32) {
+        let phase = (elapsed.as_secs_f32() / PERIOD.as_secs_f32()).fract();
+
+        match self {
+            Self::Sine => {
+                let wave = |phase: f32| 0.5 - 0.5 * (phase * TAU).cos();
+
+                (wave(phase), wave(phase - 0.25))
+            }
+            Self::Spiky => {
+                let spike = |elapsed: Duration| {
+                    if elapsed.as_millis() % SPIKE_INTERVAL.as_millis() < SPIKE_LENGTH.as_millis() {
+                        1.0
+                    } else {
+                        BASELINE
+                    }
+                };
+
+                // The GPU spikes in between the CPU.
+                (spike(elapsed), spike(elapsed + SPIKE_INTERVAL / 2))
+            }
+            Self::Ramp => (phase, (phase + 0.5).fract()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn loads_stay_in_range() {
+        for waveform in [Waveform::Sine, Waveform::Spiky, Waveform::Ramp] {
+            for step in 0..120 {
+                let (cpu, gpu) = waveform.load(Duration::from_millis(step * 500));
+
+                assert!((0.0..=1.0).contains(&cpu), "{} CPU: {}", waveform, cpu);
+                assert!((0.0..=1.0).contains(&gpu), "{} GPU: {}", waveform, gpu);
+            }
+        }
+    }
+}
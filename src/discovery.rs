@@ -0,0 +1,60 @@
+use anyhow::Result;
+use log::*;
+use mdns_sd::{ServiceDaemon, ServiceEvent};
+use std::{net::SocketAddr, time::Duration};
+use tokio::time::{timeout_at, Instant};
+
+/// Service type OpenRGB SDK servers are expected to be advertised under (e.g. by an Avahi service file).
+const SERVICE_TYPE: &str = "_openrgb._tcp.local.";
+
+pub const BROWSE_TIMEOUT: Duration = Duration::from_secs(3);
+
+#[derive(Debug, Clone)]
+pub struct DiscoveredServer {
+    pub name: String,
+    pub host: String,
+    pub addresses: Vec<SocketAddr>,
+}
+
+/// Browses the LAN for OpenRGB SDK servers, stopping after `duration` or once `limit` servers are found.
+pub async fn browse_servers(duration: Duration, limit: usize) -> Result<Vec<DiscoveredServer>> {
+    let mdns = ServiceDaemon::new()?;
+    let receiver = mdns.browse(SERVICE_TYPE)?;
+
+    let mut servers: Vec<DiscoveredServer> = vec![];
+    let deadline = Instant::now() + duration;
+    while servers.len() < limit {
+        let Ok(Ok(event)) = timeout_at(deadline, receiver.recv_async()).await else {
+            break;
+        };
+
+        if let ServiceEvent::ServiceResolved(service) = event {
+            if servers.iter().any(|server| server.name == service.fullname) {
+                continue;
+            }
+
+            let mut addresses = service
+                .addresses
+                .iter()
+                .map(|address| SocketAddr::new(address.to_ip_addr(), service.port))
+                .collect::<Vec<_>>();
+
+            // Prefer IPv4, link-local IPv6 addresses are rarely usable without a scope.
+            addresses.sort_by_key(|address| address.is_ipv6());
+
+            debug!("Found {} at {:?}.", service.fullname, addresses);
+
+            servers.push(DiscoveredServer {
+                name: service.fullname.clone(),
+                host: service.host.clone(),
+                addresses,
+            });
+        }
+    }
+
+    if let Err(e) = mdns.shutdown() {
+        warn!("Failed to shutdown mDNS daemon: {}", e);
+    }
+
+    Ok(servers)
+}
@@ -0,0 +1,281 @@
+use crate::config::DiskConfig;
+use anyhow::Result;
+
+/// Samples the throughput of the disks, from `/proc/diskstats` on Linux and the performance
+/// counters of the physical disks on Windows.
+pub struct DiskMonitor {
+    counter: Counter,
+    /// Bytes per second.
+    saturation: f64,
+}
+
+impl DiskMonitor {
+    pub fn new(config: &DiskConfig) -> Self {
+        Self {
+            counter: Counter::new(&config.devices, config.direction),
+            saturation: config.saturation.max(f32::EPSILON) as f64 * 1_000_000.0,
+        }
+    }
+
+    /// Throughput mapped from 0 to 1 at the saturation point, or `None` until two samples were
+    /// taken.
+    pub fn load(&mut self) -> Result<Option<f32>> {
+        let Some(throughput) = self.counter.bytes_per_second()? else {
+            return Ok(None);
+        };
+
+        Ok(Some((throughput / self.saturation).clamp(0.0, 1.0) as f32))
+    }
+}
+
+#[cfg(target_os = "linux")]
+use linux::Counter;
+#[cfg(windows)]
+use windows::Counter;
+
+#[cfg(not(any(target_os = "linux", windows)))]
+struct Counter;
+
+#[cfg(not(any(target_os = "linux", windows)))]
+impl Counter {
+    fn new(_: &[String], _: crate::config::DiskDirection) -> Self {
+        Self
+    }
+
+    fn bytes_per_second(&mut self) -> Result<Option<f64>> {
+        Ok(None)
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod linux {
+    use crate::{config::DiskDirection, sampler::read_file};
+    use anyhow::Result;
+    use std::{collections::HashMap, path::Path, time::Instant};
+
+    /// Unit of the sector counts of `/proc/diskstats`, whatever the sector size of the disk.
+    const SECTOR_SIZE: u64 = 512;
+
+    pub struct Counter {
+        devices: Vec<String>,
+        direction: DiskDirection,
+        /// Bytes transferred so far, and when they were read.
+        last: Option<(u64, Instant)>,
+        /// Whether each device seen is a whole disk, checked once.
+        whole_disks: HashMap<String, bool>,
+        /// Contents of `/proc/diskstats`, kept from one sample to the next.
+        diskstats: String,
+    }
+
+    impl Counter {
+        pub fn new(devices: &[String], direction: DiskDirection) -> Self {
+            Self {
+                devices: devices.to_vec(),
+                direction,
+                last: None,
+                whole_disks: HashMap::new(),
+                diskstats: String::new(),
+            }
+        }
+
+        pub fn bytes_per_second(&mut self) -> Result<Option<f64>> {
+            let diskstats = read_file("/proc/diskstats", &mut self.diskstats)?;
+            let bytes = transferred(diskstats, self.direction, |name| {
+                if self.devices.is_empty() {
+                    // Partitions, loop devices and device mapper volumes would count twice.
+                    if let Some(whole) = self.whole_disks.get(name) {
+                        return *whole;
+                    }
+                    let whole = Path::new("/sys/block").join(name).join("device").exists();
+                    self.whole_disks.insert(name.to_owned(), whole);
+
+                    whole
+                } else {
+                    self.devices.iter().any(|device| device == name)
+                }
+            });
+            let now = Instant::now();
+
+            let throughput = self.last.map(|(last, time)| {
+                bytes.saturating_sub(last) as f64 / now.duration_since(time).as_secs_f64()
+            });
+            self.last = Some((bytes, now));
+
+            Ok(throughput.filter(|throughput| throughput.is_finite()))
+        }
+    }
+
+    /// Bytes transferred by the devices of `diskstats` that are `wanted`, since boot.
+    pub(super) fn transferred(
+        diskstats: &str,
+        direction: DiskDirection,
+        mut wanted: impl FnMut(&str) -> bool,
+    ) -> u64 {
+        diskstats
+            .lines()
+            .filter_map(|line| {
+                // Up to the sectors written, missing ones left empty.
+                let mut fields = [""; 10];
+                for (field, value) in fields.iter_mut().zip(line.split_whitespace()) {
+                    *field = value;
+                }
+                if fields[2].is_empty() || !wanted(fields[2]) {
+                    return None;
+                }
+
+                let sectors = |index: usize| fields[index].parse::<u64>().unwrap_or_default();
+                let sectors = match direction {
+                    DiskDirection::Read => sectors(5),
+                    DiskDirection::Write => sectors(9),
+                    DiskDirection::Both => sectors(5) + sectors(9),
+                };
+
+                Some(sectors * SECTOR_SIZE)
+            })
+            .sum()
+    }
+}
+
+#[cfg(windows)]
+mod windows {
+    use crate::config::DiskDirection;
+    use anyhow::{anyhow, Result};
+    use windows_sys::Win32::System::Performance::{
+        PdhAddEnglishCounterW, PdhCloseQuery, PdhCollectQueryData, PdhGetFormattedCounterValue,
+        PdhOpenQueryW, PDH_FMT_COUNTERVALUE, PDH_FMT_DOUBLE, PDH_HCOUNTER, PDH_HQUERY,
+    };
+
+    /// Performance counters of the physical disks, the rates being computed by Windows between
+    /// two collections.
+    pub struct Counter {
+        query: PDH_HQUERY,
+        counters: Vec<PDH_HCOUNTER>,
+        /// Whether the query was collected once, a rate needing two collections.
+        primed: bool,
+    }
+
+    impl Counter {
+        pub fn new(devices: &[String], direction: DiskDirection) -> Self {
+            let mut counter = Self {
+                query: std::ptr::null_mut(),
+                counters: vec![],
+                primed: false,
+            };
+
+            // Safety: the query handle is closed on drop.
+            if unsafe { PdhOpenQueryW(std::ptr::null(), 0, &mut counter.query) } != 0 {
+                counter.query = std::ptr::null_mut();
+
+                return counter;
+            }
+
+            let name = match direction {
+                DiskDirection::Read => "Disk Read Bytes/sec",
+                DiskDirection::Write => "Disk Write Bytes/sec",
+                DiskDirection::Both => "Disk Bytes/sec",
+            };
+            let instances = if devices.is_empty() {
+                vec!["_Total".to_owned()]
+            } else {
+                devices.to_vec()
+            };
+            for instance in instances {
+                let path = format!("\\PhysicalDisk({})\\{}", instance, name)
+                    .encode_utf16()
+                    .chain(std::iter::once(0))
+                    .collect::<Vec<_>>();
+                let mut handle = std::ptr::null_mut();
+
+                // Safety: the path is null terminated, the counter belongs to the query.
+                if unsafe { PdhAddEnglishCounterW(counter.query, path.as_ptr(), 0, &mut handle) }
+                    == 0
+                {
+                    counter.counters.push(handle);
+                }
+            }
+
+            counter
+        }
+
+        pub fn bytes_per_second(&mut self) -> Result<Option<f64>> {
+            if self.query.is_null() || self.counters.is_empty() {
+                return Err(anyhow!("No performance counter for the disks."));
+            }
+
+            // Safety: the query is open until drop.
+            let status = unsafe { PdhCollectQueryData(self.query) };
+            if status != 0 {
+                return Err(anyhow!("Cannot collect the disk counters: {:#x}", status));
+            }
+            if !std::mem::replace(&mut self.primed, true) {
+                return Ok(None);
+            }
+
+            let mut total = 0.0;
+            for &counter in &self.counters {
+                let mut value = PDH_FMT_COUNTERVALUE::default();
+
+                // Safety: the counter belongs to the open query, the value is written as a
+                // double as requested.
+                let status = unsafe {
+                    PdhGetFormattedCounterValue(
+                        counter,
+                        PDH_FMT_DOUBLE,
+                        std::ptr::null_mut(),
+                        &mut value,
+                    )
+                };
+                if status != 0 {
+                    return Err(anyhow!("Cannot read a disk counter: {:#x}", status));
+                }
+
+                // Safety: formatted as a double above.
+                total += unsafe { value.Anonymous.doubleValue };
+            }
+
+            Ok(Some(total))
+        }
+    }
+
+    // Safety: the query is only used through `&mut self`, PDH handles aren't tied to a thread.
+    unsafe impl Send for Counter {}
+
+    impl Drop for Counter {
+        fn drop(&mut self) {
+            if !self.query.is_null() {
+                // Safety: opened in `new`, closing it frees its counters too.
+                unsafe { PdhCloseQuery(self.query) };
+            }
+        }
+    }
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use super::linux::transferred;
+    use crate::config::DiskDirection;
+
+    #[test]
+    fn the_sectors_of_the_wanted_disks_are_summed() {
+        let diskstats = "\
+ 259       0 nvme0n1 100 0 2000 10 50 0 1000 20 0 30 30 0 0 0 0 0 0
+ 259       1 nvme0n1p1 90 0 1800 10 40 0 800 20 0 30 30 0 0 0 0 0 0
+   8       0 sda 10 0 4 1 5 0 6 2 0 3 3 0 0 0 0 0 0
+   7       0 loop0 7 0 9 1 0 0 0 0 0 1 1 0 0 0 0 0 0
+";
+        let disks = |name: &str| name == "nvme0n1" || name == "sda";
+
+        assert_eq!(
+            transferred(diskstats, DiskDirection::Both, disks),
+            (2000 + 1000 + 4 + 6) * 512
+        );
+        assert_eq!(
+            transferred(diskstats, DiskDirection::Read, disks),
+            (2000 + 4) * 512
+        );
+        assert_eq!(
+            transferred(diskstats, DiskDirection::Write, |name| name == "nvme0n1p1"),
+            800 * 512
+        );
+    }
+}
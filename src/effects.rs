@@ -0,0 +1,890 @@
+use crate::{
+    config::{format_color, hex_color, hex_colors, Metric, Palette},
+    layout::Point,
+};
+use chrono::{DateTime, Local, NaiveDateTime, Timelike};
+use openrgb::data::Color;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::{
+    f32::consts::TAU,
+    fmt,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+const BLINK_PERIOD_MS: u128 = 500;
+
+/// Seconds for the rainbow to go once along the LEDs.
+const RAINBOW_PERIOD: f32 = 2.0;
+
+const DATETIME_FORMAT: &str = "%Y-%m-%d %H:%M";
+
+/// Range covered by the log scale: a value of 1% lights up about 15% of the LEDs.
+const LOG_SCALE_RANGE: f32 = 100.0;
+
+/// How the LEDs of a segment follow the metric.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+pub enum Effect {
+    /// Every LED shows the same color, blended from the palette.
+    Block,
+    /// The LEDs fill up with the load color one after the other.
+    Gradient(Fill),
+    /// The LEDs drain from the load color, the last one first.
+    ReversedGradient(Fill),
+    /// The LEDs fill up like a gradient, in a hue following a second metric.
+    HueShift(HueShift),
+    /// Every LED shows the color of the band the metric is in.
+    TrafficLight(TrafficLight),
+    /// Each LED is a bucket of values, from idle to full load, lit as often as the recent
+    /// samples fell into it.
+    Histogram,
+    /// The local time, regardless of the metric.
+    Clock(Clock),
+    /// The LEDs empty as a deadline approaches, regardless of the metric.
+    Countdown(Countdown),
+    /// A rainbow moving along the LEDs, regardless of the metric.
+    Rainbow,
+    /// Waves flowing across the case, their crests blending to the load color with the metric.
+    Wave(Wave),
+    /// Every LED shows the same fixed color.
+    Color(Solid),
+    /// Every LED off, unlike a disabled zone that is left to OpenRGB.
+    Off,
+}
+
+/// Options of the effects filling up the LEDs.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Fill {
+    pub scale: Scale,
+    /// Value, from 0 to 1, under which no LED is lit, the LEDs filling up from there to full
+    /// load.
+    pub threshold: f32,
+    pub direction: Direction,
+}
+
+impl Fill {
+    /// Share of the LEDs lit at `value`.
+    fn level(&self, value: f32) -> f32 {
+        let threshold = self.threshold.clamp(0.0, 0.99);
+
+        self.scale
+            .apply(((value - threshold) / (1.0 - threshold)).clamp(0.0, 1.0))
+    }
+
+    fn orient(&self, colors: &mut [Color]) {
+        if self.direction == Direction::Backward {
+            colors.reverse();
+        }
+    }
+}
+
+/// End of a zone the LEDs fill up from.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Direction {
+    /// From the first LED OpenRGB reports.
+    #[default]
+    Forward,
+    /// From the last one, for strips mounted the other way round.
+    Backward,
+}
+
+/// How a value maps to the share of LEDs lit.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Scale {
+    #[default]
+    Linear,
+    /// Spreads the low values over more LEDs, for bursty metrics that idle near 0.
+    Log,
+}
+
+impl Scale {
+    fn apply(self, value: f32) -> f32 {
+        match self {
+            Self::Linear => value,
+            Self::Log => {
+                (1.0 + value.clamp(0.0, 1.0) * (LOG_SCALE_RANGE - 1.0)).ln() / LOG_SCALE_RANGE.ln()
+            }
+        }
+    }
+}
+
+/// Two metrics on one strip, e.g. the GPU load filling it in a hue going from blue to red
+/// with the GPU temperature.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HueShift {
+    /// Metric setting the hue.
+    pub metric: Metric,
+    /// Hues at 0 and 1 of the metric, in degrees, going the way from the first to the second.
+    #[serde(default = "HueShift::default_from")]
+    pub from: f32,
+    #[serde(default)]
+    pub to: f32,
+    #[serde(default)]
+    pub scale: Scale,
+}
+
+impl HueShift {
+    fn default_from() -> f32 {
+        240.0
+    }
+
+    /// Fills the `colors` of the LEDs up to `value` in the hue of `hue_value`, both from 0 to 1,
+    /// over the palette's idle color.
+    pub fn render(&self, value: f32, hue_value: f32, palette: &Palette, colors: &mut [Color]) {
+        let degrees = lerp(hue_value.clamp(0.0, 1.0), self.from, self.to);
+
+        generate_gradient_led_colors(
+            self.scale.apply(value),
+            &palette.idle,
+            &hue(degrees / 360.0),
+            colors,
+        );
+    }
+}
+
+/// Waves computed from the position of each LED in the case, the time and the metric, so they
+/// flow across every controller of the layout as one canvas.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Wave {
+    /// Way the waves go, in degrees: 0 from left to right, 90 from top to bottom.
+    pub angle: f32,
+    /// Center the waves spread from in rings, `[x, y]` from 0 to 1 across the case, instead of
+    /// going along `angle`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub origin: Option<[f32; 2]>,
+    /// Distance between two crests, in widths of the case.
+    pub wavelength: f32,
+    /// Crests passing a LED per second.
+    pub speed: f32,
+}
+
+impl Default for Wave {
+    fn default() -> Self {
+        Self {
+            angle: 0.0,
+            origin: None,
+            wavelength: 0.5,
+            speed: 0.5,
+        }
+    }
+}
+
+impl Wave {
+    /// Sets the `colors` of the LEDs at `positions` at the time `now`, the crests going from
+    /// the idle color to the load one as `value` goes from 0 to 1.
+    pub fn render(
+        &self,
+        value: f32,
+        palette: &Palette,
+        positions: impl Iterator<Item = Point>,
+        colors: &mut [Color],
+        now: SystemTime,
+    ) {
+        let time = now
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs_f64();
+        // Wrapped before going to f32, which can't tell apart the seconds since 1970.
+        let phase = (time * self.speed as f64).fract() as f32;
+        let (sin, cos) = self.angle.to_radians().sin_cos();
+
+        for (color, point) in colors.iter_mut().zip(positions) {
+            let distance = match self.origin {
+                Some([x, y]) => (point.x - x).hypot(point.y - y),
+                None => point.x * cos + point.y * sin,
+            };
+            let crest = 0.5 + 0.5 * (TAU * (distance / self.wavelength.max(0.01) - phase)).cos();
+
+            *color = lerp_color(value.clamp(0.0, 1.0) * crest, &palette.idle, &palette.load);
+        }
+    }
+}
+
+/// Hands sweeping around a fan ring, or the time in binary on a short strip.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Clock {
+    pub style: ClockStyle,
+    #[serde(with = "hex_color")]
+    pub hours: Color,
+    #[serde(with = "hex_color")]
+    pub minutes: Color,
+    #[serde(with = "hex_color")]
+    pub seconds: Color,
+    #[serde(with = "hex_color")]
+    pub background: Color,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ClockStyle {
+    /// One LED per hand, going around the LEDs like a clock face.
+    #[default]
+    Analog,
+    /// Hours, minutes then seconds as binary numbers, most significant bit first, as long as
+    /// there are LEDs left.
+    Binary,
+}
+
+impl Default for Clock {
+    fn default() -> Self {
+        Self {
+            style: ClockStyle::Analog,
+            hours: Color::new(0xFF, 0x00, 0x00),
+            minutes: Color::new(0x00, 0xFF, 0x00),
+            seconds: Color::new(0x00, 0x00, 0xFF),
+            background: Color::new(0x00, 0x00, 0x00),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Solid {
+    #[serde(with = "hex_color")]
+    pub color: Color,
+    /// Blink, e.g. for notifications.
+    #[serde(default)]
+    pub blink: bool,
+}
+
+/// Time left until `deadline`, in the palette's load color over its idle color.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Countdown {
+    /// Local date and time, `2026-12-24 18:00` or just `2026-12-24` for midnight.
+    #[serde(with = "local_datetime")]
+    pub deadline: NaiveDateTime,
+    /// Days shown by the full strip, the LEDs start emptying when fewer are left.
+    #[serde(default = "Countdown::default_days")]
+    pub days: f32,
+    /// Flash during the final hour.
+    #[serde(default)]
+    pub flash: bool,
+}
+
+impl Countdown {
+    fn default_days() -> f32 {
+        7.0
+    }
+
+    /// Share of the strip still lit at the local time `now`.
+    fn remaining(&self, now: NaiveDateTime) -> f32 {
+        let left = (self.deadline - now).as_seconds_f32() / (self.days * 24.0 * 3600.0);
+
+        left.clamp(0.0, 1.0)
+    }
+
+    fn final_hour(&self, now: NaiveDateTime) -> bool {
+        let left = self.deadline - now;
+
+        left > chrono::TimeDelta::zero() && left <= chrono::TimeDelta::hours(1)
+    }
+}
+
+/// Discrete bands instead of a blend, e.g. green, amber and red.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TrafficLight {
+    /// Values where the next band starts, in increasing order.
+    pub thresholds: Vec<f32>,
+    /// Color of each band, one more than the thresholds.
+    #[serde(with = "hex_colors")]
+    pub colors: Vec<Color>,
+    /// Blink while in the top band.
+    pub blink: bool,
+}
+
+impl Default for TrafficLight {
+    fn default() -> Self {
+        Self {
+            thresholds: vec![0.5, 0.8],
+            colors: vec![
+                Color::new(0x00, 0xFF, 0x00),
+                Color::new(0xFF, 0xBF, 0x00),
+                Color::new(0xFF, 0x00, 0x00),
+            ],
+            blink: false,
+        }
+    }
+}
+
+impl fmt::Display for Effect {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Block => write!(f, "block"),
+            Self::Gradient(fill) => write!(f, "gradient{}", fill),
+            Self::ReversedGradient(fill) => write!(f, "reversed gradient{}", fill),
+            Self::Histogram => write!(f, "histogram"),
+            Self::HueShift(hue_shift) => {
+                write!(
+                    f,
+                    "hue shift by {} from {}° to {}°",
+                    hue_shift.metric, hue_shift.from, hue_shift.to
+                )?;
+                if hue_shift.scale == Scale::Log {
+                    write!(f, ", log scale")?;
+                }
+
+                Ok(())
+            }
+            Self::Rainbow => write!(f, "rainbow"),
+            Self::Wave(wave) => match wave.origin {
+                Some([x, y]) => write!(f, "waves from ({}, {})", x, y),
+                None => write!(f, "waves at {}°", wave.angle),
+            },
+            Self::Off => write!(f, "off"),
+            Self::Color(solid) => {
+                write!(f, "color {}", format_color(&solid.color))?;
+                if solid.blink {
+                    write!(f, ", blinking")?;
+                }
+
+                Ok(())
+            }
+            Self::Clock(clock) => match clock.style {
+                ClockStyle::Analog => write!(f, "analog clock"),
+                ClockStyle::Binary => write!(f, "binary clock"),
+            },
+            Self::Countdown(countdown) => {
+                write!(
+                    f,
+                    "countdown to {} over {} days",
+                    countdown.deadline.format(DATETIME_FORMAT),
+                    countdown.days
+                )?;
+                if countdown.flash {
+                    write!(f, ", flashing in the final hour")?;
+                }
+
+                Ok(())
+            }
+            Self::TrafficLight(traffic_light) => {
+                write!(f, "traffic light")?;
+                for (index, color) in traffic_light.colors.iter().enumerate() {
+                    match traffic_light.thresholds.get(index) {
+                        Some(threshold) => write!(f, " {} < {}", format_color(color), threshold)?,
+                        None => write!(f, " {}", format_color(color))?,
+                    }
+                }
+
+                if traffic_light.blink {
+                    write!(f, ", blinking")?;
+                }
+
+                Ok(())
+            }
+        }
+    }
+}
+
+impl fmt::Display for Fill {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.scale == Scale::Log {
+            write!(f, ", log scale")?;
+        }
+        if self.threshold > 0.0 {
+            write!(f, " from {}%", self.threshold * 100.0)?;
+        }
+        if self.direction == Direction::Backward {
+            write!(f, ", backward")?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Effect {
+    /// Like `render`, the rainbow and the waves going across the case when the layout gives
+    /// the `positions` of the LEDs.
+    pub fn render_placed(
+        &self,
+        value: f32,
+        history: &[f32],
+        palette: &Palette,
+        positions: Option<&[Point]>,
+        colors: &mut [Color],
+        now: SystemTime,
+    ) {
+        match (self, positions) {
+            (Self::Rainbow, Some(positions)) if positions.len() == colors.len() => {
+                rainbow(positions.iter().map(|point| point.x), colors, now)
+            }
+            (Self::Wave(wave), Some(positions)) if positions.len() == colors.len() => {
+                wave.render(value, palette, positions.iter().copied(), colors, now)
+            }
+            _ => self.render(value, history, palette, colors, now),
+        }
+    }
+
+    /// Sets the `colors` of the LEDs to show `value`, from 0 to 1, at the time `now`, without
+    /// allocating. `history` holds the recent values, oldest first. Hue shifts are rendered at
+    /// the `from` hue, their second metric being resolved by the caller, see
+    /// `HueShift::render`.
+    pub fn render(
+        &self,
+        value: f32,
+        history: &[f32],
+        palette: &Palette,
+        colors: &mut [Color],
+        now: SystemTime,
+    ) {
+        let start_color = &palette.idle;
+        let end_color = &palette.load;
+        let size = colors.len();
+
+        match self {
+            Self::Block => colors.fill(lerp_color(value, start_color, end_color)),
+            Self::Gradient(fill) => {
+                generate_gradient_led_colors(fill.level(value), start_color, end_color, colors);
+                fill.orient(colors);
+            }
+            Self::ReversedGradient(fill) => {
+                generate_gradient_led_colors(
+                    1.0 - fill.level(value),
+                    end_color,
+                    start_color,
+                    colors,
+                );
+                fill.orient(colors);
+            }
+            Self::HueShift(hue_shift) => hue_shift.render(value, 0.0, palette, colors),
+            Self::TrafficLight(traffic_light) => {
+                let band = traffic_light
+                    .thresholds
+                    .iter()
+                    .filter(|threshold| value >= **threshold)
+                    .count();
+                let top_band = band == traffic_light.thresholds.len();
+
+                let color = if top_band && traffic_light.blink && blink_off(now) {
+                    Color::new(0, 0, 0)
+                } else {
+                    traffic_light
+                        .colors
+                        .get(band)
+                        .or(traffic_light.colors.last())
+                        .copied()
+                        .unwrap_or(*end_color)
+                };
+
+                colors.fill(color);
+            }
+            Self::Histogram => match history {
+                [] => generate_histogram_led_colors(&[value], start_color, end_color, colors),
+                history => generate_histogram_led_colors(history, start_color, end_color, colors),
+            },
+            Self::Clock(clock) => {
+                let time = DateTime::<Local>::from(now);
+
+                generate_clock_led_colors(
+                    clock,
+                    (time.hour(), time.minute(), time.second()),
+                    colors,
+                );
+            }
+            Self::Rainbow => rainbow(
+                (0..size).map(|index| index as f32 / size as f32),
+                colors,
+                now,
+            ),
+            Self::Wave(wave) => wave.render(
+                value,
+                palette,
+                (0..size).map(|index| Point {
+                    x: index as f32 / size as f32,
+                    y: 0.0,
+                }),
+                colors,
+                now,
+            ),
+            Self::Color(solid) if solid.blink && blink_off(now) => colors.fill(Color::new(0, 0, 0)),
+            Self::Color(solid) => colors.fill(solid.color),
+            Self::Off => colors.fill(Color::new(0, 0, 0)),
+            Self::Countdown(countdown) => {
+                let local = DateTime::<Local>::from(now).naive_local();
+                if countdown.flash && countdown.final_hour(local) && blink_off(now) {
+                    return colors.fill(Color::new(0, 0, 0));
+                }
+
+                generate_gradient_led_colors(
+                    countdown.remaining(local),
+                    start_color,
+                    end_color,
+                    colors,
+                );
+            }
+        }
+    }
+}
+
+mod local_datetime {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(
+        datetime: &NaiveDateTime,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&datetime.format(DATETIME_FORMAT).to_string())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<NaiveDateTime, D::Error> {
+        let value = String::deserialize(deserializer)?;
+
+        NaiveDateTime::parse_from_str(&value, DATETIME_FORMAT)
+            .or_else(|_| NaiveDateTime::parse_from_str(&value, "%Y-%m-%dT%H:%M:%S"))
+            .or_else(|_| {
+                chrono::NaiveDate::parse_from_str(&value, "%Y-%m-%d")
+                    .map(|date| date.and_time(chrono::NaiveTime::MIN))
+            })
+            .map_err(|_| {
+                serde::de::Error::custom(format!(
+                    "Invalid date: {} (expected e.g. 2026-12-24 18:00)",
+                    value
+                ))
+            })
+    }
+}
+
+fn blink_off(now: SystemTime) -> bool {
+    let millis = now
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis();
+
+    (millis / BLINK_PERIOD_MS) % 2 == 1
+}
+
+/// Fully saturated color of the given hue, wrapping around every 1.
+pub fn hue(hue: f32) -> Color {
+    let hue = hue.rem_euclid(1.0) * 6.0;
+    let rising = ((hue % 1.0) * 255.0) as u8;
+    let falling = 255 - rising;
+
+    match hue as u32 {
+        0 => Color::new(255, rising, 0),
+        1 => Color::new(falling, 255, 0),
+        2 => Color::new(0, 255, rising),
+        3 => Color::new(0, falling, 255),
+        4 => Color::new(rising, 0, 255),
+        _ => Color::new(255, 0, falling),
+    }
+}
+
+fn lerp(value: f32, start: f32, end: f32) -> f32 {
+    (1.0 - value) * start + (value * end)
+}
+
+fn lerp_color(value: f32, start_color: &Color, end_color: &Color) -> Color {
+    Color::new(
+        lerp(value, start_color.r as f32, end_color.r as f32) as u8,
+        lerp(value, start_color.g as f32, end_color.g as f32) as u8,
+        lerp(value, start_color.b as f32, end_color.b as f32) as u8,
+    )
+}
+
+/// Rainbow moving along the positions, from 0 to 1.
+fn rainbow(positions: impl Iterator<Item = f32>, colors: &mut [Color], now: SystemTime) {
+    let time = now
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs_f32()
+        % RAINBOW_PERIOD;
+
+    for (color, position) in colors.iter_mut().zip(positions) {
+        *color = hue(position + time / RAINBOW_PERIOD);
+    }
+}
+
+fn generate_gradient_led_colors(
+    value: f32,
+    start_color: &Color,
+    end_color: &Color,
+    colors: &mut [Color],
+) {
+    let value = value * colors.len() as f32;
+
+    for (index, color) in colors.iter_mut().enumerate() {
+        let value = value - index as f32;
+        let value = value.clamp(0.0, 1.0);
+
+        *color = lerp_color(value, start_color, end_color);
+    }
+}
+
+fn generate_histogram_led_colors(
+    values: &[f32],
+    start_color: &Color,
+    end_color: &Color,
+    colors: &mut [Color],
+) {
+    let size = colors.len();
+    // Counted again for each bucket rather than kept in a buffer, there are few enough values.
+    let count = |bucket: usize| {
+        values
+            .iter()
+            .filter(|value| {
+                ((value.clamp(0.0, 1.0) * size as f32) as usize).min(size.saturating_sub(1))
+                    == bucket
+            })
+            .count()
+    };
+
+    let highest = (0..size).map(count).max().unwrap_or_default().max(1);
+
+    for (index, led) in colors.iter_mut().enumerate() {
+        let color = lerp_color((index as f32 + 0.5) / size as f32, start_color, end_color);
+        let brightness = count(index) as f32 / highest as f32;
+
+        *led = lerp_color(brightness, &Color::new(0, 0, 0), &color);
+    }
+}
+
+fn generate_clock_led_colors(
+    clock: &Clock,
+    (hours, minutes, seconds): (u32, u32, u32),
+    colors: &mut [Color],
+) {
+    colors.fill(clock.background);
+    let size = colors.len();
+    if size == 0 {
+        return;
+    }
+
+    match clock.style {
+        ClockStyle::Analog => {
+            let minutes = minutes as f32 + seconds as f32 / 60.0;
+            let hours = (hours % 12) as f32 + minutes / 60.0;
+
+            // Later hands are drawn over the earlier ones.
+            for (turn, color) in [
+                (seconds as f32 / 60.0, clock.seconds),
+                (minutes / 60.0, clock.minutes),
+                (hours / 12.0, clock.hours),
+            ] {
+                colors[(turn * size as f32) as usize % size] = color;
+            }
+        }
+        ClockStyle::Binary => {
+            let bits = [
+                (hours, 5, clock.hours),
+                (minutes, 6, clock.minutes),
+                (seconds, 6, clock.seconds),
+            ]
+            .into_iter()
+            .flat_map(|(value, width, color)| {
+                (0..width)
+                    .rev()
+                    .map(move |bit| (value >> bit & 1 == 1).then_some(color))
+            });
+
+            for (led, bit) in colors.iter_mut().zip(bits) {
+                if let Some(color) = bit {
+                    *led = color;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn log_scale_lights_up_low_values() {
+        let palette = Palette::default();
+        let lit = |effect: &Effect, value: f32| {
+            let mut colors = [Color::new(0, 0, 0); 20];
+            effect.render(value, &[], &palette, &mut colors, UNIX_EPOCH);
+
+            colors
+                .iter()
+                .filter(|color| **color == palette.load)
+                .count()
+        };
+
+        let linear = Effect::Gradient(Fill::default());
+        let log = Effect::Gradient(Fill {
+            scale: Scale::Log,
+            ..Default::default()
+        });
+
+        assert_eq!(lit(&linear, 0.02), 0);
+        assert_eq!(lit(&log, 0.02), 4);
+        assert_eq!(lit(&log, 0.0), 0);
+        assert_eq!(lit(&log, 1.0), 20);
+    }
+
+    #[test]
+    fn fill_starts_at_its_threshold_from_its_end() {
+        let palette = Palette::default();
+        let fill = Effect::Gradient(Fill {
+            threshold: 0.5,
+            direction: Direction::Backward,
+            ..Default::default()
+        });
+
+        let mut colors = [Color::new(0, 0, 0); 4];
+
+        fill.render(0.4, &[], &palette, &mut colors, UNIX_EPOCH);
+        assert!(colors.iter().all(|color| *color == palette.idle));
+
+        fill.render(0.75, &[], &palette, &mut colors, UNIX_EPOCH);
+        assert_eq!(
+            colors,
+            [palette.idle, palette.idle, palette.load, palette.load]
+        );
+    }
+
+    #[test]
+    fn histogram_counts_recent_values() {
+        let palette = Palette {
+            idle: Color::new(0xFF, 0xFF, 0xFF),
+            load: Color::new(0xFF, 0xFF, 0xFF),
+        };
+        let history = [0.1, 0.1, 0.3, 0.9];
+
+        let mut colors = [Color::new(0, 0, 0); 4];
+        Effect::Histogram.render(0.9, &history, &palette, &mut colors, UNIX_EPOCH);
+
+        assert_eq!(
+            colors,
+            [
+                Color::new(0xFF, 0xFF, 0xFF),
+                Color::new(0x7F, 0x7F, 0x7F),
+                Color::new(0, 0, 0),
+                Color::new(0x7F, 0x7F, 0x7F),
+            ]
+        );
+    }
+
+    #[test]
+    fn clock_faces() {
+        let clock = Clock::default();
+        let off = clock.background;
+
+        // 3:15:30 on a 12 LED fan ring.
+        let mut colors = [off; 12];
+        generate_clock_led_colors(&clock, (15, 15, 30), &mut colors);
+        assert_eq!(colors[3], clock.hours);
+        assert_eq!(colors[6], clock.seconds);
+        assert_eq!(colors.iter().filter(|color| **color != off).count(), 2);
+
+        // The minute hand is right under the hour hand.
+        generate_clock_led_colors(&clock, (3, 16, 0), &mut colors);
+        assert_eq!(colors[0], clock.seconds);
+        assert_eq!(colors[3], clock.hours);
+
+        let binary = Clock {
+            style: ClockStyle::Binary,
+            ..Default::default()
+        };
+        let mut colors = [off; 11];
+        generate_clock_led_colors(&binary, (18, 5, 59), &mut colors);
+        let (h, m) = (binary.hours, binary.minutes);
+        assert_eq!(colors, [h, off, off, h, off, off, off, off, m, off, m]);
+    }
+
+    #[test]
+    fn countdown_empties_towards_the_deadline() {
+        let countdown: Countdown =
+            toml::from_str("deadline = \"2026-12-24\"\ndays = 4\nflash = true").unwrap();
+        let at = |value: &str| NaiveDateTime::parse_from_str(value, DATETIME_FORMAT).unwrap();
+
+        assert_eq!(countdown.remaining(at("2026-12-01 00:00")), 1.0);
+        assert_eq!(countdown.remaining(at("2026-12-22 00:00")), 0.5);
+        assert_eq!(countdown.remaining(at("2026-12-25 00:00")), 0.0);
+
+        assert!(countdown.final_hour(at("2026-12-23 23:30")));
+        assert!(!countdown.final_hour(at("2026-12-23 22:30")));
+        assert!(!countdown.final_hour(at("2026-12-24 00:30")));
+    }
+
+    #[test]
+    fn hue_shift_follows_the_second_metric() {
+        let hue_shift: HueShift = toml::from_str("metric = \"thermal\"").unwrap();
+        let palette = Palette::default();
+
+        let mut colors = [Color::new(0, 0, 0); 4];
+
+        hue_shift.render(0.5, 0.0, &palette, &mut colors);
+        assert_eq!(colors[..2], [Color::new(0, 0, 255); 2]);
+        assert_eq!(colors[2..], [palette.idle; 2]);
+
+        hue_shift.render(0.5, 1.0, &palette, &mut colors);
+        assert_eq!(colors[..2], [Color::new(255, 0, 0); 2]);
+
+        // Green half way, going through cyan.
+        hue_shift.render(1.0, 0.5, &palette, &mut colors[..1]);
+        assert_eq!(colors[0], Color::new(0, 255, 0));
+    }
+
+    #[test]
+    fn traffic_light_bands() {
+        let effect = Effect::TrafficLight(TrafficLight {
+            blink: true,
+            ..Default::default()
+        });
+        let palette = Palette::default();
+        let color = |value: f32, millis: u64| {
+            let mut colors = [Color::new(0, 0, 0)];
+            effect.render(
+                value,
+                &[],
+                &palette,
+                &mut colors,
+                UNIX_EPOCH + Duration::from_millis(millis),
+            );
+
+            colors[0]
+        };
+
+        assert_eq!(color(0.2, 0), Color::new(0x00, 0xFF, 0x00));
+        assert_eq!(color(0.5, 0), Color::new(0xFF, 0xBF, 0x00));
+        assert_eq!(color(0.9, 0), Color::new(0xFF, 0x00, 0x00));
+        assert_eq!(color(0.9, BLINK_PERIOD_MS as u64), Color::new(0, 0, 0));
+        assert_eq!(
+            color(0.5, BLINK_PERIOD_MS as u64),
+            Color::new(0xFF, 0xBF, 0x00)
+        );
+    }
+
+    #[test]
+    fn waves_flow_across_the_positions() {
+        let palette = Palette::default();
+        let wave = Wave {
+            wavelength: 1.0,
+            ..Wave::default()
+        };
+        let positions = [Point { x: 0.0, y: 0.0 }, Point { x: 0.5, y: 0.9 }];
+        let colors = |wave: &Wave, value, seconds| {
+            let mut colors = [Color::new(0, 0, 0); 2];
+            wave.render(
+                value,
+                &palette,
+                positions.iter().copied(),
+                &mut colors,
+                UNIX_EPOCH + Duration::from_secs(seconds),
+            );
+
+            colors
+        };
+
+        assert_eq!(colors(&wave, 1.0, 0), [palette.load, palette.idle]);
+        // Half a wavelength later, at 0.5 crests per second.
+        assert_eq!(colors(&wave, 1.0, 1), [palette.idle, palette.load]);
+        assert_eq!(colors(&wave, 0.0, 0), [palette.idle, palette.idle]);
+
+        let rings = Wave {
+            origin: Some([0.5, 0.9]),
+            ..wave
+        };
+        assert_eq!(colors(&rings, 1.0, 0)[1], palette.load);
+    }
+}
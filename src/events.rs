@@ -0,0 +1,148 @@
+use crate::{
+    config::{format_color, Config, Metric},
+    sampler::Samples,
+};
+use openrgb::data::Color;
+use serde::Serialize;
+use std::{
+    collections::BTreeMap,
+    time::{SystemTime, UNIX_EPOCH},
+};
+use tokio::sync::broadcast;
+
+/// Events kept for slow subscribers, which miss the older ones.
+const EVENT_BUFFER: usize = 64;
+
+/// Changes of the running client, streamed to dashboards and overlays.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+pub enum Event {
+    /// Metric values as sampled, from 0 to 1, and `formatted` in their units.
+    Metrics {
+        #[serde(flatten)]
+        samples: Samples,
+        formatted: BTreeMap<Metric, String>,
+    },
+    /// Colors sent to the LEDs of every controller, as `#RRGGBB`.
+    Frame {
+        controllers: Vec<ControllerColors>,
+    },
+    /// Reduced motion or high contrast switched.
+    Mode {
+        mode: &'static str,
+        enabled: bool,
+    },
+    /// A sequence started playing.
+    Sequence {
+        name: String,
+    },
+    /// A zone, or a whole controller without zone, stopped or resumed being updated.
+    Zone {
+        controller: String,
+        zone: Option<String>,
+        enabled: bool,
+    },
+    /// Brightness of every LED changed, from 0 to 1.
+    Brightness {
+        level: f32,
+    },
+    /// Switched to a named profile, `None` being the configuration without profile.
+    Profile {
+        name: Option<String>,
+    },
+    /// Switched to a scene, `None` being the configuration without scene.
+    Scene {
+        name: Option<String>,
+    },
+    /// Connected to OpenRGB, `reconnect` when the connection was lost before.
+    Connected {
+        reconnect: bool,
+        /// The server restarted meanwhile, resetting the controllers.
+        restarted: bool,
+    },
+    Disconnected {
+        reason: String,
+    },
+    /// Something went wrong that the user should know about.
+    Alert {
+        message: String,
+    },
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ControllerColors {
+    pub name: String,
+    pub colors: Vec<String>,
+}
+
+impl Event {
+    pub fn metrics(samples: Samples, config: &Config) -> Self {
+        Self::Metrics {
+            samples,
+            formatted: samples
+                .quantities(config)
+                .map(|(metric, quantity)| (metric, quantity.to_string()))
+                .collect(),
+        }
+    }
+
+    pub fn frame(frame: &[(String, Vec<Color>)]) -> Self {
+        Self::Frame {
+            controllers: frame
+                .iter()
+                .map(|(name, colors)| ControllerColors {
+                    name: name.clone(),
+                    colors: colors.iter().map(format_color).collect(),
+                })
+                .collect(),
+        }
+    }
+
+    /// JSON with the Unix time of the event in milliseconds, e.g.
+    /// `{"time":1700000000000,"type":"sequence","name":"release"}`.
+    pub fn to_json(&self) -> String {
+        #[derive(Serialize)]
+        struct Timed<'a> {
+            time: u64,
+            #[serde(flatten)]
+            event: &'a Event,
+        }
+
+        let time = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis() as u64;
+
+        serde_json::to_string(&Timed { time, event: self }).unwrap()
+    }
+}
+
+/// Publishes the events of the running client to whoever subscribed.
+#[derive(Debug)]
+pub struct Events {
+    sender: broadcast::Sender<Event>,
+}
+
+impl Default for Events {
+    fn default() -> Self {
+        Self {
+            sender: broadcast::channel(EVENT_BUFFER).0,
+        }
+    }
+}
+
+impl Events {
+    pub fn emit(&self, event: Event) {
+        // Nobody listening is fine.
+        let _ = self.sender.send(event);
+    }
+
+    /// Whether anyone listens, to skip preparing costly events otherwise.
+    pub fn is_observed(&self) -> bool {
+        self.sender.receiver_count() > 0
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<Event> {
+        self.sender.subscribe()
+    }
+}
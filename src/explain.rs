@@ -0,0 +1,115 @@
+use crate::{
+    accessibility::Accessibility,
+    args::Target,
+    color_manager::render,
+    config::{format_color, Config},
+    connection::{ConnectionOptions, Connector},
+    control::ControlState,
+    mapping::{resolve, ControllerInfo},
+    sampler::Sampler,
+    units::quantity,
+};
+use anyhow::{anyhow, Result};
+use log::*;
+use std::{sync::Arc, time::SystemTime};
+
+/// Prints the rules that decided the colors of a controller or zone, the metric sampled now and
+/// the colors that result.
+pub async fn explain(
+    options: &Target,
+    config: &Config,
+    connection: &ConnectionOptions,
+) -> Result<()> {
+    let client = Connector::new(connection)?.connect().await?;
+
+    let mut controller = None;
+    for controller_id in 0..client.get_controller_count().await? {
+        let candidate = client.get_controller(controller_id).await?;
+        if config.designates(&options.controller, &candidate.name, &candidate.location) {
+            controller = Some(candidate);
+
+            break;
+        }
+    }
+
+    let controller = controller.ok_or_else(|| {
+        anyhow!(
+            "No controller named {:?}, `explain-mapping` lists them.",
+            options.controller
+        )
+    })?;
+    let info = ControllerInfo::from(&controller).with_overrides(config);
+
+    let range = match &options.zone {
+        Some(zone_name) => info
+            .zone_range(zone_name)
+            .ok_or_else(|| anyhow!("No zone named {:?} on {}.", zone_name, info.name))?,
+        None => 0..info.leds,
+    };
+
+    let mut plan = resolve(config, &info);
+    Accessibility::new(config.reduced_motion, config.high_contrast).adjust(&mut plan);
+
+    println!("Controller: {}", info.name);
+    if let Some(zone) = &options.zone {
+        println!("Zone: {} (LEDs {} to {})", zone, range.start, range.end);
+    }
+
+    let Some(metric) = plan.metric else {
+        println!("Left alone ({}).", plan.metric_rule);
+
+        return Ok(());
+    };
+
+    info!("Sampling...");
+    let control = Arc::new(ControlState::default());
+    let mut sampler = Sampler::new(config, control.clone())?;
+    let samples = sampler.warm_up().await?;
+    let usage = samples.usage();
+
+    println!("Metric: {} ({})", metric, plan.metric_rule);
+    println!(
+        "Smoothed value: {}",
+        samples.get(metric).map_or_else(
+            || "unavailable".to_owned(),
+            |value| match quantity(metric, value, config) {
+                Some(quantity) => format!("{:.3} ({})", value, quantity),
+                None => format!("{:.3}", value),
+            }
+        )
+    );
+    println!(
+        "Palette: {} -> {} ({})",
+        format_color(&plan.palette.idle),
+        format_color(&plan.palette.load),
+        plan.palette_rule
+    );
+
+    println!("Effects ({}):", plan.layout_rule);
+    let mut start = 0;
+    for segment in &plan.segments {
+        let end = start + segment.leds;
+        if start < range.end && range.start < end {
+            println!(
+                "    {}: {} over LEDs {} to {}",
+                segment.name, segment.effect, start, end
+            );
+            if let Some(idle_effect) = &segment.idle_effect {
+                println!("        {} while the metric is idle", idle_effect);
+            }
+        }
+
+        start = end;
+    }
+
+    let colors = render(&plan, &usage, &control.history, SystemTime::now());
+    let colors = colors
+        .get(range.clone())
+        .unwrap_or_default()
+        .iter()
+        .map(format_color)
+        .collect::<Vec<_>>();
+    println!("Colors: {}", colors.join(" "));
+
+    Ok(())
+}
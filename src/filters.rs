@@ -0,0 +1,353 @@
+use crate::{mapping::Plan, power::PowerModel, safety::FlashLimiter};
+use anyhow::{bail, Result};
+use openrgb::data::Color;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::{
+    collections::HashMap,
+    fmt,
+    iter::repeat_n,
+    str::FromStr,
+    time::{Instant, SystemTime, UNIX_EPOCH},
+};
+
+/// Burn-in protection: the brightness goes down by up to 10% and back over 20 minutes, and
+/// the colors move by one LED every 10 minutes.
+const DRIFT_DEPTH: f32 = 0.1;
+const DRIFT_PERIOD: f32 = 20.0 * 60.0;
+const SHIFT_PERIOD: u64 = 10 * 60;
+
+/// Channel values from 0 to 255, kept fractional until sent.
+type Frame = Vec<[f32; 3]>;
+
+/// Transformation of a controller's frame before it's sent, in the order configured.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+pub enum Filter {
+    /// Raises the channels to the power `value`, darkening the low levels the LEDs render too
+    /// bright.
+    Gamma { value: f32 },
+    /// Scales every channel, from 0 to 1.
+    Brightness { value: f32 },
+    /// Order the controller expects the channels in, e.g. `GRB`.
+    ChannelOrder { order: ChannelOrder },
+    /// Dims the frame so the LEDs draw at most `watts`, see `PowerModel`.
+    PowerCap { watts: f32 },
+    /// Rounds the channels with the error carried over to the next frame, so dimmed colors
+    /// average to their exact level.
+    Dither,
+    /// Changes too slow to be noticed, spreading the wear of LEDs showing the same colors for
+    /// long periods.
+    BurnIn { mode: BurnIn },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum BurnIn {
+    /// The brightness slowly goes down a little and back.
+    Drift,
+    /// The colors slowly move along the LEDs of each zone, wrapping around.
+    Shift,
+}
+
+/// Index of the red, green and blue channels in what the controller expects.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ChannelOrder([usize; 3]);
+
+impl FromStr for ChannelOrder {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self> {
+        let upper = value.to_ascii_uppercase();
+        let index = |channel: char| upper.find(channel);
+
+        match (index('R'), index('G'), index('B')) {
+            (Some(r), Some(g), Some(b)) if upper.len() == 3 => Ok(Self([r, g, b])),
+            _ => bail!("Invalid channel order: {} (expected e.g. GRB)", value),
+        }
+    }
+}
+
+impl fmt::Display for ChannelOrder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut order = [' '; 3];
+        for (channel, index) in ['R', 'G', 'B'].into_iter().zip(self.0) {
+            order[index] = channel;
+        }
+
+        write!(f, "{}", order.iter().collect::<String>())
+    }
+}
+
+impl Serialize for ChannelOrder {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+impl<'de> Deserialize<'de> for ChannelOrder {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+impl fmt::Display for Filter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Gamma { value } => write!(f, "gamma {}", value),
+            Self::Brightness { value } => write!(f, "brightness {}", value),
+            Self::ChannelOrder { order } => write!(f, "channel order {}", order),
+            Self::PowerCap { watts } => write!(f, "power cap {} W", watts),
+            Self::Dither => write!(f, "dither"),
+            Self::BurnIn {
+                mode: BurnIn::Drift,
+            } => write!(f, "burn-in drift"),
+            Self::BurnIn {
+                mode: BurnIn::Shift,
+            } => write!(f, "burn-in shift"),
+        }
+    }
+}
+
+/// The post-processing stage: the filters of each controller, then the photosensitivity
+/// limits, which nothing comes after.
+#[derive(Debug)]
+pub struct PostProcessor {
+    limiter: FlashLimiter,
+    /// Rounding error of the last frame of each controller, for `Dither`.
+    residuals: HashMap<String, Frame>,
+    /// LED count of each segment and the frame being filtered, kept from one controller to the
+    /// next.
+    zones: Vec<usize>,
+    frame: Frame,
+}
+
+impl PostProcessor {
+    pub fn new(limiter: FlashLimiter) -> Self {
+        Self {
+            limiter,
+            residuals: HashMap::new(),
+            zones: vec![],
+            frame: vec![],
+        }
+    }
+
+    /// Processes the colors about to be sent to a controller, identified by `key`.
+    pub fn process(&mut self, key: &str, plan: &Plan, colors: &mut [Color], now: SystemTime) {
+        if !plan.filters.is_empty() {
+            self.zones.clear();
+            self.zones
+                .extend(plan.segments.iter().map(|segment| segment.leds));
+            if !self.residuals.contains_key(key) {
+                self.residuals.insert(key.to_owned(), vec![]);
+            }
+            let residual = self.residuals.get_mut(key).unwrap();
+
+            self.frame.clear();
+            self.frame.extend(
+                colors
+                    .iter()
+                    .map(|color| [color.r as f32, color.g as f32, color.b as f32]),
+            );
+            for filter in &plan.filters {
+                filter.apply(&mut self.frame, &self.zones, &plan.power, residual, now);
+            }
+
+            for (color, led) in colors.iter_mut().zip(&self.frame) {
+                *color = quantize(led);
+            }
+        }
+
+        self.limiter.limit(key, colors, Instant::now());
+    }
+}
+
+impl Filter {
+    fn apply(
+        &self,
+        frame: &mut Frame,
+        zones: &[usize],
+        power: &PowerModel,
+        residual: &mut Frame,
+        now: SystemTime,
+    ) {
+        match self {
+            Self::Gamma { value } => {
+                for channel in channels(frame) {
+                    *channel = 255.0 * (*channel / 255.0).clamp(0.0, 1.0).powf(*value);
+                }
+            }
+            Self::Brightness { value } => {
+                for channel in channels(frame) {
+                    *channel *= value.max(0.0);
+                }
+            }
+            Self::ChannelOrder { order } => {
+                for led in frame.iter_mut() {
+                    let mut ordered = [0.0; 3];
+                    for (channel, index) in led.iter().zip(order.0) {
+                        ordered[index] = *channel;
+                    }
+
+                    *led = ordered;
+                }
+            }
+            Self::PowerCap { watts } => {
+                let idle = power.estimate_watts(repeat_n(Color::new(0, 0, 0), frame.len()));
+                let drawn = power.estimate_watts(frame.iter().map(quantize));
+
+                if drawn > *watts as f64 && drawn > idle {
+                    let scale = ((*watts as f64 - idle).max(0.0) / (drawn - idle)) as f32;
+                    for channel in channels(frame) {
+                        *channel *= scale;
+                    }
+                }
+            }
+            Self::Dither => {
+                if residual.len() != frame.len() {
+                    residual.clear();
+                    residual.resize(frame.len(), [0.0; 3]);
+                }
+
+                for (led, error) in frame.iter_mut().zip(residual.iter_mut()) {
+                    for (channel, error) in led.iter_mut().zip(error.iter_mut()) {
+                        let exact = *channel + *error;
+                        *channel = exact.round().clamp(0.0, 255.0);
+                        *error = exact - *channel;
+                    }
+                }
+            }
+            Self::BurnIn { mode } => {
+                let seconds = now.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+
+                match mode {
+                    BurnIn::Drift => {
+                        let phase = (seconds as f32 % DRIFT_PERIOD) / DRIFT_PERIOD;
+                        let brightness =
+                            1.0 - DRIFT_DEPTH * (0.5 - 0.5 * (phase * std::f32::consts::TAU).cos());
+
+                        for channel in channels(frame) {
+                            *channel *= brightness;
+                        }
+                    }
+                    BurnIn::Shift => {
+                        let mut start = 0;
+                        for zone in zones {
+                            let end = (start + zone).min(frame.len());
+                            let zone = &mut frame[start..end];
+                            if !zone.is_empty() {
+                                let offset = (seconds / SHIFT_PERIOD) as usize % zone.len();
+                                zone.rotate_right(offset);
+                            }
+
+                            start = end;
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn channels(frame: &mut Frame) -> impl Iterator<Item = &mut f32> {
+    frame.iter_mut().flatten()
+}
+
+fn quantize(led: &[f32; 3]) -> Color {
+    let channel = |value: f32| value.round().clamp(0.0, 255.0) as u8;
+
+    Color::new(channel(led[0]), channel(led[1]), channel(led[2]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn apply(filter: Filter, colors: &[Color], seconds: u64) -> Vec<Color> {
+        let mut frame = colors
+            .iter()
+            .map(|color| [color.r as f32, color.g as f32, color.b as f32])
+            .collect::<Frame>();
+        filter.apply(
+            &mut frame,
+            &[colors.len()],
+            &PowerModel::default(),
+            &mut vec![],
+            UNIX_EPOCH + Duration::from_secs(seconds),
+        );
+
+        frame.iter().map(quantize).collect()
+    }
+
+    #[test]
+    fn filters() {
+        let red = Color::new(0xFF, 0x00, 0x00);
+        let green = Color::new(0x00, 0xFF, 0x00);
+        let blue = Color::new(0x00, 0x00, 0xFF);
+        let grey = Color::new(0x80, 0x80, 0x80);
+
+        let order = "GRB".parse().unwrap();
+        assert_eq!(
+            apply(Filter::ChannelOrder { order }, &[Color::new(1, 2, 3)], 0),
+            [Color::new(2, 1, 3)]
+        );
+        assert_eq!(
+            apply(Filter::Brightness { value: 0.5 }, &[grey], 0),
+            [Color::new(0x40, 0x40, 0x40)]
+        );
+        assert_eq!(
+            apply(Filter::Gamma { value: 2.0 }, &[grey], 0),
+            [Color::new(0x40, 0x40, 0x40)]
+        );
+
+        // 10 LEDs at full white draw 3 W, plus 50 mW for the drivers.
+        let capped = apply(
+            Filter::PowerCap { watts: 1.55 },
+            &[Color::new(0xFF, 0xFF, 0xFF); 10],
+            0,
+        );
+        let watts = PowerModel::default().estimate_watts(capped);
+        assert!((1.5..=1.55).contains(&watts), "{} W", watts);
+
+        let shift = Filter::BurnIn {
+            mode: BurnIn::Shift,
+        };
+        assert_eq!(
+            apply(shift, &[red, green, blue], SHIFT_PERIOD),
+            [blue, red, green]
+        );
+
+        let drift = Filter::BurnIn {
+            mode: BurnIn::Drift,
+        };
+        assert_eq!(apply(drift.clone(), &[red], 0), [red]);
+        assert_eq!(
+            apply(drift, &[red], DRIFT_PERIOD as u64 / 2),
+            [Color::new(0xE6, 0x00, 0x00)]
+        );
+    }
+
+    #[test]
+    fn dither_averages_to_the_exact_level() {
+        let mut frame = vec![];
+        let mut residual = vec![];
+        let mut total = 0.0;
+        for _ in 0..10 {
+            frame = vec![[0.5, 0.0, 0.0]];
+            Filter::Dither.apply(
+                &mut frame,
+                &[1],
+                &PowerModel::default(),
+                &mut residual,
+                UNIX_EPOCH,
+            );
+
+            total += frame[0][0];
+        }
+
+        assert_eq!(total, 5.0);
+        assert!(frame[0][0] == 0.0 || frame[0][0] == 1.0);
+    }
+}
@@ -0,0 +1,234 @@
+use crate::{
+    companion,
+    config::{Config, Metric},
+    control::ControlState,
+    mapping::Plan,
+};
+use anyhow::{bail, Result};
+use log::*;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpStream,
+    time::timeout,
+};
+
+/// Time between the reports pushed to the aggregating client.
+const PUSH_INTERVAL: Duration = Duration::from_secs(2);
+/// Time allowed to a push, the aggregating client being on the LAN.
+const PUSH_TIMEOUT: Duration = Duration::from_secs(5);
+/// Hosts that stopped pushing for this long leave the dashboard.
+const STALE_AFTER: Duration = Duration::from_secs(30);
+
+/// Pushes the metrics of this machine to the web server of another client, whose dashboard
+/// shows every machine pushing to it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PushConfig {
+    /// Address of the web server of the aggregating client, e.g. `rack.lan:6745`.
+    pub address: String,
+    /// Companion token of the aggregating client, which only records the reports authenticated
+    /// with it. Best kept as a `secret://` name.
+    pub token: String,
+    /// Name of this machine on the dashboard, instead of its host name.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub host: Option<String>,
+}
+
+/// Zone showing a metric.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Binding {
+    pub controller: String,
+    pub zone: String,
+    pub metric: Metric,
+}
+
+/// Latest metrics of a machine and the zones showing them.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HostReport {
+    pub host: String,
+    /// From 0 to 1.
+    pub levels: BTreeMap<Metric, f32>,
+    /// In their units, e.g. `52.3 °C`.
+    pub formatted: BTreeMap<Metric, String>,
+    pub bindings: Vec<Binding>,
+}
+
+/// Reports of the machines pushing to this client, and the zones of this one.
+#[derive(Debug, Default)]
+pub struct Fleet {
+    hosts: Mutex<BTreeMap<String, (HostReport, Instant)>>,
+    /// Zones of each local controller showing a metric.
+    bindings: Mutex<BTreeMap<String, Vec<Binding>>>,
+}
+
+impl Fleet {
+    pub fn record(&self, report: HostReport) {
+        self.hosts
+            .lock()
+            .unwrap()
+            .insert(report.host.clone(), (report, Instant::now()));
+    }
+
+    /// Records the zones of a local controller showing a metric, from its plan.
+    pub fn bind(&self, plan: &Plan) {
+        let Some(metric) = plan.metric else {
+            return;
+        };
+
+        let mut bindings = self.bindings.lock().unwrap();
+        let unchanged = bindings.get(&plan.controller).is_some_and(|bound| {
+            bound.len() == plan.segments.len()
+                && bound.iter().zip(&plan.segments).all(|(binding, segment)| {
+                    binding.zone == segment.name
+                        && binding.metric == segment.metric.unwrap_or(metric)
+                })
+        });
+        if unchanged {
+            return;
+        }
+
+        bindings.insert(
+            plan.controller.clone(),
+            plan.segments
+                .iter()
+                .map(|segment| Binding {
+                    controller: plan.controller.clone(),
+                    zone: segment.name.clone(),
+                    metric: segment.metric.unwrap_or(metric),
+                })
+                .collect(),
+        );
+    }
+
+    /// Report of this machine, `None` before the first samples.
+    pub fn local_report(&self, control: &ControlState, config: &Config) -> Option<HostReport> {
+        let samples = (*control.samples.borrow())?;
+        let quantities = samples.quantities(config).collect::<Vec<_>>();
+
+        Some(HostReport {
+            host: host_name(config),
+            levels: quantities
+                .iter()
+                .filter_map(|(metric, _)| Some((*metric, samples.get(*metric)?)))
+                .collect(),
+            formatted: quantities
+                .iter()
+                .map(|(metric, quantity)| (*metric, quantity.to_string()))
+                .collect(),
+            bindings: self
+                .bindings
+                .lock()
+                .unwrap()
+                .values()
+                .flatten()
+                .cloned()
+                .collect(),
+        })
+    }
+
+    /// Reports of the machines that pushed recently, by host name.
+    pub fn reports(&self) -> Vec<HostReport> {
+        let mut hosts = self.hosts.lock().unwrap();
+        hosts.retain(|_, (_, received)| received.elapsed() < STALE_AFTER);
+
+        hosts.values().map(|(report, _)| report.clone()).collect()
+    }
+}
+
+/// Name of this machine on the dashboards.
+pub fn host_name(config: &Config) -> String {
+    if let Some(host) = config.push.as_ref().and_then(|push| push.host.clone()) {
+        return host;
+    }
+
+    std::env::var("COMPUTERNAME")
+        .ok()
+        .or_else(|| std::fs::read_to_string("/proc/sys/kernel/hostname").ok())
+        .map(|host| host.trim().to_owned())
+        .filter(|host| !host.is_empty())
+        .unwrap_or_else(|| "localhost".to_owned())
+}
+
+/// Pushes the report of this machine regularly, until the task is aborted.
+pub async fn run_push(control: Arc<ControlState>) {
+    let mut failing = false;
+    loop {
+        tokio::time::sleep(PUSH_INTERVAL).await;
+
+        let config = control.config();
+        let Some(push) = &config.push else {
+            continue;
+        };
+        let Some(report) = control.fleet.local_report(&control, &config) else {
+            continue;
+        };
+
+        match timeout(PUSH_TIMEOUT, send(&push.address, &push.token, &report)).await {
+            Ok(Ok(())) => {
+                if failing {
+                    info!("Pushing the metrics to {} again.", push.address);
+                }
+                failing = false;
+            }
+            Ok(Err(e)) if !failing => {
+                warn!("Failed to push the metrics to {}: {}", push.address, e);
+                failing = true;
+            }
+            Err(_) if !failing => {
+                warn!("Pushing the metrics to {} timed out.", push.address);
+                failing = true;
+            }
+            _ => {}
+        }
+    }
+}
+
+async fn send(address: &str, token: &str, report: &HostReport) -> Result<()> {
+    let body = serde_json::to_vec(report)?;
+    let mut stream = TcpStream::connect(address).await?;
+
+    let head = format!(
+        "POST /push HTTP/1.1\r\nHost: {}\r\nAuthorization: Bearer {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        address,
+        companion::bearer(token)?,
+        body.len()
+    );
+    stream.write_all(head.as_bytes()).await?;
+    stream.write_all(&body).await?;
+
+    let mut response = vec![];
+    stream.read_to_end(&mut response).await?;
+    let status_line = response.split(|&byte| byte == b'\n').next().unwrap_or(&[]);
+    if !status_line.starts_with(b"HTTP/1.1 2") {
+        bail!("{}", String::from_utf8_lossy(status_line).trim());
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reports_replace_the_previous_one_of_their_host() {
+        let fleet = Fleet::default();
+        for cpu in [0.2, 0.7] {
+            fleet.record(HostReport {
+                host: "nas".to_owned(),
+                levels: BTreeMap::from([(Metric::Cpu, cpu)]),
+                formatted: BTreeMap::new(),
+                bindings: vec![],
+            });
+        }
+
+        let reports = fleet.reports();
+        assert_eq!(reports.len(), 1);
+        assert_eq!(reports[0].levels[&Metric::Cpu], 0.7);
+    }
+}
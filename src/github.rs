@@ -0,0 +1,136 @@
+use crate::{
+    hex,
+    notifications::{Notification, Notifications},
+};
+use anyhow::{anyhow, bail, Result};
+use hmac::{Hmac, KeyInit, Mac};
+use log::*;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::Sha256;
+
+/// Repository events of a GitHub webhook shown as notifications on the LEDs.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GithubConfig {
+    /// Secret of the webhook, deliveries not signed with it are rejected.
+    pub secret: String,
+    #[serde(default)]
+    pub rules: Vec<GithubRule>,
+}
+
+/// Notification shown for the events matching all the conditions set.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GithubRule {
+    /// Event name, as in the `X-GitHub-Event` header, e.g. `push`, `pull_request` or `issues`.
+    pub event: String,
+    /// Action of the event, e.g. `opened` or `labeled`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub action: Option<String>,
+    /// Name of the label added or removed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+    /// Full name of the repository, e.g. `T-Hacker/open_rgb_client`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub repository: Option<String>,
+    #[serde(flatten)]
+    pub notification: Notification,
+}
+
+impl GithubRule {
+    fn matches(&self, event: &str, payload: &Value) -> bool {
+        let field = |pointer: &str| payload.pointer(pointer).and_then(Value::as_str);
+        let condition = |expected: &Option<String>, pointer: &str| {
+            expected.as_ref().is_none_or(|expected| {
+                field(pointer).is_some_and(|value| value.eq_ignore_ascii_case(expected))
+            })
+        };
+
+        self.event.eq_ignore_ascii_case(event)
+            && condition(&self.action, "/action")
+            && condition(&self.label, "/label/name")
+            && condition(&self.repository, "/repository/full_name")
+    }
+}
+
+/// Checks the `X-Hub-Signature-256` header of a delivery, `sha256=` and the HMAC of the body.
+pub fn verify(secret: &str, signature: Option<&str>, body: &[u8]) -> Result<()> {
+    let signature = signature
+        .and_then(|signature| signature.strip_prefix("sha256="))
+        .ok_or_else(|| anyhow!("Missing signature."))?;
+    let signature = hex::decode(signature).ok_or_else(|| anyhow!("Invalid signature."))?;
+
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes())?;
+    mac.update(body);
+    if mac.verify_slice(&signature).is_err() {
+        bail!("Wrong signature.");
+    }
+
+    Ok(())
+}
+
+/// Shows the notifications of the rules matching a delivery, returns how many matched.
+pub fn handle(
+    config: &GithubConfig,
+    event: &str,
+    body: &[u8],
+    notifications: &Notifications,
+) -> Result<usize> {
+    let payload = serde_json::from_slice::<Value>(body)?;
+
+    let mut matched = 0;
+    for rule in &config.rules {
+        if rule.matches(event, &payload) {
+            info!(
+                "GitHub {} event, notifying on {}.",
+                event, rule.notification.controller
+            );
+
+            notifications.show(rule.notification.clone());
+            matched += 1;
+        }
+    }
+
+    Ok(matched)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn signature() {
+        // Example of the GitHub documentation.
+        let body = b"Hello, World!";
+        let signature = "sha256=757107ea0eb2509fc211221cce984b8a37570b6d7586c22c46f4379c8b043e17";
+
+        assert!(verify("It's a Secret to Everybody", Some(signature), body).is_ok());
+        assert!(verify("Another secret", Some(signature), body).is_err());
+        assert!(verify("It's a Secret to Everybody", None, body).is_err());
+    }
+
+    #[test]
+    fn rules_match_the_payload() {
+        let rule: GithubRule = toml::from_str(
+            r##"
+            event = "issues"
+            action = "labeled"
+            label = "bug"
+            controller = "Corsair Lighting Node Pro"
+            effect = { type = "color", color = "#FF0000", blink = true }
+            duration = "10s"
+            "##,
+        )
+        .unwrap();
+
+        let payload = serde_json::json!({
+            "action": "labeled",
+            "label": { "name": "bug" },
+            "repository": { "full_name": "T-Hacker/open_rgb_client" },
+        });
+        assert!(rule.matches("issues", &payload));
+        assert!(!rule.matches("push", &payload));
+
+        let payload = serde_json::json!({ "action": "labeled", "label": { "name": "docs" } });
+        assert!(!rule.matches("issues", &payload));
+    }
+}
@@ -0,0 +1,159 @@
+use crate::{config::GpuTemperatureConfig, sampler::read_file};
+use anyhow::Result;
+#[cfg(feature = "nvidia")]
+use log::*;
+#[cfg(feature = "nvidia")]
+use nvml::enum_wrappers::device::TemperatureSensor;
+use std::path::PathBuf;
+
+/// Samples the GPU utilization and temperature, through NVML when built with the `nvidia`
+/// feature and NVML works, and from the amdgpu driver otherwise.
+pub struct GpuMonitor {
+    source: Source,
+    idle: f32,
+    hot: f32,
+}
+
+enum Source {
+    #[cfg(feature = "nvidia")]
+    Nvml(Box<nvml::Nvml>),
+    Amdgpu(Amdgpu),
+}
+
+/// sysfs files of the first card of the amdgpu driver.
+struct Amdgpu {
+    busy_percent: Option<PathBuf>,
+    /// `temp1_input` of the hwmon of the card, the edge temperature in millidegrees Celsius.
+    temp_input: Option<PathBuf>,
+    /// Contents of the file last read, kept from one sample to the next.
+    buffer: String,
+}
+
+impl GpuMonitor {
+    /// Never fails: without NVML nor an amdgpu card, the GPU metrics are just unavailable.
+    pub fn new(temperature: &GpuTemperatureConfig) -> Self {
+        #[cfg(feature = "nvidia")]
+        let source = match init_nvml() {
+            Ok(nvml) => Source::Nvml(Box::new(nvml)),
+            Err(e) => {
+                let amdgpu = Amdgpu::find();
+                match amdgpu.busy_percent {
+                    Some(_) => warn!("NVML unavailable ({}), reading the amdgpu driver.", e),
+                    None => warn!("NVML unavailable ({}), the GPU won't be sampled.", e),
+                }
+
+                Source::Amdgpu(amdgpu)
+            }
+        };
+        #[cfg(not(feature = "nvidia"))]
+        let source = Source::Amdgpu(Amdgpu::find());
+
+        Self {
+            source,
+            idle: temperature.idle,
+            hot: temperature.hot,
+        }
+    }
+
+    /// Utilization of the first GPU from 0 to 1, or `None` when it can't be monitored.
+    pub fn utilization(&mut self) -> Result<Option<f32>> {
+        match &mut self.source {
+            #[cfg(feature = "nvidia")]
+            Source::Nvml(nvml) => {
+                let utilization = nvml.device_by_index(0)?.utilization_rates()?;
+
+                Ok(Some(utilization.gpu as f32 / 100.0))
+            }
+            Source::Amdgpu(amdgpu) => {
+                let Some(busy_percent) = &amdgpu.busy_percent else {
+                    return Ok(None);
+                };
+
+                let busy_percent = read_file(busy_percent, &mut amdgpu.buffer)?
+                    .trim()
+                    .parse::<f32>()?;
+
+                Ok(Some(busy_percent / 100.0))
+            }
+        }
+    }
+
+    /// Temperature of the first GPU mapped from 0 at the idle temperature to 1 at the hot one,
+    /// or `None` when it can't be monitored.
+    pub fn temperature(&mut self) -> Result<Option<f32>> {
+        let Some(celsius) = self.celsius()? else {
+            return Ok(None);
+        };
+
+        Ok(Some(
+            ((celsius - self.idle) / (self.hot - self.idle)).clamp(0.0, 1.0),
+        ))
+    }
+
+    fn celsius(&mut self) -> Result<Option<f32>> {
+        match &mut self.source {
+            #[cfg(feature = "nvidia")]
+            Source::Nvml(nvml) => {
+                let temperature = nvml
+                    .device_by_index(0)?
+                    .temperature(TemperatureSensor::Gpu)?;
+
+                Ok(Some(temperature as f32))
+            }
+            Source::Amdgpu(amdgpu) => {
+                let Some(temp_input) = &amdgpu.temp_input else {
+                    return Ok(None);
+                };
+
+                Ok(Some(
+                    read_file(temp_input, &mut amdgpu.buffer)?
+                        .trim()
+                        .parse::<f32>()?
+                        / 1000.0,
+                ))
+            }
+        }
+    }
+}
+
+#[cfg(feature = "nvidia")]
+fn init_nvml() -> Result<nvml::Nvml> {
+    let nvml = nvml::Nvml::init()?;
+
+    // Fail now rather than on every sample.
+    nvml.device_by_index(0)?;
+
+    Ok(nvml)
+}
+
+/// Whether the machine has a card of the amdgpu driver, read when NVML isn't available.
+#[cfg(all(target_os = "linux", feature = "nvidia"))]
+pub fn has_amdgpu() -> bool {
+    Amdgpu::find().busy_percent.is_some()
+}
+
+impl Amdgpu {
+    fn find() -> Self {
+        // AMD GPUs and APUs (e.g. the Steam Deck) report their load in sysfs.
+        let device = std::fs::read_dir("/sys/class/drm")
+            .into_iter()
+            .flatten()
+            .flatten()
+            .map(|card| card.path().join("device"))
+            .find(|device| device.join("gpu_busy_percent").exists());
+        let temp_input = device.as_ref().and_then(|device| {
+            std::fs::read_dir(device.join("hwmon"))
+                .into_iter()
+                .flatten()
+                .flatten()
+                .map(|hwmon| hwmon.path().join("temp1_input"))
+                .find(|path| path.exists())
+        });
+
+        Self {
+            busy_percent: device.map(|device| device.join("gpu_busy_percent")),
+            temp_input,
+            buffer: String::new(),
+        }
+    }
+}
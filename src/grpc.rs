@@ -0,0 +1,198 @@
+use crate::{
+    control::{self, ControlRequest, ControlState, Injection},
+    sampler::Samples,
+};
+use anyhow::Result;
+use log::*;
+use std::{
+    net::SocketAddr,
+    pin::Pin,
+    sync::{atomic::Ordering, Arc},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+use tokio_stream::{wrappers::WatchStream, Stream, StreamExt};
+use tonic::{transport::Server, Request, Response, Status};
+
+mod proto {
+    tonic::include_proto!("open_rgb_client.v1");
+}
+
+use proto::control_server::{Control, ControlServer};
+
+const DEFAULT_INJECTION_DURATION: Duration = Duration::from_secs(30);
+
+/// Serves the control requests over gRPC, until the process exits.
+pub async fn serve(address: SocketAddr, state: Arc<ControlState>) -> Result<()> {
+    info!("gRPC control listening on {}.", address);
+
+    Server::builder()
+        .add_service(ControlServer::new(ControlService { state }))
+        .serve(address)
+        .await?;
+
+    Ok(())
+}
+
+struct ControlService {
+    state: Arc<ControlState>,
+}
+
+impl ControlService {
+    fn apply(&self, request: ControlRequest) -> Result<Response<proto::Empty>, Status> {
+        control::apply(request, &self.state, &self.state.config())
+            .map(|_| Response::new(proto::Empty {}))
+            .map_err(|e| Status::invalid_argument(e.to_string()))
+    }
+}
+
+#[tonic::async_trait]
+impl Control for ControlService {
+    async fn get_status(
+        &self,
+        _: Request<proto::GetStatusRequest>,
+    ) -> Result<Response<proto::Status>, Status> {
+        let accessibility = &self.state.accessibility;
+
+        Ok(Response::new(proto::Status {
+            metrics: self.state.samples.borrow().as_ref().map(metrics),
+            reduced_motion: accessibility.reduced_motion.load(Ordering::Relaxed),
+            high_contrast: accessibility.high_contrast.load(Ordering::Relaxed),
+            sequence: self.state.player.sequence(),
+            disabled_zones: self
+                .state
+                .zones
+                .list()
+                .into_iter()
+                .map(|(controller, zone)| proto::Zone { controller, zone })
+                .collect(),
+            profile: self.state.profiles.active(),
+        }))
+    }
+
+    type StreamMetricsStream = Pin<Box<dyn Stream<Item = Result<proto::Metrics, Status>> + Send>>;
+
+    async fn stream_metrics(
+        &self,
+        _: Request<proto::StreamMetricsRequest>,
+    ) -> Result<Response<Self::StreamMetricsStream>, Status> {
+        let samples = WatchStream::new(self.state.samples.subscribe())
+            .filter_map(|samples| samples.as_ref().map(metrics).map(Ok));
+
+        Ok(Response::new(Box::pin(samples)))
+    }
+
+    async fn get_history(
+        &self,
+        request: Request<proto::GetHistoryRequest>,
+    ) -> Result<Response<proto::History>, Status> {
+        let since = match request.into_inner().last_seconds {
+            Some(seconds) => SystemTime::now()
+                .checked_sub(
+                    Duration::try_from_secs_f64(seconds)
+                        .map_err(|e| Status::invalid_argument(e.to_string()))?,
+                )
+                .unwrap_or(UNIX_EPOCH),
+            None => UNIX_EPOCH,
+        };
+
+        Ok(Response::new(proto::History {
+            samples: self
+                .state
+                .history
+                .since(since)
+                .iter()
+                .map(|entry| proto::TimedMetrics {
+                    time: entry.time,
+                    metrics: Some(metrics(&entry.samples)),
+                })
+                .collect(),
+        }))
+    }
+
+    async fn inject(
+        &self,
+        request: Request<proto::InjectRequest>,
+    ) -> Result<Response<proto::Empty>, Status> {
+        let request = request.into_inner();
+
+        let injections = request
+            .injections
+            .iter()
+            .map(|injection| format!("{}={}", injection.metric, injection.value).parse())
+            .collect::<Result<Vec<Injection>>>()
+            .map_err(|e| Status::invalid_argument(e.to_string()))?;
+        let duration = match request.duration_seconds {
+            Some(seconds) => Duration::try_from_secs_f64(seconds)
+                .map_err(|e| Status::invalid_argument(e.to_string()))?,
+            None => DEFAULT_INJECTION_DURATION,
+        };
+
+        self.apply(ControlRequest::Inject {
+            injections,
+            duration,
+        })
+    }
+
+    async fn play(
+        &self,
+        request: Request<proto::PlayRequest>,
+    ) -> Result<Response<proto::Empty>, Status> {
+        self.apply(ControlRequest::Play {
+            sequence: request.into_inner().sequence,
+        })
+    }
+
+    async fn set_zone(
+        &self,
+        request: Request<proto::SetZoneRequest>,
+    ) -> Result<Response<proto::Empty>, Status> {
+        let request = request.into_inner();
+        let zone = request
+            .zone
+            .ok_or_else(|| Status::invalid_argument("Missing zone."))?;
+
+        self.apply(ControlRequest::SetZone {
+            controller: zone.controller,
+            zone: zone.zone,
+            enabled: request.enabled,
+        })
+    }
+
+    async fn set_reduced_motion(
+        &self,
+        request: Request<proto::Switch>,
+    ) -> Result<Response<proto::Empty>, Status> {
+        self.apply(ControlRequest::ReducedMotion(request.into_inner().enabled))
+    }
+
+    async fn set_high_contrast(
+        &self,
+        request: Request<proto::Switch>,
+    ) -> Result<Response<proto::Empty>, Status> {
+        self.apply(ControlRequest::HighContrast(request.into_inner().enabled))
+    }
+
+    async fn switch_profile(
+        &self,
+        request: Request<proto::SwitchProfileRequest>,
+    ) -> Result<Response<proto::Empty>, Status> {
+        self.apply(ControlRequest::SwitchProfile {
+            name: request.into_inner().name,
+        })
+    }
+}
+
+fn metrics(samples: &Samples) -> proto::Metrics {
+    proto::Metrics {
+        cpu: samples.cpu,
+        gpu: samples.gpu,
+        memory: samples.memory,
+        disk: samples.disk,
+        power: samples.power,
+        cpu_temperature: samples.cpu_temperature,
+        gpu_temperature: samples.gpu_temperature,
+        thermal: samples.thermal,
+        battery: samples.battery,
+        mail: samples.mail,
+    }
+}
@@ -0,0 +1,16 @@
+/// Lowercase hexadecimal, as used by the HMACs of the companion apps and GitHub.
+pub fn encode(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// Bytes written in hexadecimal, in either case, `None` when `digits` isn't.
+pub fn decode(digits: &str) -> Option<Vec<u8>> {
+    (0..digits.len())
+        .step_by(2)
+        .map(|i| {
+            digits
+                .get(i..i + 2)
+                .and_then(|byte| u8::from_str_radix(byte, 16).ok())
+        })
+        .collect()
+}
@@ -0,0 +1,186 @@
+use crate::{
+    config::{Config, Metric},
+    control::{self, ControlRequest},
+    sampler::Samples,
+    sequence::duration,
+    units,
+};
+use anyhow::Result;
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::VecDeque,
+    sync::Mutex,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+/// Samples shown by the effects with more than the latest value, e.g. the histogram: a minute
+/// at the default sampling rate.
+const EFFECT_SAMPLES: usize = 128;
+/// Most samples kept whatever the retention, half an hour at the shortest sampling interval.
+const MAX_ENTRIES: usize = 36_000;
+
+/// How long the samples are kept, for the effects, the control API and the dashboard.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HistoryConfig {
+    #[serde(with = "duration")]
+    pub retention: Duration,
+}
+
+impl Default for HistoryConfig {
+    fn default() -> Self {
+        Self {
+            retention: Duration::from_secs(10 * 60),
+        }
+    }
+}
+
+/// A sample and its Unix time in milliseconds.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct Entry {
+    pub time: u64,
+    #[serde(flatten)]
+    pub samples: Samples,
+}
+
+/// Samples of the last `retention`, oldest first.
+#[derive(Debug, Default)]
+pub struct History {
+    entries: Mutex<VecDeque<Entry>>,
+}
+
+impl History {
+    /// Adds a sample, dropping the ones older than `retention`.
+    pub fn push(&self, time: SystemTime, samples: Samples, retention: Duration) {
+        let time = unix_millis(time);
+        let oldest = time.saturating_sub(retention.as_millis() as u64);
+
+        let mut entries = self.entries.lock().unwrap();
+        while entries
+            .front()
+            .is_some_and(|entry| entry.time < oldest || entries.len() >= MAX_ENTRIES)
+        {
+            entries.pop_front();
+        }
+        entries.push_back(Entry { time, samples });
+    }
+
+    /// Makes room for `entries` samples, up to the most kept, so that pushing them allocates
+    /// nothing.
+    pub fn reserve(&self, entries: usize) {
+        let mut kept = self.entries.lock().unwrap();
+        let additional = entries.min(MAX_ENTRIES).saturating_sub(kept.len());
+        kept.reserve(additional);
+    }
+
+    /// Replaces `values` with the latest values of a metric for the effects, 0 when it was
+    /// unavailable, reusing their memory.
+    pub fn values(&self, metric: Metric, values: &mut Vec<f32>) {
+        let entries = self.entries.lock().unwrap();
+
+        values.clear();
+        values.extend(
+            entries
+                .range(entries.len().saturating_sub(EFFECT_SAMPLES)..)
+                .map(|entry| entry.samples.get(metric).unwrap_or_default()),
+        );
+    }
+
+    /// Samples taken at `time` or later.
+    pub fn since(&self, time: SystemTime) -> Vec<Entry> {
+        let time = unix_millis(time);
+        let entries = self.entries.lock().unwrap();
+        let start = entries.partition_point(|entry| entry.time < time);
+
+        entries.range(start..).copied().collect()
+    }
+}
+
+pub fn unix_millis(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64
+}
+
+/// Prints the samples the running client took within `last` as CSV, in their units, a column
+/// being empty while its metric was unavailable.
+pub async fn print(config: &Config, last: Duration) -> Result<()> {
+    const METRICS: [Metric; 10] = [
+        Metric::Cpu,
+        Metric::Gpu,
+        Metric::CpuTemperature,
+        Metric::GpuTemperature,
+        Metric::Memory,
+        Metric::Disk,
+        Metric::Power,
+        Metric::Thermal,
+        Metric::Battery,
+        Metric::Mail,
+    ];
+
+    let answer = control::query(&ControlRequest::History { last }).await?;
+    let entries = serde_json::from_str::<Vec<Entry>>(&answer)?;
+
+    println!("time,cpu,gpu,cpu_temperature,gpu_temperature,memory,disk,power,thermal,battery,mail");
+    for entry in entries {
+        let time = DateTime::from_timestamp_millis(entry.time as i64)
+            .unwrap_or_default()
+            .with_timezone(&Local);
+
+        let mut line = time.format("%Y-%m-%d %H:%M:%S%.3f").to_string();
+        for metric in METRICS {
+            line.push(',');
+            if let Some(quantity) = entry
+                .samples
+                .get(metric)
+                .and_then(|level| units::quantity(metric, level, config))
+            {
+                line += &quantity.value.to_string();
+            }
+        }
+
+        println!("{}", line);
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sampler::Brightness;
+
+    #[test]
+    fn keeps_the_samples_of_the_retention() {
+        let history = History::default();
+        let retention = Duration::from_secs(60);
+        for second in 0..300 {
+            let samples = Samples {
+                cpu: second as f32 / 300.0,
+                gpu: None,
+                cpu_temperature: None,
+                gpu_temperature: None,
+                memory: None,
+                disk: None,
+                power: None,
+                thermal: None,
+                battery: None,
+                mail: None,
+                brightness: Brightness::default(),
+            };
+            history.push(UNIX_EPOCH + Duration::from_secs(second), samples, retention);
+        }
+
+        let entries = history.since(UNIX_EPOCH);
+        assert_eq!(entries.len(), 61);
+        assert_eq!(entries[0].time, 239_000);
+        assert_eq!(
+            history.since(UNIX_EPOCH + Duration::from_secs(290)).len(),
+            10
+        );
+        let mut values = vec![1.0; 200];
+        history.values(Metric::Gpu, &mut values);
+        assert_eq!(values, vec![0.0; 61]);
+    }
+}
@@ -0,0 +1,491 @@
+use crate::{
+    companion,
+    config::Config,
+    control::ControlState,
+    fleet::HostReport,
+    github, units,
+    websocket::{self, Frame},
+};
+use anyhow::{anyhow, bail, Result};
+use log::*;
+use serde::{Deserialize, Serialize};
+use std::{
+    net::{Ipv4Addr, SocketAddr},
+    sync::Arc,
+    time::UNIX_EPOCH,
+};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader},
+    net::{TcpListener, TcpStream},
+    sync::{broadcast::error::RecvError, mpsc},
+};
+
+/// Longest request line or header accepted.
+const MAX_LINE: usize = 8 * 1024;
+const MAX_HEADERS: usize = 64;
+/// Largest request body accepted, GitHub pushes of many commits included.
+const MAX_BODY: usize = 5 * 1024 * 1024;
+
+/// Page showing the LEDs and metrics live, for OBS browser sources.
+const OVERLAY_PAGE: &str = include_str!("../assets/overlay.html");
+/// Grid of the machines pushing their metrics to this client.
+const DASHBOARD_PAGE: &str = include_str!("../assets/dashboard.html");
+
+/// Local web server for dashboards and overlays.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HttpConfig {
+    /// Beyond loopback, every request needs the bearer token derived from the companion token,
+    /// see `companion::bearer`.
+    #[serde(default = "HttpConfig::default_address")]
+    pub address: SocketAddr,
+}
+
+impl HttpConfig {
+    fn default_address() -> SocketAddr {
+        (Ipv4Addr::LOCALHOST, 6745).into()
+    }
+}
+
+/// The start of an HTTP/1.1 request, the body is left to the handlers.
+#[derive(Debug)]
+struct Request {
+    method: String,
+    /// Without the query string.
+    path: String,
+    query: String,
+    headers: Vec<(String, String)>,
+}
+
+impl Request {
+    fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(header, _)| header.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// From the `Authorization` header, or the `token` query parameter of the pages and
+    /// WebSockets opened by browsers, which can't set headers.
+    fn bearer(&self) -> Option<&str> {
+        self.header("Authorization")
+            .and_then(|authorization| authorization.strip_prefix("Bearer "))
+            .or_else(|| {
+                self.query
+                    .split('&')
+                    .find_map(|parameter| parameter.strip_prefix("token="))
+            })
+    }
+}
+
+/// Serves the HTTP endpoints of a running client, until the process exits.
+pub async fn serve(address: SocketAddr, state: Arc<ControlState>) -> Result<()> {
+    let listener = TcpListener::bind(address).await?;
+    info!("HTTP server listening on {}.", address);
+    let public = !address.ip().is_loopback();
+
+    loop {
+        let (stream, address) = listener.accept().await?;
+        let state = state.clone();
+
+        tokio::spawn(async move {
+            if let Err(e) = handle_connection(stream, state, public).await {
+                debug!("HTTP connection from {} failed: {}", address, e);
+            }
+        });
+    }
+}
+
+/// `public` when other machines can reach the server, whose requests must then be
+/// authenticated.
+async fn handle_connection(
+    stream: TcpStream,
+    state: Arc<ControlState>,
+    public: bool,
+) -> Result<()> {
+    let mut stream = BufReader::new(stream);
+    let request = read_request(&mut stream).await?;
+    let config = state.config();
+
+    // The companion apps and GitHub sign their requests instead.
+    if public && !matches!(request.path.as_str(), "/companion" | "/github") {
+        let verified = match &config.companion {
+            Some(companion) => companion::verify_bearer(&companion.token, request.bearer()),
+            None => Err(anyhow!("No companion token to check it against.")),
+        };
+
+        if let Err(e) = verified {
+            debug!("Rejected a request to {}: {}", request.path, e);
+
+            return respond(
+                stream.get_mut(),
+                "401 Unauthorized",
+                "text/plain",
+                b"Wrong token\n",
+            )
+            .await;
+        }
+    }
+
+    match (request.method.as_str(), request.path.as_str()) {
+        ("GET", "/events") => {
+            // WebSocket clients wait for the handshake before sending anything, so nothing is
+            // left in the buffer.
+            stream_events(stream.into_inner(), &request, &state).await
+        }
+        ("GET", "/metrics") => {
+            let mut metrics = state
+                .runtime
+                .borrow()
+                .map(|runtime| runtime.to_prometheus())
+                .unwrap_or_default();
+            metrics += &state.power.to_prometheus();
+            if let Some(samples) = *state.samples.borrow() {
+                metrics += &units::to_prometheus(&samples.quantities(&config).collect::<Vec<_>>());
+            }
+
+            respond(
+                stream.get_mut(),
+                "200 OK",
+                "text/plain; version=0.0.4",
+                metrics.as_bytes(),
+            )
+            .await
+        }
+        ("GET", "/overlay") => {
+            respond(
+                stream.get_mut(),
+                "200 OK",
+                "text/html; charset=utf-8",
+                OVERLAY_PAGE.as_bytes(),
+            )
+            .await
+        }
+        ("GET", "/dashboard") => {
+            respond(
+                stream.get_mut(),
+                "200 OK",
+                "text/html; charset=utf-8",
+                DASHBOARD_PAGE.as_bytes(),
+            )
+            .await
+        }
+        ("GET", "/hosts") => {
+            // This machine first, then the ones pushing to it.
+            let mut reports = state.fleet.reports();
+            if let Some(local) = state.fleet.local_report(&state, &config) {
+                reports.retain(|report| report.host != local.host);
+                reports.insert(0, local);
+            }
+
+            respond(
+                stream.get_mut(),
+                "200 OK",
+                "application/json",
+                &serde_json::to_vec(&reports)?,
+            )
+            .await
+        }
+        ("GET", "/history") => {
+            respond(
+                stream.get_mut(),
+                "200 OK",
+                "application/json",
+                &serde_json::to_vec(&state.history.since(UNIX_EPOCH))?,
+            )
+            .await
+        }
+        ("POST", "/push") if config.companion.is_some() => {
+            push_report(&mut stream, &request, &state, &config).await
+        }
+        ("GET", "/companion") if config.companion.is_some() => {
+            companion_session(stream.into_inner(), &request, &state, &config).await
+        }
+        ("POST", "/github") if config.github.is_some() => {
+            let body = read_body(&mut stream, &request).await?;
+
+            github_webhook(stream.get_mut(), &request, &body, &state, &config).await
+        }
+        _ => {
+            respond(
+                stream.get_mut(),
+                "404 Not Found",
+                "text/plain",
+                b"Not found\n",
+            )
+            .await
+        }
+    }
+}
+
+async fn read_request(stream: &mut BufReader<TcpStream>) -> Result<Request> {
+    let request_line = read_line(stream).await?;
+    let mut words = request_line.split_whitespace();
+    let (Some(method), Some(target)) = (words.next(), words.next()) else {
+        bail!("Invalid request line: {:?}", request_line);
+    };
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+
+    let mut headers = vec![];
+    loop {
+        let line = read_line(stream).await?;
+        if line.is_empty() {
+            break;
+        }
+
+        if headers.len() == MAX_HEADERS {
+            bail!("Too many headers.");
+        }
+
+        let (name, value) = line
+            .split_once(':')
+            .ok_or_else(|| anyhow!("Invalid header: {:?}", line))?;
+        headers.push((name.trim().to_owned(), value.trim().to_owned()));
+    }
+
+    Ok(Request {
+        method: method.to_owned(),
+        path: path.to_owned(),
+        query: query.to_owned(),
+        headers,
+    })
+}
+
+/// Reads a line without its line ending.
+async fn read_line(stream: &mut BufReader<TcpStream>) -> Result<String> {
+    let mut line = vec![];
+    let read = (&mut *stream)
+        .take(MAX_LINE as u64)
+        .read_until(b'\n', &mut line)
+        .await?;
+    if read == 0 {
+        bail!("Connection closed.");
+    }
+    if !line.ends_with(b"\n") {
+        bail!("Line too long.");
+    }
+
+    Ok(String::from_utf8(line)?.trim_end().to_owned())
+}
+
+async fn read_body(stream: &mut BufReader<TcpStream>, request: &Request) -> Result<Vec<u8>> {
+    let length = request
+        .header("Content-Length")
+        .ok_or_else(|| anyhow!("Missing Content-Length."))?
+        .parse::<usize>()?;
+    if length > MAX_BODY {
+        bail!("Body too large: {} bytes", length);
+    }
+
+    let mut body = vec![0; length];
+    stream.read_exact(&mut body).await?;
+
+    Ok(body)
+}
+
+async fn respond(
+    stream: &mut (impl AsyncWrite + Unpin),
+    status: &str,
+    content_type: &str,
+    body: &[u8],
+) -> Result<()> {
+    let head = format!(
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        status,
+        content_type,
+        body.len()
+    );
+    stream.write_all(head.as_bytes()).await?;
+    stream.write_all(body).await?;
+
+    Ok(())
+}
+
+/// Records the report of a machine pushing to this client, authenticated with the companion
+/// token.
+async fn push_report(
+    stream: &mut BufReader<TcpStream>,
+    request: &Request,
+    state: &ControlState,
+    config: &Config,
+) -> Result<()> {
+    let Some(companion) = &config.companion else {
+        return respond(
+            stream.get_mut(),
+            "404 Not Found",
+            "text/plain",
+            b"Not found\n",
+        )
+        .await;
+    };
+
+    // Checked before reading the body, which is up to `MAX_BODY` long.
+    if let Err(e) = companion::verify_bearer(&companion.token, request.bearer()) {
+        warn!("Rejected a pushed report: {}", e);
+
+        return respond(
+            stream.get_mut(),
+            "401 Unauthorized",
+            "text/plain",
+            b"Wrong token\n",
+        )
+        .await;
+    }
+
+    let body = read_body(stream, request).await?;
+    match serde_json::from_slice::<HostReport>(&body) {
+        Ok(report) => {
+            state.fleet.record(report);
+
+            respond(stream.get_mut(), "200 OK", "text/plain", b"Recorded\n").await
+        }
+        Err(e) => {
+            let body = format!("{}\n", e);
+
+            respond(
+                stream.get_mut(),
+                "400 Bad Request",
+                "text/plain",
+                body.as_bytes(),
+            )
+            .await
+        }
+    }
+}
+
+async fn github_webhook(
+    stream: &mut TcpStream,
+    request: &Request,
+    body: &[u8],
+    state: &ControlState,
+    config: &Config,
+) -> Result<()> {
+    let Some(github) = &config.github else {
+        return respond(stream, "404 Not Found", "text/plain", b"Not found\n").await;
+    };
+
+    if let Err(e) = github::verify(&github.secret, request.header("X-Hub-Signature-256"), body) {
+        warn!("Rejected GitHub delivery: {}", e);
+
+        return respond(
+            stream,
+            "401 Unauthorized",
+            "text/plain",
+            b"Wrong signature\n",
+        )
+        .await;
+    }
+
+    let event = request.header("X-GitHub-Event").unwrap_or_default();
+    match github::handle(github, event, body, &state.notifications) {
+        Ok(matched) => {
+            let body = format!("{} rules matched\n", matched);
+
+            respond(stream, "200 OK", "text/plain", body.as_bytes()).await
+        }
+        Err(e) => {
+            let body = format!("{}\n", e);
+
+            respond(stream, "400 Bad Request", "text/plain", body.as_bytes()).await
+        }
+    }
+}
+
+/// Answers a WebSocket handshake, `None` when the request isn't one, after telling the client.
+async fn upgrade(mut stream: TcpStream, request: &Request) -> Result<Option<TcpStream>> {
+    let key = match request.header("Sec-WebSocket-Key") {
+        Some(key)
+            if request
+                .header("Upgrade")
+                .is_some_and(|upgrade| upgrade.eq_ignore_ascii_case("websocket")) =>
+        {
+            key
+        }
+        _ => {
+            respond(
+                &mut stream,
+                "400 Bad Request",
+                "text/plain",
+                b"Expected a WebSocket handshake\n",
+            )
+            .await?;
+
+            return Ok(None);
+        }
+    };
+
+    let handshake = format!(
+        "HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Accept: {}\r\n\r\n",
+        websocket::accept_key(key)
+    );
+    stream.write_all(handshake.as_bytes()).await?;
+
+    Ok(Some(stream))
+}
+
+async fn companion_session(
+    mut stream: TcpStream,
+    request: &Request,
+    state: &ControlState,
+    config: &Config,
+) -> Result<()> {
+    let Some(companion) = &config.companion else {
+        return respond(&mut stream, "404 Not Found", "text/plain", b"Not found\n").await;
+    };
+    let Some(stream) = upgrade(stream, request).await? else {
+        return Ok(());
+    };
+
+    companion::session(stream, state, &companion.token).await
+}
+
+/// Streams the events of the client as JSON text messages, until the client goes away.
+async fn stream_events(stream: TcpStream, request: &Request, state: &ControlState) -> Result<()> {
+    let mut events = state.events.subscribe();
+    let Some(stream) = upgrade(stream, request).await? else {
+        return Ok(());
+    };
+
+    let (mut reader, mut writer) = stream.into_split();
+
+    // Frames from the client are read on the side, reading isn't cancel safe.
+    let (frames_sender, mut frames) = mpsc::channel(4);
+    let reader = tokio::spawn(async move {
+        while let Ok(frame) = websocket::read_frame(&mut reader).await {
+            if frames_sender.send(frame).await.is_err() {
+                break;
+            }
+        }
+    });
+
+    let result = async {
+        loop {
+            tokio::select! {
+                event = events.recv() => match event {
+                    Ok(event) => {
+                        websocket::write_frame(&mut writer, websocket::TEXT, event.to_json().as_bytes()).await?
+                    }
+                    Err(RecvError::Lagged(missed)) => debug!("Event stream missed {} events.", missed),
+                    Err(RecvError::Closed) => break,
+                },
+                frame = frames.recv() => match frame {
+                    Some(Frame { opcode: websocket::PING, payload }) => {
+                        websocket::write_frame(&mut writer, websocket::PONG, &payload).await?
+                    }
+                    Some(Frame { opcode: websocket::CLOSE, .. }) | None => {
+                        websocket::write_frame(&mut writer, websocket::CLOSE, &[]).await?;
+
+                        break;
+                    }
+                    Some(_) => {}
+                },
+            }
+        }
+
+        Ok(())
+    }
+    .await;
+
+    reader.abort();
+
+    result
+}
@@ -0,0 +1,85 @@
+use fluent_bundle::{concurrent::FluentBundle, FluentArgs, FluentResource};
+use std::sync::OnceLock;
+use unic_langid::LanguageIdentifier;
+
+/// Message catalogs shipped with the client, English being the fallback for missing messages.
+const CATALOGS: &[(&str, &str)] = &[
+    ("en", include_str!("../locales/en.ftl")),
+    ("ja", include_str!("../locales/ja.ftl")),
+];
+
+static BUNDLES: OnceLock<Vec<FluentBundle<FluentResource>>> = OnceLock::new();
+
+/// Picks the language of the messages, the system one when not given.
+///
+/// Must be called before the first message is looked up, later calls are ignored.
+pub fn init(language: Option<&str>) {
+    let language = language
+        .map(str::to_owned)
+        .or_else(sys_locale::get_locale)
+        .unwrap_or_default();
+
+    BUNDLES.get_or_init(|| load_bundles(&language));
+}
+
+/// Formats the message `id` in the current language, see the `tr!` macro.
+pub fn message(id: &str, args: Option<&FluentArgs>) -> String {
+    let bundles =
+        BUNDLES.get_or_init(|| load_bundles(&sys_locale::get_locale().unwrap_or_default()));
+
+    for bundle in bundles {
+        let Some(pattern) = bundle.get_message(id).and_then(|message| message.value()) else {
+            continue;
+        };
+
+        let mut errors = vec![];
+        return bundle
+            .format_pattern(pattern, args, &mut errors)
+            .into_owned();
+    }
+
+    id.to_owned()
+}
+
+fn load_bundles(language: &str) -> Vec<FluentBundle<FluentResource>> {
+    // POSIX locales look like `ja_JP.UTF-8`, only the language matters here.
+    let language = language
+        .split(['.', '@'])
+        .next()
+        .unwrap_or_default()
+        .replace('_', "-")
+        .parse::<LanguageIdentifier>()
+        .unwrap_or_default();
+
+    CATALOGS
+        .iter()
+        .filter(|(catalog, _)| *catalog == language.language.as_str())
+        .chain(CATALOGS.iter().filter(|(catalog, _)| *catalog == "en"))
+        .map(|(catalog, source)| {
+            let resource = FluentResource::try_new(source.to_string())
+                .unwrap_or_else(|_| panic!("Invalid message catalog: {}", catalog));
+
+            let mut bundle = FluentBundle::new_concurrent(vec![catalog.parse().unwrap()]);
+            // Bidi isolation marks would end up verbatim in terminals.
+            bundle.set_use_isolating(false);
+            bundle.add_resource_overriding(resource);
+
+            bundle
+        })
+        .collect()
+}
+
+/// Formats a message from the catalog: `tr!("status-power", "watts" => watts)`.
+macro_rules! tr {
+    ($id:literal) => {
+        $crate::i18n::message($id, None)
+    };
+    ($id:literal, $($name:literal => $value:expr),+ $(,)?) => {{
+        let mut args = fluent_bundle::FluentArgs::new();
+        $(args.set($name, $value);)+
+
+        $crate::i18n::message($id, Some(&args))
+    }};
+}
+
+pub(crate) use tr;
@@ -0,0 +1,202 @@
+use crate::{
+    config::{format_color, Format, Metric, Palette},
+    connection::{ConnectionOptions, Connector},
+    i18n::tr,
+    mapping::ControllerInfo,
+    migration::CURRENT_VERSION,
+    paths,
+    wizard::suggested_metric,
+};
+use anyhow::{bail, Context, Result};
+use std::path::Path;
+
+/// Writes a starter configuration with an entry per controller OpenRGB reports and their zones
+/// commented out, so the names don't have to be guessed. An existing file is left untouched.
+pub async fn init_config(path: &Path, connection: &ConnectionOptions) -> Result<()> {
+    if path.exists() {
+        bail!("{:?} already exists, remove it first.", path);
+    }
+
+    let client = Connector::new(connection)?.connect().await?;
+
+    let mut controllers = vec![];
+    for controller_id in 0..client.get_controller_count().await? {
+        let controller = client.get_controller(controller_id).await?;
+        controllers.push((
+            ControllerInfo::from(&controller),
+            suggested_metric(&controller),
+        ));
+    }
+
+    // YAML and JSON files get the same settings, without the comments.
+    let text = match Format::of(path) {
+        Format::Toml => starter_config(&controllers),
+        format => format.write(&Format::Toml.parse(&starter_config(&controllers))?)?,
+    };
+
+    paths::create_parent(path)?;
+    std::fs::write(path, text).with_context(|| format!("Cannot write {:?}", path))?;
+
+    println!(
+        "{}",
+        tr!(
+            "init-config-saved",
+            "controllers" => controllers.len(),
+            "path" => path.display().to_string()
+        )
+    );
+
+    Ok(())
+}
+
+/// Configuration mapping each controller to its metric, with comments on what to change.
+pub fn starter_config(controllers: &[(ControllerInfo, Metric)]) -> String {
+    let palette = Palette::default();
+
+    let mut text = String::new();
+    text += "# Starter configuration written by `open_rgb_client --init-config`, the\n";
+    text += "# README lists every setting. Check it against OpenRGB after editing it with\n";
+    text += "# `open_rgb_client --check-config`.\n\n";
+    text += "# Schema of this file, migrated automatically by newer clients.\n";
+    text += &format!("version = {}\n\n", CURRENT_VERSION);
+    text += "# Colors the LEDs blend between, from idle to full load.\n";
+    text += "[palette]\n";
+    text += &format!("idle = \"{}\"\n", format_color(&palette.idle));
+    text += &format!("load = \"{}\"\n", format_color(&palette.load));
+
+    text += "\n# One entry per controller. `metric` is cpu, gpu, cpu_temperature,\n";
+    text += "# gpu_temperature, memory, disk, power, thermal, battery, mail, or none to\n";
+    text += "# leave the controller to OpenRGB.\n";
+    for (controller, metric) in controllers {
+        text += &format!("\n# {} LEDs\n", controller.leds);
+        text += "[[devices]]\n";
+        text += &format!("name = {}\n", quoted(&controller.name));
+        text += &format!("metric = \"{}\"\n", metric);
+
+        if controller.zones.is_empty() {
+            continue;
+        }
+
+        text += "# Zones can show another metric, or be left alone with `disabled = true`:\n";
+        text += "# zones = [\n";
+        for zone in &controller.zones {
+            text += &format!(
+                "#     {{ name = {}, metric = \"{}\" }}, # {} LEDs\n",
+                quoted(&zone.name),
+                metric,
+                zone.leds
+            );
+        }
+        text += "# ]\n";
+    }
+
+    text
+}
+
+/// Writes a configuration reproducing the layouts the client used to hardcode for the
+/// original author's machine, so it can move to the configuration without any visual change.
+/// An existing file is left untouched.
+pub fn migrate_builtin(path: &Path) -> Result<()> {
+    if path.exists() {
+        bail!("{:?} already exists, remove it first.", path);
+    }
+
+    let text = match Format::of(path) {
+        Format::Toml => builtin_config(),
+        format => format.write(&Format::Toml.parse(&builtin_config())?)?,
+    };
+
+    paths::create_parent(path)?;
+    std::fs::write(path, text).with_context(|| format!("Cannot write {:?}", path))?;
+
+    println!(
+        "{}",
+        tr!("builtin-config-saved", "path" => path.display().to_string())
+    );
+
+    Ok(())
+}
+
+/// Configuration spelling out the hardcoded mapping of the first releases: every controller
+/// showing the CPU as a block, but the RAM draining from the top, the GPU showing its own
+/// load and the top strip of the motherboard filling up.
+pub fn builtin_config() -> String {
+    let palette = Palette::default();
+
+    let mut text = String::new();
+    text += "# Layouts the client used to hardcode, written by\n";
+    text += "# `open_rgb_client migrate-builtin`. The README lists every setting.\n\n";
+    text += &format!("version = {}\n", CURRENT_VERSION);
+    text += "default_metric = \"cpu\"\n\n";
+    text += "[palette]\n";
+    text += &format!("idle = \"{}\"\n", format_color(&palette.idle));
+    text += &format!("load = \"{}\"\n", format_color(&palette.load));
+
+    text += "\n[[devices]]\n";
+    text += "name = \"ENE DRAM\"\n";
+    text += "metric = \"cpu\"\n";
+    text += "effect = { type = \"reversed-gradient\" }\n";
+
+    text += "\n[[devices]]\n";
+    text += "name = \"EVGA GeForce RTX 3080Ti FTW3 Ultra\"\n";
+    text += "metric = \"gpu\"\n";
+    text += "effect = { type = \"block\" }\n";
+
+    text += "\n[[devices]]\n";
+    text += "name = \"X570 AORUS ELITE\"\n";
+    text += "metric = \"cpu\"\n";
+    text += "zones = [\n";
+    text += "    { name = \"D_LED1 Bottom\", effect = { type = \"block\" } },\n";
+    text += "    { name = \"D_LED2 Top\", effect = { type = \"gradient\" } },\n";
+    text += "    { name = \"Motherboard\", effect = { type = \"block\" } },\n";
+    text += "]\n";
+
+    text += "\n# Every other controller.\n";
+    text += "[[devices]]\n";
+    text += "name = \"*\"\n";
+    text += "effect = { type = \"block\" }\n";
+
+    text
+}
+
+/// A TOML string, names being free to contain quotes and backslashes.
+fn quoted(name: &str) -> String {
+    toml::Value::String(name.to_owned()).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        config::Config,
+        mapping::{controller, zoned_controller},
+    };
+
+    #[test]
+    fn starter_config_loads_with_a_device_per_controller() {
+        let controllers = [
+            (
+                zoned_controller("X570 AORUS ELITE", &[("D_LED1 \"Bottom\"", 8)]),
+                Metric::Cpu,
+            ),
+            (controller("NVIDIA GeForce RTX 3080", ""), Metric::Gpu),
+        ];
+
+        let config: Config = toml::from_str(&starter_config(&controllers)).unwrap();
+
+        assert_eq!(config.devices.len(), 2);
+        assert_eq!(config.devices[0].name, "X570 AORUS ELITE");
+        assert_eq!(config.devices[1].metric, Some(Metric::Gpu));
+    }
+
+    #[test]
+    fn builtin_config_spells_out_the_hardcoded_layouts() {
+        let config: Config = toml::from_str(&builtin_config()).unwrap();
+
+        assert_eq!(config.default_metric, Some(Metric::Cpu));
+        assert_eq!(config.devices.len(), 4);
+        assert_eq!(config.devices[1].metric, Some(Metric::Gpu));
+        assert_eq!(config.devices[2].zones.len(), 3);
+        assert_eq!(config.devices[3].name, "*");
+    }
+}
@@ -0,0 +1,91 @@
+use std::{
+    collections::BTreeMap,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+    time::Duration,
+};
+
+/// Frames between two measures, each costing a round trip to the server per controller.
+const MEASURE_EVERY: u64 = 10;
+/// Weight of a new measure in the latency of a controller, smoothing out the jitter.
+const MEASURE_WEIGHT: f64 = 0.2;
+/// Longest lead of the animations, so a stalled server doesn't push them far ahead.
+const MAX_LEAD: Duration = Duration::from_millis(500);
+
+/// Time each controller takes to show a frame once it is sent, from sending its colors until
+/// the server answers the next request, which it does once it applied them.
+#[derive(Debug, Default)]
+pub struct Latencies {
+    frames: AtomicU64,
+    /// By controller key.
+    controllers: Mutex<BTreeMap<String, Duration>>,
+}
+
+impl Latencies {
+    /// Whether the latencies are measured during the frame starting, once every few frames.
+    pub fn measuring(&self) -> bool {
+        self.frames
+            .fetch_add(1, Ordering::Relaxed)
+            .is_multiple_of(MEASURE_EVERY)
+    }
+
+    pub fn record(&self, controller: &str, latency: Duration) {
+        let mut controllers = self.controllers.lock().unwrap();
+        match controllers.get_mut(controller) {
+            Some(average) => {
+                *average = average.mul_f64(1.0 - MEASURE_WEIGHT) + latency.mul_f64(MEASURE_WEIGHT)
+            }
+            None => {
+                controllers.insert(controller.to_owned(), latency);
+            }
+        }
+    }
+
+    /// Time the animations of a controller are rendered ahead by, so they show in phase with
+    /// the ones of faster controllers: a chase going from one to the other looks continuous.
+    pub fn lead(&self, controller: &str) -> Duration {
+        self.controllers
+            .lock()
+            .unwrap()
+            .get(controller)
+            .map_or(Duration::ZERO, |latency| (*latency).min(MAX_LEAD))
+    }
+
+    /// Latencies in milliseconds, by controller key.
+    pub fn millis(&self) -> BTreeMap<String, f64> {
+        self.controllers
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(controller, latency)| (controller.clone(), latency.as_secs_f64() * 1000.0))
+            .collect()
+    }
+
+    /// Forgets the latencies, another server taking its own time.
+    pub fn clear(&self) {
+        self.controllers.lock().unwrap().clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn slow_controllers_are_rendered_ahead() {
+        let latencies = Latencies::default();
+        assert!(latencies.measuring());
+        assert!(!latencies.measuring());
+        assert_eq!(latencies.lead("Fan"), Duration::ZERO);
+
+        latencies.record("Fan", Duration::from_millis(20));
+        latencies.record("Fan", Duration::from_millis(70));
+        assert_eq!(latencies.lead("Fan"), Duration::from_millis(30));
+
+        latencies.record("Strip", Duration::from_secs(3));
+        assert_eq!(latencies.lead("Strip"), MAX_LEAD);
+        assert_eq!(latencies.millis()["Fan"], 30.0);
+    }
+}
@@ -0,0 +1,299 @@
+use crate::{
+    config::{name_matches, Config},
+    connection::{ConnectionOptions, Connector},
+    i18n::tr,
+    mapping::ControllerInfo,
+    paths,
+};
+use anyhow::{Context, Result};
+use log::*;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// Physical positions of the LEDs in the case, for the rainbow and the spatial effects to move
+/// across it rather than along each controller.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LayoutConfig {
+    /// Width of the case, in the unit of the positions: the rainbow goes across from 0 to it.
+    pub width: f32,
+    /// Height of the case, from 0 at the top, 0 leaving every LED on the same row.
+    #[serde(default)]
+    pub height: f32,
+    #[serde(default)]
+    pub zones: Vec<LayoutZone>,
+}
+
+/// LEDs of a zone, or of a whole controller, lined up from a position.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LayoutZone {
+    /// Controller name, as reported by OpenRGB, pattern or alias.
+    pub controller: String,
+    /// Zone name, as reported by OpenRGB, the whole controller when not set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub zone: Option<String>,
+    /// Horizontal position of the first LED.
+    pub x: f32,
+    /// Vertical position of the first LED, from the top.
+    #[serde(default)]
+    pub y: f32,
+    #[serde(default)]
+    pub direction: LayoutDirection,
+    /// Distance between two LEDs.
+    #[serde(default = "LayoutZone::default_spacing")]
+    pub spacing: f32,
+}
+
+impl LayoutZone {
+    fn default_spacing() -> f32 {
+        1.0
+    }
+
+    /// Position of each LED, across the case from 0 to 1.
+    fn positions<'a>(
+        &'a self,
+        leds: usize,
+        layout: &'a LayoutConfig,
+    ) -> impl Iterator<Item = Point> + 'a {
+        let (step_x, step_y) = match self.direction {
+            LayoutDirection::Right => (self.spacing, 0.0),
+            LayoutDirection::Left => (-self.spacing, 0.0),
+            LayoutDirection::Down => (0.0, self.spacing),
+            LayoutDirection::Up => (0.0, -self.spacing),
+        };
+        let scale = |value: f32, size: f32| if size > 0.0 { value / size } else { 0.0 };
+
+        (0..leds).map(move |index| Point {
+            x: scale(self.x + index as f32 * step_x, layout.width),
+            y: scale(self.y + index as f32 * step_y, layout.height),
+        })
+    }
+}
+
+/// Position of a LED in the case, from 0 to 1 across its width and down its height.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Point {
+    pub x: f32,
+    pub y: f32,
+}
+
+/// Way the LEDs of a zone go from the first one.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LayoutDirection {
+    #[default]
+    Right,
+    Left,
+    Up,
+    Down,
+}
+
+/// Position of every LED of a controller in the case, `None` unless the layout places all of
+/// them.
+pub fn positions(config: &Config, controller: &ControllerInfo) -> Option<Vec<Point>> {
+    let layout = config.layout.as_ref()?;
+    let entries = layout
+        .zones
+        .iter()
+        .filter(|entry| config.matches(&entry.controller, &controller.name, &controller.location))
+        .collect::<Vec<_>>();
+
+    if let Some(entry) = entries.iter().find(|entry| entry.zone.is_none()) {
+        return Some(entry.positions(controller.leds, layout).collect());
+    }
+    if controller.zones.is_empty() {
+        return None;
+    }
+
+    let mut positions = Vec::with_capacity(controller.leds);
+    for zone in &controller.zones {
+        let entry = entries.iter().find(|entry| {
+            entry
+                .zone
+                .as_deref()
+                .is_some_and(|pattern| name_matches(pattern, &zone.name))
+        })?;
+        positions.extend(entry.positions(zone.leds, layout));
+    }
+
+    Some(positions)
+}
+
+/// Virtual controller saved by the Visual Map plugin of OpenRGB.
+#[derive(Debug, Deserialize)]
+struct VisualMap {
+    #[serde(default)]
+    ctrl_zones: Vec<MapZone>,
+}
+
+#[derive(Debug, Deserialize)]
+struct MapZone {
+    controller: MapController,
+    zone_idx: usize,
+    #[serde(default)]
+    settings: MapSettings,
+}
+
+#[derive(Debug, Deserialize)]
+struct MapController {
+    name: String,
+    #[serde(default)]
+    location: String,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct MapSettings {
+    x: f32,
+    y: f32,
+    led_spacing: f32,
+    reverse: bool,
+    /// 0 for a horizontal line, 1 for a vertical one.
+    shape: i32,
+}
+
+/// Turns a Visual Map export into a layout, written to `output`, e.g. a file of `devices.d`.
+/// The zones are named and measured by the server, the map only having their indices.
+pub async fn import(file: &Path, output: &Path, connection: &ConnectionOptions) -> Result<()> {
+    let map = serde_json::from_str::<VisualMap>(
+        &std::fs::read_to_string(file).with_context(|| format!("Cannot read {:?}", file))?,
+    )
+    .with_context(|| format!("Invalid Visual Map export {:?}", file))?;
+
+    let client = Connector::new(connection)?.connect().await?;
+    let mut controllers = vec![];
+    for controller_id in 0..client.get_controller_count().await? {
+        controllers.push(client.get_controller(controller_id).await?);
+    }
+
+    let mut layout = LayoutConfig {
+        width: 0.0,
+        height: 0.0,
+        zones: vec![],
+    };
+    for map_zone in &map.ctrl_zones {
+        let found = controllers.iter().find(|controller| {
+            controller.name == map_zone.controller.name
+                && controller.location == map_zone.controller.location
+        });
+        let Some((controller, zone)) = found
+            .and_then(|controller| Some((controller, controller.zones.get(map_zone.zone_idx)?)))
+        else {
+            warn!(
+                "{} ({}) zone {} isn't on the server, skipped.",
+                map_zone.controller.name, map_zone.controller.location, map_zone.zone_idx
+            );
+
+            continue;
+        };
+
+        let settings = &map_zone.settings;
+        let spacing = settings.led_spacing + 1.0;
+        let direction = match (settings.shape, settings.reverse) {
+            (1, false) => LayoutDirection::Down,
+            (1, true) => LayoutDirection::Up,
+            (_, false) => LayoutDirection::Right,
+            (_, true) => LayoutDirection::Left,
+        };
+        // Reversed zones go from their last LED, at the far end.
+        let length = zone.leds_count.saturating_sub(1) as f32 * spacing;
+        let (x, y) = match direction {
+            LayoutDirection::Left => (settings.x + length, settings.y),
+            LayoutDirection::Up => (settings.x, settings.y + length),
+            _ => (settings.x, settings.y),
+        };
+        let (right, bottom) = match direction {
+            LayoutDirection::Right | LayoutDirection::Left => (settings.x + length, settings.y),
+            LayoutDirection::Up | LayoutDirection::Down => (settings.x, settings.y + length),
+        };
+        layout.width = layout.width.max(right + 1.0);
+        layout.height = layout.height.max(bottom + 1.0);
+
+        layout.zones.push(LayoutZone {
+            controller: controller.name.clone(),
+            zone: Some(zone.name.clone()),
+            x,
+            y,
+            direction,
+            spacing,
+        });
+    }
+
+    #[derive(Serialize)]
+    struct Imported<'a> {
+        layout: &'a LayoutConfig,
+    }
+
+    let mut text = format!(
+        "# Imported from {} by `open_rgb_client import-layout`.\n\n",
+        file.display()
+    );
+    text += &toml::to_string(&Imported { layout: &layout })?;
+
+    paths::create_parent(output)?;
+    std::fs::write(output, text).with_context(|| format!("Cannot write {:?}", output))?;
+
+    println!(
+        "{}",
+        tr!(
+            "layout-imported",
+            "zones" => layout.zones.len(),
+            "path" => output.display().to_string()
+        )
+    );
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mapping::{zoned_controller, ZoneInfo};
+
+    #[test]
+    fn zones_are_placed_across_the_case() {
+        let config: Config = toml::from_str(
+            r#"
+            [layout]
+            width = 10
+            height = 4
+
+            [[layout.zones]]
+            controller = "X570 AORUS ELITE"
+            zone = "D_LED1*"
+            x = 8
+            direction = "left"
+            spacing = 2
+
+            [[layout.zones]]
+            controller = "X570 AORUS ELITE"
+            zone = "D_LED2 Top"
+            x = 1
+            y = 1
+            direction = "down"
+            "#,
+        )
+        .unwrap();
+        let mut controller = zoned_controller(
+            "X570 AORUS ELITE",
+            &[("D_LED1 Bottom", 3), ("D_LED2 Top", 2)],
+        );
+
+        let point = |x, y| Point { x, y };
+        assert_eq!(
+            positions(&config, &controller),
+            Some(vec![
+                point(0.8, 0.0),
+                point(0.6, 0.0),
+                point(0.4, 0.0),
+                point(0.1, 0.25),
+                point(0.1, 0.5),
+            ])
+        );
+
+        controller.zones.push(ZoneInfo {
+            name: "Aura Mainboard".to_owned(),
+            leds: 1,
+        });
+        assert_eq!(positions(&config, &controller), None);
+    }
+}
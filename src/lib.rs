@@ -0,0 +1,1204 @@
+mod accessibility;
+#[cfg(feature = "alloc-audit")]
+mod alloc_audit;
+mod args;
+mod base64;
+mod battery;
+mod boot;
+mod bot;
+mod budget;
+mod capabilities;
+mod check;
+mod color_manager;
+mod color_names;
+mod companion;
+mod config;
+mod connection;
+mod control;
+mod cpu;
+mod cpu_temperature;
+mod demo;
+mod disk;
+
+#[cfg(feature = "mdns")]
+mod discovery;
+
+mod effects;
+mod events;
+mod explain;
+mod filters;
+mod fleet;
+mod github;
+mod gpu;
+
+#[cfg(feature = "grpc")]
+mod grpc;
+
+mod hex;
+mod history;
+mod http;
+mod i18n;
+mod init;
+mod latency;
+mod layout;
+mod list_devices;
+mod logging;
+mod mail;
+mod mapping;
+mod memory;
+mod migration;
+mod notifications;
+mod paths;
+mod power;
+#[cfg(target_os = "linux")]
+mod preflight;
+mod preview;
+mod priority;
+mod profile;
+mod reload;
+mod remote;
+mod runtime;
+mod safety;
+mod sampler;
+mod scene;
+mod sdk;
+mod secrets;
+mod sequence;
+mod server;
+mod set_color;
+mod state;
+mod stress;
+mod sync;
+mod test_pattern;
+mod thermal;
+mod units;
+mod websocket;
+mod wizard;
+mod wled;
+
+#[cfg(feature = "tls")]
+mod tls;
+
+#[cfg(target_os = "windows")]
+mod windows;
+
+#[cfg(not(target_os = "windows"))]
+mod linux;
+
+#[cfg(feature = "nvidia")]
+extern crate nvml_wrapper as nvml;
+
+#[cfg(feature = "alloc-audit")]
+#[global_allocator]
+static ALLOCATOR: alloc_audit::CountingAllocator = alloc_audit::CountingAllocator;
+
+#[cfg(target_os = "windows")]
+use crate::windows::{
+    install_scheduled_task, install_service, remove_scheduled_task, remove_service, start_service,
+};
+
+#[cfg(not(target_os = "windows"))]
+use crate::linux::{
+    install_scheduled_task, install_service, remove_scheduled_task, remove_service, start_service,
+};
+
+use crate::{
+    args::{Args, Command, PanicPolicy},
+    color_manager::{set_all_effect, set_all_light_color, set_all_static_color, Pipeline},
+    config::{Config, Metric, DROP_IN_DIRECTORY},
+    connection::{controller_list_version, Backoff, ConnectionOptions, Connector},
+    control::{Connection, ControlRequest, ControlState, LiveStatus},
+    events::Event,
+    explain::explain,
+    history::unix_millis,
+    i18n::tr,
+    list_devices::list_devices,
+    mapping::{resolve, ControllerInfo},
+    paths::Kind,
+    safety::FlashLimiter,
+    sampler::{Sampler, Samples},
+    server::{follow_server, supervise_server},
+    set_color::set_color,
+    state::State,
+    stress::stress_test,
+    test_pattern::test_pattern,
+    wizard::{confirm, run_wizard},
+    wled::WledSink,
+};
+use anyhow::{bail, Result};
+use log::*;
+use openrgb::{data::Color, OpenRGB};
+use simplelog::{
+    ColorChoice, Config as LogConfig, SharedLogger, TermLogger, TerminalMode, WriteLogger,
+};
+use std::{
+    fmt::{self, Display},
+    io::IsTerminal,
+    path::Path,
+    pin::pin,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::{Duration, SystemTime},
+};
+use tokio::{net::TcpStream, sync::Notify};
+
+const LOG_FILE: &str = "open_rgb_client_log.txt";
+
+const SAFE_MODE_COLOR: Color = Color::new(0x20, 0x20, 0x20);
+const SAFE_MODE_INTERVAL: Duration = Duration::from_secs(5);
+
+const PANIC_RESTART_DELAY: Duration = Duration::from_secs(1);
+
+/// Frame interval while a sequence plays, smooth enough for animated effects.
+const SEQUENCE_FRAME_INTERVAL: Duration = Duration::from_millis(33);
+
+struct ShutdownSignal {
+    shutdown_notify: Arc<Notify>,
+    should_shutdown: AtomicBool,
+}
+
+impl ShutdownSignal {
+    fn new() -> Self {
+        Self {
+            shutdown_notify: Arc::new(Notify::new()),
+            should_shutdown: AtomicBool::new(false),
+        }
+    }
+
+    fn shutdown(&self) {
+        self.should_shutdown.store(true, Ordering::Relaxed);
+        self.shutdown_notify.notify_waiters();
+    }
+}
+
+/// Runs the command of the command line, `sidecar` for the build running along with OpenRGB
+/// rather than as a service: it always launches or follows OpenRGB and exits with it.
+pub async fn run(sidecar: bool) -> Result<()> {
+    let args = Args::from_env(sidecar)?;
+    i18n::init(args.language.as_deref());
+    paths::init(args.data_dir.as_deref());
+    remote::init(args.remote.clone());
+
+    // Come up in safe mode when the previous runs keep crashing.
+    let safe_mode = matches!(args.command, Command::Run | Command::Service) && {
+        let mut state = State::load();
+        let safe_mode = state.record_start(&args.safe_mode);
+        state.save()?;
+
+        safe_mode
+    };
+
+    // Setup logging.
+    let mut loggers: Vec<Box<dyn SharedLogger>> = vec![];
+
+    let mut log_filters = args.log_level.clone().unwrap_or_default();
+    if safe_mode {
+        log_filters.level = log_filters.level.max(LevelFilter::Debug);
+    }
+
+    let in_service_mode = args.command == Command::Service;
+    // The relay answers on the standard output.
+    if !in_service_mode && args.command != Command::Relay {
+        loggers.push(TermLogger::new(
+            LevelFilter::Trace,
+            LogConfig::default(),
+            TerminalMode::Mixed,
+            ColorChoice::Auto,
+        ));
+    }
+
+    if cfg!(debug_assertions) {
+        if let Ok(file) = paths::file(Kind::State, LOG_FILE).and_then(|path| {
+            paths::create_parent(&path)?;
+
+            Ok(std::fs::File::create(path)?)
+        }) {
+            loggers.push(WriteLogger::new(
+                LevelFilter::Trace,
+                LogConfig::default(),
+                file,
+            ));
+        }
+    }
+
+    logging::init(loggers, log_filters)?;
+
+    log_panics::init();
+
+    for arg in &args.ignored {
+        warn!("Ignoring unknown argument {}.", arg);
+    }
+
+    if safe_mode {
+        warn!("Too many restarts, starting in safe mode.");
+    }
+
+    if args.sidecar && matches!(args.command, Command::Install | Command::Service) {
+        bail!("The sidecar runs along with OpenRGB, install open_rgb_client as a service instead.");
+    }
+
+    match args.command {
+        Command::Install => {
+            let config = load_config(&args)?;
+            if let Some(boot) = &config.boot {
+                if let Err(e) =
+                    boot::save(&config, boot, &connection_options(&args, &config)?).await
+                {
+                    warn!("Boot frame not saved: {}", e);
+                }
+            }
+
+            if args.scheduled_task {
+                install_scheduled_task(&args).await?
+            } else {
+                install_service(&args).await?
+            }
+        }
+        Command::Remove if args.scheduled_task => remove_scheduled_task().await?,
+        Command::Remove => remove_service().await?,
+        Command::Service => {
+            let exe_path = std::env::current_exe().unwrap();
+            info!("Service is starting... [{:?}]", exe_path);
+
+            start_service()?;
+
+            info!("Service quit.");
+        }
+        Command::Discover => discover_servers().await?,
+        Command::Status => print_status().await?,
+        Command::ExplainMapping => explain_mapping(&args).await?,
+        Command::ListDevices => {
+            let config = load_config(&args)?;
+
+            list_devices(&config, &connection_options(&args, &config)?).await?
+        }
+        Command::CheckConfig => {
+            let config = load_config(&args)?;
+
+            check::check_config(&config, &connection_options(&args, &config)?).await?
+        }
+        Command::InitConfig => {
+            init::init_config(
+                &Config::path(args.config.as_deref())?,
+                &connection_options(&args, &Config::default())?,
+            )
+            .await?
+        }
+        Command::MigrateBuiltin => init::migrate_builtin(&Config::path(args.config.as_deref())?)?,
+        Command::ImportLayout => {
+            let config_path = Config::path(args.config.as_deref())?;
+            let output = config_path
+                .parent()
+                .unwrap_or(Path::new("."))
+                .join(DROP_IN_DIRECTORY)
+                .join("visual-map.toml");
+
+            layout::import(
+                &args.visual_map,
+                &output,
+                &connection_options(&args, &Config::default())?,
+            )
+            .await?
+        }
+        Command::Inject => {
+            control::send(&ControlRequest::Inject {
+                injections: args.inject.injections.clone(),
+                duration: args.inject.duration,
+            })
+            .await?
+        }
+        Command::Play => {
+            control::send(&ControlRequest::Play {
+                sequence: args.sequence.clone(),
+            })
+            .await?
+        }
+        Command::SwitchProfile => {
+            control::send(&ControlRequest::SwitchProfile {
+                name: args.named_profile.clone(),
+            })
+            .await?
+        }
+        Command::SwitchScene => {
+            control::send(&ControlRequest::SwitchScene {
+                name: args.scene.clone(),
+            })
+            .await?
+        }
+        Command::History => history::print(&load_config(&args)?, args.history).await?,
+        Command::Enable | Command::Disable => {
+            control::send(&ControlRequest::SetZone {
+                controller: args.target.controller.clone(),
+                zone: args.target.zone.clone(),
+                enabled: args.command == Command::Enable,
+            })
+            .await?
+        }
+        Command::ReducedMotion => {
+            control::send(&ControlRequest::ReducedMotion(args.switch)).await?
+        }
+        Command::HighContrast => control::send(&ControlRequest::HighContrast(args.switch)).await?,
+        Command::Relay => remote::relay().await?,
+        Command::LogLevel => {
+            control::send(&ControlRequest::LogLevel(
+                args.log_level.clone().unwrap_or_default(),
+            ))
+            .await?
+        }
+        Command::SetSecret => {
+            println!("{}", tr!("secret-prompt", "name" => args.secret.as_str()));
+            let mut secret = String::new();
+            std::io::stdin().read_line(&mut secret)?;
+
+            secrets::store(&args.secret, secret.trim_end())?
+        }
+        Command::DeleteSecret => secrets::delete(&args.secret)?,
+        Command::StressTest => {
+            let config = load_config(&args)?;
+
+            stress_test(
+                &args.stress,
+                &connection_options(&args, &config)?,
+                wait_for_termination(),
+            )
+            .await?
+        }
+        Command::TestPattern => {
+            let config = load_config(&args)?;
+
+            test_pattern(
+                &args.target,
+                args.pattern,
+                &config,
+                &connection_options(&args, &config)?,
+                wait_for_termination(),
+            )
+            .await?
+        }
+        Command::SetColor => {
+            let config = load_config(&args)?;
+
+            set_color(
+                &args.target,
+                args.color,
+                &config,
+                &connection_options(&args, &config)?,
+                args.dry_run,
+            )
+            .await?
+        }
+        Command::Explain => {
+            let config = load_config(&args)?;
+
+            explain(&args.target, &config, &connection_options(&args, &config)?).await?
+        }
+        Command::Setup => {
+            run_wizard(&Config::path(args.config.as_deref())?).await?;
+        }
+        Command::Run => {
+            let config_path = Config::path(args.config.as_deref())?;
+            if !config_path.exists()
+                && std::io::stdin().is_terminal()
+                && confirm(&tr!("setup-offer"), true)?
+            {
+                run_wizard(&config_path).await?;
+            }
+
+            launch_client(&args, shutdown_on_signal()).await?
+        }
+        Command::Demo => {
+            info!(
+                "Demo of the {} waveform, nothing is sampled.",
+                args.waveform
+            );
+
+            launch_client(&args, shutdown_on_signal()).await?
+        }
+    };
+
+    info!("Done.");
+
+    Ok(())
+}
+
+fn shutdown_on_signal() -> Arc<ShutdownSignal> {
+    let shutdown_signal = Arc::new(ShutdownSignal::new());
+
+    let shutdown_signal_copy = shutdown_signal.clone();
+    tokio::spawn(async move {
+        wait_for_termination().await;
+
+        info!("Shutting down...");
+        shutdown_signal_copy.shutdown();
+    });
+
+    shutdown_signal
+}
+
+#[cfg(unix)]
+async fn wait_for_termination() {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let mut terminate = signal(SignalKind::terminate()).unwrap();
+    tokio::select! {
+        _ = tokio::signal::ctrl_c() => {},
+        _ = terminate.recv() => {},
+    }
+}
+
+/// Closing the console, logging off and shutting down too, which reach the scheduled task.
+#[cfg(not(unix))]
+async fn wait_for_termination() {
+    use tokio::signal::windows::{ctrl_close, ctrl_logoff, ctrl_shutdown};
+
+    let mut close = ctrl_close().unwrap();
+    let mut logoff = ctrl_logoff().unwrap();
+    let mut shutdown = ctrl_shutdown().unwrap();
+    tokio::select! {
+        _ = tokio::signal::ctrl_c() => {},
+        _ = close.recv() => {},
+        _ = logoff.recv() => {},
+        _ = shutdown.recv() => {},
+    }
+}
+
+async fn launch_client(args: &Args, shutdown_signal: Arc<ShutdownSignal>) -> Result<()> {
+    let config = Arc::new(load_config(args)?);
+    let connection = connection_options(args, &config)?;
+
+    if let Some(process) = &config.process {
+        if let Err(e) = priority::apply(process) {
+            warn!("Failed to set the priority of the process: {:#}", e);
+        }
+    }
+
+    let control = Arc::new(ControlState {
+        demo: (args.command == Command::Demo).then_some(args.waveform),
+        dry_run: args.dry_run,
+        preview: args.preview,
+        ..ControlState::new(config.clone())
+    });
+    if let Some(name) = control.profiles.scene() {
+        info!("Using scene {}.", name);
+    } else if let Some(name) = control.profiles.active() {
+        info!("Using profile {}.", name);
+    }
+    let control_server = tokio::spawn({
+        let control = control.clone();
+
+        async move {
+            if let Err(e) = control::serve(control).await {
+                warn!("Control socket unavailable: {}", e);
+            }
+        }
+    });
+
+    #[cfg(feature = "grpc")]
+    let grpc = config.grpc.clone().map(|grpc| {
+        let control = control.clone();
+
+        tokio::spawn(async move {
+            if let Err(e) = grpc::serve(grpc.address, control).await {
+                warn!("gRPC control unavailable: {}", e);
+            }
+        })
+    });
+
+    #[cfg(not(feature = "grpc"))]
+    if config.grpc.is_some() {
+        warn!("This build has no gRPC support, only the control socket is available.");
+    }
+
+    let http = config.http.clone().map(|http| {
+        let control = control.clone();
+
+        tokio::spawn(async move {
+            if let Err(e) = http::serve(http.address, control).await {
+                warn!("HTTP server unavailable: {}", e);
+            }
+        })
+    });
+
+    let sync = config.sync.clone().map(|sync| {
+        tokio::spawn(async move {
+            if let Err(e) = sync::run(sync).await {
+                warn!("Sync unavailable: {}", e);
+            }
+        })
+    });
+
+    let push = config
+        .push
+        .is_some()
+        .then(|| tokio::spawn(fleet::run_push(control.clone())));
+
+    let schedule = config
+        .scenes
+        .values()
+        .any(|scene| !scene.schedule.is_empty())
+        .then(|| tokio::spawn(scene::run_schedule(control.clone())));
+    scene::register_hotkeys(control.clone());
+
+    let companion = match (&config.companion, &config.http) {
+        (Some(companion), Some(http)) => {
+            let (discovery_port, http_port) = (companion.discovery_port, http.address.port());
+            let control = control.clone();
+
+            Some(tokio::spawn(async move {
+                if let Err(e) = companion::run_discovery(discovery_port, http_port, control).await {
+                    warn!("Companion discovery unavailable: {}", e);
+                }
+            }))
+        }
+        (Some(_), None) => {
+            warn!("The companion apps need the HTTP server, set `[http]`.");
+
+            None
+        }
+        _ => None,
+    };
+
+    #[cfg(feature = "bot")]
+    let bot = config
+        .bot
+        .clone()
+        .map(|bot| tokio::spawn(bot::run(bot, control.clone())));
+
+    #[cfg(not(feature = "bot"))]
+    if config.bot.is_some() {
+        warn!("This build has no chat bot support, build with the `bot` feature.");
+    }
+
+    let server_supervisor = match args.server.clone() {
+        Some(options) if args.sidecar => {
+            let port = server::local_port(&connection)?;
+            let shutdown_signal = shutdown_signal.clone();
+
+            Some(tokio::spawn(async move {
+                follow_server(options, port).await;
+
+                info!("Shutting down along with OpenRGB...");
+                shutdown_signal.shutdown();
+            }))
+        }
+        Some(options) => {
+            let port = server::local_port(&connection_options(args, &config)?)?;
+            info!("Supervising OpenRGB server [{:?}]...", options.path);
+
+            Some(tokio::spawn(supervise_server(options, port)))
+        }
+        None => None,
+    };
+
+    let config_path = Config::path(args.config.as_deref())?;
+    let reload = tokio::spawn({
+        let args = args.clone();
+        let control = control.clone();
+        let config = control.config.clone();
+
+        // The named profile in use is applied over the reloaded configuration.
+        let load = move || Ok(control.profiles.apply(load_config(&args)?));
+
+        async move {
+            if let Err(e) = reload::watch(&config_path, load, config).await {
+                warn!("Configuration changes need a restart: {}", e);
+            }
+        }
+    });
+
+    // The pipeline runs in its own task so its panics can be handled according to the policy,
+    // and is restarted with the new configuration when it changes.
+    let mut config_changes = control.config.subscribe();
+    let mut force_safe_mode = false;
+    let result = loop {
+        let pipeline_signal = Arc::new(ShutdownSignal::new());
+        let mut pipeline = tokio::spawn(run_pipeline(
+            connection.clone(),
+            control.config(),
+            control.clone(),
+            force_safe_mode,
+            pipeline_signal.clone(),
+        ));
+
+        let mut reloading = false;
+        let joined = loop {
+            let shutdown = shutdown_signal.shutdown_notify.notified();
+            if shutdown_signal.should_shutdown.load(Ordering::Relaxed) {
+                pipeline_signal.shutdown();
+
+                break (&mut pipeline).await;
+            }
+
+            tokio::select! {
+                joined = &mut pipeline => break joined,
+                _ = shutdown => {}
+                Ok(()) = config_changes.changed() => {
+                    info!("Configuration changed, restarting the pipeline...");
+
+                    reloading = true;
+                    pipeline_signal.shutdown();
+
+                    break (&mut pipeline).await;
+                }
+            }
+        };
+
+        match joined {
+            Ok(result) if reloading => {
+                if let Err(e) = result {
+                    warn!("Pipeline failed before the reload: {}", e);
+                }
+
+                continue;
+            }
+            Ok(result) => break result,
+            Err(e) if e.is_panic() => match args.panic_policy {
+                PanicPolicy::Abort => {
+                    error!("Pipeline panicked, aborting.");
+
+                    std::process::abort();
+                }
+                PanicPolicy::Restart => {
+                    warn!("Pipeline panicked, restarting it...");
+
+                    control.events.emit(Event::Alert {
+                        message: "Pipeline panicked, restarting it.".to_owned(),
+                    });
+                }
+                PanicPolicy::SafeMode => {
+                    warn!("Pipeline panicked, restarting it in safe mode...");
+
+                    control.events.emit(Event::Alert {
+                        message: "Pipeline panicked, restarting it in safe mode.".to_owned(),
+                    });
+
+                    force_safe_mode = true;
+                }
+            },
+            Err(e) => break Err(e.into()),
+        }
+
+        tokio::time::sleep(PANIC_RESTART_DELAY).await;
+    };
+
+    if let Some(server_supervisor) = server_supervisor {
+        server_supervisor.abort();
+    }
+    control_server.abort();
+    reload.abort();
+    if let Some(http) = http {
+        http.abort();
+    }
+    if let Some(sync) = sync {
+        sync.abort();
+    }
+    if let Some(push) = push {
+        push.abort();
+    }
+    if let Some(schedule) = schedule {
+        schedule.abort();
+    }
+    if let Some(companion) = companion {
+        companion.abort();
+    }
+    #[cfg(feature = "bot")]
+    if let Some(bot) = bot {
+        bot.abort();
+    }
+    #[cfg(feature = "grpc")]
+    if let Some(grpc) = grpc {
+        grpc.abort();
+    }
+
+    result
+}
+
+fn load_config(args: &Args) -> Result<Config> {
+    let mut config = Config::load(args.config.as_deref())?;
+    args.sampling.apply(&mut config);
+    profile::apply(&mut config, args.profile);
+    sampler::sampling(&config)?;
+
+    Ok(config)
+}
+
+/// Connection options from the command line, with the configured hosts when none was given.
+fn connection_options(args: &Args, config: &Config) -> Result<ConnectionOptions> {
+    let mut connection = args.connection.clone();
+    if connection.hosts.is_empty() {
+        connection.hosts = config
+            .hosts
+            .iter()
+            .map(|host| host.parse())
+            .collect::<Result<_>>()?;
+    }
+
+    Ok(connection)
+}
+
+async fn run_pipeline(
+    connection: ConnectionOptions,
+    config: Arc<Config>,
+    control: Arc<ControlState>,
+    force_safe_mode: bool,
+    shutdown_signal: Arc<ShutdownSignal>,
+) -> Result<()> {
+    let mut state = State::load();
+    if force_safe_mode {
+        state.safe_mode = true;
+    }
+
+    let result = match Connector::new(&connection) {
+        Ok(connector) => run_client(connector, &config, control, &mut state, shutdown_signal).await,
+        Err(e) => Err(e),
+    };
+
+    if result.is_ok() {
+        state.record_clean_shutdown();
+    }
+
+    if let Err(e) = state.save() {
+        warn!("Failed to save state: {}", e);
+    }
+
+    result
+}
+
+async fn print_status() -> Result<()> {
+    let state = State::load();
+    println!(
+        "{}",
+        tr!("status-state-file", "path" => State::path()?.display().to_string())
+    );
+
+    if state.safe_mode {
+        println!(
+            "{}",
+            tr!("status-safe-mode", "starts" => state.unclean_starts.len())
+        );
+    }
+
+    if state.controllers.is_empty() {
+        println!("{}", tr!("status-no-controllers"));
+    }
+
+    for (controller, stats) in &state.controllers {
+        println!(
+            "{}",
+            tr!(
+                "status-controller",
+                "controller" => controller.as_str(),
+                "hours" => format!("{:.1}", stats.active_hours),
+                "brightness" => format!("{:.0}", stats.average_brightness * 100.0)
+            )
+        );
+    }
+
+    if !state.metrics.is_empty() {
+        let metrics = state
+            .metrics
+            .iter()
+            .map(|(metric, quantity)| format!("{} {}", metric, quantity))
+            .collect::<Vec<_>>()
+            .join(", ");
+
+        println!("{}", tr!("status-metrics", "metrics" => metrics));
+    }
+
+    if let Some(runtime) = state.runtime {
+        println!(
+            "{}",
+            tr!(
+                "status-runtime",
+                "cpu" => format!("{:.1}", runtime.cpu_percent),
+                "memory" => runtime
+                    .rss_bytes
+                    .map_or("?".to_owned(), |rss| format!("{:.1}", rss as f64 / 1024.0 / 1024.0)),
+                "tasks" => runtime.tasks,
+                "jitter" => format!("{:.1}", runtime.frame_jitter_ms)
+            )
+        );
+    }
+
+    match control::query(&ControlRequest::Status).await {
+        Ok(answer) => {
+            for line in serde_json::from_str::<LiveStatus>(&answer)?.lines() {
+                println!("{}", line);
+            }
+        }
+        Err(e) => {
+            debug!("No running client: {}", e);
+
+            println!("{}", tr!("status-not-running"));
+        }
+    }
+
+    Ok(())
+}
+
+/// Prints how the LEDs of every controller are rendered with the current configuration.
+async fn explain_mapping(args: &Args) -> Result<()> {
+    let config = load_config(args)?;
+    let client = Connector::new(&connection_options(args, &config)?)?
+        .connect()
+        .await?;
+
+    let controller_count = client.get_controller_count().await?;
+    for controller_id in 0..controller_count {
+        let controller = client.get_controller(controller_id).await?;
+
+        print!(
+            "{}",
+            resolve(
+                &config,
+                &ControllerInfo::from(&controller).with_overrides(&config)
+            )
+        );
+    }
+
+    Ok(())
+}
+
+#[cfg(feature = "mdns")]
+async fn discover_servers() -> Result<()> {
+    use crate::discovery::{browse_servers, BROWSE_TIMEOUT};
+
+    info!("Browsing for OpenRGB servers...");
+
+    let servers = browse_servers(BROWSE_TIMEOUT, usize::MAX).await?;
+    if servers.is_empty() {
+        println!("{}", tr!("discover-none"));
+    }
+
+    for server in servers {
+        println!("{} ({})", server.name, server.host);
+        for address in server.addresses {
+            println!("    {}", address);
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(not(feature = "mdns"))]
+async fn discover_servers() -> Result<()> {
+    anyhow::bail!("This build has no mDNS support.")
+}
+
+async fn run_client(
+    mut connector: Connector,
+    config: &Config,
+    control: Arc<ControlState>,
+    state: &mut State,
+    shutdown_signal: Arc<ShutdownSignal>,
+) -> Result<()> {
+    let outage = match &config.outage {
+        Some(outage) => WledSink::new(outage)
+            .await
+            .map_err(|e| warn!("WLED outage strip unavailable: {}", e))
+            .ok(),
+        None => None,
+    };
+
+    // Once, what the client can read doesn't change between reconnects. Safe mode samples
+    // nothing.
+    #[cfg(target_os = "linux")]
+    if !state.safe_mode {
+        preflight::check(config)?;
+    }
+
+    let mut pipeline = Pipeline::new(FlashLimiter::new(&config.safety), control.dry_run);
+
+    let mut connected_before = false;
+    let mut backoff = Backoff::default();
+    loop {
+        info!("Connecting to OpenRGB...");
+
+        let client = loop {
+            if shutdown_signal.should_shutdown.load(Ordering::Relaxed) {
+                return Ok(());
+            }
+
+            let client = tokio::select! {
+                _ = shutdown_signal.shutdown_notify.notified() => continue,
+                client = connector.connect() => client,
+            };
+
+            if let Ok(client) = client {
+                info!("Connected.");
+                backoff.reset();
+
+                break client;
+            } else {
+                let delay = backoff.next_delay(&config.reconnect)?;
+                warn!(
+                    "Failed to connect to OpenRGB. Retrying in {:.1} s...",
+                    delay.as_secs_f32()
+                );
+
+                if let Some(outage) = &outage {
+                    if let Err(e) = outage.show(&config.palette, sync::now()).await {
+                        debug!("Failed to update the WLED outage strip: {}", e);
+                    }
+                }
+
+                tokio::select! {
+                    _ = shutdown_signal.shutdown_notify.notified() => {}
+                    _ = tokio::time::sleep(delay) => {}
+                }
+            }
+        };
+
+        // Unknown the first time, as if the server had just started.
+        let known_version = *control.controller_list_version.lock().unwrap();
+        let restarted = match controller_list_version(&client).await {
+            Ok(version) => known_version != Some(version),
+            Err(e) => {
+                debug!("Failed to read the controller list: {}", e);
+
+                false
+            }
+        };
+        if restarted && known_version.is_some() {
+            info!("The OpenRGB server restarted, setting the controllers up again.");
+        }
+
+        // The latencies are measured again, the server may be another one or under another load.
+        control.latencies.clear();
+        control.events.emit(Event::Connected {
+            reconnect: connected_before,
+            restarted: restarted && known_version.is_some(),
+        });
+        control.connection.send_replace(Connection::Connected {
+            server: connector.server(),
+            since: unix_millis(SystemTime::now()),
+        });
+
+        let reconnect = connected_before;
+        connected_before = true;
+
+        if state.safe_mode {
+            info!("Starting safe mode loop...");
+
+            control.events.emit(Event::Alert {
+                message: "Running in safe mode.".to_owned(),
+            });
+
+            match run_safe_mode(&client, config, control.dry_run, &shutdown_signal).await {
+                Ok(()) => return Ok(()),
+                Err(e) => {
+                    error!("Failed to set safe mode color: {}", e);
+
+                    continue;
+                }
+            }
+        }
+
+        pipeline.connect(&client, &connector, config).await;
+
+        let sequence = match (restarted, reconnect) {
+            (true, _) => config
+                .started_sequence
+                .as_ref()
+                .or(config.reconnected_sequence.as_ref().filter(|_| reconnect)),
+            (false, true) => config.reconnected_sequence.as_ref(),
+            (false, false) => None,
+        };
+        if let Some(sequence) = sequence.and_then(|name| config.sequence(name)) {
+            control.player.play(sequence.clone());
+        }
+
+        info!("Initializing GPU monitoring...");
+        let mut sampler = Sampler::new(config, control.clone())?;
+
+        info!("Starting service loop...");
+
+        #[cfg(feature = "alloc-audit")]
+        let mut audit = alloc_audit::FrameAudit::default();
+
+        let mut first_frame = true;
+        loop {
+            if shutdown_signal.should_shutdown.load(Ordering::Relaxed) {
+                return Ok(());
+            }
+
+            #[cfg(feature = "alloc-audit")]
+            audit.start_frame();
+
+            let frame = pin!(sample_and_set(
+                &client,
+                config,
+                &control,
+                &mut sampler,
+                &mut pipeline,
+                state
+            ));
+            // Only the allocations of the frame itself count, not the ones of the tasks running
+            // on the same threads between its polls.
+            #[cfg(feature = "alloc-audit")]
+            let frame = audit.count(frame);
+
+            tokio::select! {
+                _ = shutdown_signal.shutdown_notify.notified() => continue,
+                result = frame => {
+                    if let Err(e) = result {
+                        error!("Failed to sample and set: {}", e);
+
+                        control.events.emit(Event::Disconnected {
+                            reason: e.to_string(),
+                        });
+                        control.connection.send_replace(Connection::Disconnected {
+                            reason: e.to_string(),
+                        });
+
+                        break;
+                    }
+                }
+            }
+
+            #[cfg(feature = "alloc-audit")]
+            audit.end_frame();
+
+            // What the controllers are left at once set up, to tell a restart of the server
+            // from a dropped connection next time.
+            if first_frame {
+                first_frame = false;
+                *control.controller_list_version.lock().unwrap() =
+                    controller_list_version(&client).await.ok();
+            }
+        }
+    }
+}
+
+/// Keeps every LED at a static dim color, without sampling anything, until shutdown.
+async fn run_safe_mode(
+    client: &OpenRGB<TcpStream>,
+    config: &Config,
+    dry_run: bool,
+    shutdown_signal: &ShutdownSignal,
+) -> Result<()> {
+    loop {
+        if shutdown_signal.should_shutdown.load(Ordering::Relaxed) {
+            return Ok(());
+        }
+
+        // Reapplied periodically, which also notices a lost connection.
+        set_all_static_color(client, config, &SAFE_MODE_COLOR, dry_run).await?;
+
+        tokio::select! {
+            _ = shutdown_signal.shutdown_notify.notified() => {},
+            _ = tokio::time::sleep(SAFE_MODE_INTERVAL) => {},
+        }
+    }
+}
+
+async fn sample_and_set(
+    client: &OpenRGB<TcpStream>,
+    config: &Config,
+    control: &ControlState,
+    sampler: &mut Sampler,
+    pipeline: &mut Pipeline,
+    state: &mut State,
+) -> Result<()> {
+    if let Some(step) = control.player.current_step() {
+        // Sequences are short, their steps are cloned.
+        #[cfg(feature = "alloc-audit")]
+        alloc_audit::expect_allocations();
+
+        let frame = set_all_effect(client, config, &step.effect, control, pipeline).await?;
+        state.record_frame(frame);
+        control.record_updates(frame);
+        if control.preview {
+            preview::print_frame(frame);
+        }
+        if control.events.is_observed() {
+            control.events.emit(Event::frame(frame));
+        }
+
+        tokio::time::sleep(SEQUENCE_FRAME_INTERVAL).await;
+
+        return Ok(());
+    }
+
+    let samples = sampler.sample().await?;
+    control.samples.send_replace(Some(samples));
+    let observed = control.events.is_observed();
+    // The events, the preview and the alerts are built and printed on the heap.
+    #[cfg(feature = "alloc-audit")]
+    if observed || control.preview {
+        alloc_audit::expect_allocations();
+    }
+    if observed {
+        control.events.emit(Event::metrics(samples, config));
+    }
+    for alert in control.alerts.check(&config.alerts, &samples) {
+        #[cfg(feature = "alloc-audit")]
+        alloc_audit::expect_allocations();
+
+        info!("Alert: {} above {:.0}%.", alert.metric, alert.above * 100.0);
+
+        control.notifications.show(alert.notification.clone());
+        control.events.emit(Event::Alert {
+            message: format!("{} above {:.0}%", alert.metric, alert.above * 100.0),
+        });
+    }
+
+    let frame = set_all_light_color(
+        client,
+        config,
+        &samples.usage(),
+        &control.history,
+        control,
+        pipeline,
+    )
+    .await?;
+
+    state.record_frame(frame);
+    control.record_updates(frame);
+    if control.preview {
+        preview::print_frame(frame);
+    }
+    state.record_samples(&samples, config);
+    state.save_if_due();
+    control.runtime.send_replace(state.runtime);
+    if observed {
+        control.events.emit(Event::frame(frame));
+    }
+
+    info!(
+        "CPU: {} GPU: {} CPU temperature: {} GPU temperature: {} Memory: {} Disk: {} Thermal: {} Battery: {} Mail: {} LEDs: {:.1} W",
+        format_sample(&samples, Metric::Cpu, config),
+        format_sample(&samples, Metric::Gpu, config),
+        format_sample(&samples, Metric::CpuTemperature, config),
+        format_sample(&samples, Metric::GpuTemperature, config),
+        format_sample(&samples, Metric::Memory, config),
+        format_sample(&samples, Metric::Disk, config),
+        format_sample(&samples, Metric::Thermal, config),
+        format_sample(&samples, Metric::Battery, config),
+        format_sample(&samples, Metric::Mail, config),
+        control.power.total()
+    );
+
+    tokio::task::yield_now().await;
+
+    Ok(())
+}
+
+/// A sample in its unit, formatted only when logged.
+fn format_sample<'a>(
+    samples: &'a Samples,
+    metric: Metric,
+    config: &'a Config,
+) -> impl Display + 'a {
+    fmt::from_fn(move |f| {
+        match samples
+            .get(metric)
+            .and_then(|level| units::quantity(metric, level, config))
+        {
+            Some(quantity) => write!(f, "{}", quantity),
+            None => f.write_str("unavailable"),
+        }
+    })
+}
@@ -1,13 +1,91 @@
-use anyhow::Result;
+use crate::{args::Args, control::CONTROL_GROUP};
+use anyhow::{bail, Result};
+use log::info;
+use tokio::process::Command;
 
-pub fn start_service() {
+const SERVICE_NAME: &str = "open_rgb_client";
+const SERVICE_DESCRIPTION: &str = "OpenRGB Client that changes light color based on system load.";
+const SERVICE_UNIT_PATH: &str = "/etc/systemd/system/open_rgb_client.service";
+
+pub fn start_service() -> Result<()> {
     unreachable!()
 }
 
-pub async fn install_service() -> Result<()> {
-    unreachable!()
+pub async fn install_service(args: &Args) -> Result<()> {
+    // First, try to remove the service.
+    remove_service().await.unwrap_or_default();
+
+    let service_binary_path = std::env::current_exe()?;
+
+    let exec_start = std::iter::once(service_binary_path.to_string_lossy().into_owned())
+        .chain(args.service_arguments())
+        .map(|argument| quote_argument(&argument))
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    let unit = format!(
+        "[Unit]\n\
+         Description={SERVICE_DESCRIPTION}\n\
+         After=network.target\n\
+         \n\
+         [Service]\n\
+         ExecStart={exec_start}\n\
+         Restart=always\n\
+         RuntimeDirectory={SERVICE_NAME}\n\
+         RuntimeDirectoryMode=0750\n\
+         \n\
+         [Install]\n\
+         WantedBy=multi-user.target\n"
+    );
+
+    // Its members can use the control socket of the service.
+    let status = Command::new("groupadd")
+        .args(["--force", "--system", CONTROL_GROUP])
+        .status()
+        .await?;
+    if !status.success() {
+        bail!("groupadd {} failed ({}).", CONTROL_GROUP, status);
+    }
+
+    info!("Writing {}...", SERVICE_UNIT_PATH);
+    tokio::fs::write(SERVICE_UNIT_PATH, unit).await?;
+
+    systemctl(&["daemon-reload"]).await?;
+    systemctl(&["enable", "--now", SERVICE_NAME]).await?;
+
+    Ok(())
 }
 
 pub async fn remove_service() -> Result<()> {
-    unreachable!()
+    systemctl(&["disable", "--now", SERVICE_NAME]).await?;
+
+    tokio::fs::remove_file(SERVICE_UNIT_PATH).await?;
+    systemctl(&["daemon-reload"]).await?;
+
+    Ok(())
+}
+
+pub async fn install_scheduled_task(_args: &Args) -> Result<()> {
+    bail!("Scheduled tasks are a Windows alternative to the service, use --install alone.")
+}
+
+pub async fn remove_scheduled_task() -> Result<()> {
+    bail!("Scheduled tasks are a Windows alternative to the service, use --remove alone.")
+}
+
+async fn systemctl(args: &[&str]) -> Result<()> {
+    let status = Command::new("systemctl").args(args).status().await?;
+    if !status.success() {
+        bail!("systemctl {} failed ({}).", args.join(" "), status);
+    }
+
+    Ok(())
+}
+
+fn quote_argument(argument: &str) -> String {
+    if argument.contains(char::is_whitespace) {
+        format!("\"{}\"", argument.replace('"', "\\\""))
+    } else {
+        argument.to_owned()
+    }
 }
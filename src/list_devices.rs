@@ -0,0 +1,127 @@
+use crate::{
+    config::{format_color, Config},
+    connection::{ConnectionOptions, Connector},
+    i18n::tr,
+    mapping::{resolve, ControllerInfo},
+};
+use anyhow::Result;
+
+/// Prints every controller OpenRGB reports with its location, modes and zones, annotated with
+/// the aliases matching it and how the configuration renders it, to write mappings against.
+pub async fn list_devices(config: &Config, connection: &ConnectionOptions) -> Result<()> {
+    let client = Connector::new(connection)?.connect().await?;
+
+    for controller_id in 0..client.get_controller_count().await? {
+        let controller = client.get_controller(controller_id).await?;
+        let info = ControllerInfo::from(&controller).with_overrides(config);
+        let plan = resolve(config, &info);
+
+        println!(
+            "{}",
+            tr!(
+                "list-devices-controller",
+                "name" => controller.name.as_str(),
+                "leds" => info.leds
+            )
+        );
+        println!(
+            "    {}",
+            tr!("list-devices-location", "location" => controller.location.as_str())
+        );
+
+        let aliases = config
+            .aliases
+            .iter()
+            .filter(|(_, alias)| alias.matches(&controller.name, &controller.location))
+            .map(|(name, _)| name.as_str())
+            .collect::<Vec<_>>();
+        if !aliases.is_empty() {
+            println!(
+                "    {}",
+                tr!("list-devices-aliases", "aliases" => aliases.join(", "))
+            );
+        }
+
+        let modes = controller
+            .modes
+            .iter()
+            .enumerate()
+            .map(|(index, mode)| {
+                if index == controller.active_mode as usize {
+                    tr!("list-devices-active-mode", "mode" => mode.name.as_str())
+                } else {
+                    mode.name.clone()
+                }
+            })
+            .collect::<Vec<_>>();
+        println!(
+            "    {}",
+            tr!("list-devices-modes", "modes" => modes.join(", "))
+        );
+
+        let Some(metric) = plan.metric else {
+            println!(
+                "    {}",
+                tr!("list-devices-left-alone", "rule" => plan.metric_rule.as_str())
+            );
+
+            continue;
+        };
+        println!(
+            "    {}",
+            tr!(
+                "list-devices-metric",
+                "metric" => metric.to_string(),
+                "rule" => plan.metric_rule.as_str()
+            )
+        );
+        println!(
+            "    {}",
+            tr!("list-devices-layout", "rule" => plan.layout_rule.as_str())
+        );
+        let by_zone = plan
+            .segments
+            .iter()
+            .any(|segment| info.zones.iter().any(|zone| zone.name == segment.name));
+        if !by_zone {
+            if let Some(segment) = plan.segments.first() {
+                println!(
+                    "    {}",
+                    tr!("list-devices-effect", "effect" => segment.effect.to_string())
+                );
+            }
+        }
+
+        for zone in &info.zones {
+            let segment = plan
+                .segments
+                .iter()
+                .find(|segment| segment.name == zone.name);
+            let rendering = match segment {
+                _ if plan.disables(&zone.name) => tr!("list-devices-disabled"),
+                Some(segment) if segment.placeholder.is_some() => tr!(
+                    "list-devices-metric-off",
+                    "metric" => segment.metric.unwrap_or(metric).to_string(),
+                    "color" => segment.placeholder.as_ref().map(format_color).unwrap_or_default()
+                ),
+                Some(segment) => match segment.metric {
+                    Some(metric) => format!("{}, {}", metric, segment.effect),
+                    None => segment.effect.to_string(),
+                },
+                None => tr!("list-devices-whole-controller"),
+            };
+
+            println!(
+                "        {}",
+                tr!(
+                    "list-devices-zone",
+                    "zone" => zone.name.as_str(),
+                    "leds" => zone.leds,
+                    "rendering" => rendering
+                )
+            );
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,153 @@
+use anyhow::{bail, Result};
+use log::{LevelFilter, Log, Metadata, Record};
+use simplelog::{CombinedLogger, SharedLogger};
+use std::{fmt, str::FromStr, sync::RwLock};
+
+static FILTERS: RwLock<LogFilters> = RwLock::new(LogFilters {
+    level: LevelFilter::Info,
+    modules: Vec::new(),
+});
+
+/// Log level, and the modules logging more or less than it, e.g.
+/// `info,open_rgb_client::mail=debug,openrgb=warn`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LogFilters {
+    pub level: LevelFilter,
+    /// Levels of the modules and their submodules, the longest matching path winning.
+    pub modules: Vec<(String, LevelFilter)>,
+}
+
+impl LogFilters {
+    fn level_of(&self, target: &str) -> LevelFilter {
+        self.modules
+            .iter()
+            .filter(|(module, _)| {
+                target
+                    .strip_prefix(module.as_str())
+                    .is_some_and(|rest| rest.is_empty() || rest.starts_with("::"))
+            })
+            .max_by_key(|(module, _)| module.len())
+            .map_or(self.level, |(_, level)| *level)
+    }
+
+    /// Most verbose level of any module.
+    fn max(&self) -> LevelFilter {
+        self.modules
+            .iter()
+            .map(|(_, level)| *level)
+            .fold(self.level, Ord::max)
+    }
+}
+
+impl Default for LogFilters {
+    fn default() -> Self {
+        Self::from(LevelFilter::Info)
+    }
+}
+
+impl From<LevelFilter> for LogFilters {
+    fn from(level: LevelFilter) -> Self {
+        Self {
+            level,
+            modules: vec![],
+        }
+    }
+}
+
+impl FromStr for LogFilters {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self> {
+        let mut filters = Self::default();
+        for filter in value.split(',').map(str::trim) {
+            match filter.split_once('=') {
+                Some((module, level)) if !module.trim().is_empty() => filters
+                    .modules
+                    .push((module.trim().to_owned(), level.trim().parse()?)),
+                Some(_) => bail!("Invalid log filter: {} (expected <module>=<level>)", filter),
+                None => filters.level = filter.parse()?,
+            }
+        }
+
+        Ok(filters)
+    }
+}
+
+impl fmt::Display for LogFilters {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.level.as_str().to_ascii_lowercase())?;
+        for (module, level) in &self.modules {
+            write!(f, ",{}={}", module, level.as_str().to_ascii_lowercase())?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Loggers behind the filters, which decide what gets logged.
+struct Filtered(Box<CombinedLogger>);
+
+impl Log for Filtered {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= FILTERS.read().unwrap().level_of(metadata.target())
+            && self.0.enabled(metadata)
+    }
+
+    fn log(&self, record: &Record) {
+        if self.enabled(record.metadata()) {
+            // simplelog formats the lines on the heap, which the frames logging them don't
+            // account for.
+            #[cfg(feature = "alloc-audit")]
+            crate::alloc_audit::uncounted(|| self.0.log(record));
+            #[cfg(not(feature = "alloc-audit"))]
+            self.0.log(record);
+        }
+    }
+
+    fn flush(&self) {
+        self.0.flush();
+    }
+}
+
+/// Installs the loggers, which must take every level, behind `filters`.
+pub fn init(loggers: Vec<Box<dyn SharedLogger>>, filters: LogFilters) -> Result<()> {
+    log::set_boxed_logger(Box::new(Filtered(CombinedLogger::new(loggers))))?;
+    set(filters);
+
+    Ok(())
+}
+
+/// Replaces the filters, e.g. from the control socket, to debug without restarting.
+pub fn set(filters: LogFilters) {
+    log::set_max_level(filters.max());
+    *FILTERS.write().unwrap() = filters;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn modules_log_at_the_level_of_their_longest_path() {
+        let filters = "warn, open_rgb_client=info,open_rgb_client::mail=trace"
+            .parse::<LogFilters>()
+            .unwrap();
+
+        assert_eq!(filters.level_of("openrgb"), LevelFilter::Warn);
+        assert_eq!(
+            filters.level_of("open_rgb_client::sampler"),
+            LevelFilter::Info
+        );
+        assert_eq!(
+            filters.level_of("open_rgb_client::mail::imap"),
+            LevelFilter::Trace
+        );
+        assert_eq!(filters.level_of("open_rgb_client_x"), LevelFilter::Warn);
+        assert_eq!(filters.max(), LevelFilter::Trace);
+        assert_eq!(
+            filters.to_string(),
+            "warn,open_rgb_client=info,open_rgb_client::mail=trace"
+        );
+        assert!("open_rgb_client=loud".parse::<LogFilters>().is_err());
+    }
+}
@@ -0,0 +1,383 @@
+use crate::{budget::Budget, config::MailConfig};
+use std::collections::BTreeMap;
+#[cfg(feature = "mail")]
+use tokio::{sync::watch, task::JoinHandle};
+
+/// Logins to the mail server, reconnecting after errors.
+#[cfg(feature = "mail")]
+const MAIL_BUDGET: Budget = Budget::per_hour(20);
+/// OAuth2 access token refreshes, the tokens being cached until they expire.
+#[cfg(feature = "mail")]
+const OAUTH2_BUDGET: Budget = Budget::per_hour(10);
+
+/// Samples the unread mail of an IMAP mailbox, watched with IDLE in the background when built
+/// with the `mail` feature.
+pub struct MailMonitor {
+    full: u32,
+    #[cfg(feature = "mail")]
+    unread: Option<(watch::Receiver<Option<u32>>, JoinHandle<()>)>,
+}
+
+impl MailMonitor {
+    #[cfg(feature = "mail")]
+    pub fn new(config: Option<&MailConfig>, budgets: &BTreeMap<String, Budget>) -> Self {
+        use crate::budget::source;
+
+        let unread = config.map(|config| {
+            let (sender, receiver) = watch::channel(None);
+            let sources = imap::Sources {
+                mail: source("mail", budgets, MAIL_BUDGET),
+                oauth2: source("oauth2", budgets, OAUTH2_BUDGET),
+            };
+
+            (
+                receiver,
+                tokio::spawn(imap::watch(config.clone(), sources, sender)),
+            )
+        });
+
+        Self {
+            full: config.map_or(1, |config| config.full.max(1)),
+            unread,
+        }
+    }
+
+    #[cfg(not(feature = "mail"))]
+    pub fn new(config: Option<&MailConfig>, _: &BTreeMap<String, Budget>) -> Self {
+        if config.is_some() {
+            log::warn!("This build has no mail support, the mail metric is unavailable.");
+        }
+
+        Self { full: 1 }
+    }
+
+    /// Unread count from 0 to 1 at the `full` count, or `None` while the mailbox can't be
+    /// reached.
+    #[cfg(feature = "mail")]
+    pub fn unread(&self) -> Option<f32> {
+        let (unread, _) = self.unread.as_ref()?;
+        let unread = (*unread.borrow())?;
+
+        Some((unread as f32 / self.full as f32).min(1.0))
+    }
+
+    #[cfg(not(feature = "mail"))]
+    pub fn unread(&self) -> Option<f32> {
+        let _ = self.full;
+
+        None
+    }
+}
+
+#[cfg(feature = "mail")]
+impl Drop for MailMonitor {
+    fn drop(&mut self) {
+        if let Some((_, task)) = &self.unread {
+            task.abort();
+        }
+    }
+}
+
+#[cfg(feature = "mail")]
+mod imap {
+    use crate::{
+        base64,
+        budget::Source,
+        config::{MailConfig, OAuth2Config},
+    };
+    use anyhow::{anyhow, bail, Result};
+    use log::*;
+    use std::{sync::Arc, time::Duration};
+    use tokio::{
+        io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader},
+        net::TcpStream,
+        sync::watch,
+        time::{sleep, timeout},
+    };
+    use tokio_rustls::{
+        client::TlsStream,
+        rustls::{crypto::ring, pki_types::ServerName, ClientConfig, RootCertStore},
+        TlsConnector,
+    };
+
+    /// Servers drop clients idling for 30 minutes, IDLE is restarted before.
+    const IDLE_TIMEOUT: Duration = Duration::from_secs(25 * 60);
+    const RETRY_DELAY: Duration = Duration::from_secs(30);
+
+    /// Expiry of the access tokens without `expires_in`, and margin before the actual expiry.
+    const TOKEN_VALIDITY: Duration = Duration::from_secs(60 * 60);
+    const TOKEN_MARGIN: Duration = Duration::from_secs(60);
+
+    pub struct Sources {
+        pub mail: Arc<Source>,
+        pub oauth2: Arc<Source>,
+    }
+
+    /// Keeps `unread` up to date until aborted, reconnecting when the connection is lost.
+    pub async fn watch(config: MailConfig, sources: Sources, unread: watch::Sender<Option<u32>>) {
+        loop {
+            if let Err(e) = idle(&config, &sources, &unread).await {
+                warn!("Mailbox {} unavailable: {}", config.mailbox, e);
+            }
+
+            unread.send_replace(None);
+            sleep(RETRY_DELAY).await;
+        }
+    }
+
+    async fn idle(
+        config: &MailConfig,
+        sources: &Sources,
+        unread: &watch::Sender<Option<u32>>,
+    ) -> Result<()> {
+        sources.mail.take().await;
+        let mut session = Session::connect(config).await?;
+
+        match (&config.password, &config.oauth2) {
+            (Some(password), None) => {
+                session
+                    .command(&format!(
+                        "LOGIN {} {}",
+                        quote(&config.user),
+                        quote(password)
+                    ))
+                    .await?;
+            }
+            (None, Some(oauth2)) => {
+                let key = format!("{} {}", oauth2.token_url, oauth2.client_id);
+                let token = sources.oauth2.fetch(&key, access_token(oauth2)).await?;
+                let response = format!("user={}\x01auth=Bearer {}\x01\x01", config.user, token);
+
+                session
+                    .command(&format!(
+                        "AUTHENTICATE XOAUTH2 {}",
+                        base64::encode(response.as_bytes())
+                    ))
+                    .await?;
+            }
+            _ => bail!("Set either a password or OAuth2 credentials for the mailbox."),
+        }
+
+        // Read only, so counting doesn't mark anything as read.
+        session
+            .command(&format!("EXAMINE {}", quote(&config.mailbox)))
+            .await?;
+        info!("Watching mailbox {} on {}.", config.mailbox, config.host);
+
+        loop {
+            let count = session
+                .command(&format!("SEARCH {}", config.search))
+                .await?
+                .iter()
+                .filter_map(|line| line.strip_prefix("* SEARCH"))
+                .map(|ids| ids.split_whitespace().count() as u32)
+                .sum();
+            unread.send_replace(Some(count));
+
+            // Wait for the mailbox to change.
+            let tag = session.send("IDLE").await?;
+            if !session.read_line().await?.starts_with('+') {
+                bail!("IDLE refused.");
+            }
+
+            let changed = timeout(IDLE_TIMEOUT, async {
+                loop {
+                    let line = session.read_line().await?;
+                    if line.starts_with('*')
+                        && ["EXISTS", "EXPUNGE", "FETCH"]
+                            .iter()
+                            .any(|change| line.contains(change))
+                    {
+                        return Ok::<_, anyhow::Error>(());
+                    }
+                }
+            })
+            .await;
+            if let Ok(changed) = changed {
+                changed?;
+            }
+
+            session.write("DONE").await?;
+            session.response(&tag).await?;
+        }
+    }
+
+    struct Session {
+        stream: BufReader<TlsStream<TcpStream>>,
+        tag: u32,
+        /// Line being read, kept when reading is interrupted by the end of IDLE.
+        line: Vec<u8>,
+    }
+
+    impl Session {
+        async fn connect(config: &MailConfig) -> Result<Self> {
+            let stream = tls_connect(&config.host, config.port).await?;
+
+            let mut session = Self {
+                stream: BufReader::new(stream),
+                tag: 0,
+                line: vec![],
+            };
+
+            let greeting = session.read_line().await?;
+            if !greeting.starts_with("* OK") {
+                bail!("Unexpected greeting: {}", greeting);
+            }
+
+            Ok(session)
+        }
+
+        /// Sends a command and returns its untagged responses, failing when it's refused.
+        async fn command(&mut self, command: &str) -> Result<Vec<String>> {
+            let tag = self.send(command).await?;
+
+            self.response(&tag).await
+        }
+
+        async fn send(&mut self, command: &str) -> Result<String> {
+            self.tag += 1;
+            let tag = format!("a{}", self.tag);
+            self.write(&format!("{} {}", tag, command)).await?;
+
+            Ok(tag)
+        }
+
+        async fn response(&mut self, tag: &str) -> Result<Vec<String>> {
+            let mut untagged = vec![];
+            loop {
+                let line = self.read_line().await?;
+
+                if let Some(status) = line.strip_prefix(&format!("{} ", tag)) {
+                    if !status.starts_with("OK") {
+                        bail!("{}", status);
+                    }
+
+                    return Ok(untagged);
+                }
+
+                // Continuation request, only sent when the authentication failed, with the
+                // details; an empty response gets the tagged error.
+                if line.starts_with('+') {
+                    self.write("").await?;
+
+                    continue;
+                }
+
+                untagged.push(line);
+            }
+        }
+
+        async fn write(&mut self, line: &str) -> Result<()> {
+            let stream = self.stream.get_mut();
+            stream.write_all(format!("{}\r\n", line).as_bytes()).await?;
+            stream.flush().await?;
+
+            Ok(())
+        }
+
+        /// Reads a line without its line ending, can be interrupted and called again.
+        async fn read_line(&mut self) -> Result<String> {
+            if self.stream.read_until(b'\n', &mut self.line).await? == 0 {
+                bail!("Connection closed.");
+            }
+            let line = std::mem::take(&mut self.line);
+
+            Ok(String::from_utf8_lossy(&line).trim_end().to_owned())
+        }
+    }
+
+    async fn tls_connect(host: &str, port: u16) -> Result<TlsStream<TcpStream>> {
+        let mut roots = RootCertStore::empty();
+        roots.extend(webpki_roots::TLS_SERVER_ROOTS.iter().cloned());
+
+        let config = ClientConfig::builder_with_provider(Arc::new(ring::default_provider()))
+            .with_safe_default_protocol_versions()?
+            .with_root_certificates(roots)
+            .with_no_client_auth();
+
+        let stream = TcpStream::connect((host, port)).await?;
+        let stream = TlsConnector::from(Arc::new(config))
+            .connect(ServerName::try_from(host.to_owned())?, stream)
+            .await?;
+
+        Ok(stream)
+    }
+
+    /// Exchanges the refresh token for an access token, returned with how long it's valid.
+    async fn access_token(oauth2: &OAuth2Config) -> Result<(String, Duration)> {
+        let url = oauth2
+            .token_url
+            .strip_prefix("https://")
+            .ok_or_else(|| anyhow!("Token URL must be HTTPS: {}", oauth2.token_url))?;
+        let (host, path) = url.split_at(url.find('/').unwrap_or(url.len()));
+        let path = if path.is_empty() { "/" } else { path };
+
+        let mut form = vec![
+            ("grant_type", "refresh_token"),
+            ("refresh_token", oauth2.refresh_token.as_str()),
+            ("client_id", oauth2.client_id.as_str()),
+        ];
+        if let Some(client_secret) = &oauth2.client_secret {
+            form.push(("client_secret", client_secret));
+        }
+        let body = form
+            .iter()
+            .map(|(name, value)| format!("{}={}", name, form_encode(value)))
+            .collect::<Vec<_>>()
+            .join("&");
+
+        // HTTP/1.0 so the response is never chunked.
+        let mut stream = tls_connect(host, 443).await?;
+        let request = format!(
+            "POST {} HTTP/1.0\r\nHost: {}\r\nContent-Type: application/x-www-form-urlencoded\r\nContent-Length: {}\r\n\r\n{}",
+            path,
+            host,
+            body.len(),
+            body
+        );
+        stream.write_all(request.as_bytes()).await?;
+
+        let mut response = vec![];
+        stream.read_to_end(&mut response).await?;
+        let response = String::from_utf8_lossy(&response);
+        let (head, body) = response
+            .split_once("\r\n\r\n")
+            .ok_or_else(|| anyhow!("Invalid token response."))?;
+        if head.split_whitespace().nth(1) != Some("200") {
+            bail!(
+                "Token refresh failed: {}",
+                head.lines().next().unwrap_or_default()
+            );
+        }
+
+        let token = serde_json::from_str::<serde_json::Value>(body)?;
+        let access_token = token["access_token"]
+            .as_str()
+            .ok_or_else(|| anyhow!("No access token in the token response."))?;
+        let validity = token["expires_in"]
+            .as_u64()
+            .map_or(TOKEN_VALIDITY, Duration::from_secs);
+
+        Ok((
+            access_token.to_owned(),
+            validity.saturating_sub(TOKEN_MARGIN),
+        ))
+    }
+
+    /// IMAP quoted string.
+    fn quote(value: &str) -> String {
+        format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+    }
+
+    fn form_encode(value: &str) -> String {
+        value
+            .bytes()
+            .map(|byte| match byte {
+                b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                    (byte as char).to_string()
+                }
+                _ => format!("%{:02X}", byte),
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,747 @@
+use crate::{
+    config::{format_color, name_matches, Config, DeviceConfig, Metric, Palette},
+    effects::Effect,
+    filters::Filter,
+    layout::{self, Point},
+    power::PowerModel,
+};
+use openrgb::data::{Color, Controller};
+use serde::Deserialize;
+use std::{fmt, ops::Range, sync::OnceLock};
+
+/// Metrics and layouts of known controllers, used where the configuration doesn't set them.
+const KNOWN_DEVICES: &str = include_str!("../assets/devices.toml");
+
+#[derive(Deserialize)]
+struct KnownDevices {
+    devices: Vec<DeviceConfig>,
+}
+
+/// What the resolver needs to know about a controller, as reported by OpenRGB.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ControllerInfo {
+    pub name: String,
+    /// Where OpenRGB found the controller, e.g. `HID: /dev/hidraw3`.
+    #[serde(default)]
+    pub location: String,
+    pub leds: usize,
+    #[serde(default)]
+    pub zones: Vec<ZoneInfo>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ZoneInfo {
+    pub name: String,
+    pub leds: usize,
+}
+
+impl ControllerInfo {
+    /// LEDs of a zone, zones being laid out one after the other.
+    pub fn zone_range(&self, zone: &str) -> Option<Range<usize>> {
+        let index = self
+            .zones
+            .iter()
+            .position(|candidate| candidate.name.eq_ignore_ascii_case(zone))?;
+        let start = self.zones[..index]
+            .iter()
+            .map(|zone| zone.leds)
+            .sum::<usize>();
+
+        Some(start..start + self.zones[index].leds)
+    }
+
+    /// The controller with the LED counts of its zones set in the configuration.
+    pub fn with_overrides(mut self, config: &Config) -> Self {
+        let Some(device) = config.device(&self.name, &self.location) else {
+            return self;
+        };
+
+        for zone in &mut self.zones {
+            if let Some(leds) = device
+                .zones
+                .iter()
+                .find(|zone_config| name_matches(&zone_config.name, &zone.name))
+                .and_then(|zone_config| zone_config.leds)
+            {
+                zone.leds = leds;
+            }
+        }
+
+        if !self.zones.is_empty() {
+            self.leds = self.zones.iter().map(|zone| zone.leds).sum();
+        }
+
+        self
+    }
+
+    /// Colors of the LEDs of this controller laid out over the zones of `reported`, as OpenRGB
+    /// reports them: zones longer than reported are cut, shorter ones padded with black.
+    pub fn fit(&self, reported: &ControllerInfo, colors: Vec<Color>) -> Vec<Color> {
+        let mut fitted = Vec::with_capacity(reported.leds);
+        self.fit_into(reported, &colors, &mut fitted);
+
+        fitted
+    }
+
+    /// Lays out `colors` like `fit`, into `fitted`, reusing its memory.
+    pub fn fit_into(&self, reported: &ControllerInfo, colors: &[Color], fitted: &mut Vec<Color>) {
+        fitted.clear();
+        let same = self.zones.len() == reported.zones.len()
+            && self
+                .zones
+                .iter()
+                .zip(&reported.zones)
+                .all(|(zone, reported)| zone.leds == reported.leds);
+        if same {
+            fitted.extend_from_slice(colors);
+
+            return;
+        }
+
+        let mut start = 0;
+        for (zone, reported) in self.zones.iter().zip(&reported.zones) {
+            let end = (start + zone.leds).min(colors.len());
+            let leds = &colors[start.min(end)..end];
+            let leds = &leds[..leds.len().min(reported.leds)];
+            fitted.extend_from_slice(leds);
+            fitted.resize(
+                fitted.len() + reported.leds - leds.len(),
+                Color::new(0, 0, 0),
+            );
+
+            start = end;
+        }
+    }
+}
+
+impl From<&Controller> for ControllerInfo {
+    fn from(controller: &Controller) -> Self {
+        Self {
+            name: controller.name.clone(),
+            location: controller.location.clone(),
+            leds: controller.leds.len(),
+            zones: controller
+                .zones
+                .iter()
+                .map(|zone| ZoneInfo {
+                    name: zone.name.clone(),
+                    leds: zone.leds_count as usize,
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Consecutive LEDs of a controller rendered with the same style, usually a zone.
+#[derive(Debug, Clone)]
+pub struct Segment {
+    pub name: String,
+    pub leds: usize,
+    /// Metric shown instead of the plan's.
+    pub metric: Option<Metric>,
+    pub effect: Effect,
+    /// Shown instead of `effect` while the metric is idle.
+    pub idle_effect: Option<Effect>,
+    /// Color of every LED instead of the effect, while the metric is in `disabled_metrics`.
+    pub placeholder: Option<Color>,
+}
+
+/// How a controller's LEDs are rendered, resolved from the configuration.
+#[derive(Debug, Clone)]
+pub struct Plan {
+    pub controller: String,
+    /// `None` when the controller is left alone.
+    pub metric: Option<Metric>,
+    pub palette: Palette,
+    pub segments: Vec<Segment>,
+    /// Post-processing of the frames, in order.
+    pub filters: Vec<Filter>,
+    /// Electrical model of the LEDs, for the power estimate and `power_cap`.
+    pub power: PowerModel,
+    /// Zones the client doesn't update at all.
+    pub disabled_zones: Vec<String>,
+    /// Position of each LED in the case, when the layout places them all.
+    pub positions: Option<Vec<Point>>,
+    /// Why the metric, palette and segments were chosen, for `explain`.
+    pub metric_rule: String,
+    pub palette_rule: String,
+    pub layout_rule: String,
+}
+
+impl Plan {
+    /// Whether the configuration leaves a zone alone.
+    pub fn disables(&self, zone: &str) -> bool {
+        self.disabled_zones
+            .iter()
+            .any(|disabled| disabled.eq_ignore_ascii_case(zone))
+    }
+}
+
+impl fmt::Display for Plan {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let Some(metric) = self.metric else {
+            return writeln!(f, "{}: left alone", self.controller);
+        };
+
+        writeln!(
+            f,
+            "{}: {}, {} -> {}",
+            self.controller,
+            metric,
+            format_color(&self.palette.idle),
+            format_color(&self.palette.load)
+        )?;
+
+        let disabled = |zone: &str| {
+            self.disabled_zones
+                .iter()
+                .any(|disabled| disabled.eq_ignore_ascii_case(zone))
+        };
+
+        for segment in &self.segments {
+            write!(f, "    {} ({} LEDs): ", segment.name, segment.leds)?;
+            if let Some(metric) = segment.metric {
+                write!(f, "{}, ", metric)?;
+            }
+            match (&segment.placeholder, &segment.idle_effect) {
+                _ if disabled(&segment.name) => writeln!(f, "disabled")?,
+                (Some(placeholder), _) => writeln!(f, "metric off, {}", format_color(placeholder))?,
+                (None, Some(idle_effect)) => {
+                    writeln!(f, "{}, {} when idle", segment.effect, idle_effect)?
+                }
+                (None, None) => writeln!(f, "{}", segment.effect)?,
+            }
+        }
+
+        if !self.filters.is_empty() {
+            let filters = self
+                .filters
+                .iter()
+                .map(Filter::to_string)
+                .collect::<Vec<_>>();
+
+            writeln!(f, "    filters: {}", filters.join(", "))?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Resolves the plan of a controller, without talking to OpenRGB.
+pub fn resolve(config: &Config, controller: &ControllerInfo) -> Plan {
+    let device_rule = || format!("device \"{}\" in the configuration", controller.name);
+    let known = known_device(&controller.name);
+
+    let (name, location) = (&controller.name, &controller.location);
+    let (metric, metric_rule) = match config.device_metric(name, location) {
+        _ if !config.allows(name, location) => (
+            Metric::None,
+            "`controllers` allow and deny lists".to_owned(),
+        ),
+        Some(metric) => (metric, device_rule()),
+        None => match (known.and_then(|known| known.metric), config.default_metric) {
+            (Some(metric), _) => (metric, "built-in rule for this controller".to_owned()),
+            (None, Some(metric)) => (
+                metric,
+                "default metric of the configuration or machine profile".to_owned(),
+            ),
+            (None, None) => (Metric::Cpu, "built-in default".to_owned()),
+        },
+    };
+
+    let palette_rule = match config
+        .device(name, location)
+        .and_then(|device| device.palette.as_ref())
+    {
+        Some(_) => device_rule(),
+        None if config.palettes.contains_key(&metric) => {
+            format!("palette of the {} metric", metric)
+        }
+        None => "global palette".to_owned(),
+    };
+
+    let device = config.device(name, location);
+    // Off unless the zone or the device has a placeholder.
+    let placeholder = |segment_metric: Option<Metric>, zone_placeholder: Option<Color>| {
+        config
+            .disabled_metrics
+            .contains(&segment_metric.unwrap_or(metric))
+            .then(|| {
+                zone_placeholder
+                    .or(device.and_then(|device| device.placeholder))
+                    .unwrap_or(Color::new(0, 0, 0))
+            })
+    };
+    let device_idle_effect = device.and_then(|device| device.idle_effect.clone());
+    let zone_configs = device.map_or(&[][..], |device| &device.zones);
+
+    // The built-in layout, unless the configuration has an effect for the whole device.
+    let configured_effect = device.and_then(|device| device.effect.as_ref());
+    let known = known.filter(|_| configured_effect.is_none());
+    let known_zones = known.map_or(&[][..], |known| &known.zones);
+    let device_effect = configured_effect.or(known.and_then(|known| known.effect.as_ref()));
+
+    let layout_rule = match known {
+        _ if !zone_configs.is_empty() && !controller.zones.is_empty() => {
+            format!("zones of {}", device_rule())
+        }
+        _ if configured_effect.is_some() => device_rule(),
+        Some(known) if known.effect.is_some() || !known.zones.is_empty() => {
+            "built-in layout for this controller".to_owned()
+        }
+        _ => "whole controller".to_owned(),
+    };
+
+    let by_zone = !zone_configs.is_empty() || !known_zones.is_empty();
+
+    let segments = if by_zone && !controller.zones.is_empty() {
+        controller
+            .zones
+            .iter()
+            .map(|zone| {
+                let zone_config = zone_configs
+                    .iter()
+                    .chain(known_zones)
+                    .find(|zone_config| name_matches(&zone_config.name, &zone.name));
+
+                let metric = zone_config.and_then(|zone_config| zone_config.metric);
+
+                Segment {
+                    name: zone.name.clone(),
+                    leds: zone.leds,
+                    metric,
+                    effect: zone_config
+                        .and_then(|zone_config| zone_config.effect.as_ref())
+                        .or(device_effect)
+                        .cloned()
+                        .unwrap_or(Effect::Block),
+                    idle_effect: zone_config
+                        .and_then(|zone_config| zone_config.idle_effect.clone())
+                        .or_else(|| device_idle_effect.clone()),
+                    placeholder: placeholder(
+                        metric,
+                        zone_config.and_then(|zone_config| zone_config.placeholder),
+                    ),
+                }
+            })
+            .collect()
+    } else {
+        vec![Segment {
+            name: "all LEDs".to_owned(),
+            leds: controller.leds,
+            metric: None,
+            effect: device_effect.cloned().unwrap_or(Effect::Block),
+            idle_effect: device_idle_effect,
+            placeholder: placeholder(None, None),
+        }]
+    };
+
+    let disabled_zones = zone_configs
+        .iter()
+        .filter(|zone_config| zone_config.disabled)
+        .map(|zone_config| zone_config.name.clone())
+        .collect();
+
+    Plan {
+        controller: controller.name.clone(),
+        metric: (metric != Metric::None).then_some(metric),
+        palette: config.device_palette(name, location, metric).clone(),
+        segments,
+        filters: device.map_or(vec![], |device| {
+            device
+                .burn_in
+                .map(|mode| Filter::BurnIn { mode })
+                .into_iter()
+                .chain(device.filters.iter().cloned())
+                .collect()
+        }),
+        power: device.and_then(|device| device.power).unwrap_or_default(),
+        disabled_zones,
+        positions: layout::positions(config, controller),
+        metric_rule,
+        palette_rule,
+        layout_rule,
+    }
+}
+
+/// Built-in entry of a known controller.
+fn known_device(name: &str) -> Option<&'static DeviceConfig> {
+    static DEVICES: OnceLock<Vec<DeviceConfig>> = OnceLock::new();
+
+    DEVICES
+        .get_or_init(|| {
+            toml::from_str::<KnownDevices>(KNOWN_DEVICES)
+                .expect("Invalid built-in device list")
+                .devices
+        })
+        .iter()
+        .find(|device| name_matches(&device.name, name))
+}
+
+/// A controller of 8 LEDs without zones, for the tests.
+#[cfg(test)]
+pub(crate) fn controller(name: &str, location: &str) -> ControllerInfo {
+    ControllerInfo {
+        name: name.to_owned(),
+        location: location.to_owned(),
+        leds: 8,
+        zones: vec![],
+    }
+}
+
+/// A controller made of the given zones, with their names and LEDs, for the tests.
+#[cfg(test)]
+pub(crate) fn zoned_controller(name: &str, zones: &[(&str, usize)]) -> ControllerInfo {
+    ControllerInfo {
+        leds: zones.iter().map(|(_, leds)| leds).sum(),
+        zones: zones
+            .iter()
+            .map(|&(name, leds)| ZoneInfo {
+                name: name.to_owned(),
+                leds,
+            })
+            .collect(),
+        ..controller(name, "")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        color_manager::{render, Usage},
+        history::History,
+    };
+    use std::time::{Duration, UNIX_EPOCH};
+
+    /// A fixture lists controllers as OpenRGB reports them and, optionally, a configuration.
+    #[derive(Deserialize)]
+    struct Fixture {
+        #[serde(default)]
+        config: Config,
+        controllers: Vec<ControllerInfo>,
+    }
+
+    fn check_golden(fixture: &str, golden: &str) {
+        let fixture: Fixture = toml::from_str(fixture).unwrap();
+
+        let plans = fixture
+            .controllers
+            .iter()
+            .map(|controller| resolve(&fixture.config, controller).to_string())
+            .collect::<String>();
+
+        assert_eq!(plans, golden);
+    }
+
+    #[test]
+    fn aorus_golden() {
+        check_golden(
+            include_str!("../tests/fixtures/mapping/aorus.toml"),
+            include_str!("../tests/fixtures/mapping/aorus.golden"),
+        );
+    }
+
+    #[test]
+    fn asus_golden() {
+        check_golden(
+            include_str!("../tests/fixtures/mapping/asus.toml"),
+            include_str!("../tests/fixtures/mapping/asus.golden"),
+        );
+    }
+
+    #[test]
+    fn corsair_golden() {
+        check_golden(
+            include_str!("../tests/fixtures/mapping/corsair.toml"),
+            include_str!("../tests/fixtures/mapping/corsair.golden"),
+        );
+    }
+
+    #[test]
+    fn renders_every_led() {
+        let fixture: Fixture =
+            toml::from_str(include_str!("../tests/fixtures/mapping/aorus.toml")).unwrap();
+
+        for controller in &fixture.controllers {
+            let plan = resolve(&fixture.config, controller);
+
+            assert_eq!(
+                render(&plan, &Usage::default(), &History::default(), UNIX_EPOCH).len(),
+                controller.leds
+            );
+        }
+    }
+
+    #[test]
+    fn gradient_fills_up_with_the_load() {
+        let controller = zoned_controller("X570 AORUS ELITE", &[("D_LED2 Top", 4)]);
+        let plan = resolve(&Config::default(), &controller);
+
+        let usage = Usage {
+            cpu: 0.5,
+            ..Default::default()
+        };
+
+        let white = Color::new(0xFF, 0xFF, 0xFF);
+        let red = Color::new(0xFF, 0x00, 0x00);
+        assert_eq!(
+            render(&plan, &usage, &History::default(), UNIX_EPOCH),
+            vec![red, red, white, white]
+        );
+    }
+
+    #[test]
+    fn stale_metric_dims_its_zones() {
+        let config: Config = toml::from_str(
+            r#"
+            [[devices]]
+            name = "X570 AORUS ELITE"
+            metric = "mail"
+            "#,
+        )
+        .unwrap();
+        let controller = zoned_controller("X570 AORUS ELITE", &[("D_LED2 Top", 2)]);
+        let plan = resolve(&config, &controller);
+
+        let mut usage = Usage {
+            mail: 0.5,
+            ..Default::default()
+        };
+        usage.brightness.mail = config.staleness.brightness(Duration::from_secs(90));
+
+        assert_eq!(
+            render(&plan, &usage, &History::default(), UNIX_EPOCH),
+            vec![Color::new(0x99, 0, 0), Color::new(0x99, 0x99, 0x99)]
+        );
+    }
+
+    #[test]
+    fn zones_of_a_metric_turned_off_show_their_placeholder() {
+        let config: Config = toml::from_str(
+            r##"
+            disabled_metrics = ["gpu"]
+
+            [[devices]]
+            name = "X570 AORUS ELITE"
+            metric = "cpu"
+            placeholder = "#101010"
+            zones = [
+                { name = "D_LED1 Bottom", metric = "gpu" },
+                { name = "D_LED2 Top", metric = "gpu", placeholder = "#000000" },
+            ]
+            "##,
+        )
+        .unwrap();
+        let controller = zoned_controller(
+            "X570 AORUS ELITE",
+            &[
+                ("D_LED1 Bottom", 1),
+                ("D_LED2 Top", 1),
+                ("Aura Mainboard", 2),
+            ],
+        );
+        let plan = resolve(&config, &controller);
+
+        let usage = Usage {
+            cpu: 1.0,
+            gpu: 1.0,
+            ..Default::default()
+        };
+        let red = Color::new(0xFF, 0x00, 0x00);
+        assert_eq!(
+            render(&plan, &usage, &History::default(), UNIX_EPOCH),
+            vec![Color::new(0x10, 0x10, 0x10), Color::new(0, 0, 0), red, red]
+        );
+    }
+
+    #[test]
+    fn zone_led_count_override() {
+        let config: Config = toml::from_str(
+            r#"
+            [[devices]]
+            name = "X570 AORUS ELITE"
+            zones = [{ name = "D_LED2 Top", leds = 2 }]
+            "#,
+        )
+        .unwrap();
+        let reported =
+            zoned_controller("X570 AORUS ELITE", &[("D_LED2 Top", 4), ("Motherboard", 1)]);
+        let controller = reported.clone().with_overrides(&config);
+        assert_eq!(controller.leds, 3);
+
+        let usage = Usage {
+            cpu: 1.0,
+            ..Default::default()
+        };
+        let colors = render(
+            &resolve(&config, &controller),
+            &usage,
+            &History::default(),
+            UNIX_EPOCH,
+        );
+
+        let (red, off) = (Color::new(0xFF, 0, 0), Color::new(0, 0, 0));
+        assert_eq!(
+            controller.fit(&reported, colors),
+            vec![red, red, off, off, red]
+        );
+    }
+
+    #[test]
+    fn metric_palette_comes_before_the_global_one() {
+        let config: Config = toml::from_str(
+            r##"
+            default_metric = "gpu"
+
+            [palettes.gpu]
+            idle = "#000000"
+            load = "#00FF00"
+
+            [[devices]]
+            name = "ENE DRAM"
+            palette = { idle = "#000000", load = "#0000FF" }
+            "##,
+        )
+        .unwrap();
+        let plan = resolve(&config, &controller("Corsair Lighting Node Pro", ""));
+        assert_eq!(plan.palette.load, Color::new(0, 0xFF, 0));
+        assert_eq!(plan.palette_rule, "palette of the gpu metric");
+
+        let plan = resolve(&config, &controller("ENE DRAM", ""));
+        assert_eq!(plan.palette.load, Color::new(0, 0, 0xFF));
+    }
+
+    #[test]
+    fn metric_none_leaves_the_controller_alone() {
+        let config: Config = toml::from_str(
+            r#"
+            [[devices]]
+            name = "ENE DRAM"
+            metric = "none"
+            "#,
+        )
+        .unwrap();
+
+        assert!(resolve(&config, &controller("ENE DRAM", ""))
+            .metric
+            .is_none());
+    }
+
+    #[test]
+    fn patterns_match_controller_and_zone_names() {
+        let config: Config = toml::from_str(
+            r#"
+            [[devices]]
+            name = "EVGA GeForce RTX*"
+            metric = "gpu"
+            zones = [{ name = "Fan ?", metric = "cpu" }]
+            "#,
+        )
+        .unwrap();
+        let fans = |name: &str| zoned_controller(name, &[("Fan 1", 2), ("Fan 10", 2)]);
+
+        let plan = resolve(&config, &fans("EVGA GeForce RTX 3080Ti FTW3 Ultra"));
+        assert_eq!(plan.metric, Some(Metric::Gpu));
+        assert_eq!(plan.segments[0].metric, Some(Metric::Cpu));
+        assert_eq!(plan.segments[1].metric, None);
+
+        assert_ne!(
+            resolve(&config, &fans("EVGA GeForce GTX 1080")).metric,
+            Some(Metric::Gpu)
+        );
+    }
+
+    #[test]
+    fn denied_controller_is_left_alone() {
+        let config: Config = toml::from_str(
+            r#"
+            [controllers]
+            deny = ["Razer BlackWidow"]
+
+            [[devices]]
+            name = "Razer BlackWidow"
+            metric = "gpu"
+            "#,
+        )
+        .unwrap();
+        assert!(resolve(&config, &controller("Razer BlackWidow", ""))
+            .metric
+            .is_none());
+        assert!(resolve(&config, &controller("ENE DRAM", ""))
+            .metric
+            .is_some());
+    }
+
+    #[test]
+    fn profile_overrides_the_configured_devices() {
+        let config: Config = toml::from_str(
+            r#"
+            default_metric = "cpu"
+
+            [[devices]]
+            name = "Corsair Lighting Node Pro"
+            metric = "thermal"
+
+            [profiles.gaming]
+            default_metric = "gpu"
+            devices = [{ name = "Corsair Lighting Node Pro", metric = "none" }]
+            "#,
+        )
+        .unwrap();
+        let gaming = config.with_profile("gaming").unwrap();
+        assert!(
+            resolve(&gaming, &controller("Corsair Lighting Node Pro", ""))
+                .metric
+                .is_none()
+        );
+        assert_eq!(
+            resolve(&gaming, &controller("ENE DRAM", "")).metric,
+            Some(Metric::Gpu)
+        );
+        assert_eq!(
+            resolve(&config, &controller("Corsair Lighting Node Pro", "")).metric,
+            Some(Metric::Thermal)
+        );
+        assert!(config.with_profile("work").is_none());
+    }
+
+    #[test]
+    fn aliases_match_their_controllers_by_name_and_location() {
+        let config: Config = toml::from_str(
+            r#"
+            default_metric = "cpu"
+
+            [aliases]
+            gpu = { name = "EVGA GeForce*", location = "I2C: /dev/i2c-3*" }
+
+            [[devices]]
+            name = "gpu"
+            metric = "gpu"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(
+            resolve(
+                &config,
+                &controller(
+                    "EVGA GeForce RTX 3080 XC3 Ultra",
+                    "I2C: /dev/i2c-3, address 0x2D"
+                )
+            )
+            .metric,
+            Some(Metric::Gpu)
+        );
+        assert_eq!(
+            resolve(
+                &config,
+                &controller(
+                    "EVGA GeForce RTX 3080 XC3 Ultra",
+                    "I2C: /dev/i2c-4, address 0x2D"
+                )
+            )
+            .metric,
+            Some(Metric::Cpu)
+        );
+    }
+}
@@ -0,0 +1,71 @@
+#[cfg(target_os = "linux")]
+use crate::sampler::read_file;
+use anyhow::{anyhow, Result};
+
+/// Samples the share of the physical memory in use.
+pub struct MemoryMonitor {
+    /// Contents of /proc/meminfo, kept from one sample to the next.
+    #[cfg(target_os = "linux")]
+    meminfo: String,
+}
+
+impl MemoryMonitor {
+    pub fn new() -> Self {
+        Self {
+            #[cfg(target_os = "linux")]
+            meminfo: String::new(),
+        }
+    }
+
+    /// Memory in use from 0 to 1, the caches the system can reclaim counting as free.
+    pub fn usage(&mut self) -> Result<Option<f32>> {
+        #[cfg(target_os = "linux")]
+        let (total, available) = memory(&mut self.meminfo)?;
+        #[cfg(windows)]
+        let (total, available) = memory()?;
+        if total == 0 {
+            return Ok(None);
+        }
+
+        Ok(Some(1.0 - available as f32 / total as f32))
+    }
+}
+
+/// Total and available physical memory, in bytes.
+#[cfg(target_os = "linux")]
+fn memory(meminfo: &mut String) -> Result<(u64, u64)> {
+    let meminfo = read_file("/proc/meminfo", meminfo)?;
+    let field = |name: &str| {
+        meminfo
+            .lines()
+            .find_map(|line| line.strip_prefix(name)?.strip_prefix(':'))
+            .and_then(|value| {
+                value
+                    .trim()
+                    .trim_end_matches("kB")
+                    .trim()
+                    .parse::<u64>()
+                    .ok()
+            })
+            .map(|kilobytes| kilobytes * 1024)
+            .ok_or_else(|| anyhow!("No {} in /proc/meminfo.", name))
+    };
+
+    Ok((field("MemTotal")?, field("MemAvailable")?))
+}
+
+#[cfg(windows)]
+fn memory() -> Result<(u64, u64)> {
+    use windows_sys::Win32::System::SystemInformation::{GlobalMemoryStatusEx, MEMORYSTATUSEX};
+
+    // Safety: the structure is plain data, its length set as the call requires.
+    unsafe {
+        let mut status: MEMORYSTATUSEX = std::mem::zeroed();
+        status.dwLength = std::mem::size_of::<MEMORYSTATUSEX>() as u32;
+        if GlobalMemoryStatusEx(&mut status) == 0 {
+            return Err(anyhow!("{}", std::io::Error::last_os_error()));
+        }
+
+        Ok((status.ullTotalPhys, status.ullAvailPhys))
+    }
+}
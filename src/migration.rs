@@ -0,0 +1,113 @@
+use crate::config::Format;
+use anyhow::{bail, Context, Result};
+use log::*;
+use std::path::{Path, PathBuf};
+use toml::{Table, Value};
+
+/// Version of the configuration schema written by this client, in the `version` key of the
+/// files. Files without one predate the versioning and are version 1.
+pub const CURRENT_VERSION: u32 = 1;
+
+/// Rewrites a configuration table from one schema version to the next.
+type Migration = fn(&mut Table) -> Result<()>;
+
+/// Migrations from each version to the next, the first one upgrading version 1 to 2. A change
+/// to the schema that breaks existing files bumps `CURRENT_VERSION` and adds its migration here.
+const MIGRATIONS: &[Migration] = &[];
+
+/// Upgrades a configuration file read into `table` to the current schema, saving the old file
+/// next to it first, e.g. as `open_rgb_client.toml.v1.bak`, and writing the migrated one in
+/// its place. The comments of a migrated file are lost, they stay in the backup.
+pub fn upgrade(path: &Path, table: &mut Table) -> Result<()> {
+    let version = migrate(table, MIGRATIONS)
+        .with_context(|| format!("Cannot migrate the configuration {:?}", path))?;
+    if version == CURRENT_VERSION {
+        return Ok(());
+    }
+
+    let backup = backup_path(path, version);
+    std::fs::copy(path, &backup).with_context(|| format!("Cannot back up {:?}", path))?;
+    std::fs::write(path, Format::of(path).write(table)?)
+        .with_context(|| format!("Cannot write {:?}", path))?;
+
+    info!(
+        "Migrated the configuration {:?} from version {} to {}, the old file is saved as {:?}.",
+        path, version, CURRENT_VERSION, backup
+    );
+
+    Ok(())
+}
+
+/// Applies the migrations from the version of the table to the last one, returning the
+/// version it had.
+fn migrate(table: &mut Table, migrations: &[Migration]) -> Result<u32> {
+    let latest = migrations.len() as u32 + 1;
+    let version = match table.get("version") {
+        None => 1,
+        Some(Value::Integer(version)) if *version >= 1 => *version as u32,
+        Some(version) => bail!("Invalid configuration version: {}", version),
+    };
+    if version > latest {
+        bail!(
+            "Configuration version {} is newer than this client supports ({}), update the client.",
+            version,
+            latest
+        );
+    }
+
+    for migration in &migrations[version as usize - 1..] {
+        migration(table)?;
+    }
+    table.insert("version".to_owned(), Value::Integer(latest as i64));
+
+    Ok(version)
+}
+
+fn backup_path(path: &Path, version: u32) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_owned();
+    name.push(format!(".v{}.bak", version));
+
+    path.with_file_name(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tables_go_through_the_migrations_after_their_version() {
+        let migrations: &[Migration] = &[
+            |table| {
+                if let Some(metric) = table.remove("metric") {
+                    table.insert("default_metric".to_owned(), metric);
+                }
+
+                Ok(())
+            },
+            |table| {
+                table.insert("migrated".to_owned(), Value::Boolean(true));
+
+                Ok(())
+            },
+        ];
+
+        let mut table = toml::from_str::<Table>("metric = \"gpu\"").unwrap();
+        assert_eq!(migrate(&mut table, migrations).unwrap(), 1);
+        assert_eq!(
+            table.to_string(),
+            "default_metric = \"gpu\"\nmigrated = true\nversion = 3\n"
+        );
+
+        let mut table = toml::from_str::<Table>("version = 2\nmetric = \"gpu\"").unwrap();
+        assert_eq!(migrate(&mut table, migrations).unwrap(), 2);
+        assert!(table.contains_key("metric"));
+
+        let mut table = toml::from_str::<Table>("version = 4").unwrap();
+        assert!(migrate(&mut table, migrations).is_err());
+
+        assert_eq!(
+            backup_path(Path::new("/etc/open_rgb_client.toml"), 1),
+            Path::new("/etc/open_rgb_client.toml.v1.bak")
+        );
+    }
+}
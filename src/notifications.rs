@@ -0,0 +1,110 @@
+use crate::{
+    config::{Config, Palette},
+    effects::Effect,
+    mapping::{ControllerInfo, Plan, Segment},
+    sequence::duration,
+};
+use openrgb::data::Color;
+use serde::{Deserialize, Serialize};
+use std::{
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+/// Temporary change of a zone, or of a whole controller without zone, e.g. on a webhook.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Notification {
+    pub controller: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub zone: Option<String>,
+    /// Shown instead of the effects of the zone.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub effect: Option<Effect>,
+    /// Palette the zone is rendered with instead.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub palette: Option<Palette>,
+    #[serde(with = "duration")]
+    pub duration: Duration,
+}
+
+/// Notifications shown by the running client, until they expire.
+#[derive(Debug, Default)]
+pub struct Notifications {
+    active: Mutex<Vec<(Notification, Instant)>>,
+}
+
+impl Notifications {
+    pub fn show(&self, notification: Notification) {
+        let until = Instant::now() + notification.duration;
+
+        let mut active = self.active.lock().unwrap();
+        active.retain(|(_, until)| *until > Instant::now());
+        active.push((notification, until));
+    }
+
+    /// Renders the notifications of a controller over its colors, the latest one on top, with
+    /// `render` rendering a plan as the controller's are.
+    pub fn apply(
+        &self,
+        config: &Config,
+        controller: &ControllerInfo,
+        plan: &Plan,
+        colors: &mut [Color],
+        render: impl Fn(Plan) -> Vec<Color>,
+    ) {
+        let mut active = self.active.lock().unwrap();
+        active.retain(|(_, until)| *until > Instant::now());
+
+        for (notification, _) in active.iter() {
+            if !config.designates(
+                &notification.controller,
+                &controller.name,
+                &controller.location,
+            ) {
+                continue;
+            }
+
+            let range = match &notification.zone {
+                Some(zone) => match controller.zone_range(zone) {
+                    Some(range) => range,
+                    None => continue,
+                },
+                None => 0..controller.leds,
+            };
+            let range = range.start.min(colors.len())..range.end.min(colors.len());
+
+            // Rendered with a plan of their own.
+            #[cfg(feature = "alloc-audit")]
+            crate::alloc_audit::expect_allocations();
+
+            let mut plan = plan.clone();
+            if let Some(palette) = &notification.palette {
+                plan.palette = palette.clone();
+            }
+            if let Some(effect) = &notification.effect {
+                plan.segments = vec![Segment {
+                    name: "notification".to_owned(),
+                    leds: range.len(),
+                    metric: None,
+                    effect: effect.clone(),
+                    idle_effect: None,
+                    placeholder: None,
+                }];
+            }
+            // Notifications show even over the zones of a metric turned off.
+            for segment in &mut plan.segments {
+                segment.placeholder = None;
+            }
+
+            let rendered = render(plan);
+            let rendered = match notification.effect {
+                Some(_) => Some(&rendered[..]),
+                None => rendered.get(range.clone()),
+            };
+
+            if let Some(rendered) = rendered.filter(|rendered| rendered.len() == range.len()) {
+                colors[range].copy_from_slice(rendered);
+            }
+        }
+    }
+}
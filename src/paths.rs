@@ -0,0 +1,82 @@
+use anyhow::{Context, Result};
+use std::{
+    path::{Path, PathBuf},
+    sync::OnceLock,
+};
+
+/// Directory of the client's files under the platform ones.
+const APP_DIR: &str = "open_rgb_client";
+
+static DATA_DIR: OnceLock<Option<PathBuf>> = OnceLock::new();
+
+/// What a file is for, deciding the platform directory it lives in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    /// Written by the user or the setup, e.g. the configuration.
+    Config,
+    /// Written by the client, e.g. its state and log.
+    State,
+}
+
+/// Puts every file of the client in `data_dir` instead of the platform directories, for
+/// `--data-dir`.
+///
+/// Must be called before the first path is resolved, later calls are ignored.
+pub fn init(data_dir: Option<&Path>) {
+    DATA_DIR.get_or_init(|| data_dir.map(|dir| std::path::absolute(dir).unwrap_or(dir.into())));
+}
+
+/// Location of the file `name`: in the `--data-dir` directory when given, next to the
+/// executable when it's already there, as portable installs and older versions keep it, and in
+/// the platform directory of its kind otherwise.
+pub fn file(kind: Kind, name: &str) -> Result<PathBuf> {
+    if let Some(dir) = DATA_DIR.get_or_init(|| None) {
+        return Ok(dir.join(name));
+    }
+
+    let portable = std::env::current_exe()?.with_file_name(name);
+    if portable.exists() {
+        return Ok(portable);
+    }
+
+    Ok(platform_dir(kind)?.join(APP_DIR).join(name))
+}
+
+/// Creates the directory of a file about to be written.
+pub fn create_parent(path: &Path) -> Result<()> {
+    match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => std::fs::create_dir_all(dir)
+            .with_context(|| format!("Cannot create the directory {:?}", dir)),
+        _ => Ok(()),
+    }
+}
+
+/// The service runs as LocalSystem, so the files go where it and the users share them.
+#[cfg(windows)]
+fn platform_dir(_kind: Kind) -> Result<PathBuf> {
+    std::env::var_os("PROGRAMDATA")
+        .or_else(|| std::env::var_os("APPDATA"))
+        .map(PathBuf::from)
+        .context("Neither %PROGRAMDATA% nor %APPDATA% is set, use --data-dir")
+}
+
+/// `$XDG_CONFIG_HOME` and `$XDG_STATE_HOME`, or their defaults under the home directory.
+#[cfg(not(windows))]
+fn platform_dir(kind: Kind) -> Result<PathBuf> {
+    let (variable, default) = match kind {
+        Kind::Config => ("XDG_CONFIG_HOME", ".config"),
+        Kind::State => ("XDG_STATE_HOME", ".local/state"),
+    };
+
+    // Relative values are invalid per the specification and ignored.
+    if let Some(dir) = std::env::var_os(variable)
+        .map(PathBuf::from)
+        .filter(|dir| dir.is_absolute())
+    {
+        return Ok(dir);
+    }
+
+    std::env::var_os("HOME")
+        .map(|home| PathBuf::from(home).join(default))
+        .context("HOME is not set, use --data-dir")
+}
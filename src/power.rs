@@ -0,0 +1,95 @@
+use openrgb::data::Color;
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, sync::Mutex};
+
+/// Electrical model of a controller's LEDs, used to estimate the power drawn by a frame.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PowerModel {
+    /// Supply voltage of the LEDs.
+    pub voltage: f64,
+    /// Current drawn by one channel (R, G or B) of one LED at full brightness.
+    pub channel_milliamps: f64,
+    /// Current drawn by one LED driver even when the LED is off.
+    pub idle_milliamps: f64,
+}
+
+impl Default for PowerModel {
+    /// Typical 5V addressable LED (WS2812B and alikes).
+    fn default() -> Self {
+        Self {
+            voltage: 5.0,
+            channel_milliamps: 20.0,
+            idle_milliamps: 1.0,
+        }
+    }
+}
+
+impl PowerModel {
+    pub fn estimate_watts(&self, colors: impl IntoIterator<Item = Color>) -> f64 {
+        let milliamps = colors
+            .into_iter()
+            .map(|color| {
+                let level = (color.r as f64 + color.g as f64 + color.b as f64) / 255.0;
+
+                self.idle_milliamps + level * self.channel_milliamps
+            })
+            .sum::<f64>();
+
+        milliamps / 1000.0 * self.voltage
+    }
+}
+
+/// Estimated power drawn by the LEDs of each controller for the last frame sent, by controller
+/// key.
+#[derive(Debug, Default)]
+pub struct LedPower(Mutex<BTreeMap<String, f64>>);
+
+impl LedPower {
+    pub fn record(&self, key: &str, watts: f64) {
+        let mut controllers = self.0.lock().unwrap();
+        match controllers.get_mut(key) {
+            Some(recorded) => *recorded = watts,
+            None => {
+                controllers.insert(key.to_owned(), watts);
+            }
+        }
+    }
+
+    /// Forgets the controllers missing from a frame, no longer lit by the client.
+    pub fn retain(&self, frame: &[(String, Vec<Color>)]) {
+        self.0
+            .lock()
+            .unwrap()
+            .retain(|key, _| frame.iter().any(|(controller, _)| controller == key));
+    }
+
+    pub fn controllers(&self) -> BTreeMap<String, f64> {
+        self.0.lock().unwrap().clone()
+    }
+
+    pub fn total(&self) -> f64 {
+        self.0.lock().unwrap().values().sum()
+    }
+
+    /// The estimates in the Prometheus text format.
+    pub fn to_prometheus(&self) -> String {
+        let controllers = self.0.lock().unwrap();
+        if controllers.is_empty() {
+            return String::new();
+        }
+
+        let mut text = String::new();
+        text += "# HELP open_rgb_client_led_power_watts Estimated power drawn by the LEDs of the last frame.\n";
+        text += "# TYPE open_rgb_client_led_power_watts gauge\n";
+        for (controller, watts) in controllers.iter() {
+            text += &format!(
+                "open_rgb_client_led_power_watts{{controller=\"{}\"}} {}\n",
+                controller.replace('\\', "\\\\").replace('"', "\\\""),
+                watts
+            );
+        }
+
+        text
+    }
+}
@@ -0,0 +1,186 @@
+#[cfg(feature = "nvidia")]
+use crate::gpu;
+use crate::{
+    config::{Config, Metric},
+    control,
+    cpu_temperature::CpuTemperatureMonitor,
+    state::State,
+    thermal::ThermalMonitor,
+};
+use anyhow::{bail, Result};
+use log::*;
+#[cfg(feature = "nvidia")]
+use nvml::error::NvmlError;
+use std::{fs::OpenOptions, io, path::Path};
+
+#[cfg(feature = "nvidia")]
+const NVIDIA_DEVICES: &[&str] = &["/dev/nvidiactl", "/dev/nvidia0"];
+
+#[cfg(feature = "nvidia")]
+const NVIDIA_UDEV_RULE: &str =
+    r#"KERNEL=="nvidia*", SUBSYSTEM=="nvidia*", GROUP="video", MODE="0660""#;
+
+/// Checks the client can read everything it samples before it starts, explaining how to fix
+/// the access problems found instead of failing later with a bare OS error. Only the sources of
+/// metrics shown by the configuration stop the client, the others are warned about.
+pub fn check(config: &Config) -> Result<()> {
+    let mut problems = vec![];
+
+    if let Err(e) = std::fs::read_to_string("/proc/stat") {
+        problems.push(format!(
+            "Cannot read /proc/stat for the CPU usage ({}).\n\
+             Make sure /proc is mounted in the environment the client runs in \
+             (containers and sandboxes such as systemd's ProtectProc may hide it).",
+            e
+        ));
+    }
+
+    #[cfg(feature = "nvidia")]
+    {
+        let mut gpu_problems = vec![];
+        for device in NVIDIA_DEVICES {
+            match OpenOptions::new().read(true).write(true).open(device) {
+                Err(e) if e.kind() == io::ErrorKind::PermissionDenied => {
+                    gpu_problems.push(format!(
+                        "No permission to open {} for the GPU usage.\n\
+                         Add the user running the client to the group owning it, then log in \
+                         again:\n\
+                         \n    sudo usermod -aG $(stat -c %G {}) $USER\n\n\
+                         or let the video group use the NVIDIA devices with a udev rule in \
+                         /etc/udev/rules.d/70-nvidia.rules:\n\
+                         \n    {}\n",
+                        device, device, NVIDIA_UDEV_RULE
+                    ))
+                }
+                // A missing device is reported by NVML below, with a better explanation.
+                _ => {}
+            }
+        }
+
+        if let Err(e) = nvml::Nvml::init() {
+            gpu_problems.push(explain_nvml_error(e));
+        }
+
+        // The client reads the amdgpu driver instead when NVML doesn't work.
+        let mapped = config.maps(Metric::Gpu) || config.maps(Metric::GpuTemperature);
+        if mapped && !gpu::has_amdgpu() {
+            problems.extend(gpu_problems);
+        } else {
+            for problem in gpu_problems {
+                warn!("{}", problem);
+            }
+        }
+    }
+
+    if config.maps(Metric::Thermal) {
+        let path = format!(
+            "/sys/class/thermal/thermal_zone{}/temp",
+            config.thermal.zone
+        );
+        match ThermalMonitor::new(&config.thermal).load() {
+            Ok(Some(_)) => {}
+            Ok(None) => problems.push(format!(
+                "The thermal zone {} doesn't exist, {} is missing.\n\
+                 List the zones with `ls /sys/class/thermal` and set `zone` in the `[thermal]` \
+                 section of the configuration to one of them.",
+                config.thermal.zone, path
+            )),
+            Err(e) => problems.push(format!(
+                "Cannot read {} for the thermal metric ({}).\n\
+                 Make sure /sys is mounted in the environment the client runs in, \
+                 or set `zone` in the `[thermal]` section of the configuration to a readable \
+                 zone.",
+                path, e
+            )),
+        }
+    }
+
+    if config.maps(Metric::CpuTemperature) {
+        match CpuTemperatureMonitor::new(&config.cpu_temperature).load(config) {
+            Ok(Some(_)) => {}
+            Ok(None) => problems.push(match &config.cpu_temperature.sensor {
+                Some(sensor) => format!(
+                    "The CPU has no hwmon sensor labeled {:?}.\n\
+                     List the labels with `cat /sys/class/hwmon/hwmon*/temp*_label` and set \
+                     `sensor` in the `[cpu_temperature]` section of the configuration to one of \
+                     them, or remove it to use the package temperature.",
+                    sensor
+                ),
+                None => "No hwmon driver of the CPU found under /sys/class/hwmon.\n\
+                     Load the one of your CPU, `sudo modprobe coretemp` for Intel or \
+                     `sudo modprobe k10temp` for AMD, and make it load at boot by listing it in \
+                     /etc/modules-load.d/."
+                    .to_owned(),
+            }),
+            Err(e) => problems.push(format!(
+                "Cannot read the hwmon sensor of the CPU temperature ({}).\n\
+                 Make sure /sys is mounted in the environment the client runs in.",
+                e
+            )),
+        }
+    }
+
+    // Not fatal, the client runs on without them.
+    for problem in control::check_socket_directory() {
+        warn!("The control socket won't be available: {}", problem);
+    }
+    if let Err(e) = check_writable(&State::path()?) {
+        warn!(
+            "Cannot write {:?} ({}), statistics and crash detection won't persist. \
+             Install the client in a directory writable by the user running it, \
+             or give that user ownership of the file.",
+            State::path()?,
+            e
+        );
+    }
+
+    if !problems.is_empty() {
+        bail!(
+            "The client can't sample this system:\n\n{}",
+            problems.join("\n\n")
+        );
+    }
+
+    Ok(())
+}
+
+#[cfg(feature = "nvidia")]
+fn explain_nvml_error(error: NvmlError) -> String {
+    match error {
+        NvmlError::LibloadingError(e) => format!(
+            "Cannot load the NVIDIA management library ({}).\n\
+             Install the package shipping libnvidia-ml.so.1 for your driver \
+             (nvidia-utils on Arch, libnvidia-compute-<version> on Debian and Ubuntu).",
+            e
+        ),
+        NvmlError::DriverNotLoaded => "The NVIDIA driver isn't loaded.\n\
+             Load it with `sudo modprobe nvidia`, or check `dmesg` for why it failed to load."
+            .to_owned(),
+        NvmlError::NoPermission => format!(
+            "NVML was denied access to the GPU.\n\
+             Add the user running the client to the video group, or install the udev rule:\n\
+             \n    {}\n",
+            NVIDIA_UDEV_RULE
+        ),
+        e => format!("Failed to initialize NVML: {}", e),
+    }
+}
+
+/// Checks `path` can be written. When it doesn't exist yet, a probe file is created and removed
+/// next to it instead, not to race with a client saving it.
+fn check_writable(path: &Path) -> io::Result<()> {
+    if path.exists() {
+        OpenOptions::new().append(true).open(path)?;
+    } else {
+        let mut probe = path.as_os_str().to_owned();
+        probe.push(format!(".{}.probe", std::process::id()));
+
+        OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&probe)?;
+        std::fs::remove_file(&probe)?;
+    }
+
+    Ok(())
+}
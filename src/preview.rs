@@ -0,0 +1,29 @@
+use openrgb::data::Color;
+use std::io::Write;
+
+/// Prints the colors of a frame as 24-bit ANSI blocks, a line per controller and a block per
+/// LED, to judge gradients and fills without looking at the case.
+pub fn print_frame(frame: &[(String, Vec<Color>)]) {
+    let width = frame
+        .iter()
+        .map(|(controller, _)| controller.chars().count())
+        .max()
+        .unwrap_or_default();
+
+    let mut text = String::new();
+    for (controller, colors) in frame {
+        // The controllers left alone have no colors.
+        if colors.is_empty() {
+            continue;
+        }
+
+        text += &format!("{:<width$} ", controller, width = width);
+        for color in colors {
+            text += &format!("\x1b[38;2;{};{};{}m█", color.r, color.g, color.b);
+        }
+        text += "\x1b[0m\n";
+    }
+
+    // Written at once, so the log lines don't end up in the middle of a frame.
+    let _ = std::io::stdout().lock().write_all(text.as_bytes());
+}
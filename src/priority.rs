@@ -0,0 +1,153 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+/// Scheduling of the client, so it doesn't compete with the workloads it shows.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ProcessConfig {
+    pub priority: Priority,
+    /// Cores the client runs on, all of them when empty.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub cores: Vec<usize>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Priority {
+    #[default]
+    Normal,
+    /// Nice 10 and the lowest best-effort I/O priority on Linux, below normal on Windows.
+    BelowNormal,
+    /// Nice 19 and idle I/O on Linux, idle on Windows: the client only runs when nothing else
+    /// wants the CPU.
+    Idle,
+}
+
+/// Lowers the priority of the process and pins it to its cores.
+#[cfg(target_os = "linux")]
+pub fn apply(config: &ProcessConfig) -> Result<()> {
+    use anyhow::{bail, Context};
+    use std::io::Error;
+
+    const IOPRIO_WHO_PROCESS: libc::c_int = 1;
+    const IOPRIO_CLASS_SHIFT: libc::c_int = 13;
+    const IOPRIO_CLASS_BE: libc::c_int = 2;
+    const IOPRIO_CLASS_IDLE: libc::c_int = 3;
+
+    let (nice, io_priority) = match config.priority {
+        Priority::Normal => (None, None),
+        Priority::BelowNormal => (Some(10), Some(IOPRIO_CLASS_BE << IOPRIO_CLASS_SHIFT | 7)),
+        Priority::Idle => (Some(19), Some(IOPRIO_CLASS_IDLE << IOPRIO_CLASS_SHIFT)),
+    };
+
+    let cores = if config.cores.is_empty() {
+        None
+    } else {
+        // Safety: cpu_set_t is a plain bit set, valid when zeroed.
+        let mut cores: libc::cpu_set_t = unsafe { std::mem::zeroed() };
+        for &core in &config.cores {
+            if core >= libc::CPU_SETSIZE as usize {
+                bail!("Invalid core {} in `process.cores`.", core);
+            }
+            // Safety: the core is within the set.
+            unsafe { libc::CPU_SET(core, &mut cores) };
+        }
+
+        Some(cores)
+    };
+
+    // The priorities and the affinity are per thread on Linux: the threads already running are
+    // set one by one, the later ones inherit them.
+    for entry in std::fs::read_dir("/proc/self/task").context("Cannot list the threads")? {
+        let Some(thread) = entry?
+            .file_name()
+            .to_str()
+            .and_then(|thread| thread.parse::<libc::pid_t>().ok())
+        else {
+            continue;
+        };
+
+        // A thread that exited since the listing is fine.
+        let failed =
+            |result: bool| result && Error::last_os_error().raw_os_error() != Some(libc::ESRCH);
+
+        // Safety: plain system calls, on a thread of this process.
+        unsafe {
+            if let Some(nice) = nice {
+                if failed(libc::setpriority(libc::PRIO_PROCESS, thread as libc::id_t, nice) != 0) {
+                    bail!("Cannot set the nice value: {}", Error::last_os_error());
+                }
+            }
+            if let Some(io_priority) = io_priority {
+                if failed(
+                    libc::syscall(
+                        libc::SYS_ioprio_set,
+                        IOPRIO_WHO_PROCESS,
+                        thread,
+                        io_priority,
+                    ) != 0,
+                ) {
+                    bail!("Cannot set the I/O priority: {}", Error::last_os_error());
+                }
+            }
+            if let Some(cores) = &cores {
+                if failed(libc::sched_setaffinity(thread, std::mem::size_of_val(cores), cores) != 0)
+                {
+                    bail!("Cannot pin to cores: {}", Error::last_os_error());
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Lowers the priority of the process and pins it to its cores.
+#[cfg(windows)]
+pub fn apply(config: &ProcessConfig) -> Result<()> {
+    use anyhow::bail;
+    use std::io::Error;
+    use windows_sys::Win32::System::Threading::{
+        GetCurrentProcess, SetPriorityClass, SetProcessAffinityMask, BELOW_NORMAL_PRIORITY_CLASS,
+        IDLE_PRIORITY_CLASS,
+    };
+
+    let priority_class = match config.priority {
+        Priority::Normal => None,
+        Priority::BelowNormal => Some(BELOW_NORMAL_PRIORITY_CLASS),
+        Priority::Idle => Some(IDLE_PRIORITY_CLASS),
+    };
+
+    let mut mask = 0usize;
+    for &core in &config.cores {
+        if core >= usize::BITS as usize {
+            bail!("Invalid core {} in `process.cores`.", core);
+        }
+        mask |= 1 << core;
+    }
+
+    // Safety: the pseudo handle of the current process is always valid.
+    unsafe {
+        let process = GetCurrentProcess();
+
+        if let Some(priority_class) = priority_class {
+            if SetPriorityClass(process, priority_class) == 0 {
+                bail!("Cannot set the priority: {}", Error::last_os_error());
+            }
+        }
+        if mask != 0 && SetProcessAffinityMask(process, mask) == 0 {
+            bail!("Cannot pin to cores: {}", Error::last_os_error());
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(not(any(target_os = "linux", windows)))]
+pub fn apply(config: &ProcessConfig) -> Result<()> {
+    if config.priority != Priority::Normal || !config.cores.is_empty() {
+        anyhow::bail!("The priority and cores of the process can't be set on this system.");
+    }
+
+    Ok(())
+}
@@ -0,0 +1,87 @@
+use crate::config::{Config, Metric};
+use anyhow::{bail, Result};
+use log::*;
+use std::{fmt, str::FromStr};
+
+/// Sampling interval on SteamOS, in milliseconds, longer to spare the battery.
+const STEAMOS_SAMPLE_INTERVAL: u64 = 2000;
+
+/// Kind of machine, with defaults of its own.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Profile {
+    /// No defaults of its own.
+    Desktop,
+    SteamOs,
+    RaspberryPi,
+}
+
+impl Profile {
+    pub fn detect() -> Self {
+        if is_steamos() {
+            Self::SteamOs
+        } else if is_raspberry_pi() {
+            Self::RaspberryPi
+        } else {
+            Self::Desktop
+        }
+    }
+}
+
+impl FromStr for Profile {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self> {
+        match value.to_ascii_lowercase().as_str() {
+            "desktop" => Ok(Self::Desktop),
+            "steamos" => Ok(Self::SteamOs),
+            "raspberry-pi" => Ok(Self::RaspberryPi),
+            _ => bail!("Unknown profile: {}", value),
+        }
+    }
+}
+
+impl fmt::Display for Profile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Desktop => "desktop",
+            Self::SteamOs => "steamos",
+            Self::RaspberryPi => "raspberry-pi",
+        })
+    }
+}
+
+/// Fills what the configuration leaves unset with defaults suited to the machine, the one of
+/// `profile` or the detected one.
+pub fn apply(config: &mut Config, profile: Option<Profile>) {
+    match profile.unwrap_or_else(Profile::detect) {
+        Profile::Desktop => {}
+        Profile::SteamOs => {
+            info!("SteamOS profile, devices show the APU load by default and sampling is slower.");
+
+            config.default_metric.get_or_insert(Metric::Gpu);
+            config
+                .sample_interval
+                .get_or_insert(STEAMOS_SAMPLE_INTERVAL);
+        }
+        Profile::RaspberryPi => {
+            info!("Raspberry Pi profile, devices show the SoC temperature by default.");
+
+            config.default_metric.get_or_insert(Metric::Thermal);
+        }
+    }
+}
+
+pub fn is_raspberry_pi() -> bool {
+    std::fs::read_to_string("/proc/device-tree/model")
+        .is_ok_and(|model| model.starts_with("Raspberry Pi"))
+}
+
+/// Running on SteamOS (e.g. a Steam Deck), or in a gamescope session on another distribution.
+pub fn is_steamos() -> bool {
+    let os_release = std::fs::read_to_string("/etc/os-release").unwrap_or_default();
+    if os_release.lines().any(|line| line.trim() == "ID=steamos") {
+        return true;
+    }
+
+    std::env::var("XDG_CURRENT_DESKTOP").is_ok_and(|desktop| desktop == "gamescope")
+}
@@ -0,0 +1,84 @@
+use crate::config::{Config, DROP_IN_DIRECTORY};
+use anyhow::{anyhow, Result};
+use log::*;
+use notify::{Event, EventKind, RecursiveMode, Watcher};
+use std::{path::Path, sync::Arc, time::Duration};
+use tokio::{
+    sync::{mpsc, watch},
+    time::timeout,
+};
+
+/// Editors write a file in several steps, the reload waits for them to settle.
+const SETTLE_TIME: Duration = Duration::from_millis(500);
+
+/// Reloads the configuration with `load` whenever the file at `path` changes, until the
+/// task is aborted. A configuration that doesn't load is logged and the current one kept.
+pub async fn watch(
+    path: &Path,
+    load: impl Fn() -> Result<Config>,
+    config: watch::Sender<Arc<Config>>,
+) -> Result<()> {
+    let file_name = path
+        .file_name()
+        .ok_or_else(|| anyhow!("Invalid configuration path {:?}", path))?
+        .to_owned();
+    let directory = match path.parent() {
+        Some(directory) if !directory.as_os_str().is_empty() => directory,
+        _ => Path::new("."),
+    };
+
+    // The directory is watched rather than the file, which editors replace when saving.
+    let (sender, mut events) = mpsc::unbounded_channel();
+    let mut watcher = notify::recommended_watcher(move |event: notify::Result<Event>| {
+        let _ = sender.send(event);
+    })?;
+    watcher.watch(directory, RecursiveMode::NonRecursive)?;
+    let drop_in = directory.join(DROP_IN_DIRECTORY);
+    if drop_in.is_dir() {
+        watcher.watch(&drop_in, RecursiveMode::NonRecursive)?;
+    }
+    info!("Watching configuration {:?} for changes.", path);
+
+    let is_change = |event: notify::Result<Event>| match event {
+        Ok(event) => {
+            !matches!(event.kind, EventKind::Access(_))
+                && event
+                    .paths
+                    .iter()
+                    .any(|path| path.file_name() == Some(&file_name) || path.starts_with(&drop_in))
+        }
+        Err(e) => {
+            warn!("Configuration watch error: {}", e);
+
+            false
+        }
+    };
+
+    while let Some(event) = events.recv().await {
+        if !is_change(event) {
+            continue;
+        }
+        while let Ok(Some(_)) = timeout(SETTLE_TIME, events.recv()).await {}
+
+        let loaded = match load() {
+            Ok(loaded) => loaded,
+            Err(e) => {
+                warn!(
+                    "Configuration change ignored, keeping the current one: {:#}",
+                    e
+                );
+
+                continue;
+            }
+        };
+
+        // Saving without changes, or touching the file, doesn't restart anything.
+        let unchanged =
+            serde_json::to_value(&loaded).ok() == serde_json::to_value(&**config.borrow()).ok();
+        if !unchanged {
+            config.send_replace(Arc::new(loaded));
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,80 @@
+use crate::control;
+use anyhow::{anyhow, bail, Result};
+use std::{process::Stdio, sync::OnceLock};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+    process::Command,
+};
+
+static REMOTE: OnceLock<Option<Remote>> = OnceLock::new();
+
+/// Machine whose running client the control commands go to, over SSH, for `--remote`.
+#[derive(Debug, Clone)]
+pub struct Remote {
+    /// SSH destination, e.g. `user@host` or a host of `~/.ssh/config`.
+    pub destination: String,
+    /// Client on the remote machine, relaying the requests to its control socket.
+    pub command: String,
+}
+
+/// Sends the control requests to `remote` rather than to the client running here.
+///
+/// Must be called before the first request, later calls are ignored.
+pub fn init(remote: Option<Remote>) {
+    REMOTE.get_or_init(|| remote);
+}
+
+pub fn destination() -> Option<&'static Remote> {
+    REMOTE.get_or_init(|| None).as_ref()
+}
+
+/// Sends a request line through `open_rgb_client relay` on the remote machine and returns the
+/// answer line of its client. SSH authenticates and encrypts, so the control socket never has
+/// to be exposed on the network.
+pub async fn exchange(remote: &Remote, line: &str) -> Result<String> {
+    // Batch mode fails rather than prompting for a password in the middle of the output.
+    let mut ssh = Command::new("ssh")
+        .args(["-T", "-o", "BatchMode=yes", "--", &remote.destination])
+        .args([&remote.command, "relay"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()
+        .map_err(|e| anyhow!("Cannot run ssh: {}", e))?;
+
+    let mut stdin = ssh.stdin.take().ok_or_else(|| anyhow!("No ssh input."))?;
+    stdin.write_all(line.as_bytes()).await?;
+    drop(stdin);
+
+    let output = ssh.wait_with_output().await?;
+    let response = String::from_utf8_lossy(&output.stdout);
+    if response.trim().is_empty() {
+        bail!(
+            "Cannot reach {} over SSH: {}",
+            remote.destination,
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+
+    Ok(response.into_owned())
+}
+
+/// Relays a request line from the standard input to the client running here and its answer to
+/// the standard output, for `exchange` on the other end.
+pub async fn relay() -> Result<()> {
+    let mut line = String::new();
+    BufReader::new(tokio::io::stdin())
+        .read_line(&mut line)
+        .await?;
+
+    let response = control::exchange(&line)
+        .await
+        .unwrap_or_else(|e| format!("error: {}\n", e));
+
+    let mut stdout = tokio::io::stdout();
+    stdout.write_all(response.as_bytes()).await?;
+    stdout.flush().await?;
+
+    Ok(())
+}
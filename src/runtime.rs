@@ -0,0 +1,187 @@
+#[cfg(target_os = "linux")]
+use crate::sampler::read_file;
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, Instant};
+
+/// Time between two readings of the resource usage of the process.
+const SAMPLE_INTERVAL: Duration = Duration::from_secs(5);
+/// Weight of each new frame in the jitter, as in RTP's interarrival jitter.
+const JITTER_GAIN: f64 = 1.0 / 16.0;
+
+/// Resources used by the client itself, to check it stays a lightweight background service.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+pub struct RuntimeStats {
+    /// CPU time used since the previous reading, in percent of one core.
+    pub cpu_percent: f64,
+    /// Memory in use, `None` where it can't be read.
+    pub rss_bytes: Option<u64>,
+    /// Tasks alive in the async runtime.
+    pub tasks: usize,
+    /// Smoothed variation of the time between consecutive frames.
+    pub frame_jitter_ms: f64,
+}
+
+impl RuntimeStats {
+    /// The stats in the Prometheus text format.
+    pub fn to_prometheus(self) -> String {
+        let mut text = String::new();
+        let mut gauge = |name: &str, help: &str, value: String| {
+            let name = format!("open_rgb_client_{}", name);
+            text += &format!("# HELP {} {}\n", name, help);
+            text += &format!("# TYPE {} gauge\n", name);
+            text += &format!("{} {}\n", name, value);
+        };
+
+        gauge(
+            "cpu_percent",
+            "CPU used by the client, in percent of one core.",
+            self.cpu_percent.to_string(),
+        );
+        if let Some(rss_bytes) = self.rss_bytes {
+            gauge(
+                "resident_memory_bytes",
+                "Memory in use by the client.",
+                rss_bytes.to_string(),
+            );
+        }
+        gauge(
+            "tasks",
+            "Tasks alive in the client's async runtime.",
+            self.tasks.to_string(),
+        );
+        gauge(
+            "frame_jitter_seconds",
+            "Smoothed variation of the time between consecutive frames.",
+            (self.frame_jitter_ms / 1000.0).to_string(),
+        );
+
+        text
+    }
+}
+
+/// Keeps the runtime stats up to date as frames are rendered.
+#[derive(Debug, Default)]
+pub struct RuntimeMonitor {
+    last_frame: Option<Instant>,
+    last_interval: Option<Duration>,
+    jitter: f64,
+    /// Time and CPU time of the process at the previous reading.
+    last_sample: Option<(Instant, Duration)>,
+    stats: RuntimeStats,
+    /// Contents of the files of /proc last read, kept from one reading to the next.
+    #[cfg(target_os = "linux")]
+    buffer: String,
+}
+
+impl RuntimeMonitor {
+    /// Accounts a frame rendered at `now`, returning the stats when they were read again.
+    pub fn record_frame(&mut self, now: Instant) -> Option<RuntimeStats> {
+        if let Some(last_frame) = self.last_frame {
+            let interval = now - last_frame;
+            if let Some(last_interval) = self.last_interval {
+                let difference = interval.abs_diff(last_interval).as_secs_f64() * 1000.0;
+                self.jitter += (difference - self.jitter) * JITTER_GAIN;
+            }
+            self.last_interval = Some(interval);
+        }
+        self.last_frame = Some(now);
+
+        if self
+            .last_sample
+            .is_some_and(|(time, _)| now - time < SAMPLE_INTERVAL)
+        {
+            return None;
+        }
+
+        #[cfg(target_os = "linux")]
+        let usage = process_usage(&mut self.buffer);
+        #[cfg(not(target_os = "linux"))]
+        let usage = process_usage();
+        let cpu_time = usage.map(|(cpu_time, _)| cpu_time).unwrap_or_default();
+        if let Some((time, last_cpu_time)) = self.last_sample {
+            self.stats.cpu_percent = cpu_time.saturating_sub(last_cpu_time).as_secs_f64()
+                / (now - time).as_secs_f64()
+                * 100.0;
+        }
+        self.last_sample = Some((now, cpu_time));
+
+        self.stats.rss_bytes = usage.map(|(_, rss_bytes)| rss_bytes);
+        self.stats.tasks = tokio::runtime::Handle::try_current()
+            .map_or(0, |runtime| runtime.metrics().num_alive_tasks());
+        self.stats.frame_jitter_ms = self.jitter;
+
+        Some(self.stats)
+    }
+}
+
+/// CPU time used by the process so far, and its resident memory.
+#[cfg(target_os = "linux")]
+fn process_usage(buffer: &mut String) -> Option<(Duration, u64)> {
+    // Times in /proc are in USER_HZ, which is 100 on every architecture.
+    const TICKS_PER_SECOND: u64 = 100;
+
+    let stat = read_file("/proc/self/stat", buffer).ok()?;
+    // The command name may contain spaces, the fields after it are the 14th and 15th.
+    let mut fields = stat.rsplit_once(')')?.1.split_whitespace().skip(11);
+    let user_ticks: u64 = fields.next()?.parse().ok()?;
+    let system_ticks: u64 = fields.next()?.parse().ok()?;
+    let cpu_time = Duration::from_millis((user_ticks + system_ticks) * 1000 / TICKS_PER_SECOND);
+
+    let status = read_file("/proc/self/status", buffer).ok()?;
+    let rss_kilobytes: u64 = status
+        .lines()
+        .find_map(|line| line.strip_prefix("VmRSS:"))?
+        .trim()
+        .trim_end_matches("kB")
+        .trim()
+        .parse()
+        .ok()?;
+
+    Some((cpu_time, rss_kilobytes * 1024))
+}
+
+#[cfg(windows)]
+fn process_usage() -> Option<(Duration, u64)> {
+    use windows_sys::Win32::{
+        Foundation::FILETIME,
+        System::{
+            ProcessStatus::{K32GetProcessMemoryInfo, PROCESS_MEMORY_COUNTERS},
+            Threading::{GetCurrentProcess, GetProcessTimes},
+        },
+    };
+
+    let filetime = |time: FILETIME| {
+        // In units of 100 ns.
+        let units = (time.dwHighDateTime as u64) << 32 | time.dwLowDateTime as u64;
+        Duration::from_nanos(units * 100)
+    };
+
+    unsafe {
+        let process = GetCurrentProcess();
+
+        let mut times = [FILETIME {
+            dwLowDateTime: 0,
+            dwHighDateTime: 0,
+        }; 4];
+        let [creation, exit, kernel, user] = &mut times;
+        if GetProcessTimes(process, creation, exit, kernel, user) == 0 {
+            return None;
+        }
+
+        let mut counters: PROCESS_MEMORY_COUNTERS = std::mem::zeroed();
+        let size = std::mem::size_of::<PROCESS_MEMORY_COUNTERS>() as u32;
+        if K32GetProcessMemoryInfo(process, &mut counters, size) == 0 {
+            return None;
+        }
+
+        Some((
+            filetime(times[2]) + filetime(times[3]),
+            counters.WorkingSetSize as u64,
+        ))
+    }
+}
+
+#[cfg(not(any(target_os = "linux", windows)))]
+fn process_usage() -> Option<(Duration, u64)> {
+    None
+}
@@ -0,0 +1,163 @@
+use log::*;
+use openrgb::data::Color;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+/// Flashes per second from which flashing lights can trigger seizures.
+const DANGER_FREQUENCY: f32 = 3.0;
+
+/// Limits applied to every frame sent, whatever the effects, sequences and modes produce.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SafetyConfig {
+    /// Flashes per second an LED can make at most, a flash going one way and back.
+    pub max_flash_frequency: f32,
+    /// Change of relative luminance, from 0 to 1, counting as a flash.
+    pub max_luminance_delta: f32,
+}
+
+impl Default for SafetyConfig {
+    fn default() -> Self {
+        Self {
+            max_flash_frequency: 2.0,
+            max_luminance_delta: 0.1,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Led {
+    color: Color,
+    /// Luminance reached by the last flash, and when.
+    flash: Option<(f32, Instant)>,
+}
+
+/// Keeps the LEDs from flashing faster than allowed: after a large luminance change, an LED
+/// stays within the luminance delta of where it went until the next change is allowed.
+#[derive(Debug)]
+pub struct FlashLimiter {
+    /// Shortest time between two large changes, two of them making a flash.
+    min_interval: Duration,
+    max_delta: f32,
+    controllers: HashMap<String, Vec<Led>>,
+}
+
+impl FlashLimiter {
+    pub fn new(config: &SafetyConfig) -> Self {
+        if config.max_flash_frequency >= DANGER_FREQUENCY {
+            warn!(
+                "Flashes up to {} Hz are allowed, {} Hz and more can trigger seizures.",
+                config.max_flash_frequency, DANGER_FREQUENCY
+            );
+        }
+
+        Self {
+            min_interval: Duration::from_secs_f32(
+                1.0 / (2.0 * config.max_flash_frequency.max(0.01)),
+            ),
+            max_delta: config.max_luminance_delta.clamp(0.0, 1.0),
+            controllers: HashMap::new(),
+        }
+    }
+
+    /// Limits the colors about to be sent to a controller, identified by `key`.
+    pub fn limit(&mut self, key: &str, colors: &mut [Color], now: Instant) {
+        // Looked up first, the key is only copied for a new controller.
+        if !self.controllers.contains_key(key) {
+            self.controllers.insert(key.to_owned(), vec![]);
+        }
+        let leds = self.controllers.get_mut(key).unwrap();
+        if leds.len() != colors.len() {
+            // First frame, or the controller changed: nothing to compare with.
+            *leds = colors
+                .iter()
+                .map(|color| Led {
+                    color: *color,
+                    flash: None,
+                })
+                .collect();
+
+            return;
+        }
+
+        for (led, color) in leds.iter_mut().zip(colors.iter_mut()) {
+            let previous = luminance(&led.color);
+            let target = luminance(color);
+
+            match led.flash {
+                Some((reached, since)) if now - since < self.min_interval => {
+                    let bound = target.clamp(reached - self.max_delta, reached + self.max_delta);
+                    if bound != target {
+                        let share = match target - previous {
+                            change if change.abs() > f32::EPSILON => {
+                                ((bound - previous) / change).clamp(0.0, 1.0)
+                            }
+                            _ => 0.0,
+                        };
+
+                        *color = lerp_color(share, &led.color, color);
+                    }
+                }
+                _ if (target - previous).abs() > self.max_delta => {
+                    led.flash = Some((target, now));
+                }
+                _ => {}
+            }
+
+            led.color = *color;
+        }
+    }
+}
+
+/// Relative luminance, from 0 to 1.
+fn luminance(color: &Color) -> f32 {
+    (0.2126 * color.r as f32 + 0.7152 * color.g as f32 + 0.0722 * color.b as f32) / 255.0
+}
+
+fn lerp_color(share: f32, start: &Color, end: &Color) -> Color {
+    let lerp = |start: u8, end: u8| (start as f32 + (end as f32 - start as f32) * share) as u8;
+
+    Color::new(
+        lerp(start.r, end.r),
+        lerp(start.g, end.g),
+        lerp(start.b, end.b),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn strobe_is_held_back() {
+        let mut limiter = FlashLimiter::new(&SafetyConfig::default());
+        let black = Color::new(0, 0, 0);
+        let white = Color::new(0xFF, 0xFF, 0xFF);
+        let start = Instant::now();
+
+        // A 10 Hz strobe, changing every 50 ms.
+        let mut sent = vec![];
+        for frame in 0..40u32 {
+            let mut colors = [if frame % 2 == 0 { black } else { white }];
+            limiter.limit(
+                "strip",
+                &mut colors,
+                start + Duration::from_millis(50) * frame,
+            );
+
+            sent.push(luminance(&colors[0]));
+        }
+
+        let large_changes = sent
+            .windows(2)
+            .filter(|pair| (pair[1] - pair[0]).abs() > 0.2)
+            .count();
+
+        // 2 seconds at 2 flashes per second at most, each flash being two changes.
+        assert!(large_changes <= 8, "{} large changes", large_changes);
+        assert!(large_changes > 0);
+    }
+}
@@ -0,0 +1,672 @@
+use crate::{
+    battery::BatteryMonitor,
+    color_manager::Usage,
+    config::{Config, Metric, StalenessConfig},
+    control::ControlState,
+    cpu::CpuMonitor,
+    cpu_temperature::CpuTemperatureMonitor,
+    disk::DiskMonitor,
+    gpu::GpuMonitor,
+    mail::MailMonitor,
+    memory::MemoryMonitor,
+    thermal::ThermalMonitor,
+    units::{quantity, Quantity},
+};
+use anyhow::{bail, Result};
+use log::*;
+use ringbuffer::{AllocRingBuffer, RingBuffer};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{HashMap, HashSet},
+    fs::File,
+    io::{self, Read},
+    ops::RangeInclusive,
+    path::Path,
+    sync::Arc,
+    time::{Duration, Instant, SystemTime},
+};
+
+/// Default sampling interval and averaging window, in milliseconds.
+const SAMPLE_INTERVAL: u64 = 500;
+const AVERAGING_WINDOW: u64 = 4000;
+
+/// Sampling intervals allowed, in milliseconds: shorter ones make the CPU utilization noisy.
+const SAMPLE_INTERVALS: RangeInclusive<u64> = 50..=60_000;
+/// Longest averaging window, in milliseconds.
+const MAX_AVERAGING_WINDOW: u64 = 10 * 60_000;
+
+/// Change of a metric between two samples under which it is considered stable.
+const STABLE_CHANGE: f32 = 0.02;
+
+/// Time between two samples, on top of the longest interval, past which the machine is taken
+/// as having slept.
+const SUSPEND_GAP: Duration = Duration::from_secs(10);
+/// Brightness of the zones of the averaged metrics while their window fills up after a resume.
+const WARMING_BRIGHTNESS: f32 = 0.5;
+
+/// Margin around 0 to 1 within which samples are clamped rather than rejected, for rounding
+/// errors.
+const RANGE_SLACK: f32 = 0.01;
+
+/// Latest value of each metric, `None` for the ones this machine can't provide.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct Samples {
+    pub cpu: f32,
+    pub gpu: Option<f32>,
+    pub cpu_temperature: Option<f32>,
+    pub gpu_temperature: Option<f32>,
+    pub memory: Option<f32>,
+    pub disk: Option<f32>,
+    pub power: Option<f32>,
+    pub thermal: Option<f32>,
+    pub battery: Option<f32>,
+    pub mail: Option<f32>,
+    pub brightness: Brightness,
+}
+
+/// Brightness of the zones of each metric, going down while its value is stale, see
+/// `StalenessConfig`, or warming up after a resume.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Brightness {
+    pub cpu: f32,
+    pub gpu: f32,
+    pub cpu_temperature: f32,
+    pub gpu_temperature: f32,
+    pub memory: f32,
+    pub disk: f32,
+    pub power: f32,
+    pub thermal: f32,
+    pub battery: f32,
+    pub mail: f32,
+}
+
+impl Default for Brightness {
+    fn default() -> Self {
+        Self {
+            cpu: 1.0,
+            gpu: 1.0,
+            cpu_temperature: 1.0,
+            gpu_temperature: 1.0,
+            memory: 1.0,
+            disk: 1.0,
+            power: 1.0,
+            thermal: 1.0,
+            battery: 1.0,
+            mail: 1.0,
+        }
+    }
+}
+
+impl Brightness {
+    pub fn get(&self, metric: Metric) -> f32 {
+        match metric {
+            Metric::Cpu => self.cpu,
+            Metric::Gpu => self.gpu,
+            Metric::CpuTemperature => self.cpu_temperature,
+            Metric::GpuTemperature => self.gpu_temperature,
+            Metric::Memory => self.memory,
+            Metric::Disk => self.disk,
+            Metric::Power => self.power,
+            Metric::Thermal => self.thermal,
+            Metric::Battery => self.battery,
+            Metric::Mail => self.mail,
+            Metric::None => 1.0,
+        }
+    }
+}
+
+impl Samples {
+    pub fn get(&self, metric: Metric) -> Option<f32> {
+        match metric {
+            Metric::Cpu => Some(self.cpu),
+            Metric::Gpu => self.gpu,
+            Metric::CpuTemperature => self.cpu_temperature,
+            Metric::GpuTemperature => self.gpu_temperature,
+            Metric::Memory => self.memory,
+            Metric::Disk => self.disk,
+            Metric::Power => self.power,
+            Metric::Thermal => self.thermal,
+            Metric::Battery => self.battery,
+            Metric::Mail => self.mail,
+            Metric::None => None,
+        }
+    }
+
+    /// Values of the available metrics in their units.
+    pub fn quantities<'a>(
+        &'a self,
+        config: &'a Config,
+    ) -> impl Iterator<Item = (Metric, Quantity)> + 'a {
+        [
+            Metric::Cpu,
+            Metric::Gpu,
+            Metric::CpuTemperature,
+            Metric::GpuTemperature,
+            Metric::Memory,
+            Metric::Disk,
+            Metric::Power,
+            Metric::Thermal,
+            Metric::Battery,
+            Metric::Mail,
+        ]
+        .into_iter()
+        .filter_map(|metric| Some((metric, quantity(metric, self.get(metric)?, config)?)))
+    }
+
+    /// Devices showing an unavailable metric stay at the idle color.
+    pub fn usage(&self) -> Usage {
+        Usage {
+            cpu: self.cpu,
+            gpu: self.gpu.unwrap_or_default(),
+            cpu_temperature: self.cpu_temperature.unwrap_or_default(),
+            gpu_temperature: self.gpu_temperature.unwrap_or_default(),
+            memory: self.memory.unwrap_or_default(),
+            disk: self.disk.unwrap_or_default(),
+            power: self.power.unwrap_or_default(),
+            thermal: self.thermal.unwrap_or_default(),
+            battery: self.battery.unwrap_or_default(),
+            mail: self.mail.unwrap_or_default(),
+            brightness: self.brightness,
+        }
+    }
+}
+
+/// Sampling settings from the command line, taking precedence over the configuration.
+#[derive(Debug, Clone, Default)]
+pub struct SamplingOptions {
+    /// Milliseconds.
+    pub interval: Option<u64>,
+    /// Milliseconds.
+    pub window: Option<u64>,
+}
+
+impl SamplingOptions {
+    pub fn apply(&self, config: &mut Config) {
+        if let Some(interval) = self.interval {
+            config.sample_interval = Some(interval);
+        }
+        if let Some(window) = self.window {
+            config.averaging_window = Some(window);
+        }
+    }
+}
+
+pub struct Sampler {
+    interval: Duration,
+    min_interval: Duration,
+    max_interval: Duration,
+    last: Option<Samples>,
+    cpu: CpuMonitor,
+    gpu: GpuMonitor,
+    cpu_temperature: CpuTemperatureMonitor,
+    memory: MemoryMonitor,
+    disk: DiskMonitor,
+    thermal: ThermalMonitor,
+    battery: BatteryMonitor,
+    mail: MailMonitor,
+    staleness: StalenessConfig,
+    /// Last value of each metric and when it was sampled.
+    held: HashMap<Metric, (f32, Instant)>,
+    /// Metrics whose monitor failed, to log the failure once.
+    failing: HashSet<Metric>,
+    /// Time the CPU and GPU utilization are averaged over, whatever the interval.
+    window: Duration,
+    /// Latest utilization samples and when they were taken.
+    cpu_samples: AllocRingBuffer<(Instant, f32)>,
+    gpu_samples: AllocRingBuffer<(Instant, f32)>,
+    /// Wall clock time of the latest measure, which unlike `Instant` goes on while the machine
+    /// sleeps.
+    measured: Option<SystemTime>,
+    /// Samples left until the averages are meaningful again after a resume.
+    warming: usize,
+    /// How long the samples stay in the history of the control state.
+    retention: Duration,
+    control: Arc<ControlState>,
+    /// Start of the sampling, the time of the demo waveforms.
+    start: Instant,
+}
+
+impl Sampler {
+    pub fn new(config: &Config, control: Arc<ControlState>) -> Result<Self> {
+        let (interval, max_interval, window) = sampling(config)?;
+        // As many samples as the window holds at the shortest interval.
+        let capacity = window_samples(window, interval).next_power_of_two();
+        // Likewise for the history, both ends of the retention included.
+        control
+            .history
+            .reserve(window_samples(config.history.retention, interval) + 1);
+
+        Ok(Self {
+            interval,
+            min_interval: interval,
+            max_interval,
+            last: None,
+            cpu: CpuMonitor::new(),
+            gpu: GpuMonitor::new(&config.gpu_temperature),
+            cpu_temperature: CpuTemperatureMonitor::new(&config.cpu_temperature),
+            memory: MemoryMonitor::new(),
+            disk: DiskMonitor::new(&config.disk),
+            thermal: ThermalMonitor::new(&config.thermal),
+            battery: BatteryMonitor::new(),
+            mail: MailMonitor::new(config.mail.as_ref(), &config.budgets),
+            staleness: config.staleness.clone(),
+            held: HashMap::new(),
+            failing: HashSet::new(),
+            window,
+            cpu_samples: AllocRingBuffer::new(capacity),
+            gpu_samples: AllocRingBuffer::new(capacity),
+            measured: None,
+            warming: 0,
+            retention: config.history.retention,
+            control,
+            start: Instant::now(),
+        })
+    }
+
+    /// Samples until the averages are as smooth as in a running client, at the shortest
+    /// interval.
+    pub async fn warm_up(&mut self) -> Result<Samples> {
+        for _ in 1..window_samples(self.window, self.interval) {
+            self.measure().await?;
+        }
+
+        self.measure().await
+    }
+
+    /// Samples the metrics, adapting the interval of the next sample to how much they moved.
+    pub async fn sample(&mut self) -> Result<Samples> {
+        let samples = self.measure().await?;
+        self.adapt(&samples);
+
+        Ok(samples)
+    }
+
+    /// Measures the CPU utilization over the sampling interval, then reads the other metrics.
+    async fn measure(&mut self) -> Result<Samples> {
+        let non_idle = loop {
+            // CPU utilization.
+            let start = self.cpu.times()?;
+            tokio::time::sleep(self.interval).await;
+            let end = self.cpu.times()?;
+
+            // After a sleep, the averages span the gap and the CPU time measured over it is
+            // off: they start over, from a new measure.
+            let now = SystemTime::now();
+            let gap = self
+                .measured
+                .replace(now)
+                .and_then(|measured| now.duration_since(measured).ok())
+                .unwrap_or_default();
+            if gap <= self.max_interval + SUSPEND_GAP {
+                break end.non_idle_since(start);
+            }
+
+            info!("Resumed after {:?}, restarting the averages.", gap);
+            self.resume();
+        };
+
+        let measured = Instant::now();
+        let demo = self
+            .control
+            .demo
+            .map(|waveform| waveform.load(self.start.elapsed()));
+        let cpu = match demo {
+            Some((cpu, _)) => cpu,
+            None => non_idle,
+        };
+        // A rejected sample leaves the average to the previous ones.
+        if let Some(cpu) = self.check(Metric::Cpu, Ok(Some(cpu))) {
+            self.cpu_samples.push((measured, cpu));
+        }
+
+        // Metrics turned off aren't read, e.g. so the GPU of a laptop can sleep.
+        let config = self.control.config();
+        let enabled = |metric| !config.disabled_metrics.contains(&metric);
+
+        // GPU utilization.
+        let gpu = match demo {
+            Some((_, gpu)) => Ok(Some(gpu)),
+            None if enabled(Metric::Gpu) => self.gpu.utilization(),
+            None => Ok(None),
+        };
+        let gpu = match self.check(Metric::Gpu, gpu) {
+            Some(gpu_usage) => {
+                self.gpu_samples.push((measured, gpu_usage));
+
+                Some(average(&self.gpu_samples, self.window, measured))
+            }
+            None => None,
+        };
+
+        // Memory and temperatures, already smooth.
+        let cpu_temperature = if enabled(Metric::CpuTemperature) {
+            self.cpu_temperature.load(&config)
+        } else {
+            Ok(None)
+        };
+        let cpu_temperature = self.check(Metric::CpuTemperature, cpu_temperature);
+        let gpu_temperature = if enabled(Metric::GpuTemperature) {
+            self.gpu.temperature()
+        } else {
+            Ok(None)
+        };
+        let gpu_temperature = self.check(Metric::GpuTemperature, gpu_temperature);
+        let memory = if enabled(Metric::Memory) {
+            self.memory.usage()
+        } else {
+            Ok(None)
+        };
+        let memory = self.check(Metric::Memory, memory);
+        let disk = if enabled(Metric::Disk) {
+            self.disk.load()
+        } else {
+            Ok(None)
+        };
+        let disk = self.check(Metric::Disk, disk);
+        // Estimated from the last frame sent, so it follows what the LEDs show.
+        let power = enabled(Metric::Power).then(|| {
+            (self.control.power.total() as f32 / config.power.saturation.max(f32::EPSILON))
+                .clamp(0.0, 1.0)
+        });
+        let power = self.check(Metric::Power, Ok(power));
+        let thermal = if enabled(Metric::Thermal) {
+            self.thermal.load()
+        } else {
+            Ok(None)
+        };
+        let thermal = self.check(Metric::Thermal, thermal);
+        let battery = if enabled(Metric::Battery) {
+            self.battery.drain()
+        } else {
+            Ok(None)
+        };
+        let battery = self.check(Metric::Battery, battery);
+        let mail = self.mail.unread().filter(|_| enabled(Metric::Mail));
+        let mail = self.check(Metric::Mail, Ok(mail));
+
+        let mut samples = Samples {
+            cpu: average(&self.cpu_samples, self.window, measured),
+            gpu,
+            cpu_temperature,
+            gpu_temperature,
+            memory,
+            disk,
+            power,
+            thermal,
+            battery,
+            mail,
+            brightness: Brightness::default(),
+        };
+        if self.warming > 0 {
+            self.warming -= 1;
+            samples.brightness.cpu = WARMING_BRIGHTNESS;
+            samples.brightness.gpu = WARMING_BRIGHTNESS;
+        }
+        self.control.injections.apply(&mut samples);
+        self.hold_stale(&mut samples, &config.disabled_metrics);
+        self.control
+            .history
+            .push(SystemTime::now(), samples, self.retention);
+
+        Ok(samples)
+    }
+
+    /// Forgets the samples taken before the machine slept, and the interval they led to.
+    fn resume(&mut self) {
+        self.cpu_samples.clear();
+        self.gpu_samples.clear();
+        self.held.clear();
+        self.last = None;
+        self.interval = self.min_interval;
+        self.warming = window_samples(self.window, self.min_interval);
+    }
+
+    /// The sample of a metric, `None` when its monitor failed or returned an invalid value, the
+    /// last valid one being held instead.
+    fn check(&mut self, metric: Metric, sample: Result<Option<f32>>) -> Option<f32> {
+        match sample.and_then(validate) {
+            Ok(sample) => {
+                if self.failing.remove(&metric) {
+                    info!("Sampling {} again.", metric);
+                }
+
+                sample
+            }
+            Err(e) => {
+                // Failing monitors return errors, which allocate.
+                #[cfg(feature = "alloc-audit")]
+                crate::alloc_audit::expect_allocations();
+
+                if self.failing.insert(metric) {
+                    warn!("Can't sample {}: {}", metric, e);
+                }
+
+                None
+            }
+        }
+    }
+
+    /// Keeps the last value of the metrics that can't be sampled anymore, dimming their zones
+    /// as it gets old. The ones turned off have no value.
+    fn hold_stale(&mut self, samples: &mut Samples, disabled: &[Metric]) {
+        let now = Instant::now();
+        let staleness = &self.staleness;
+
+        for (metric, sample, brightness) in [
+            (Metric::Gpu, &mut samples.gpu, &mut samples.brightness.gpu),
+            (
+                Metric::CpuTemperature,
+                &mut samples.cpu_temperature,
+                &mut samples.brightness.cpu_temperature,
+            ),
+            (
+                Metric::GpuTemperature,
+                &mut samples.gpu_temperature,
+                &mut samples.brightness.gpu_temperature,
+            ),
+            (
+                Metric::Memory,
+                &mut samples.memory,
+                &mut samples.brightness.memory,
+            ),
+            (
+                Metric::Disk,
+                &mut samples.disk,
+                &mut samples.brightness.disk,
+            ),
+            (
+                Metric::Power,
+                &mut samples.power,
+                &mut samples.brightness.power,
+            ),
+            (
+                Metric::Thermal,
+                &mut samples.thermal,
+                &mut samples.brightness.thermal,
+            ),
+            (
+                Metric::Battery,
+                &mut samples.battery,
+                &mut samples.brightness.battery,
+            ),
+            (
+                Metric::Mail,
+                &mut samples.mail,
+                &mut samples.brightness.mail,
+            ),
+        ] {
+            if disabled.contains(&metric) {
+                self.held.remove(&metric);
+
+                continue;
+            }
+
+            match *sample {
+                Some(value) => {
+                    self.held.insert(metric, (value, now));
+                }
+                None => {
+                    if let Some((value, sampled)) = self.held.get(&metric) {
+                        *sample = Some(*value);
+                        *brightness = staleness.brightness(now - *sampled);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Doubles the interval while the metrics are stable, back to the shortest as soon as one
+    /// of them moves.
+    fn adapt(&mut self, samples: &Samples) {
+        let stable = self.last.is_some_and(|last| {
+            let (last, usage) = (last.usage(), samples.usage());
+
+            [
+                (last.cpu, usage.cpu),
+                (last.gpu, usage.gpu),
+                (last.cpu_temperature, usage.cpu_temperature),
+                (last.gpu_temperature, usage.gpu_temperature),
+                (last.memory, usage.memory),
+                (last.disk, usage.disk),
+                (last.power, usage.power),
+                (last.thermal, usage.thermal),
+                (last.battery, usage.battery),
+                (last.mail, usage.mail),
+            ]
+            .iter()
+            .all(|(last, value)| (value - last).abs() < STABLE_CHANGE)
+        });
+        self.last = Some(*samples);
+
+        let interval = if stable {
+            (self.interval * 2).min(self.max_interval)
+        } else {
+            self.min_interval
+        };
+
+        if interval != self.interval {
+            debug!("Sampling every {:?}.", interval);
+
+            self.interval = interval;
+        }
+    }
+}
+
+/// Sampling interval of the configuration, the longest one it slows down to and the averaging
+/// window, failing when they're out of range.
+pub fn sampling(config: &Config) -> Result<(Duration, Duration, Duration)> {
+    let interval = config.sample_interval.unwrap_or(SAMPLE_INTERVAL);
+    if !SAMPLE_INTERVALS.contains(&interval) {
+        bail!(
+            "Sampling interval of {} ms out of range, it takes {} to {} ms.",
+            interval,
+            SAMPLE_INTERVALS.start(),
+            SAMPLE_INTERVALS.end()
+        );
+    }
+
+    let window = config.averaging_window.unwrap_or(AVERAGING_WINDOW);
+    if !(interval..=MAX_AVERAGING_WINDOW).contains(&window) {
+        bail!(
+            "Averaging window of {} ms out of range, it takes the sampling interval ({} ms) to {} ms.",
+            window,
+            interval,
+            MAX_AVERAGING_WINDOW
+        );
+    }
+
+    let max_interval = config.max_sample_interval.unwrap_or(interval);
+    if !(interval..=*SAMPLE_INTERVALS.end()).contains(&max_interval) {
+        bail!(
+            "Longest sampling interval of {} ms out of range, it takes the sampling interval ({} ms) to {} ms.",
+            max_interval,
+            interval,
+            SAMPLE_INTERVALS.end()
+        );
+    }
+
+    Ok((
+        Duration::from_millis(interval),
+        Duration::from_millis(max_interval),
+        Duration::from_millis(window),
+    ))
+}
+
+/// Contents of a file read into `buffer`, reusing its memory: the monitors read theirs at every
+/// sample.
+pub fn read_file(path: impl AsRef<Path>, buffer: &mut String) -> io::Result<&str> {
+    buffer.clear();
+    File::open(path)?.read_to_string(buffer)?;
+
+    Ok(buffer)
+}
+
+/// Number of samples taken over `window` at `interval`.
+fn window_samples(window: Duration, interval: Duration) -> usize {
+    window.as_millis().div_ceil(interval.as_millis()) as usize
+}
+
+/// A sample clamped to 0 to 1, failing on NaN, infinite and out of range values: a flaky sensor
+/// or driver would otherwise show up as black or garbage frames.
+fn validate(sample: Option<f32>) -> Result<Option<f32>> {
+    match sample {
+        // NaN is in no range.
+        Some(value) if !(-RANGE_SLACK..=1.0 + RANGE_SLACK).contains(&value) => {
+            bail!("invalid sample {}", value)
+        }
+        sample => Ok(sample.map(|value| value.clamp(0.0, 1.0))),
+    }
+}
+
+/// Average of the samples taken within `window` before `now`, 0 before the first one. The
+/// same time is averaged over whether the sampling slowed down or not.
+fn average(samples: &AllocRingBuffer<(Instant, f32)>, window: Duration, now: Instant) -> f32 {
+    let (total, count) = samples
+        .iter()
+        .rev()
+        .take_while(|(taken, _)| now.saturating_duration_since(*taken) < window)
+        .fold((0.0, 0), |(total, count), (_, sample)| {
+            (total + sample, count + 1)
+        });
+
+    total / count.max(1) as f32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn averages_span_the_window_whatever_the_interval() {
+        let now = Instant::now();
+        let mut samples = AllocRingBuffer::new(8);
+        for (age, sample) in [(5000, 1.0), (3000, 0.5), (1000, 0.0), (0, 0.25)] {
+            samples.push((now - Duration::from_millis(age), sample));
+        }
+
+        assert_eq!(average(&samples, Duration::from_millis(4000), now), 0.25);
+        assert_eq!(average(&samples, Duration::from_millis(500), now), 0.25);
+        assert_eq!(
+            average(&AllocRingBuffer::new(8), Duration::from_secs(4), now),
+            0.0
+        );
+    }
+
+    #[test]
+    fn longest_interval_is_validated() {
+        let mut config = Config {
+            max_sample_interval: Some(4000),
+            ..Config::default()
+        };
+        let (interval, max_interval, _) = sampling(&config).unwrap();
+        assert_eq!(
+            (interval, max_interval),
+            (Duration::from_millis(500), Duration::from_secs(4))
+        );
+
+        for max_sample_interval in [100, 60_001] {
+            config.max_sample_interval = Some(max_sample_interval);
+            assert!(sampling(&config).is_err(), "{}", max_sample_interval);
+        }
+    }
+}
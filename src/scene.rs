@@ -0,0 +1,408 @@
+use crate::{
+    config::{Metric, ProfileConfig},
+    control::{self, ControlRequest, ControlState},
+    notifications::Notification,
+    sampler::Samples,
+};
+use anyhow::{anyhow, bail, Result};
+use chrono::{Datelike, Local, NaiveDateTime, NaiveTime};
+use log::*;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::{
+    collections::HashSet,
+    fmt,
+    str::FromStr,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+/// Time between two looks at the schedules.
+const SCHEDULE_INTERVAL: Duration = Duration::from_secs(20);
+/// Drop of a metric under the level of an alert before it can be raised again, so a metric
+/// hovering around the level doesn't raise it on every sample.
+const ALERT_HYSTERESIS: f32 = 0.05;
+const DAYS: [&str; 7] = ["mon", "tue", "wed", "thu", "fri", "sat", "sun"];
+
+/// Named set of layers, alerts and triggers switched to as a whole, e.g. `night` or `stream`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SceneConfig {
+    /// Profile the scene starts from, the configuration as loaded when not set.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub profile: Option<String>,
+    /// Metrics, palettes and devices applied over the profile, as a profile's are.
+    #[serde(flatten)]
+    pub layers: ProfileConfig,
+    /// Alerts raised on top of the configured ones while the scene is in use.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub alerts: Vec<AlertRule>,
+    /// Brightness of every LED set when switching to the scene, from 0 to 1.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub brightness: Option<f32>,
+    /// Sequence played when switching to the scene.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sequence: Option<String>,
+    /// Local times the client switches to the scene.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub schedule: Vec<ScheduleEntry>,
+    /// Key combination switching to the scene from anywhere, e.g. `Ctrl+Alt+N`, on Windows.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hotkey: Option<String>,
+}
+
+/// Notification shown when a metric goes above a level.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AlertRule {
+    pub metric: Metric,
+    /// From 0 to 1, as the metric is sampled: 0.9 is 90% load, or 90% of the way from the idle
+    /// temperature to the hot one.
+    pub above: f32,
+    pub notification: Notification,
+}
+
+/// Alerts raised, until their metric goes back under their level.
+#[derive(Debug, Default)]
+pub struct Alerts {
+    raised: Mutex<HashSet<(Metric, u32)>>,
+}
+
+impl Alerts {
+    /// Rules whose metric just went above their level.
+    pub fn check<'a>(&self, rules: &'a [AlertRule], samples: &Samples) -> Vec<&'a AlertRule> {
+        let mut raised = self.raised.lock().unwrap();
+
+        rules
+            .iter()
+            .filter(|rule| {
+                let key = (rule.metric, rule.above.to_bits());
+                match samples.get(rule.metric) {
+                    Some(value) if value > rule.above => raised.insert(key),
+                    Some(value) if value > rule.above - ALERT_HYSTERESIS => false,
+                    _ => {
+                        raised.remove(&key);
+
+                        false
+                    }
+                }
+            })
+            .collect()
+    }
+}
+
+/// Local time of the day, on some days of the week or all of them, e.g. `22:00` or
+/// `mon-fri 09:00`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScheduleEntry {
+    /// From Monday to Sunday.
+    days: [bool; 7],
+    time: NaiveTime,
+}
+
+impl ScheduleEntry {
+    /// Latest time of the entry after `since` and up to `now`.
+    fn passed(&self, since: NaiveDateTime, now: NaiveDateTime) -> Option<NaiveDateTime> {
+        let mut date = now.date();
+        while date >= since.date() {
+            let time = date.and_time(self.time);
+            if time > since
+                && time <= now
+                && self.days[date.weekday().num_days_from_monday() as usize]
+            {
+                return Some(time);
+            }
+
+            date = date.pred_opt()?;
+        }
+
+        None
+    }
+}
+
+impl FromStr for ScheduleEntry {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self> {
+        let (days, time) = match value.trim().rsplit_once(' ') {
+            Some((days, time)) => (parse_days(days)?, time),
+            None => ([true; 7], value.trim()),
+        };
+        let time = NaiveTime::parse_from_str(time, "%H:%M")
+            .map_err(|_| anyhow!("Invalid time in the schedule: {} (expected HH:MM)", value))?;
+
+        Ok(Self { days, time })
+    }
+}
+
+/// Days such as `mon-fri`, `sat,sun` or `daily`.
+fn parse_days(value: &str) -> Result<[bool; 7]> {
+    let day = |name: &str| {
+        DAYS.iter()
+            .position(|day| name.trim().eq_ignore_ascii_case(day))
+            .ok_or_else(|| anyhow!("Unknown day in the schedule: {}", name))
+    };
+
+    if value.eq_ignore_ascii_case("daily") {
+        return Ok([true; 7]);
+    }
+
+    let mut days = [false; 7];
+    for range in value.split(',') {
+        let (first, last) = match range.split_once('-') {
+            Some((first, last)) => (day(first)?, day(last)?),
+            None => (day(range)?, day(range)?),
+        };
+        if last < first {
+            bail!("Days out of order in the schedule: {}", range);
+        }
+
+        days[first..=last].fill(true);
+    }
+
+    Ok(days)
+}
+
+impl fmt::Display for ScheduleEntry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.days != [true; 7] {
+            let days = DAYS
+                .iter()
+                .zip(self.days)
+                .filter(|(_, on)| *on)
+                .map(|(day, _)| *day)
+                .collect::<Vec<_>>();
+            write!(f, "{} ", days.join(","))?;
+        }
+
+        write!(f, "{}", self.time.format("%H:%M"))
+    }
+}
+
+impl Serialize for ScheduleEntry {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+impl<'de> Deserialize<'de> for ScheduleEntry {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+/// Switches to the scenes as their times come, starting with the scene of the latest time
+/// passed in the last week, until the process exits.
+pub async fn run_schedule(state: Arc<ControlState>) {
+    let mut since = Local::now().naive_local() - chrono::Duration::days(7);
+    loop {
+        let now = Local::now().naive_local();
+        let config = state.config();
+        let due = config
+            .scenes
+            .iter()
+            .flat_map(|(name, scene)| {
+                scene
+                    .schedule
+                    .iter()
+                    .filter_map(move |entry| Some((entry.passed(since, now)?, name)))
+            })
+            .max();
+        since = now;
+
+        if let Some((_, name)) = due {
+            let request = ControlRequest::SwitchScene {
+                name: Some(name.clone()),
+            };
+            if let Err(e) = control::apply(request, &state, &config) {
+                warn!("Scheduled switch to the scene {} failed: {}", name, e);
+            }
+        }
+
+        tokio::time::sleep(SCHEDULE_INTERVAL).await;
+    }
+}
+
+/// Registers the hotkeys of the scenes, switching to them from anywhere in the session, until
+/// the process exits.
+#[cfg(windows)]
+pub fn register_hotkeys(state: Arc<ControlState>) {
+    use windows_sys::Win32::UI::{
+        Input::KeyboardAndMouse::{RegisterHotKey, MOD_NOREPEAT},
+        WindowsAndMessaging::{GetMessageW, MSG, WM_HOTKEY},
+    };
+
+    let config = state.config();
+    let hotkeys = config
+        .scenes
+        .iter()
+        .filter_map(|(name, scene)| Some((name.clone(), scene.hotkey.clone()?)))
+        .collect::<Vec<_>>();
+    if hotkeys.is_empty() {
+        return;
+    }
+
+    let runtime = tokio::runtime::Handle::current();
+    std::thread::spawn(move || {
+        let mut scenes = vec![];
+        for (name, hotkey) in hotkeys {
+            let (modifiers, key) = match parse_hotkey(&hotkey) {
+                Ok(hotkey) => hotkey,
+                Err(e) => {
+                    warn!("Hotkey of the scene {} not registered: {}", name, e);
+
+                    continue;
+                }
+            };
+
+            // Safety: the hotkeys are registered for the messages of this thread, no window.
+            let registered = unsafe {
+                RegisterHotKey(
+                    std::ptr::null_mut(),
+                    scenes.len() as i32,
+                    modifiers | MOD_NOREPEAT,
+                    key,
+                )
+            };
+            if registered == 0 {
+                warn!(
+                    "Hotkey {} of the scene {} not registered, another application may use it: {}",
+                    hotkey,
+                    name,
+                    std::io::Error::last_os_error()
+                );
+
+                continue;
+            }
+
+            info!("Hotkey {} switches to the scene {}.", hotkey, name);
+            scenes.push(name);
+        }
+
+        // Safety: the message is plain data, filled in by the call.
+        let mut message: MSG = unsafe { std::mem::zeroed() };
+        while unsafe { GetMessageW(&mut message, std::ptr::null_mut(), 0, 0) } > 0 {
+            if message.message != WM_HOTKEY {
+                continue;
+            }
+            let Some(name) = scenes.get(message.wParam) else {
+                continue;
+            };
+
+            let request = ControlRequest::SwitchScene {
+                name: Some(name.clone()),
+            };
+            let _guard = runtime.enter();
+            if let Err(e) = control::apply(request, &state, &state.config()) {
+                warn!("Switch to the scene {} failed: {}", name, e);
+            }
+        }
+    });
+}
+
+#[cfg(not(windows))]
+pub fn register_hotkeys(state: Arc<ControlState>) {
+    if state
+        .config()
+        .scenes
+        .values()
+        .any(|scene| scene.hotkey.is_some())
+    {
+        warn!(
+            "Scene hotkeys are only available on Windows, bind `open_rgb_client switch-scene \
+             <name>` to a shortcut of the desktop instead."
+        );
+    }
+}
+
+/// Modifiers and virtual key code of a combination such as `Ctrl+Alt+N` or `Win+F9`, in the
+/// values of `RegisterHotKey`.
+#[cfg_attr(not(windows), allow(dead_code))]
+fn parse_hotkey(value: &str) -> Result<(u32, u32)> {
+    let mut modifiers = 0;
+    let mut key = None;
+    for part in value.split('+').map(str::trim) {
+        match part.to_ascii_lowercase().as_str() {
+            "alt" => modifiers |= 0x1,
+            "ctrl" | "control" => modifiers |= 0x2,
+            "shift" => modifiers |= 0x4,
+            "win" | "super" => modifiers |= 0x8,
+            _ if key.is_some() => bail!("More than one key in the hotkey {:?}", value),
+            name => {
+                key = Some(match name.as_bytes() {
+                    // Letters and digits are their uppercase ASCII codes.
+                    [c] if c.is_ascii_alphanumeric() => c.to_ascii_uppercase() as u32,
+                    [b'f', number @ ..] => match std::str::from_utf8(number)?.parse::<u32>() {
+                        Ok(number @ 1..=24) => 0x70 + number - 1,
+                        _ => bail!("Unknown key {:?} in the hotkey {:?}", part, value),
+                    },
+                    _ => bail!("Unknown key {:?} in the hotkey {:?}", part, value),
+                })
+            }
+        }
+    }
+
+    match key {
+        Some(_) if modifiers == 0 => bail!("The hotkey {:?} needs a modifier, e.g. Ctrl", value),
+        Some(key) => Ok((modifiers, key)),
+        None => bail!("No key in the hotkey {:?}", value),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use openrgb::data::Color;
+
+    #[test]
+    fn schedules_pass_on_their_days() {
+        let entry = "mon-fri 09:00".parse::<ScheduleEntry>().unwrap();
+        assert_eq!(entry.to_string(), "mon,tue,wed,thu,fri 09:00");
+        assert!("sun-mon 09:00".parse::<ScheduleEntry>().is_err());
+        assert!("25:00".parse::<ScheduleEntry>().is_err());
+
+        let at = |text| NaiveDateTime::parse_from_str(text, "%Y-%m-%d %H:%M").unwrap();
+        // 2026-10-16 is a Friday.
+        assert_eq!(
+            entry.passed(at("2026-10-16 08:59"), at("2026-10-16 09:00")),
+            Some(at("2026-10-16 09:00"))
+        );
+        assert_eq!(
+            entry.passed(at("2026-10-17 08:00"), at("2026-10-18 10:00")),
+            None
+        );
+        // The latest of the week before, on Friday.
+        assert_eq!(
+            entry.passed(at("2026-10-12 10:00"), at("2026-10-18 10:00")),
+            Some(at("2026-10-16 09:00"))
+        );
+
+        let config: Config = toml::from_str(
+            r##"
+            [profiles.work]
+            default_metric = "cpu"
+
+            [scenes.night]
+            profile = "work"
+            palette = { idle = "#100020", load = "#FF4000" }
+            schedule = ["22:00"]
+
+            [[scenes.night.alerts]]
+            metric = "gpu_temperature"
+            above = 0.9
+            notification = { controller = "*", duration = "10s" }
+            "##,
+        )
+        .unwrap();
+        let night = config.with_scene("night").unwrap();
+        assert_eq!(night.default_metric, Some(Metric::Cpu));
+        assert_eq!(night.palette.idle, Color::new(0x10, 0x00, 0x20));
+        assert_eq!(night.alerts.len(), 1);
+        assert_eq!(night.active_scene.as_deref(), Some("night"));
+
+        assert_eq!(parse_hotkey("Ctrl+Alt+N").unwrap(), (0x3, 0x4E));
+        assert_eq!(parse_hotkey("win + f9").unwrap(), (0x8, 0x78));
+        assert!(parse_hotkey("N").is_err());
+    }
+}
@@ -0,0 +1,240 @@
+use anyhow::{bail, Result};
+use openrgb::data::{Color, Mode};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+/// Start of every packet of the OpenRGB SDK protocol.
+const MAGIC: &[u8; 4] = b"ORGB";
+/// Magic, device, packet ID and payload size.
+const HEADER_SIZE: usize = 16;
+const REQUEST_CONTROLLER_COUNT: u32 = 0;
+const DEVICE_LIST_UPDATED: u32 = 100;
+const RESIZE_ZONE: u32 = 1000;
+const UPDATE_LEDS: u32 = 1050;
+const UPDATE_ZONE_LEDS: u32 = 1051;
+const UPDATE_SINGLE_LED: u32 = 1052;
+const UPDATE_MODE: u32 = 1101;
+const SAVE_MODE: u32 = 1102;
+
+/// Resizes a zone of a controller, e.g. an ARGB header, to `leds` LEDs. The SDK client sends
+/// this request to the first controller whatever the controller, so it's written here.
+pub async fn resize_zone(
+    stream: &mut (impl AsyncWrite + Unpin),
+    controller_id: u32,
+    zone_id: u32,
+    leds: u32,
+) -> Result<()> {
+    let mut packet = start(controller_id, RESIZE_ZONE);
+    packet.extend_from_slice(&(zone_id as i32).to_le_bytes());
+    packet.extend_from_slice(&(leds as i32).to_le_bytes());
+
+    send(stream, &mut packet).await
+}
+
+/// Switches a controller to one of its modes, with the settings and colors of `mode`. The
+/// packet is written to `packet`, reusing its memory.
+pub async fn update_mode(
+    stream: &mut (impl AsyncWrite + Unpin),
+    packet: &mut Vec<u8>,
+    controller_id: u32,
+    mode_id: usize,
+    mode: &Mode,
+) -> Result<()> {
+    restart(packet, controller_id, UPDATE_MODE);
+    push_mode(packet, mode_id, mode);
+
+    send(stream, packet).await
+}
+
+/// Switches a controller to one of its modes and saves it to the controller's flash, which
+/// the SDK client can't do: it leaves out the mode index.
+pub async fn save_mode(
+    stream: &mut (impl AsyncWrite + Unpin),
+    controller_id: u32,
+    mode_id: usize,
+    mode: &Mode,
+) -> Result<()> {
+    let mut packet = start(controller_id, SAVE_MODE);
+    push_mode(&mut packet, mode_id, mode);
+
+    send(stream, &mut packet).await
+}
+
+/// Sets the colors of every LED of a controller. Unlike the SDK client's, the colors are
+/// borrowed and the packet is written to `packet`, reusing its memory, so a frame allocates
+/// nothing.
+pub async fn update_leds(
+    stream: &mut (impl AsyncWrite + Unpin),
+    packet: &mut Vec<u8>,
+    controller_id: u32,
+    colors: &[Color],
+) -> Result<()> {
+    restart(packet, controller_id, UPDATE_LEDS);
+    sized(packet, |packet| push_colors(packet, colors));
+
+    send(stream, packet).await
+}
+
+/// Sets the colors of the LEDs of a zone of a controller, see `update_leds`.
+pub async fn update_zone_leds(
+    stream: &mut (impl AsyncWrite + Unpin),
+    packet: &mut Vec<u8>,
+    controller_id: u32,
+    zone_id: u32,
+    colors: &[Color],
+) -> Result<()> {
+    restart(packet, controller_id, UPDATE_ZONE_LEDS);
+    sized(packet, |packet| {
+        packet.extend_from_slice(&zone_id.to_le_bytes());
+        push_colors(packet, colors);
+    });
+
+    send(stream, packet).await
+}
+
+/// Sets the color of a single LED of a controller, see `update_leds`.
+pub async fn update_led(
+    stream: &mut (impl AsyncWrite + Unpin),
+    packet: &mut Vec<u8>,
+    controller_id: u32,
+    led: u32,
+    color: Color,
+) -> Result<()> {
+    restart(packet, controller_id, UPDATE_SINGLE_LED);
+    packet.extend_from_slice(&(led as i32).to_le_bytes());
+    packet.extend_from_slice(&[color.r, color.g, color.b, 0]);
+
+    send(stream, packet).await
+}
+
+/// Number of controllers of the server, which answers once it applied the requests sent
+/// before, and whether it reported its controller list changed meanwhile.
+pub async fn controller_count(
+    stream: &mut (impl AsyncRead + AsyncWrite + Unpin),
+) -> Result<(u32, bool)> {
+    stream
+        .write_all(&header(0, REQUEST_CONTROLLER_COUNT, 0))
+        .await?;
+    stream.flush().await?;
+
+    let mut changed = false;
+    loop {
+        let mut header = [0; HEADER_SIZE];
+        stream.read_exact(&mut header).await?;
+        if &header[..4] != MAGIC {
+            bail!("Invalid packet from the OpenRGB server.");
+        }
+        let field = |index: usize| {
+            u32::from_le_bytes([
+                header[index],
+                header[index + 1],
+                header[index + 2],
+                header[index + 3],
+            ])
+        };
+        let (id, mut size) = (field(8), field(12) as usize);
+
+        if id == REQUEST_CONTROLLER_COUNT && size == 4 {
+            let mut count = [0; 4];
+            stream.read_exact(&mut count).await?;
+
+            return Ok((u32::from_le_bytes(count), changed));
+        }
+        changed |= id == DEVICE_LIST_UPDATED;
+
+        // Anything else the server sends is skipped.
+        let mut skipped = [0; 64];
+        while size > 0 {
+            let length = size.min(skipped.len());
+            stream.read_exact(&mut skipped[..length]).await?;
+            size -= length;
+        }
+    }
+}
+
+/// Copy of a mode, which the SDK client doesn't make `Clone`.
+pub fn copy_mode(mode: &Mode) -> Mode {
+    Mode {
+        name: mode.name.clone(),
+        colors: mode.colors.clone(),
+        ..*mode
+    }
+}
+
+fn header(device: u32, id: u32, size: u32) -> [u8; HEADER_SIZE] {
+    let mut header = [0; HEADER_SIZE];
+    header[..4].copy_from_slice(MAGIC);
+    header[4..8].copy_from_slice(&device.to_le_bytes());
+    header[8..12].copy_from_slice(&id.to_le_bytes());
+    header[12..].copy_from_slice(&size.to_le_bytes());
+
+    header
+}
+
+/// A new packet, its payload appended to it.
+fn start(device: u32, id: u32) -> Vec<u8> {
+    let mut packet = vec![];
+    restart(&mut packet, device, id);
+
+    packet
+}
+
+/// Starts a packet over in `packet`, keeping its memory.
+fn restart(packet: &mut Vec<u8>, device: u32, id: u32) {
+    packet.clear();
+    packet.extend_from_slice(&header(device, id, 0));
+}
+
+/// Appends what `push` appends, after its size including the size itself, as the
+/// descriptions of the protocol start.
+fn sized(packet: &mut Vec<u8>, push: impl FnOnce(&mut Vec<u8>)) {
+    let start = packet.len();
+    packet.extend_from_slice(&[0; 4]);
+    push(packet);
+
+    let size = (packet.len() - start) as u32;
+    packet[start..start + 4].copy_from_slice(&size.to_le_bytes());
+}
+
+fn push_colors(packet: &mut Vec<u8>, colors: &[Color]) {
+    packet.extend_from_slice(&(colors.len() as u16).to_le_bytes());
+    for color in colors {
+        packet.extend_from_slice(&[color.r, color.g, color.b, 0]);
+    }
+}
+
+/// Payload of the mode requests: its size, the mode index and the mode description. The raw
+/// streams never negotiate a protocol version, so the server expects the version 0
+/// description, without brightness.
+fn push_mode(packet: &mut Vec<u8>, mode_id: usize, mode: &Mode) {
+    sized(packet, |packet| {
+        packet.extend_from_slice(&(mode_id as i32).to_le_bytes());
+        packet.extend_from_slice(&((mode.name.len() + 1) as u16).to_le_bytes());
+        packet.extend_from_slice(mode.name.as_bytes());
+        packet.push(0);
+        for value in [
+            mode.value as u32,
+            mode.flags.bits(),
+            mode.speed_min.unwrap_or_default(),
+            mode.speed_max.unwrap_or_default(),
+            mode.colors_min.unwrap_or_default(),
+            mode.colors_max.unwrap_or_default(),
+            mode.speed.unwrap_or_default(),
+            mode.direction.map_or(0, |direction| direction as u32),
+            mode.color_mode.map_or(0, |color_mode| color_mode as u32),
+        ] {
+            packet.extend_from_slice(&value.to_le_bytes());
+        }
+        push_colors(packet, &mode.colors);
+    });
+}
+
+/// Writes the payload size into the header of a packet, then the packet.
+async fn send(stream: &mut (impl AsyncWrite + Unpin), packet: &mut [u8]) -> Result<()> {
+    let size = (packet.len() - HEADER_SIZE) as u32;
+    packet[12..HEADER_SIZE].copy_from_slice(&size.to_le_bytes());
+
+    stream.write_all(packet).await?;
+    stream.flush().await?;
+
+    Ok(())
+}
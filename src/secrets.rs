@@ -0,0 +1,102 @@
+use anyhow::{anyhow, bail, Context, Result};
+use toml::{Table, Value};
+
+/// Prefix of the configuration values naming a secret instead of holding it.
+const SECRET_PREFIX: &str = "secret://";
+
+/// Service the secrets are stored under in the OS keychain.
+#[cfg(feature = "keyring")]
+const KEYCHAIN_SERVICE: &str = "open_rgb_client";
+
+/// Replaces the `secret://name` strings of the configuration with the secrets they name.
+pub fn resolve(table: &mut Table) -> Result<()> {
+    for (_, value) in table.iter_mut() {
+        resolve_value(value)?;
+    }
+
+    Ok(())
+}
+
+fn resolve_value(value: &mut Value) -> Result<()> {
+    match value {
+        Value::String(string) => {
+            if let Some(name) = string.strip_prefix(SECRET_PREFIX) {
+                *string = lookup(name)?;
+            }
+        }
+        Value::Array(values) => {
+            for value in values {
+                resolve_value(value)?;
+            }
+        }
+        Value::Table(table) => resolve(table)?,
+        _ => {}
+    }
+
+    Ok(())
+}
+
+/// Secret from the credentials systemd decrypted for the service, or else the OS keychain.
+fn lookup(name: &str) -> Result<String> {
+    if name.is_empty() || name.contains(['/', '\\']) {
+        bail!("Invalid secret name: {:?}", name);
+    }
+
+    if let Some(directory) = std::env::var_os("CREDENTIALS_DIRECTORY") {
+        let path = std::path::Path::new(&directory).join(name);
+        if path.exists() {
+            let secret = std::fs::read_to_string(&path)
+                .with_context(|| format!("Can't read the credential {:?}", path))?;
+
+            return Ok(secret.trim_end().to_owned());
+        }
+    }
+
+    keychain_lookup(name)
+}
+
+#[cfg(feature = "keyring")]
+fn keychain_lookup(name: &str) -> Result<String> {
+    keyring::Entry::new(KEYCHAIN_SERVICE, name)
+        .and_then(|entry| entry.get_password())
+        .map_err(|e| anyhow!("Can't read the secret {:?} from the keychain: {}", name, e))
+}
+
+#[cfg(not(feature = "keyring"))]
+fn keychain_lookup(name: &str) -> Result<String> {
+    Err(anyhow!(
+        "Secret {:?} not found, this build has no keychain support.",
+        name
+    ))
+}
+
+/// Stores a secret in the OS keychain, replacing the previous one.
+#[cfg(feature = "keyring")]
+pub fn store(name: &str, secret: &str) -> Result<()> {
+    keyring::Entry::new(KEYCHAIN_SERVICE, name)
+        .and_then(|entry| entry.set_password(secret))
+        .map_err(|e| anyhow!("Can't store the secret {:?} in the keychain: {}", name, e))
+}
+
+#[cfg(feature = "keyring")]
+pub fn delete(name: &str) -> Result<()> {
+    keyring::Entry::new(KEYCHAIN_SERVICE, name)
+        .and_then(|entry| entry.delete_credential())
+        .map_err(|e| {
+            anyhow!(
+                "Can't delete the secret {:?} from the keychain: {}",
+                name,
+                e
+            )
+        })
+}
+
+#[cfg(not(feature = "keyring"))]
+pub fn store(_: &str, _: &str) -> Result<()> {
+    bail!("This build has no keychain support.")
+}
+
+#[cfg(not(feature = "keyring"))]
+pub fn delete(_: &str) -> Result<()> {
+    bail!("This build has no keychain support.")
+}
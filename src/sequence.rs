@@ -0,0 +1,77 @@
+use crate::{control::parse_duration, effects::Effect};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::{
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+/// Effects played one after the other over every controller when requested through the
+/// control socket, e.g. to celebrate a release.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Sequence {
+    pub name: String,
+    pub steps: Vec<Step>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Step {
+    pub effect: Effect,
+    #[serde(with = "duration")]
+    pub duration: Duration,
+}
+
+/// The sequence being played by the running client, if any.
+#[derive(Debug, Default)]
+pub struct Player {
+    playing: Mutex<Option<(Sequence, Instant)>>,
+}
+
+impl Player {
+    /// Plays `sequence` from its start, replacing the one playing.
+    pub fn play(&self, sequence: Sequence) {
+        *self.playing.lock().unwrap() = Some((sequence, Instant::now()));
+    }
+
+    #[cfg(feature = "grpc")]
+    /// Name of the sequence playing, if any.
+    pub fn sequence(&self) -> Option<String> {
+        self.playing
+            .lock()
+            .unwrap()
+            .as_ref()
+            .map(|(sequence, _)| sequence.name.clone())
+    }
+
+    /// Step to show now, `None` once the sequence is over.
+    pub fn current_step(&self) -> Option<Step> {
+        let mut playing = self.playing.lock().unwrap();
+        let (sequence, start) = playing.as_ref()?;
+
+        let mut elapsed = start.elapsed();
+        for step in &sequence.steps {
+            if elapsed < step.duration {
+                return Some(step.clone());
+            }
+
+            elapsed -= step.duration;
+        }
+
+        *playing = None;
+
+        None
+    }
+}
+
+pub mod duration {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(duration: &Duration, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&format!("{}ms", duration.as_millis()))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Duration, D::Error> {
+        let value = String::deserialize(deserializer)?;
+
+        parse_duration(&value).map_err(serde::de::Error::custom)
+    }
+}
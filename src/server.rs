@@ -0,0 +1,141 @@
+use crate::connection::{ConnectionOptions, DEFAULT_PORT};
+use anyhow::{bail, Result};
+use log::*;
+use std::{
+    net::{IpAddr, Ipv4Addr},
+    path::PathBuf,
+    time::Duration,
+};
+use tokio::{
+    net::TcpStream,
+    process::Command,
+    time::{sleep, Instant},
+};
+
+const SERVER_STARTUP_GRACE: Duration = Duration::from_secs(10);
+const SERVER_POLL_INTERVAL: Duration = Duration::from_secs(5);
+const SERVER_RESTART_DELAY: Duration = Duration::from_secs(3);
+
+#[cfg(target_os = "windows")]
+const DEFAULT_SERVER_PATH: &str = r"C:\Program Files\OpenRGB\OpenRGB.exe";
+
+#[cfg(not(target_os = "windows"))]
+const DEFAULT_SERVER_PATH: &str = "openrgb";
+
+#[derive(Debug, Clone)]
+pub struct ServerOptions {
+    pub path: PathBuf,
+    pub args: Vec<String>,
+}
+
+impl Default for ServerOptions {
+    fn default() -> Self {
+        Self {
+            path: DEFAULT_SERVER_PATH.into(),
+            args: vec![],
+        }
+    }
+}
+
+/// Port of the local server the client connects to, the one `--launch-server` supervises.
+/// Servers on other machines or behind TLS can't be started from here, and checking the local
+/// port for them would launch a second server next to the one in use.
+pub fn local_port(connection: &ConnectionOptions) -> Result<u16> {
+    if connection.tls.is_some() {
+        bail!("--launch-server starts a plain OpenRGB server, it can't be combined with --tls.");
+    }
+
+    let hosts = connection.hosts();
+    if let Some(host) = hosts.iter().find(|host| !is_local(&host.name)) {
+        bail!(
+            "--launch-server starts OpenRGB on this machine, but the client connects to {}.",
+            host
+        );
+    }
+
+    Ok(hosts[0].port.unwrap_or(connection.port))
+}
+
+fn is_local(name: &str) -> bool {
+    name.eq_ignore_ascii_case("localhost")
+        || name
+            .parse::<IpAddr>()
+            .is_ok_and(|address| address.is_loopback())
+}
+
+/// Keeps an OpenRGB server running, launching it whenever no server answers on the SDK port.
+pub async fn supervise_server(options: ServerOptions, port: u16) {
+    // OpenRGB may have been started by the same boot (autostart, another unit) and still be
+    // detecting devices, so give it a chance to come up before launching a second instance.
+    let deadline = Instant::now() + SERVER_STARTUP_GRACE;
+    while Instant::now() < deadline && !is_server_running(port).await {
+        sleep(Duration::from_secs(1)).await;
+    }
+
+    loop {
+        if is_server_running(port).await {
+            sleep(SERVER_POLL_INTERVAL).await;
+
+            continue;
+        }
+
+        info!(
+            "OpenRGB server is not running, launching {:?}...",
+            options.path
+        );
+
+        match server_command(&options, port).spawn() {
+            Ok(mut child) => match child.wait().await {
+                Ok(status) => warn!("OpenRGB server exited ({}).", status),
+                Err(e) => error!("Failed to wait for OpenRGB server: {}", e),
+            },
+            Err(e) => error!("Failed to launch OpenRGB server: {}", e),
+        }
+
+        sleep(SERVER_RESTART_DELAY).await;
+    }
+}
+
+/// Runs alongside the OpenRGB server on `port`, launching it when none answers, and returns
+/// once it's gone: when the OpenRGB launched here exits, or the one already running stops
+/// answering.
+pub async fn follow_server(options: ServerOptions, port: u16) {
+    if is_server_running(port).await {
+        while is_server_running(port).await {
+            sleep(SERVER_POLL_INTERVAL).await;
+        }
+        info!("OpenRGB server stopped.");
+
+        return;
+    }
+
+    info!("Launching OpenRGB {:?}...", options.path);
+    match server_command(&options, port).spawn() {
+        Ok(mut child) => match child.wait().await {
+            Ok(status) => info!("OpenRGB exited ({}).", status),
+            Err(e) => error!("Failed to wait for OpenRGB: {}", e),
+        },
+        Err(e) => error!("Failed to launch OpenRGB: {}", e),
+    }
+}
+
+/// OpenRGB with its SDK server on `port`, killed along with the client.
+fn server_command(options: &ServerOptions, port: u16) -> Command {
+    let mut command = Command::new(&options.path);
+    command
+        .arg("--server")
+        .args(&options.args)
+        .kill_on_drop(true);
+
+    if port != DEFAULT_PORT {
+        command.arg("--server-port").arg(port.to_string());
+    }
+
+    command
+}
+
+async fn is_server_running(port: u16) -> bool {
+    TcpStream::connect((Ipv4Addr::LOCALHOST, port))
+        .await
+        .is_ok()
+}
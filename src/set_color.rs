@@ -0,0 +1,47 @@
+use crate::{
+    args::Target,
+    color_manager::log_dry_run,
+    config::{describe_color, Config},
+    connection::{ConnectionOptions, Connector},
+    i18n::tr,
+    mapping::ControllerInfo,
+    test_pattern::Selection,
+};
+use anyhow::Result;
+use openrgb::data::Color;
+
+/// Sets every LED of a controller or one of its zones to `color` and returns, only logging them
+/// in a dry run.
+pub async fn set_color(
+    target: &Target,
+    color: Color,
+    config: &Config,
+    connection: &ConnectionOptions,
+    dry_run: bool,
+) -> Result<()> {
+    let client = Connector::new(connection)?.connect().await?;
+    let selection = Selection::find(&client, config, target).await?;
+
+    let colors = vec![color; selection.leds];
+    if dry_run {
+        log_dry_run(
+            &ControllerInfo::from(&selection.controller),
+            selection.zone_id.map(|zone_id| zone_id as usize),
+            &colors,
+        );
+    } else {
+        selection.update(&client, colors).await?;
+    }
+
+    println!(
+        "{}",
+        tr!(
+            "set-color-done",
+            "controller" => selection.controller.name.as_str(),
+            "color" => describe_color(&color),
+            "leds" => selection.leds
+        )
+    );
+
+    Ok(())
+}
@@ -0,0 +1,200 @@
+use crate::{
+    config::{Config, Metric},
+    paths::{self, Kind},
+    runtime::{RuntimeMonitor, RuntimeStats},
+    sampler::Samples,
+    units::Quantity,
+};
+use anyhow::Result;
+use log::*;
+use openrgb::data::{Color, Controller};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    path::PathBuf,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+const STATE_FILE: &str = "open_rgb_client_state.toml";
+const SAVE_INTERVAL: Duration = Duration::from_secs(60);
+
+// Longer gaps between frames (suspend, reconnects) don't count as the LEDs being driven.
+const MAX_FRAME_INTERVAL: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Clone)]
+pub struct SafeModeOptions {
+    /// Number of starts not followed by a clean shutdown tolerated within the window.
+    pub restarts: usize,
+    pub window: Duration,
+}
+
+impl Default for SafeModeOptions {
+    fn default() -> Self {
+        Self {
+            restarts: 5,
+            window: Duration::from_secs(10 * 60),
+        }
+    }
+}
+
+/// Data persisted across runs, next to the executable.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct State {
+    #[serde(default)]
+    pub controllers: BTreeMap<String, ControllerStats>,
+    /// Unix times of the recent starts that weren't followed by a clean shutdown.
+    #[serde(default)]
+    pub unclean_starts: Vec<u64>,
+    #[serde(default)]
+    pub safe_mode: bool,
+    /// Resources used by the running client, cleared when it shuts down.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub runtime: Option<RuntimeStats>,
+    /// Latest metric values of the running client, cleared when it shuts down.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub metrics: BTreeMap<Metric, Quantity>,
+
+    #[serde(skip)]
+    last_frame: Option<Instant>,
+    #[serde(skip)]
+    monitor: RuntimeMonitor,
+    #[serde(skip)]
+    last_save: Option<Instant>,
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct ControllerStats {
+    /// Cumulative time the controller has been driven by the client.
+    pub active_hours: f64,
+    /// Time weighted average of the channel levels sent to the LEDs, from 0 to 1.
+    pub average_brightness: f64,
+}
+
+impl State {
+    pub fn path() -> Result<PathBuf> {
+        paths::file(Kind::State, STATE_FILE)
+    }
+
+    pub fn load() -> Self {
+        let state = Self::path().and_then(|path| {
+            let contents = std::fs::read_to_string(path)?;
+
+            Ok(toml::from_str(&contents)?)
+        });
+
+        state.unwrap_or_else(|e| {
+            debug!("Starting with a fresh state: {}", e);
+
+            Self::default()
+        })
+    }
+
+    pub fn save(&mut self) -> Result<()> {
+        let path = Self::path()?;
+        paths::create_parent(&path)?;
+        std::fs::write(path, toml::to_string(self)?)?;
+        self.last_save = Some(Instant::now());
+
+        Ok(())
+    }
+
+    pub fn save_if_due(&mut self) {
+        if self
+            .last_save
+            .is_none_or(|last_save| last_save.elapsed() >= SAVE_INTERVAL)
+        {
+            #[cfg(feature = "alloc-audit")]
+            crate::alloc_audit::expect_allocations();
+
+            if let Err(e) = self.save() {
+                warn!("Failed to save state: {}", e);
+            }
+        }
+    }
+
+    /// Records a start, returning whether the client is crash looping and must run in safe mode.
+    pub fn record_start(&mut self, options: &SafeModeOptions) -> bool {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+
+        self.unclean_starts
+            .retain(|start| now.saturating_sub(*start) < options.window.as_secs());
+        self.unclean_starts.push(now);
+
+        self.safe_mode = self.unclean_starts.len() > options.restarts;
+        self.safe_mode
+    }
+
+    pub fn record_clean_shutdown(&mut self) {
+        self.unclean_starts.clear();
+        self.safe_mode = false;
+        self.runtime = None;
+        self.metrics.clear();
+    }
+
+    /// Accounts a frame sent to the LEDs of the given controllers.
+    pub fn record_frame(&mut self, frame: &[(String, Vec<Color>)]) {
+        let now = Instant::now();
+        let elapsed = self
+            .last_frame
+            .map(|last_frame| now - last_frame)
+            .filter(|elapsed| *elapsed <= MAX_FRAME_INTERVAL)
+            .unwrap_or_default();
+        self.last_frame = Some(now);
+        if let Some(runtime) = self.monitor.record_frame(now) {
+            self.runtime = Some(runtime);
+        }
+
+        let hours = elapsed.as_secs_f64() / 3600.0;
+
+        if hours == 0.0 {
+            return;
+        }
+
+        for (controller, colors) in frame {
+            if !self.controllers.contains_key(controller) {
+                self.controllers
+                    .insert(controller.clone(), ControllerStats::default());
+            }
+            let stats = self.controllers.get_mut(controller).unwrap();
+            let total_hours = stats.active_hours + hours;
+            stats.average_brightness = (stats.average_brightness * stats.active_hours
+                + brightness(colors) * hours)
+                / total_hours;
+            stats.active_hours = total_hours;
+        }
+    }
+
+    /// Keeps the latest value of each available metric. Updated in place, which allocates
+    /// nothing once every metric has been seen.
+    pub fn record_samples(&mut self, samples: &Samples, config: &Config) {
+        self.metrics.retain(|metric, _| {
+            samples
+                .quantities(config)
+                .any(|(sampled, _)| sampled == *metric)
+        });
+        for (metric, quantity) in samples.quantities(config) {
+            self.metrics.insert(metric, quantity);
+        }
+    }
+}
+
+/// Key identifying a controller across runs, the location tells identical devices apart.
+pub fn controller_key(controller: &Controller) -> String {
+    format!("{} ({})", controller.name, controller.location)
+}
+
+fn brightness(colors: &[Color]) -> f64 {
+    if colors.is_empty() {
+        return 0.0;
+    }
+
+    let total = colors
+        .iter()
+        .map(|color| color.r as f64 + color.g as f64 + color.b as f64)
+        .sum::<f64>();
+
+    total / (colors.len() as f64 * 3.0 * 255.0)
+}
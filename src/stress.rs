@@ -0,0 +1,201 @@
+use crate::{
+    connection::{ConnectionOptions, Connector},
+    effects::hue,
+    i18n::tr,
+};
+use anyhow::Result;
+use log::*;
+use openrgb::data::Color;
+use std::{
+    future::Future,
+    time::{Duration, Instant},
+};
+use tokio::time::MissedTickBehavior;
+
+const PATTERN_DURATION: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Clone)]
+pub struct StressOptions {
+    /// Frames sent to every controller per second.
+    pub fps: u32,
+    pub duration: Duration,
+}
+
+impl Default for StressOptions {
+    fn default() -> Self {
+        Self {
+            fps: 60,
+            duration: Duration::from_secs(60),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct ControllerStats {
+    name: String,
+    frames: usize,
+    errors: usize,
+}
+
+/// Drives every controller with generated patterns as fast as configured, then reports the
+/// update errors and the frame latencies, to validate USB controllers and hubs.
+pub async fn stress_test(
+    options: &StressOptions,
+    connection: &ConnectionOptions,
+    stop: impl Future<Output = ()>,
+) -> Result<()> {
+    let mut connector = Connector::new(connection)?;
+    let mut client = connector.connect().await?;
+
+    let mut controllers = vec![];
+    for controller_id in 0..client.get_controller_count().await? {
+        let controller = client.get_controller(controller_id).await?;
+        controllers.push((
+            controller.leds.len(),
+            ControllerStats {
+                name: controller.name,
+                ..Default::default()
+            },
+        ));
+    }
+
+    println!(
+        "{}",
+        tr!(
+            "stress-start",
+            "controllers" => controllers.len(),
+            "fps" => options.fps,
+            "minutes" => format!("{:.1}", options.duration.as_secs_f64() / 60.0)
+        )
+    );
+
+    let mut interval = tokio::time::interval(Duration::from_secs_f64(1.0 / options.fps as f64));
+    interval.set_missed_tick_behavior(MissedTickBehavior::Skip);
+
+    let mut latencies = vec![];
+    let mut reconnects = 0;
+    let start = Instant::now();
+
+    tokio::pin!(stop);
+    while start.elapsed() < options.duration {
+        tokio::select! {
+            _ = &mut stop => break,
+            _ = interval.tick() => {},
+        }
+
+        let elapsed = start.elapsed();
+        let frame_start = Instant::now();
+        let mut failed = false;
+
+        for (controller_id, (led_count, stats)) in controllers.iter_mut().enumerate() {
+            let colors = pattern(elapsed, *led_count);
+
+            stats.frames += 1;
+            if let Err(e) = client.update_leds(controller_id as u32, colors).await {
+                debug!("Failed to update {}: {}", stats.name, e);
+
+                stats.errors += 1;
+                failed = true;
+            }
+        }
+
+        // Updates have no reply, a request that has one tells when the server went through them.
+        if !failed && client.get_controller_count().await.is_ok() {
+            latencies.push(frame_start.elapsed());
+
+            continue;
+        }
+
+        warn!("Connection lost, reconnecting...");
+        reconnects += 1;
+        client = connector.connect().await?;
+    }
+
+    let elapsed = start.elapsed().as_secs_f64();
+
+    for (_, stats) in &controllers {
+        println!(
+            "{}",
+            tr!(
+                "stress-controller",
+                "controller" => stats.name.as_str(),
+                "frames" => stats.frames,
+                "errors" => stats.errors,
+                "rate" => format!("{:.2}", error_rate(stats))
+            )
+        );
+    }
+
+    latencies.sort();
+    let percentile = |percent: usize| {
+        latencies
+            .get((latencies.len() * percent / 100).min(latencies.len().saturating_sub(1)))
+            .copied()
+            .unwrap_or_default()
+    };
+    let milliseconds = |duration: Duration| format!("{:.1}", duration.as_secs_f64() * 1000.0);
+
+    println!(
+        "{}",
+        tr!(
+            "stress-latency",
+            "median" => milliseconds(percentile(50)),
+            "p99" => milliseconds(percentile(99)),
+            "max" => milliseconds(latencies.last().copied().unwrap_or_default())
+        )
+    );
+    println!(
+        "{}",
+        tr!(
+            "stress-summary",
+            "fps" => format!("{:.1}", latencies.len() as f64 / elapsed),
+            "reconnects" => reconnects
+        )
+    );
+
+    Ok(())
+}
+
+fn error_rate(stats: &ControllerStats) -> f64 {
+    if stats.frames == 0 {
+        return 0.0;
+    }
+
+    stats.errors as f64 * 100.0 / stats.frames as f64
+}
+
+/// Cycles through a moving rainbow, a full brightness strobe and random noise, which between
+/// them exercise smooth changes, current spikes and incompressible data.
+fn pattern(elapsed: Duration, led_count: usize) -> Vec<Color> {
+    let time = elapsed.as_secs_f32();
+    let frame = elapsed.as_millis() as u64;
+
+    match (elapsed.as_secs() / PATTERN_DURATION.as_secs()) % 3 {
+        0 => (0..led_count)
+            .map(|index| hue(index as f32 / led_count.max(1) as f32 + time / 2.0))
+            .collect(),
+        1 => {
+            let level = if (frame / 100).is_multiple_of(2) {
+                0xFF
+            } else {
+                0x00
+            };
+
+            vec![Color::new(level, level, level); led_count]
+        }
+        _ => {
+            let mut seed = frame.wrapping_mul(0x9E37_79B9_7F4A_7C15) | 1;
+
+            (0..led_count)
+                .map(|_| {
+                    // Xorshift, good enough for noise.
+                    seed ^= seed << 13;
+                    seed ^= seed >> 7;
+                    seed ^= seed << 17;
+
+                    Color::new(seed as u8, (seed >> 8) as u8, (seed >> 16) as u8)
+                })
+                .collect()
+        }
+    }
+}
@@ -0,0 +1,114 @@
+use anyhow::Result;
+use log::*;
+use serde::{Deserialize, Serialize};
+use std::{
+    net::{Ipv4Addr, SocketAddr},
+    sync::atomic::{AtomicI64, Ordering},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+use tokio::net::UdpSocket;
+
+const BEAT_PREFIX: &str = "open_rgb_client beat ";
+const BEAT_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Weight of a new beat in the clock offset, smoothing out the network jitter.
+const BEAT_WEIGHT: f64 = 0.2;
+
+/// Milliseconds to add to the local clock to get the leader's.
+static OFFSET_MS: AtomicI64 = AtomicI64::new(0);
+
+/// Keeps the animations of the clients on the same network in phase, the followers rendering
+/// with the clock of the leader.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SyncConfig {
+    pub role: SyncRole,
+    /// UDP port the beats are broadcast on.
+    #[serde(default = "SyncConfig::default_port")]
+    pub port: u16,
+}
+
+impl SyncConfig {
+    fn default_port() -> u16 {
+        6743
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SyncRole {
+    Leader,
+    Follower,
+}
+
+/// Time animations are rendered at, the leader's when following one.
+pub fn now() -> SystemTime {
+    let offset = OFFSET_MS.load(Ordering::Relaxed);
+    let now = SystemTime::now();
+
+    if offset >= 0 {
+        now + Duration::from_millis(offset as u64)
+    } else {
+        now - Duration::from_millis(offset.unsigned_abs())
+    }
+}
+
+/// Broadcasts the beats as the leader, or follows the leader's, until the process exits.
+pub async fn run(config: SyncConfig) -> Result<()> {
+    match config.role {
+        SyncRole::Leader => {
+            let socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0)).await?;
+            socket.set_broadcast(true)?;
+            let target = SocketAddr::from((Ipv4Addr::BROADCAST, config.port));
+            info!("Broadcasting sync beats on port {}.", config.port);
+
+            let mut interval = tokio::time::interval(BEAT_INTERVAL);
+            loop {
+                interval.tick().await;
+
+                let beat = format!("{}{}", BEAT_PREFIX, unix_millis(SystemTime::now()));
+                if let Err(e) = socket.send_to(beat.as_bytes(), target).await {
+                    debug!("Failed to send sync beat: {}", e);
+                }
+            }
+        }
+        SyncRole::Follower => {
+            let socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, config.port)).await?;
+            info!("Following sync beats on port {}.", config.port);
+
+            let mut synced = false;
+            let mut buffer = [0; 64];
+            loop {
+                let (length, from) = socket.recv_from(&mut buffer).await?;
+                let Some(leader) = std::str::from_utf8(&buffer[..length])
+                    .ok()
+                    .and_then(|beat| beat.strip_prefix(BEAT_PREFIX))
+                    .and_then(|millis| millis.trim().parse::<i64>().ok())
+                else {
+                    debug!("Ignoring invalid sync beat from {}.", from);
+
+                    continue;
+                };
+
+                let sample = leader - unix_millis(SystemTime::now());
+                let offset = if synced {
+                    let offset = OFFSET_MS.load(Ordering::Relaxed) as f64;
+
+                    (offset + (sample as f64 - offset) * BEAT_WEIGHT) as i64
+                } else {
+                    info!("Following the leader at {}, {} ms off.", from, sample);
+                    synced = true;
+
+                    sample
+                };
+
+                OFFSET_MS.store(offset, Ordering::Relaxed);
+            }
+        }
+    }
+}
+
+fn unix_millis(time: SystemTime) -> i64 {
+    time.duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as i64
+}
@@ -0,0 +1,212 @@
+use crate::{
+    args::Target,
+    config::Config,
+    connection::{ConnectionOptions, Connector},
+    i18n::tr,
+};
+use anyhow::{anyhow, bail, Result};
+use openrgb::{
+    data::{Color, Controller},
+    OpenRGB,
+};
+use std::{fmt, future::Future, str::FromStr, time::Duration};
+use tokio::net::TcpStream;
+
+/// Time each LED of the chase stays lit.
+const CHASE_STEP: Duration = Duration::from_millis(250);
+/// Time each bit of the binary index, and each color of the RGB check, is shown.
+const PATTERN_STEP: Duration = Duration::from_secs(2);
+
+const OFF: Color = Color::new(0x00, 0x00, 0x00);
+const ON: Color = Color::new(0xFF, 0xFF, 0xFF);
+/// Unset bits of the binary index, dim so the LED still shows up.
+const ZERO: Color = Color::new(0x00, 0x00, 0x20);
+
+/// Patterns for checking how the LEDs of a zone are wired.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Pattern {
+    /// One LED lit at a time, from the first to the last: a strip that lights up past its end
+    /// or stops short has a wrong LED count, one that starts in the middle is miswired.
+    #[default]
+    Chase,
+    /// Each bit of the LED indices in turn, lowest first, so the index of every LED can be
+    /// read off, e.g. across the outputs of an ARGB splitter.
+    Index,
+    /// Red, green then blue over the whole zone, to find the channel order of the LEDs.
+    Rgb,
+}
+
+impl FromStr for Pattern {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self> {
+        match value.to_ascii_lowercase().as_str() {
+            "chase" => Ok(Self::Chase),
+            "index" | "binary-index" => Ok(Self::Index),
+            "rgb" => Ok(Self::Rgb),
+            _ => bail!("Unknown test pattern: {}", value),
+        }
+    }
+}
+
+impl fmt::Display for Pattern {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Chase => "chase",
+            Self::Index => "index",
+            Self::Rgb => "rgb",
+        })
+    }
+}
+
+/// Controller, or zone of a controller, given on the command line.
+pub struct Selection {
+    pub controller_id: u32,
+    pub controller: Controller,
+    pub zone_id: Option<u32>,
+    pub leds: usize,
+}
+
+impl Selection {
+    /// Looks `target` up among the controllers of the server, by name or alias, and its zone
+    /// ignoring case.
+    pub async fn find(
+        client: &OpenRGB<TcpStream>,
+        config: &Config,
+        target: &Target,
+    ) -> Result<Self> {
+        for controller_id in 0..client.get_controller_count().await? {
+            let controller = client.get_controller(controller_id).await?;
+            if !config.designates(&target.controller, &controller.name, &controller.location) {
+                continue;
+            }
+
+            let Some(zone_name) = &target.zone else {
+                return Ok(Self {
+                    controller_id,
+                    zone_id: None,
+                    leds: controller.leds.len(),
+                    controller,
+                });
+            };
+            let zone_id = controller
+                .zones
+                .iter()
+                .position(|zone| zone.name.eq_ignore_ascii_case(zone_name))
+                .ok_or_else(|| anyhow!("No zone named {:?} on {}.", zone_name, controller.name))?;
+
+            return Ok(Self {
+                controller_id,
+                zone_id: Some(zone_id as u32),
+                leds: controller.zones[zone_id].leds_count as usize,
+                controller,
+            });
+        }
+
+        bail!(
+            "No controller named {:?}, `list-devices` lists them.",
+            target.controller
+        )
+    }
+
+    /// Sets the LEDs of the controller or zone.
+    pub async fn update(&self, client: &OpenRGB<TcpStream>, colors: Vec<Color>) -> Result<()> {
+        match self.zone_id {
+            Some(zone_id) => {
+                client
+                    .update_zone_leds(self.controller_id, zone_id, colors)
+                    .await?
+            }
+            None => client.update_leds(self.controller_id, colors).await?,
+        }
+
+        Ok(())
+    }
+}
+
+/// Shows a test pattern on a controller or one of its zones until `stop`, then turns it off.
+pub async fn test_pattern(
+    target: &Target,
+    pattern: Pattern,
+    config: &Config,
+    connection: &ConnectionOptions,
+    stop: impl Future<Output = ()>,
+) -> Result<()> {
+    let client = Connector::new(connection)?.connect().await?;
+    let selection = Selection::find(&client, config, target).await?;
+
+    println!(
+        "{}",
+        tr!("test-pattern-start", "pattern" => pattern.to_string(), "leds" => selection.leds)
+    );
+
+    tokio::pin!(stop);
+    let mut step = 0;
+    loop {
+        let (colors, message, duration) = frame(pattern, step, selection.leds);
+        if let Some(message) = message {
+            println!("{}", message);
+        }
+        selection.update(&client, colors).await?;
+
+        tokio::select! {
+            _ = &mut stop => break,
+            _ = tokio::time::sleep(duration) => {}
+        }
+
+        step += 1;
+    }
+
+    selection.update(&client, vec![OFF; selection.leds]).await?;
+
+    Ok(())
+}
+
+/// Colors of the step of a pattern, what to tell the user about it and how long it lasts.
+fn frame(pattern: Pattern, step: usize, leds: usize) -> (Vec<Color>, Option<String>, Duration) {
+    match pattern {
+        Pattern::Chase => {
+            let lit = step % leds.max(1);
+            let colors = (0..leds)
+                .map(|index| if index == lit { ON } else { OFF })
+                .collect();
+
+            (
+                colors,
+                Some(tr!("test-pattern-led", "index" => lit)),
+                CHASE_STEP,
+            )
+        }
+        Pattern::Index => {
+            // Enough bits for the last index, and a dark step between the rounds.
+            let bits = (usize::BITS - leds.saturating_sub(1).leading_zeros()).max(1) as usize;
+            let bit = step % (bits + 1);
+            if bit == bits {
+                return (vec![OFF; leds], None, PATTERN_STEP);
+            }
+
+            let colors = (0..leds)
+                .map(|index| if index >> bit & 1 == 1 { ON } else { ZERO })
+                .collect();
+
+            (
+                colors,
+                Some(tr!("test-pattern-bit", "bit" => bit, "value" => 1usize << bit)),
+                PATTERN_STEP,
+            )
+        }
+        Pattern::Rgb => {
+            let (color, name) = match step % 3 {
+                0 => (Color::new(0xFF, 0x00, 0x00), "red"),
+                1 => (Color::new(0x00, 0xFF, 0x00), "green"),
+                _ => (Color::new(0x00, 0x00, 0xFF), "blue"),
+            };
+
+            (
+                vec![color; leds],
+                Some(tr!("test-pattern-color", "color" => name)),
+                PATTERN_STEP,
+            )
+        }
+    }
+}
@@ -0,0 +1,44 @@
+use crate::{config::ThermalConfig, sampler::read_file};
+use anyhow::Result;
+use std::path::PathBuf;
+
+/// Samples the temperature of a Linux thermal zone, the SoC on single board computers.
+pub struct ThermalMonitor {
+    path: PathBuf,
+    idle: f32,
+    hot: f32,
+    /// Contents of `path`, kept from one sample to the next.
+    buffer: String,
+}
+
+impl ThermalMonitor {
+    pub fn new(config: &ThermalConfig) -> Self {
+        Self {
+            path: PathBuf::from(format!(
+                "/sys/class/thermal/thermal_zone{}/temp",
+                config.zone
+            )),
+            idle: config.idle,
+            hot: config.hot,
+            buffer: String::new(),
+        }
+    }
+
+    /// Temperature mapped from 0 at the idle temperature to 1 at the hot one, or `None` when
+    /// the zone doesn't exist.
+    pub fn load(&mut self) -> Result<Option<f32>> {
+        if !self.path.exists() {
+            return Ok(None);
+        }
+
+        // Millidegrees Celsius.
+        let temperature = read_file(&self.path, &mut self.buffer)?
+            .trim()
+            .parse::<f32>()?
+            / 1000.0;
+
+        Ok(Some(
+            ((temperature - self.idle) / (self.hot - self.idle)).clamp(0.0, 1.0),
+        ))
+    }
+}
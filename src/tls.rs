@@ -0,0 +1,201 @@
+use crate::{connection::TlsOptions, hex};
+use anyhow::{anyhow, bail, Result};
+use sha2::{Digest, Sha256};
+use std::{net::SocketAddr, sync::Arc};
+use tokio::{
+    io::{AsyncRead, AsyncWrite},
+    net::TcpStream,
+};
+use tokio_rustls::rustls::{
+    client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier},
+    crypto::{ring, verify_tls12_signature, verify_tls13_signature, CryptoProvider},
+    pki_types::{pem::PemObject, CertificateDer, ServerName, UnixTime},
+    ClientConfig, DigitallySignedStruct, Error, RootCertStore, SignatureScheme,
+};
+
+/// Opens OpenRGB connections wrapped in TLS, e.g. to a server exposed through stunnel.
+pub struct TlsConnector {
+    connector: tokio_rustls::TlsConnector,
+    server_name: Option<String>,
+}
+
+impl TlsConnector {
+    pub fn new(options: &TlsOptions) -> Result<Self> {
+        if cfg!(not(unix)) {
+            bail!("TLS connections to OpenRGB need a socket pair, only available on Linux.");
+        }
+
+        let provider = Arc::new(ring::default_provider());
+        let builder = ClientConfig::builder_with_provider(provider.clone())
+            .with_safe_default_protocol_versions()?;
+
+        let config = if !options.pins.is_empty() {
+            let pins = options
+                .pins
+                .iter()
+                .map(|pin| parse_pin(pin))
+                .collect::<Result<_>>()?;
+
+            builder
+                .dangerous()
+                .with_custom_certificate_verifier(Arc::new(PinnedCertVerifier { pins, provider }))
+                .with_no_client_auth()
+        } else {
+            let mut roots = RootCertStore::empty();
+            match &options.ca_file {
+                Some(ca_file) => {
+                    for certificate in CertificateDer::pem_file_iter(ca_file)? {
+                        roots.add(certificate?)?;
+                    }
+                }
+                None => roots.extend(webpki_roots::TLS_SERVER_ROOTS.iter().cloned()),
+            }
+
+            builder.with_root_certificates(roots).with_no_client_auth()
+        };
+
+        Ok(Self {
+            connector: tokio_rustls::TlsConnector::from(Arc::new(config)),
+            server_name: options.server_name.clone(),
+        })
+    }
+
+    /// Plain stream to the server, relayed over TLS.
+    pub async fn connect(&self, address: SocketAddr, host: &str) -> Result<TcpStream> {
+        let server_name = self.server_name.as_deref().unwrap_or(host);
+        let server_name = ServerName::try_from(server_name.to_owned())?;
+
+        let stream = TcpStream::connect(address).await?;
+        let tls_stream = self.connector.connect(server_name, stream).await?;
+
+        relay(tls_stream, address)
+    }
+}
+
+/// The SDK client only takes TCP streams, so it gets one end of a socket pair, unreachable
+/// from other processes, and the other end is relayed to the server over TLS.
+#[cfg(unix)]
+fn relay(
+    mut tls_stream: impl AsyncRead + AsyncWrite + Unpin + Send + 'static,
+    address: SocketAddr,
+) -> Result<TcpStream> {
+    use log::*;
+    use std::os::{
+        fd::{FromRawFd, IntoRawFd},
+        unix::net::UnixStream,
+    };
+
+    let (client, relay) = UnixStream::pair()?;
+    client.set_nonblocking(true)?;
+    relay.set_nonblocking(true)?;
+    let mut relay = tokio::net::UnixStream::from_std(relay)?;
+
+    tokio::spawn(async move {
+        if let Err(e) = tokio::io::copy_bidirectional(&mut relay, &mut tls_stream).await {
+            debug!("TLS relay to {} closed: {}", address, e);
+        }
+    });
+
+    // Safety: the descriptor is a connected stream socket, now owned by the TCP stream only.
+    // Reading and writing it doesn't depend on its address family.
+    let client = unsafe { std::net::TcpStream::from_raw_fd(client.into_raw_fd()) };
+
+    Ok(TcpStream::from_std(client)?)
+}
+
+#[cfg(not(unix))]
+fn relay(_: impl AsyncRead + AsyncWrite, _: SocketAddr) -> Result<TcpStream> {
+    unreachable!("Refused by TlsConnector::new.")
+}
+
+/// Accepts the server certificate only if its SHA-256 fingerprint is one of the pins.
+#[derive(Debug)]
+struct PinnedCertVerifier {
+    pins: Vec<[u8; 32]>,
+    provider: Arc<CryptoProvider>,
+}
+
+impl ServerCertVerifier for PinnedCertVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        _intermediates: &[CertificateDer<'_>],
+        _server_name: &ServerName<'_>,
+        _ocsp_response: &[u8],
+        _now: UnixTime,
+    ) -> Result<ServerCertVerified, Error> {
+        let fingerprint = Sha256::digest(end_entity.as_ref());
+        if self.pins.iter().any(|pin| pin[..] == fingerprint[..]) {
+            Ok(ServerCertVerified::assertion())
+        } else {
+            Err(Error::General(
+                "Server certificate does not match any pinned fingerprint.".to_owned(),
+            ))
+        }
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, Error> {
+        verify_tls12_signature(
+            message,
+            cert,
+            dss,
+            &self.provider.signature_verification_algorithms,
+        )
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, Error> {
+        verify_tls13_signature(
+            message,
+            cert,
+            dss,
+            &self.provider.signature_verification_algorithms,
+        )
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.provider
+            .signature_verification_algorithms
+            .supported_schemes()
+    }
+}
+
+/// Parses a SHA-256 fingerprint as printed by `openssl x509 -fingerprint -sha256` (colons optional).
+fn parse_pin(pin: &str) -> Result<[u8; 32]> {
+    let digits = pin.chars().filter(|c| *c != ':').collect::<String>();
+
+    hex::decode(&digits)
+        .and_then(|fingerprint| fingerprint.try_into().ok())
+        .ok_or_else(|| anyhow!("Invalid SHA-256 fingerprint: {}", pin))
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    #[tokio::test]
+    async fn the_client_end_of_the_relay_is_a_working_tcp_stream() {
+        let (server, mut remote) = tokio::io::duplex(64);
+        let mut client = relay(server, ([127, 0, 0, 1], 6742).into()).unwrap();
+
+        client.write_all(b"ORGB").await.unwrap();
+        let mut request = [0; 4];
+        remote.read_exact(&mut request).await.unwrap();
+        assert_eq!(&request, b"ORGB");
+
+        remote.write_all(b"pong").await.unwrap();
+        let mut reply = [0; 4];
+        client.read_exact(&mut reply).await.unwrap();
+        assert_eq!(&reply, b"pong");
+    }
+}
@@ -0,0 +1,105 @@
+use crate::config::{Config, Metric};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Unit a metric is measured in, its level from 0 to 1 being what the LEDs show.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Unit {
+    Percent,
+    Celsius,
+    /// Unread messages.
+    Messages,
+    #[serde(rename = "megabytes_per_second")]
+    MegabytesPerSecond,
+    Watts,
+}
+
+impl fmt::Display for Unit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Percent => "percent",
+            Self::Celsius => "celsius",
+            Self::Messages => "messages",
+            Self::MegabytesPerSecond => "megabytes_per_second",
+            Self::Watts => "watts",
+        })
+    }
+}
+
+/// Value of a metric in its unit.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Quantity {
+    pub value: f32,
+    pub unit: Unit,
+}
+
+impl fmt::Display for Quantity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.unit {
+            Unit::Percent => write!(f, "{:.0}%", self.value),
+            Unit::Celsius => write!(f, "{:.1} °C", self.value),
+            Unit::Messages => write!(f, "{:.0} unread", self.value),
+            Unit::MegabytesPerSecond => write!(f, "{:.1} MB/s", self.value),
+            Unit::Watts => write!(f, "{:.1} W", self.value),
+        }
+    }
+}
+
+/// The level of a metric in its unit: the utilization, memory in use or drained charge in percent, the
+/// temperatures within their configured range, the disk throughput and the power of the LEDs up to
+/// their saturation point, or the unread messages up to the `full` count.
+pub fn quantity(metric: Metric, level: f32, config: &Config) -> Option<Quantity> {
+    let (value, unit) = match metric {
+        Metric::Cpu | Metric::Gpu | Metric::Memory | Metric::Battery => {
+            (level * 100.0, Unit::Percent)
+        }
+        Metric::CpuTemperature => {
+            let cpu = &config.cpu_temperature;
+
+            (cpu.idle + level * (cpu.hot - cpu.idle), Unit::Celsius)
+        }
+        Metric::GpuTemperature => {
+            let gpu = &config.gpu_temperature;
+
+            (gpu.idle + level * (gpu.hot - gpu.idle), Unit::Celsius)
+        }
+        Metric::Thermal => {
+            let thermal = &config.thermal;
+
+            (
+                thermal.idle + level * (thermal.hot - thermal.idle),
+                Unit::Celsius,
+            )
+        }
+        Metric::Disk => (level * config.disk.saturation, Unit::MegabytesPerSecond),
+        Metric::Power => (level * config.power.saturation, Unit::Watts),
+        Metric::Mail => {
+            let full = config.mail.as_ref().map_or(1, |mail| mail.full.max(1));
+
+            (level * full as f32, Unit::Messages)
+        }
+        Metric::None => return None,
+    };
+
+    Some(Quantity { value, unit })
+}
+
+/// Metric values as a Prometheus gauge, labeled with their metric and unit.
+pub fn to_prometheus(quantities: &[(Metric, Quantity)]) -> String {
+    let mut text = String::new();
+    if quantities.is_empty() {
+        return text;
+    }
+
+    text += "# HELP open_rgb_client_metric Metric values shown by the LEDs, in their unit.\n";
+    text += "# TYPE open_rgb_client_metric gauge\n";
+    for (metric, quantity) in quantities {
+        text += &format!(
+            "open_rgb_client_metric{{metric=\"{}\",unit=\"{}\"}} {}\n",
+            metric, quantity.unit, quantity.value
+        );
+    }
+
+    text
+}
@@ -0,0 +1,99 @@
+use crate::base64;
+use anyhow::{bail, Result};
+use sha1::{Digest, Sha1};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+/// Appended to the key of the client to prove the server speaks WebSocket, from RFC 6455.
+const HANDSHAKE_GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+
+/// Frames from clients are control frames or short messages, anything larger is refused.
+const MAX_FRAME: u64 = 64 * 1024;
+
+pub const TEXT: u8 = 0x1;
+pub const CLOSE: u8 = 0x8;
+pub const PING: u8 = 0x9;
+pub const PONG: u8 = 0xA;
+
+#[derive(Debug)]
+pub struct Frame {
+    pub opcode: u8,
+    pub payload: Vec<u8>,
+}
+
+/// Answer to the `Sec-WebSocket-Key` of a handshake, for `Sec-WebSocket-Accept`.
+pub fn accept_key(key: &str) -> String {
+    let digest = Sha1::new()
+        .chain_update(key.trim())
+        .chain_update(HANDSHAKE_GUID)
+        .finalize();
+
+    base64::encode(&digest)
+}
+
+/// Writes a whole, unfragmented frame. Servers don't mask their frames.
+pub async fn write_frame(
+    writer: &mut (impl AsyncWrite + Unpin),
+    opcode: u8,
+    payload: &[u8],
+) -> Result<()> {
+    let mut frame = vec![0x80 | opcode];
+    match payload.len() {
+        len @ 0..=125 => frame.push(len as u8),
+        len @ 126..=0xFFFF => {
+            frame.push(126);
+            frame.extend((len as u16).to_be_bytes());
+        }
+        len => {
+            frame.push(127);
+            frame.extend((len as u64).to_be_bytes());
+        }
+    }
+    frame.extend(payload);
+
+    writer.write_all(&frame).await?;
+
+    Ok(())
+}
+
+/// Reads a frame from a client, unmasking its payload. Fragments are returned as they come.
+pub async fn read_frame(reader: &mut (impl AsyncRead + Unpin)) -> Result<Frame> {
+    let mut header = [0; 2];
+    reader.read_exact(&mut header).await?;
+
+    let opcode = header[0] & 0x0F;
+    let masked = header[1] & 0x80 != 0;
+    let len = match header[1] & 0x7F {
+        126 => reader.read_u16().await? as u64,
+        127 => reader.read_u64().await?,
+        len => len as u64,
+    };
+    if len > MAX_FRAME {
+        bail!("WebSocket frame too large: {} bytes", len);
+    }
+
+    let mut mask = [0; 4];
+    if masked {
+        reader.read_exact(&mut mask).await?;
+    }
+
+    let mut payload = vec![0; len as usize];
+    reader.read_exact(&mut payload).await?;
+    for (i, byte) in payload.iter_mut().enumerate() {
+        *byte ^= mask[i % 4];
+    }
+
+    Ok(Frame { opcode, payload })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn handshake_of_the_rfc() {
+        assert_eq!(
+            accept_key("dGhlIHNhbXBsZSBub25jZQ=="),
+            "s3pPLMBiTxaQ9kYGzzhZRbK+xOo="
+        );
+    }
+}
@@ -1,9 +1,17 @@
-use log::info;
-use std::ffi::OsString;
+use crate::{args::Args, launch_client, ShutdownSignal};
+use anyhow::{bail, Result};
+use log::{error, info};
+use std::{
+    ffi::{OsStr, OsString},
+    sync::Arc,
+    thread,
+    time::Duration,
+};
 use windows_service::{
     define_windows_service,
     service::{
-        ServiceAccess, ServiceControl, ServiceControlAccept, ServiceErrorControl, ServiceExitCode,
+        ServiceAccess, ServiceAction, ServiceActionType, ServiceControl, ServiceControlAccept,
+        ServiceErrorControl, ServiceExitCode, ServiceFailureActions, ServiceFailureResetPeriod,
         ServiceInfo, ServiceStartType, ServiceState, ServiceStatus, ServiceType,
     },
     service_control_handler::{register, ServiceControlHandlerResult},
@@ -16,16 +24,22 @@ const SERVICE_DISPLAY_NAME: &str = "Open RGB Client";
 const SERVICE_DESCRIPTION: &str = "OpenRGB Client that changes light color based on system load.";
 const SERVICE_TYPE: ServiceType = ServiceType::OWN_PROCESS;
 const SERVICE_ARGUMENTS: &[&OsStr] = &[];
+/// Name of the scheduled task standing for the service, see `install_scheduled_task`.
+const TASK_NAME: &str = "open_rgb_client";
 
 define_windows_service!(ffi_service_main, my_service_main);
 
-pub fn start_service() {
+pub fn start_service() -> Result<()> {
     service_dispatcher::start(SERVICE_NAME, ffi_service_main)?;
+
+    Ok(())
 }
 
 pub fn my_service_main(_arguments: Vec<OsString>) {
     info!("Service is running, initializing async runtime...");
 
+    let args = Args::from_env(false).unwrap();
+
     let threaded_rt = tokio::runtime::Builder::new_multi_thread()
         .enable_all()
         .build()
@@ -36,10 +50,7 @@ pub fn my_service_main(_arguments: Vec<OsString>) {
         info!("Async runtime is initialized.");
 
         // Create a shutdown notification to signal the main loop to stop accepting more connection.
-        let shutdown_signal = Arc::new(ShutdownSignal {
-            shutdown_notify: Arc::new(tokio::sync::Notify::new()),
-            should_shutdown: AtomicBool::new(false),
-        });
+        let shutdown_signal = Arc::new(ShutdownSignal::new());
 
         // Define system service event handler that will be receiving service events.
         let shutdown_signal_copy = shutdown_signal.clone();
@@ -51,10 +62,7 @@ pub fn my_service_main(_arguments: Vec<OsString>) {
 
                 // Handle stop
                 ServiceControl::Stop => {
-                    shutdown_signal_copy
-                        .should_shutdown
-                        .store(true, Ordering::Relaxed);
-                    shutdown_signal_copy.shutdown_notify.notify_waiters();
+                    shutdown_signal_copy.shutdown();
 
                     info!("Giving time to shutdown gracefully...");
                     thread::sleep(Duration::from_secs(1));
@@ -84,7 +92,7 @@ pub fn my_service_main(_arguments: Vec<OsString>) {
             .unwrap();
 
         // Start main work loop.
-        let exit_code = match launch_client(shutdown_signal.into()).await {
+        let exit_code = match launch_client(&args, shutdown_signal).await {
             Ok(_) => {
                 info!("Stopping without errors.");
 
@@ -113,7 +121,7 @@ pub fn my_service_main(_arguments: Vec<OsString>) {
     });
 }
 
-pub async fn install_service() -> Result<()> {
+pub async fn install_service(args: &Args) -> Result<()> {
     // First, try to remove the service.
     remove_service().await.unwrap_or_default();
 
@@ -130,7 +138,10 @@ pub async fn install_service() -> Result<()> {
         start_type: ServiceStartType::AutoStart,
         error_control: ServiceErrorControl::Normal,
         executable_path: service_binary_path,
-        launch_arguments: vec!["--service".into()],
+        launch_arguments: std::iter::once("--service".to_owned())
+            .chain(args.service_arguments())
+            .map(OsString::from)
+            .collect(),
         dependencies: vec![],
         account_name: None, // run as System
         account_password: None,
@@ -141,6 +152,21 @@ pub async fn install_service() -> Result<()> {
         ServiceAccess::START | ServiceAccess::CHANGE_CONFIG,
     )?;
     service.set_description(SERVICE_DESCRIPTION)?;
+
+    // Restart the service when it crashes or exits with an error.
+    service.update_failure_actions(ServiceFailureActions {
+        reset_period: ServiceFailureResetPeriod::After(Duration::from_secs(24 * 60 * 60)),
+        reboot_msg: None,
+        command: None,
+        actions: Some(vec![
+            ServiceAction {
+                action_type: ServiceActionType::Restart,
+                delay: Duration::from_secs(5),
+            };
+            3
+        ]),
+    })?;
+    service.set_failure_actions_on_non_crash_failures(true)?;
     service.start(SERVICE_ARGUMENTS)?;
 
     Ok(())
@@ -165,3 +191,127 @@ pub async fn remove_service() -> Result<()> {
 
     Ok(())
 }
+
+/// Registers a scheduled task starting the client at logon with the highest privileges of the
+/// user, for machines whose policy forbids creating services. It runs whether the user is
+/// logged on or not, so no console window shows up, and Task Scheduler restarts it when it fails
+/// like the service manager does.
+pub async fn install_scheduled_task(args: &Args) -> Result<()> {
+    let user = match (std::env::var("USERDOMAIN"), std::env::var("USERNAME")) {
+        (Ok(domain), Ok(name)) => format!("{}\\{}", domain, name),
+        (_, Ok(name)) => name,
+        _ => bail!("Cannot find out the current user."),
+    };
+    let arguments = args
+        .service_arguments()
+        .iter()
+        .map(|argument| quote_argument(argument))
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    // No execution time limit, the default stops the task after 3 days.
+    let task = format!(
+        r#"<?xml version="1.0" encoding="UTF-16"?>
+<Task version="1.2" xmlns="http://schemas.microsoft.com/windows/2004/02/mit/task">
+  <RegistrationInfo>
+    <Description>{description}</Description>
+  </RegistrationInfo>
+  <Triggers>
+    <LogonTrigger>
+      <UserId>{user}</UserId>
+    </LogonTrigger>
+  </Triggers>
+  <Principals>
+    <Principal id="Author">
+      <UserId>{user}</UserId>
+      <LogonType>S4U</LogonType>
+      <RunLevel>HighestAvailable</RunLevel>
+    </Principal>
+  </Principals>
+  <Settings>
+    <MultipleInstancesPolicy>IgnoreNew</MultipleInstancesPolicy>
+    <DisallowStartIfOnBatteries>false</DisallowStartIfOnBatteries>
+    <StopIfGoingOnBatteries>false</StopIfGoingOnBatteries>
+    <ExecutionTimeLimit>PT0S</ExecutionTimeLimit>
+    <RestartOnFailure>
+      <Interval>PT1M</Interval>
+      <Count>3</Count>
+    </RestartOnFailure>
+  </Settings>
+  <Actions Context="Author">
+    <Exec>
+      <Command>{command}</Command>
+      <Arguments>{arguments}</Arguments>
+    </Exec>
+  </Actions>
+</Task>
+"#,
+        description = xml_escape(SERVICE_DESCRIPTION),
+        user = xml_escape(&user),
+        command = xml_escape(&std::env::current_exe()?.to_string_lossy()),
+        arguments = xml_escape(&arguments),
+    );
+
+    // schtasks reads the definition from a file, in UTF-16 as declared.
+    let path = std::env::temp_dir().join("open_rgb_client_task.xml");
+    let bytes = std::iter::once(0xFEFF)
+        .chain(task.encode_utf16())
+        .flat_map(u16::to_le_bytes)
+        .collect::<Vec<_>>();
+    tokio::fs::write(&path, bytes).await?;
+
+    let result = schtasks(&[
+        "/Create",
+        "/F",
+        "/TN",
+        TASK_NAME,
+        "/XML",
+        &path.to_string_lossy(),
+    ])
+    .await;
+    let _ = tokio::fs::remove_file(&path).await;
+    result?;
+
+    info!("Scheduled task {} registered, starting it...", TASK_NAME);
+    schtasks(&["/Run", "/TN", TASK_NAME]).await
+}
+
+pub async fn remove_scheduled_task() -> Result<()> {
+    // Fails when the task isn't running.
+    let _ = schtasks(&["/End", "/TN", TASK_NAME]).await;
+
+    schtasks(&["/Delete", "/F", "/TN", TASK_NAME]).await
+}
+
+async fn schtasks(args: &[&str]) -> Result<()> {
+    let output = tokio::process::Command::new("schtasks")
+        .args(args)
+        .output()
+        .await?;
+    if !output.status.success() {
+        bail!(
+            "schtasks {} failed: {}",
+            args[0],
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+
+    Ok(())
+}
+
+/// Quotes an argument for the command line of the task, as the C runtime parses it.
+fn quote_argument(argument: &str) -> String {
+    if argument.is_empty() || argument.contains([' ', '\t', '"']) {
+        format!("\"{}\"", argument.replace('"', "\\\""))
+    } else {
+        argument.to_owned()
+    }
+}
+
+fn xml_escape(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
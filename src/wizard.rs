@@ -0,0 +1,211 @@
+use crate::{
+    config::{parse_color, Config, DeviceConfig, Metric, Palette},
+    connection::{ConnectionOptions, Connector},
+    i18n::{self, tr},
+    profile::{is_raspberry_pi, is_steamos},
+};
+use anyhow::{bail, Result};
+use openrgb::data::{Color, Controller, DeviceType};
+use std::{
+    io::{self, BufRead, Write},
+    path::Path,
+};
+
+/// Palettes offered by the wizard, by message id.
+const PALETTES: &[(&str, Color, Color)] = &[
+    (
+        "palette-white-red",
+        Color::new(0xFF, 0xFF, 0xFF),
+        Color::new(0xFF, 0x00, 0x00),
+    ),
+    (
+        "palette-blue-red",
+        Color::new(0x00, 0x40, 0xFF),
+        Color::new(0xFF, 0x00, 0x00),
+    ),
+    (
+        "palette-green-red",
+        Color::new(0x00, 0xFF, 0x00),
+        Color::new(0xFF, 0x00, 0x00),
+    ),
+    (
+        "palette-cyan-magenta",
+        Color::new(0x00, 0xFF, 0xFF),
+        Color::new(0xFF, 0x00, 0xFF),
+    ),
+];
+
+/// Asks the user about their setup in the terminal and writes the resulting configuration.
+pub async fn run_wizard(path: &Path) -> Result<Config> {
+    println!("{}", tr!("wizard-intro"));
+    println!();
+
+    let mut config = Config::default();
+
+    let client = loop {
+        let host = prompt(&tr!("wizard-server"), "localhost")?;
+        let options = ConnectionOptions {
+            hosts: match host.parse() {
+                Ok(host) => vec![host],
+                Err(e) => {
+                    println!("{}", e);
+
+                    continue;
+                }
+            },
+            ..Default::default()
+        };
+
+        match Connector::new(&options)?.connect().await {
+            Ok(client) => {
+                if host != "localhost" {
+                    config.hosts.push(host);
+                }
+
+                break client;
+            }
+            Err(e) => println!(
+                "{}",
+                tr!("wizard-connect-failed", "host" => host, "error" => e.to_string())
+            ),
+        }
+    };
+
+    println!();
+    println!("{}", tr!("wizard-metrics"));
+
+    let controller_count = client.get_controller_count().await?;
+    for controller_id in 0..controller_count {
+        let controller = client.get_controller(controller_id).await?;
+        if config
+            .device(&controller.name, &controller.location)
+            .is_some()
+        {
+            continue;
+        }
+
+        let default = suggested_metric(&controller).to_string();
+
+        let question = tr!(
+            "wizard-device",
+            "name" => controller.name.as_str(),
+            "leds" => controller.leds.len()
+        );
+        let metric = loop {
+            match prompt(&question, &default)?.parse::<Metric>() {
+                Ok(metric) => break metric,
+                Err(e) => println!("{}", e),
+            }
+        };
+
+        config.devices.push(DeviceConfig {
+            name: controller.name,
+            metric: Some(metric),
+            palette: None,
+            effect: None,
+            idle_effect: None,
+            burn_in: None,
+            filters: vec![],
+            power: None,
+            zones: vec![],
+            placeholder: None,
+        });
+    }
+
+    println!();
+    println!("{}", tr!("wizard-palettes"));
+    for (index, (name, _, _)) in PALETTES.iter().enumerate() {
+        println!("  {}. {}", index + 1, i18n::message(name, None));
+    }
+    println!("  {}. {}", PALETTES.len() + 1, tr!("palette-custom"));
+
+    config.palette = loop {
+        let answer = prompt(&tr!("wizard-palette"), "1")?;
+        match answer.parse::<usize>() {
+            Ok(index) if (1..=PALETTES.len()).contains(&index) => {
+                let (_, idle, load) = PALETTES[index - 1];
+
+                break Palette { idle, load };
+            }
+            Ok(index) if index == PALETTES.len() + 1 => {
+                let idle = prompt_color(&tr!("wizard-idle-color"), "#FFFFFF")?;
+                let load = prompt_color(&tr!("wizard-load-color"), "#FF0000")?;
+
+                break Palette { idle, load };
+            }
+            _ => println!("{}", tr!("wizard-palette-unknown", "answer" => answer)),
+        }
+    };
+
+    config.save(path)?;
+
+    println!();
+    println!(
+        "{}",
+        tr!("wizard-saved", "path" => path.display().to_string())
+    );
+
+    Ok(config)
+}
+
+/// Metric a controller most likely shows: the GPU on a Steam Deck and for graphics cards when
+/// their load can be sampled, the temperature on a Raspberry Pi, the memory for RAM sticks, the
+/// CPU otherwise.
+pub fn suggested_metric(controller: &Controller) -> Metric {
+    if is_steamos() || (cfg!(feature = "nvidia") && controller.r#type == DeviceType::GPU) {
+        Metric::Gpu
+    } else if is_raspberry_pi() {
+        Metric::Thermal
+    } else if controller.r#type == DeviceType::DRAM {
+        Metric::Memory
+    } else {
+        Metric::Cpu
+    }
+}
+
+/// Asks a yes/no question, `default` being the answer when the user just presses enter.
+pub fn confirm(question: &str, default: bool) -> Result<bool> {
+    let hint = if default {
+        tr!("confirm-hint-yes")
+    } else {
+        tr!("confirm-hint-no")
+    };
+    loop {
+        let answer = prompt(question, &hint)?;
+        if answer == hint {
+            return Ok(default);
+        }
+
+        match answer.to_ascii_lowercase().as_str() {
+            "y" | "yes" => return Ok(true),
+            "n" | "no" => return Ok(false),
+            _ => println!("{}", tr!("confirm-invalid")),
+        }
+    }
+}
+
+fn prompt_color(question: &str, default: &str) -> Result<Color> {
+    loop {
+        match parse_color(&prompt(question, default)?) {
+            Ok(color) => return Ok(color),
+            Err(e) => println!("{}", e),
+        }
+    }
+}
+
+fn prompt(question: &str, default: &str) -> Result<String> {
+    print!("{} [{}]: ", question, default);
+    io::stdout().flush()?;
+
+    let mut answer = String::new();
+    if io::stdin().lock().read_line(&mut answer)? == 0 {
+        bail!(tr!("wizard-aborted"));
+    }
+
+    let answer = answer.trim();
+    if answer.is_empty() {
+        Ok(default.to_owned())
+    } else {
+        Ok(answer.to_owned())
+    }
+}
@@ -0,0 +1,75 @@
+use crate::{config::Palette, effects::Effect};
+use anyhow::{anyhow, Result};
+use openrgb::data::Color;
+use serde::{Deserialize, Serialize};
+use std::{net::SocketAddr, time::SystemTime};
+use tokio::net::{lookup_host, UdpSocket};
+
+const WLED_PORT: u16 = 21324;
+
+/// DRGB, the realtime UDP protocol of WLED taking one RGB triplet per LED.
+const DRGB: u8 = 2;
+const DRGB_MAX_LEDS: usize = 490;
+
+/// Seconds WLED keeps a frame before going back to its own effects, so the strip recovers
+/// once the client stops sending.
+const FRAME_TIMEOUT: u8 = 3;
+
+/// Effect shown on a WLED strip, independent from OpenRGB, while OpenRGB can't be reached.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OutageConfig {
+    /// WLED host, with the UDP realtime port when it isn't 21324.
+    pub wled: String,
+    /// LEDs of the strip, up to 490.
+    pub leds: usize,
+    pub effect: Effect,
+}
+
+pub struct WledSink {
+    socket: UdpSocket,
+    target: SocketAddr,
+    leds: usize,
+    effect: Effect,
+}
+
+impl WledSink {
+    pub async fn new(config: &OutageConfig) -> Result<Self> {
+        let host = match config.wled.contains(':') {
+            true => config.wled.clone(),
+            false => format!("{}:{}", config.wled, WLED_PORT),
+        };
+        let target = lookup_host(&host)
+            .await?
+            .next()
+            .ok_or_else(|| anyhow!("Cannot resolve {}.", host))?;
+
+        Ok(Self {
+            socket: UdpSocket::bind("0.0.0.0:0").await?,
+            target,
+            leds: config.leds.min(DRGB_MAX_LEDS),
+            effect: config.effect.clone(),
+        })
+    }
+
+    /// Sends the current frame of the effect.
+    pub async fn show(&self, palette: &Palette, now: SystemTime) -> Result<()> {
+        let mut colors = vec![Color::new(0, 0, 0); self.leds];
+        self.effect.render(0.0, &[], palette, &mut colors, now);
+
+        self.socket
+            .send_to(&drgb_packet(&colors), self.target)
+            .await?;
+
+        Ok(())
+    }
+}
+
+fn drgb_packet(colors: &[Color]) -> Vec<u8> {
+    let mut packet = Vec::with_capacity(2 + colors.len() * 3);
+    packet.extend([DRGB, FRAME_TIMEOUT]);
+    for color in colors {
+        packet.extend([color.r, color.g, color.b]);
+    }
+
+    packet
+}